@@ -208,6 +208,28 @@ pub trait Crypto {
         pub_key: &EcdsaPub,
     ) -> Result<EcdsaSig, CryptoError>;
 
+    /// Verify an ECDSA signature over `digest`
+    ///
+    /// Returns whether `sig` is a valid signature of `digest` by `pub_key`.
+    /// Implementations without signature verification may keep the default,
+    /// which returns `NotImplemented`.
+    ///
+    /// # Arguments
+    ///
+    /// * `algs` - Which length of algorithms to use.
+    /// * `digest` - Digest of the signed data.
+    /// * `sig` - Signature to check.
+    /// * `pub_key` - Public key of the signer.
+    fn ecdsa_verify(
+        &mut self,
+        _algs: AlgLen,
+        _digest: &Digest,
+        _sig: &EcdsaSig,
+        _pub_key: &EcdsaPub,
+    ) -> Result<bool, CryptoError> {
+        Err(CryptoError::NotImplemented)
+    }
+
     /// Sign `digest` with a derived HMAC key from the CDI.
     ///
     /// # Arguments
@@ -240,6 +240,26 @@ impl Crypto for OpensslCrypto {
         Ok(super::EcdsaSig { r, s })
     }
 
+    fn ecdsa_verify(
+        &mut self,
+        algs: AlgLen,
+        digest: &Digest,
+        sig: &super::EcdsaSig,
+        pub_key: &EcdsaPub,
+    ) -> Result<bool, CryptoError> {
+        let nid = OpensslCrypto::get_curve(algs);
+        let group = EcGroup::from_curve_name(nid)?;
+        let x = BigNum::from_slice(pub_key.x.bytes())?;
+        let y = BigNum::from_slice(pub_key.y.bytes())?;
+        let ec_pub = EcKey::from_public_key_affine_coordinates(&group, &x, &y)?;
+
+        let r = BigNum::from_slice(sig.r.bytes())?;
+        let s = BigNum::from_slice(sig.s.bytes())?;
+        let sig = EcdsaSig::from_private_components(r, s)?;
+
+        Ok(sig.verify(digest.bytes(), &ec_pub)?)
+    }
+
     fn hmac_sign_with_derived(
         &mut self,
         algs: AlgLen,
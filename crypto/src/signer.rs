@@ -24,6 +24,26 @@ impl EcdsaPub {
             y: CryptoBuf::default(alg),
         }
     }
+
+    /// Build a public key with all-zero coordinates of the size used by `alg`
+    pub fn zeroed(alg: AlgLen) -> EcdsaPub {
+        Self::default(alg)
+    }
+
+    /// Build a public key from its affine coordinates
+    ///
+    /// Returns `CryptoError::Size` if either coordinate is not exactly the
+    /// size used by `alg`.
+    pub fn from_coords(x: &[u8], y: &[u8], alg: AlgLen) -> Result<EcdsaPub, CryptoError> {
+        if x.len() != alg.size() || y.len() != alg.size() {
+            return Err(CryptoError::Size);
+        }
+
+        Ok(EcdsaPub {
+            x: CryptoBuf::new(x)?,
+            y: CryptoBuf::new(y)?,
+        })
+    }
 }
 
 /// An HMAC Signature
@@ -93,6 +113,7 @@ impl CryptoBuf {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use strum::IntoEnumIterator;
 
     #[test]
     fn test_crypto_buf_init() {
@@ -115,4 +136,35 @@ mod tests {
         let default_buf = CryptoBuf::default(AlgLen::Bit384);
         assert_eq!(default_buf.bytes(), [0; AlgLen::Bit384.size()]);
     }
+
+    #[test]
+    fn test_ecdsa_pub_from_coords() {
+        for alg in AlgLen::iter() {
+            let zeroed = EcdsaPub::zeroed(alg);
+            assert_eq!(zeroed.x.len(), alg.size());
+            assert_eq!(zeroed.y.len(), alg.size());
+
+            let x = [0xAA; CryptoBuf::MAX_SIZE];
+            let y = [0xBB; CryptoBuf::MAX_SIZE];
+            let pub_key = EcdsaPub::from_coords(&x[..alg.size()], &y[..alg.size()], alg).unwrap();
+            assert_eq!(pub_key.x.bytes(), &x[..alg.size()]);
+            assert_eq!(pub_key.y.bytes(), &y[..alg.size()]);
+
+            // Coordinates that are too short or too long for the algorithm
+            assert!(matches!(
+                EcdsaPub::from_coords(&x[..alg.size() - 1], &y[..alg.size()], alg),
+                Err(CryptoError::Size)
+            ));
+            assert!(matches!(
+                EcdsaPub::from_coords(&x[..alg.size()], &y[..alg.size() - 1], alg),
+                Err(CryptoError::Size)
+            ));
+            if alg.size() < CryptoBuf::MAX_SIZE {
+                assert!(matches!(
+                    EcdsaPub::from_coords(&x[..alg.size() + 1], &y[..alg.size() + 1], alg),
+                    Err(CryptoError::Size)
+                ));
+            }
+        }
+    }
 }
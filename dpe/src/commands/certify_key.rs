@@ -5,12 +5,15 @@ use crate::{
     dpe_instance::{DpeEnv, DpeInstance, DpeTypes},
     response::{CertifyKeyResp, DpeErrorCode, Response, ResponseHdr},
     tci::TciNodeData,
-    x509::{CertWriter, DirectoryString, MeasurementData, Name},
+    x509::{
+        build_dpe_cert, CertWriter, DirectoryString, MeasurementData, Name, SignerId, Validity,
+        CERTIFY_KEY_SUBJECT_CN, MAX_ISSUER_NAME_SIZE,
+    },
     DPE_PROFILE, MAX_CERT_SIZE, MAX_HANDLES,
 };
 use bitflags::bitflags;
 use crypto::Crypto;
-use platform::{Platform, MAX_CHUNK_SIZE};
+use platform::{Platform, GENERALIZED_TIME_SIZE, MAX_CHUNK_SIZE};
 
 #[repr(C)]
 #[derive(Debug, PartialEq, Eq, zerocopy::FromBytes, zerocopy::AsBytes)]
@@ -80,14 +83,16 @@ impl CommandExecution for CertifyKeyCmd {
             .crypto
             .derive_key_pair(algs, &cdi, &self.label, b"ECC")?;
 
+        // Match the string type of the issuer name so chain builders comparing
+        // names byte for byte link this certificate to its issuer
+        let string_type = env.platform.get_name_string_type()?;
         let mut subj_serial = [0u8; DPE_PROFILE.get_hash_size() * 2];
-        env.crypto
-            .get_pubkey_serial(DPE_PROFILE.alg_len(), &pub_key, &mut subj_serial)?;
-
-        let subject_name = Name {
-            cn: DirectoryString::PrintableString(b"DPE Leaf"),
-            serial: DirectoryString::PrintableString(&subj_serial),
-        };
+        let subject_name = Name::with_pubkey_serial(
+            DirectoryString::new(string_type, CERTIFY_KEY_SUBJECT_CN),
+            &mut env.crypto,
+            &pub_key,
+            &mut subj_serial,
+        )?;
 
         // Get TCI Nodes
         const INITIALIZER: TciNodeData = TciNodeData::new();
@@ -96,46 +101,31 @@ impl CommandExecution for CertifyKeyCmd {
         if tcb_count > MAX_HANDLES {
             return Err(DpeErrorCode::InternalError);
         }
+        let mut not_before = [0u8; GENERALIZED_TIME_SIZE];
+        let mut not_after = [0u8; GENERALIZED_TIME_SIZE];
+        env.platform
+            .get_cert_validity(&mut not_before, &mut not_after)?;
         let measurements = MeasurementData {
             label: &self.label,
             tci_nodes: &nodes[..tcb_count],
             is_ca: self.uses_is_ca(),
             supports_extend_tci: dpe.support.extend_tci(),
+            validity: Some(Validity::from_platform(&not_before, &not_after)?),
+            ..Default::default()
         };
 
-        let mut issuer_name = [0u8; MAX_CHUNK_SIZE];
-        let issuer_len = env.platform.get_issuer_name(&mut issuer_name)?;
-
         let mut cert = [0u8; MAX_CERT_SIZE];
         let cert_size = match self.format {
             Self::FORMAT_X509 => {
-                let mut tbs_buffer = [0u8; MAX_CERT_SIZE];
-                let mut tbs_writer = CertWriter::new(&mut tbs_buffer, true);
-                if issuer_len > MAX_CHUNK_SIZE {
-                    return Err(DpeErrorCode::InternalError);
-                }
-                let mut bytes_written = tbs_writer.encode_ecdsa_tbs(
-                    /*serial=*/
+                let bytes_written = build_dpe_cert(
+                    env,
                     &subject_name.serial.bytes()[..20], // Serial number must be truncated to 20 bytes
-                    &issuer_name[..issuer_len],
                     &subject_name,
                     &pub_key,
                     &measurements,
+                    |crypto, digest| crypto.ecdsa_sign_with_alias(DPE_PROFILE.alg_len(), digest),
+                    &mut CertWriter::new(&mut cert, true),
                 )?;
-                if bytes_written > MAX_CERT_SIZE {
-                    return Err(DpeErrorCode::InternalError);
-                }
-
-                let tbs_digest = env
-                    .crypto
-                    .hash(DPE_PROFILE.alg_len(), &tbs_buffer[..bytes_written])?;
-                let sig = env
-                    .crypto
-                    .ecdsa_sign_with_alias(DPE_PROFILE.alg_len(), &tbs_digest)?;
-
-                let mut cert_writer = CertWriter::new(&mut cert, true);
-                bytes_written =
-                    cert_writer.encode_ecdsa_certificate(&tbs_buffer[..bytes_written], &sig)?;
                 u32::try_from(bytes_written).map_err(|_| DpeErrorCode::InternalError)?
             }
             Self::FORMAT_CSR => {
@@ -143,14 +133,17 @@ impl CommandExecution for CertifyKeyCmd {
                     return Err(DpeErrorCode::ArgumentNotSupported);
                 }
 
+                let mut issuer_name = [0u8; MAX_CHUNK_SIZE];
+                let issuer_len = env.platform.get_issuer_name(&mut issuer_name)?;
+                if issuer_len > MAX_ISSUER_NAME_SIZE {
+                    return Err(DpeErrorCode::InvalidArgument);
+                }
+
                 let mut cert_req_info_buffer = [0u8; MAX_CERT_SIZE];
                 let mut cert_req_info_writer = CertWriter::new(&mut cert_req_info_buffer, true);
-                if issuer_len > MAX_CHUNK_SIZE {
-                    return Err(DpeErrorCode::InternalError);
-                }
                 let mut bytes_written = cert_req_info_writer.encode_certification_request_info(
                     &pub_key,
-                    &subject_name,
+                    Some(&subject_name),
                     &measurements,
                 )?;
                 if bytes_written > MAX_CERT_SIZE {
@@ -187,9 +180,12 @@ impl CommandExecution for CertifyKeyCmd {
                 let mut cms_writer = CertWriter::new(&mut cert, true);
                 bytes_written = cms_writer.encode_cms(
                     &csr_buffer[..bytes_written],
-                    &subject_name.serial.bytes()[..20], // Serial number must be truncated to 20 bytes
-                    &issuer_name[..issuer_len],
+                    &SignerId::IssuerAndSerialNumber {
+                        issuer_name: &issuer_name[..issuer_len],
+                        serial_number: &subject_name.serial.bytes()[..20], // Serial number must be truncated to 20 bytes
+                    },
                     &csr_sig,
+                    None,
                 )?;
                 u32::try_from(bytes_written).map_err(|_| DpeErrorCode::InternalError)?
             }
@@ -228,14 +224,17 @@ mod tests {
     use super::*;
     use crate::{
         commands::{Command, CommandHdr, InitCtxCmd},
-        dpe_instance::tests::{TestTypes, SIMULATION_HANDLE, TEST_LOCALITIES},
+        dpe_instance::tests::{
+            TestPlatform, TestPlatformTypes, TestTypes, SIMULATION_HANDLE, TEST_LOCALITIES,
+        },
         support::Support,
+        x509::NameOrder,
     };
     use cms::{
         content_info::{CmsVersion, ContentInfo},
         signed_data::{SignedData, SignerIdentifier},
     };
-    use crypto::{AlgLen, CryptoBuf, EcdsaPub, OpensslCrypto};
+    use crypto::{AlgLen, EcdsaPub, OpensslCrypto};
     use der::{Decode, Encode};
     use openssl::{
         bn::BigNum,
@@ -244,10 +243,11 @@ mod tests {
         nid::*,
     };
     use platform::default::DefaultPlatform;
+    use platform::StringType;
     use spki::ObjectIdentifier;
     use std::str;
     use x509_parser::nom::Parser;
-    use x509_parser::oid_registry::asn1_rs::oid;
+    use x509_parser::oid_registry::asn1_rs::{oid, Tag};
     use x509_parser::prelude::public_key::PublicKey;
     use x509_parser::prelude::X509CertificateParser;
     use x509_parser::prelude::X509CertificationRequest;
@@ -313,6 +313,113 @@ mod tests {
         };
     }
 
+    const TEST_NOT_BEFORE: &[u8; GENERALIZED_TIME_SIZE] = b"20240301000000Z";
+    const TEST_NOT_AFTER: &[u8; GENERALIZED_TIME_SIZE] = b"20290228235959Z";
+
+    #[test]
+    fn test_certify_key_platform_validity() {
+        let mut env = DpeEnv::<TestPlatformTypes> {
+            crypto: OpensslCrypto::new(),
+            platform: TestPlatform {
+                validity: Some((*TEST_NOT_BEFORE, *TEST_NOT_AFTER)),
+                ..Default::default()
+            },
+        };
+        let mut dpe = DpeInstance::new(&mut env, Support::X509).unwrap();
+
+        let init_resp = match InitCtxCmd::new_use_default()
+            .execute(&mut dpe, &mut env, TEST_LOCALITIES[0])
+            .unwrap()
+        {
+            Response::InitCtx(resp) => resp,
+            _ => panic!("Incorrect return type."),
+        };
+        let certify_cmd = CertifyKeyCmd {
+            handle: init_resp.handle,
+            flags: CertifyKeyFlags::empty(),
+            label: [0; DPE_PROFILE.get_hash_size()],
+            format: CertifyKeyCmd::FORMAT_X509,
+        };
+
+        let certify_resp = match certify_cmd
+            .execute(&mut dpe, &mut env, TEST_LOCALITIES[0])
+            .unwrap()
+        {
+            Response::CertifyKey(resp) => resp,
+            _ => panic!("Wrong response type."),
+        };
+
+        let (_, cert) =
+            X509Certificate::from_der(&certify_resp.cert[..certify_resp.cert_size as usize])
+                .unwrap();
+        // 2024-03-01 00:00:00 UTC and 2029-02-28 23:59:59 UTC
+        assert_eq!(cert.validity().not_before.timestamp(), 1709251200);
+        assert_eq!(cert.validity().not_after.timestamp(), 1867017599);
+    }
+
+    /// Issue a leaf certificate and return the string tags of its subject
+    /// commonName and serialNumber
+    fn certify_key_subject_tags<T: DpeTypes>(env: &mut DpeEnv<T>) -> (Tag, Tag) {
+        let mut dpe = DpeInstance::new(env, Support::X509).unwrap();
+
+        let init_resp = match InitCtxCmd::new_use_default()
+            .execute(&mut dpe, env, TEST_LOCALITIES[0])
+            .unwrap()
+        {
+            Response::InitCtx(resp) => resp,
+            _ => panic!("Incorrect return type."),
+        };
+        let certify_cmd = CertifyKeyCmd {
+            handle: init_resp.handle,
+            flags: CertifyKeyFlags::empty(),
+            label: [0; DPE_PROFILE.get_hash_size()],
+            format: CertifyKeyCmd::FORMAT_X509,
+        };
+
+        let certify_resp = match certify_cmd
+            .execute(&mut dpe, env, TEST_LOCALITIES[0])
+            .unwrap()
+        {
+            Response::CertifyKey(resp) => resp,
+            _ => panic!("Wrong response type."),
+        };
+
+        let (_, cert) =
+            X509Certificate::from_der(&certify_resp.cert[..certify_resp.cert_size as usize])
+                .unwrap();
+        let subject = cert.subject();
+        let cn = subject.iter_common_name().next().unwrap();
+        let serial = subject
+            .iter_by_oid(&oid_registry::OID_X509_SERIALNUMBER)
+            .next()
+            .unwrap();
+        (cn.attr_value().tag(), serial.attr_value().tag())
+    }
+
+    #[test]
+    fn test_certify_key_name_string_type() {
+        let mut env = DpeEnv::<TestTypes> {
+            crypto: OpensslCrypto::new(),
+            platform: DefaultPlatform,
+        };
+        assert_eq!(
+            certify_key_subject_tags(&mut env),
+            (Tag::PrintableString, Tag::PrintableString)
+        );
+
+        let mut env = DpeEnv::<TestPlatformTypes> {
+            crypto: OpensslCrypto::new(),
+            platform: TestPlatform {
+                string_type: Some(StringType::Utf8String),
+                ..Default::default()
+            },
+        };
+        assert_eq!(
+            certify_key_subject_tags(&mut env),
+            (Tag::Utf8String, Tag::Utf8String)
+        );
+    }
+
     #[test]
     fn test_is_ca() {
         let mut env = DpeEnv::<TestTypes> {
@@ -472,10 +579,12 @@ mod tests {
         // validate signer identifier
         let sid = &signer_info.sid;
         let mut subj_serial = [0u8; DPE_PROFILE.get_hash_size() * 2];
-        let pub_key = EcdsaPub {
-            x: CryptoBuf::new(&certify_resp.derived_pubkey_x).unwrap(),
-            y: CryptoBuf::new(&certify_resp.derived_pubkey_y).unwrap(),
-        };
+        let pub_key = EcdsaPub::from_coords(
+            &certify_resp.derived_pubkey_x,
+            &certify_resp.derived_pubkey_y,
+            DPE_PROFILE.alg_len(),
+        )
+        .unwrap();
         env.crypto
             .get_pubkey_serial(DPE_PROFILE.alg_len(), &pub_key, &mut subj_serial)
             .unwrap();
@@ -552,6 +661,7 @@ mod tests {
         let subject_name = Name {
             cn: DirectoryString::PrintableString(b"DPE Leaf"),
             serial: DirectoryString::PrintableString(&subj_serial),
+            order: NameOrder::CnFirst,
         };
         let expected_subject_name = format!(
             "CN={}, serialNumber={}",
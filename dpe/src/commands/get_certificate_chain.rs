@@ -42,7 +42,7 @@ mod tests {
     use super::*;
     use crate::{
         commands::{Command, CommandHdr},
-        dpe_instance::tests::{TestTypes, TEST_LOCALITIES},
+        dpe_instance::tests::{TestPlatform, TestPlatformTypes, TestTypes, TEST_LOCALITIES},
         support::test::SUPPORT,
     };
     use crypto::OpensslCrypto;
@@ -83,4 +83,25 @@ mod tests {
             .execute(&mut dpe, &mut env, TEST_LOCALITIES[0])
         );
     }
+
+    #[test]
+    fn test_no_certificate_chain() {
+        let mut env = DpeEnv::<TestPlatformTypes> {
+            crypto: OpensslCrypto::new(),
+            platform: TestPlatform {
+                no_chain: true,
+                ..Default::default()
+            },
+        };
+        let mut dpe = DpeInstance::new(&mut env, SUPPORT).unwrap();
+
+        // A platform without a chain returns an empty chunk rather than an error
+        let Ok(Response::GetCertificateChain(resp)) =
+            TEST_GET_CERTIFICATE_CHAIN_CMD.execute(&mut dpe, &mut env, TEST_LOCALITIES[0])
+        else {
+            panic!("expected a GetCertificateChain response");
+        };
+        assert_eq!(resp.certificate_size, 0);
+        assert_eq!(resp.resp_hdr.status, DpeErrorCode::NoError.get_error_code());
+    }
 }
@@ -277,20 +277,8 @@ impl DpeInstance {
             return Err(DpeErrorCode::InvalidLocality);
         }
 
-        // Derive the new TCI as HASH(TCI_CUMULATIVE || INPUT_DATA).
-        let mut hasher = env.crypto.hash_initialize(DPE_PROFILE.alg_len())?;
-        hasher.update(&context.tci.tci_cumulative.0)?;
-        hasher.update(&measurement.0)?;
-        let digest = hasher.finish()?;
-
-        let digest_bytes = digest.bytes();
-
-        if digest_bytes.len() != context.tci.tci_cumulative.0.len() {
-            return Err(DpeErrorCode::InternalError);
-        }
-        context.tci.tci_cumulative.0.copy_from_slice(digest_bytes);
-        context.tci.tci_current = *measurement;
-        Ok(())
+        let hasher = env.crypto.hash_initialize(DPE_PROFILE.alg_len())?;
+        context.tci.extend(hasher, measurement)
     }
 
     fn serialize_internal_input_info(
@@ -353,12 +341,19 @@ impl DpeInstance {
 
         // Add internal input dice to hash
         if uses_internal_input_dice {
+            let chain_len = env.platform.get_certificate_chain_len()?;
             let mut offset = 0;
             let mut cert_chunk = [0u8; MAX_CHUNK_SIZE];
-            while let Ok(len) =
-                env.platform
-                    .get_certificate_chain(offset, MAX_CHUNK_SIZE as u32, &mut cert_chunk)
-            {
+            while offset < chain_len {
+                let len = env.platform.get_certificate_chain(
+                    offset,
+                    MAX_CHUNK_SIZE as u32,
+                    &mut cert_chunk,
+                )?;
+                // Guard against a platform that stops making progress
+                if len == 0 {
+                    return Err(DpeErrorCode::InternalError);
+                }
                 hasher.update(&cert_chunk[..len as usize])?;
                 offset += len;
             }
@@ -460,6 +455,7 @@ pub mod tests {
     use crate::{commands::CommandHdr, CURRENT_PROFILE_MAJOR_VERSION};
     use crypto::OpensslCrypto;
     use platform::default::{DefaultPlatform, AUTO_INIT_LOCALITY, TEST_CERT_CHAIN};
+    use platform::{read_chunk, PlatformError, StringType, GENERALIZED_TIME_SIZE};
     use zerocopy::AsBytes;
 
     pub struct TestTypes;
@@ -478,6 +474,113 @@ pub mod tests {
 
     pub const TEST_LOCALITIES: [u32; 2] = [AUTO_INIT_LOCALITY, u32::from_be_bytes(*b"OTHR")];
 
+    /// A `DefaultPlatform` with optional overrides, for tests that need a
+    /// platform that differs from it in one respect
+    #[derive(Default)]
+    pub struct TestPlatform {
+        /// notBefore and notAfter returned instead of the default validity
+        pub validity: Option<([u8; GENERALIZED_TIME_SIZE], [u8; GENERALIZED_TIME_SIZE])>,
+        /// String type returned instead of the default one
+        pub string_type: Option<StringType>,
+        /// Report an empty certificate chain, such as on a platform that is
+        /// not yet provisioned
+        pub no_chain: bool,
+        /// Certificate chain returned instead of the default one
+        pub cert_chain: Option<&'static [u8]>,
+    }
+
+    impl Platform for TestPlatform {
+        fn get_certificate_chain(
+            &mut self,
+            offset: u32,
+            size: u32,
+            out: &mut [u8; MAX_CHUNK_SIZE],
+        ) -> Result<u32, PlatformError> {
+            match (self.no_chain, offset) {
+                (false, _) => match self.cert_chain {
+                    Some(chain) => read_chunk(chain, offset, size, out)
+                        .map_err(|_| PlatformError::CertificateChainError),
+                    None => DefaultPlatform.get_certificate_chain(offset, size, out),
+                },
+                (true, 0) => Ok(0),
+                (true, _) => Err(PlatformError::CertificateChainError),
+            }
+        }
+
+        fn get_certificate_chain_len(&mut self) -> Result<u32, PlatformError> {
+            if self.no_chain {
+                return Ok(0);
+            }
+            match self.cert_chain {
+                Some(chain) => Ok(chain.len() as u32),
+                None => DefaultPlatform.get_certificate_chain_len(),
+            }
+        }
+
+        fn get_issuer_name(
+            &mut self,
+            out: &mut [u8; MAX_CHUNK_SIZE],
+        ) -> Result<usize, PlatformError> {
+            DefaultPlatform.get_issuer_name(out)
+        }
+
+        fn get_device_ueid(&mut self, out: &mut [u8]) -> Result<usize, PlatformError> {
+            DefaultPlatform.get_device_ueid(out)
+        }
+
+        fn get_vendor_oid_arc(&mut self, out: &mut [u8]) -> Result<usize, PlatformError> {
+            DefaultPlatform.get_vendor_oid_arc(out)
+        }
+
+        fn get_cert_validity(
+            &mut self,
+            not_before: &mut [u8; GENERALIZED_TIME_SIZE],
+            not_after: &mut [u8; GENERALIZED_TIME_SIZE],
+        ) -> Result<(), PlatformError> {
+            match self.validity {
+                Some((before, after)) => {
+                    *not_before = before;
+                    *not_after = after;
+                    Ok(())
+                }
+                None => DefaultPlatform.get_cert_validity(not_before, not_after),
+            }
+        }
+
+        fn get_name_string_type(&mut self) -> Result<StringType, PlatformError> {
+            match self.string_type {
+                Some(string_type) => Ok(string_type),
+                None => DefaultPlatform.get_name_string_type(),
+            }
+        }
+
+        fn get_vendor_id(&mut self) -> Result<u32, PlatformError> {
+            DefaultPlatform.get_vendor_id()
+        }
+
+        fn get_vendor_sku(&mut self) -> Result<u32, PlatformError> {
+            DefaultPlatform.get_vendor_sku()
+        }
+
+        fn get_auto_init_locality(&mut self) -> Result<u32, PlatformError> {
+            DefaultPlatform.get_auto_init_locality()
+        }
+
+        fn write_str(&mut self, str: &str) -> Result<(), PlatformError> {
+            DefaultPlatform.write_str(str)
+        }
+
+        fn supported_profiles(&mut self) -> Result<&[u32], PlatformError> {
+            Ok(platform::default::SUPPORTED_PROFILES)
+        }
+    }
+
+    pub struct TestPlatformTypes;
+    impl DpeTypes for TestPlatformTypes {
+        type Crypto<'a> = OpensslCrypto;
+        type Platform<'a> = TestPlatform;
+    }
+
     #[test]
     fn test_execute_serialized_command() {
         let mut env = DpeEnv::<TestTypes> {
@@ -834,6 +937,42 @@ pub mod tests {
         assert_eq!(answer, cdi_with_internal_input_dice)
     }
 
+    #[test]
+    fn test_hash_internal_input_dice_no_chain() {
+        let mut env = DpeEnv::<TestPlatformTypes> {
+            crypto: OpensslCrypto::new(),
+            platform: TestPlatform {
+                no_chain: true,
+                ..Default::default()
+            },
+        };
+        let mut dpe = DpeInstance::new(&mut env, SUPPORT | Support::INTERNAL_DICE).unwrap();
+
+        DeriveChildCmd {
+            handle: ContextHandle::default(),
+            data: [0; DPE_PROFILE.get_hash_size()],
+            flags: DeriveChildFlags::MAKE_DEFAULT | DeriveChildFlags::INTERNAL_INPUT_DICE,
+            tci_type: 0u32,
+            target_locality: 0,
+        }
+        .execute(&mut dpe, &mut env, TEST_LOCALITIES[0])
+        .unwrap();
+
+        // Without a chain, the internal input DICE adds nothing to the hash
+        let child_context_idx = dpe
+            .get_active_context_pos(&ContextHandle::default(), TEST_LOCALITIES[0])
+            .unwrap();
+        let digest = dpe
+            .compute_measurement_hash(&mut env, child_context_idx)
+            .unwrap();
+
+        let mut hasher = env.crypto.hash_initialize(DPE_PROFILE.alg_len()).unwrap();
+        for status in ChildToRootIter::new(child_context_idx, &dpe.contexts) {
+            hasher.update(status.unwrap().tci.as_bytes()).unwrap();
+        }
+        assert_eq!(digest, hasher.finish().unwrap());
+    }
+
     #[test]
     fn test_validate_context_tree() {
         let mut env = DpeEnv::<TestTypes> {
@@ -72,6 +72,37 @@ impl DpeProfile {
     pub const fn get_hash_size(&self) -> usize {
         self.get_tci_size()
     }
+    /// Length of the CertifyKey LABEL, which DPE certificates carry as the
+    /// tcg-dice-Ueid. It is also the most a tcg-dice-Ueid may hold.
+    pub const fn ueid_size(&self) -> usize {
+        match self {
+            DpeProfile::P256Sha256 => 32,
+            DpeProfile::P384Sha384 => 48,
+        }
+    }
+    /// Whether certificates report the cumulative TCI as FWID[1] by default.
+    /// A DPE instance that does not enable ExtendTci still clears this per
+    /// certificate.
+    pub const fn supports_extend_tci(&self) -> bool {
+        // Every profile defines the ExtendTci command
+        true
+    }
+    /// Upper bound on the size of the PKCS #10 CSR CertifyKey issues under
+    /// `DPE_PROFILE` for a context chain of up to `max_tci_nodes` TCI nodes,
+    /// for sizing CSR buffers. CertifyKey returns the CSR wrapped in a CMS
+    /// SignedData.
+    ///
+    /// `max_tci_nodes` is clamped to `1..=MAX_TCI_NODES`.
+    pub const fn max_csr_size(max_tci_nodes: usize) -> usize {
+        let tci_count = if max_tci_nodes == 0 {
+            1
+        } else if max_tci_nodes > x509::MAX_TCI_NODES {
+            x509::MAX_TCI_NODES
+        } else {
+            max_tci_nodes
+        };
+        x509::CertWriter::get_max_csr_size(tci_count)
+    }
     pub const fn alg_len(&self) -> crypto::AlgLen {
         match self {
             DpeProfile::P256Sha256 => crypto::AlgLen::Bit256,
@@ -80,6 +111,21 @@ impl DpeProfile {
     }
 }
 
+// ECDSA keys are sized by `alg_len()` and encoded in certificates using
+// `get_ecc_int_size()`, so the two must agree for every profile.
+const _: () = {
+    assert!(DpeProfile::P256Sha256.alg_len().size() == DpeProfile::P256Sha256.get_ecc_int_size());
+    assert!(DpeProfile::P384Sha384.alg_len().size() == DpeProfile::P384Sha384.get_ecc_int_size());
+};
+
+// CertifyKey labels are a full digest, so the UEID size tracks the hash size.
+const _: () = {
+    assert!(DpeProfile::P256Sha256.ueid_size() == 32);
+    assert!(DpeProfile::P384Sha384.ueid_size() == 48);
+    assert!(DpeProfile::P256Sha256.ueid_size() == DpeProfile::P256Sha256.get_hash_size());
+    assert!(DpeProfile::P384Sha384.ueid_size() == DpeProfile::P384Sha384.get_hash_size());
+};
+
 #[cfg(feature = "dpe_profile_p256_sha256")]
 pub const DPE_PROFILE: DpeProfile = DpeProfile::P256Sha256;
 
@@ -152,8 +152,13 @@ pub enum DpeErrorCode {
     MaxTcis = 0x1003,
     Platform(PlatformError) = 0x01000000,
     Crypto(CryptoError) = 0x02000000,
+    /// An output buffer is too small. Holds the buffer size that is needed.
+    BufferTooSmall(u32) = 0x03000000,
 }
 
+/// Every `PlatformError` maps to `DpeErrorCode::Platform`, so the spec-defined
+/// error code is `0x01000000 | PlatformError::discriminant()` and the
+/// platform's extended error detail is preserved.
 impl From<PlatformError> for DpeErrorCode {
     fn from(e: PlatformError) -> Self {
         DpeErrorCode::Platform(e)
@@ -187,14 +192,83 @@ impl DpeErrorCode {
 
     /// For error variants which have extended error info returned from
     /// underlying libraries (Platform and Crypto), return that extended error
-    /// code. For all other variants, return None.
+    /// code. For BufferTooSmall, return the needed buffer size. For all other
+    /// variants, return None.
     ///
     /// Reporting of detailed error information is platform-defined.
     pub fn get_error_detail(&self) -> Option<u32> {
         match self {
             DpeErrorCode::Platform(e) => e.get_error_detail(),
             DpeErrorCode::Crypto(e) => e.get_error_detail(),
+            DpeErrorCode::BufferTooSmall(size) => Some(*size),
             _ => None,
         }
     }
+
+    /// Get a short description of the error for logging. The Platform and
+    /// Crypto details are not included.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DpeErrorCode::NoError => "no error",
+            DpeErrorCode::InternalError => "internal error",
+            DpeErrorCode::InvalidCommand => "invalid command",
+            DpeErrorCode::InvalidArgument => "invalid argument",
+            DpeErrorCode::ArgumentNotSupported => "argument not supported",
+            DpeErrorCode::InvalidHandle => "invalid handle",
+            DpeErrorCode::InvalidLocality => "invalid locality",
+            DpeErrorCode::MaxTcis => "maximum TCIs reached",
+            DpeErrorCode::Platform(_) => "platform error",
+            DpeErrorCode::Crypto(_) => "crypto error",
+            DpeErrorCode::BufferTooSmall(_) => "buffer too small",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_platform_error_conversion() {
+        for (e, code, detail) in [
+            (PlatformError::CertificateChainError, 0x01000001, None),
+            (PlatformError::NotImplemented, 0x01000002, None),
+            (PlatformError::IssuerNameError(7), 0x01000003, Some(7)),
+            (PlatformError::PrintError(9), 0x01000004, Some(9)),
+            (PlatformError::InvalidChunk, 0x01000005, None),
+            (PlatformError::DeviceUeidError(3), 0x01000006, Some(3)),
+            (PlatformError::VendorOidArcError(4), 0x01000007, Some(4)),
+        ] {
+            let dpe_error = DpeErrorCode::from(e);
+            assert_eq!(dpe_error, DpeErrorCode::Platform(e));
+            assert_eq!(dpe_error.get_error_code(), code);
+            assert_eq!(dpe_error.get_error_detail(), detail);
+        }
+    }
+
+    #[test]
+    fn test_error_codes_and_messages() {
+        let errors = [
+            DpeErrorCode::NoError,
+            DpeErrorCode::InternalError,
+            DpeErrorCode::InvalidCommand,
+            DpeErrorCode::InvalidArgument,
+            DpeErrorCode::ArgumentNotSupported,
+            DpeErrorCode::InvalidHandle,
+            DpeErrorCode::InvalidLocality,
+            DpeErrorCode::MaxTcis,
+            DpeErrorCode::Platform(PlatformError::NotImplemented),
+            DpeErrorCode::Crypto(CryptoError::Size),
+            DpeErrorCode::BufferTooSmall(0x100),
+        ];
+
+        let mut codes: Vec<u32> = errors.iter().map(|e| e.get_error_code()).collect();
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+
+        for e in errors {
+            assert!(!e.as_str().is_empty());
+        }
+    }
 }
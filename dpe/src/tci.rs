@@ -1,5 +1,6 @@
 // Licensed under the Apache-2.0 license.
-use crate::DPE_PROFILE;
+use crate::{response::DpeErrorCode, DPE_PROFILE};
+use crypto::Hasher;
 use zerocopy::{AsBytes, FromBytes};
 use zeroize::Zeroize;
 
@@ -21,14 +22,179 @@ impl TciNodeData {
             locality: 0,
         }
     }
+
+    /// Build a node from its measurements and metadata
+    ///
+    /// # Arguments
+    ///
+    /// * `tci_current` - The current TCI. Must be the profile's TCI size.
+    /// * `tci_cumulative` - The cumulative TCI. Must be the profile's TCI size.
+    /// * `tci_type` - The TCI type.
+    /// * `locality` - The locality of the node.
+    pub fn from_parts(
+        tci_current: &[u8],
+        tci_cumulative: &[u8],
+        tci_type: u32,
+        locality: u32,
+    ) -> Result<TciNodeData, DpeErrorCode> {
+        Ok(TciNodeData {
+            tci_type,
+            tci_cumulative: TciMeasurement::try_from(tci_cumulative)?,
+            tci_current: TciMeasurement::try_from(tci_current)?,
+            locality,
+        })
+    }
+
+    /// Extend `measurement` into the node
+    ///
+    /// The new cumulative TCI is HASH(TCI_CUMULATIVE || INPUT_DATA) and the
+    /// current TCI becomes `measurement`.
+    ///
+    /// # Arguments
+    ///
+    /// * `hasher` - A freshly initialized hasher for the profile's hash
+    ///   algorithm. This lets the caller choose the hash engine.
+    /// * `measurement` - The measurement to extend.
+    pub fn extend(
+        &mut self,
+        mut hasher: impl Hasher,
+        measurement: &TciMeasurement,
+    ) -> Result<(), DpeErrorCode> {
+        hasher.update(&self.tci_cumulative.0)?;
+        hasher.update(&measurement.0)?;
+        let digest = hasher.finish()?;
+
+        let digest_bytes = digest.bytes();
+
+        if digest_bytes.len() != self.tci_cumulative.0.len() {
+            return Err(DpeErrorCode::InternalError);
+        }
+        self.tci_cumulative.0.copy_from_slice(digest_bytes);
+        self.tci_current = *measurement;
+        Ok(())
+    }
 }
 
 #[repr(transparent)]
 #[derive(Copy, Clone, Debug, AsBytes, FromBytes, PartialEq, Eq, Zeroize)]
 pub struct TciMeasurement(pub [u8; DPE_PROFILE.get_tci_size()]);
 
+impl TciMeasurement {
+    /// Build a measurement from `bytes`, which must be exactly the profile's
+    /// hash size. Shorter or longer input is rejected with `InvalidArgument`
+    /// rather than padded or truncated.
+    pub fn try_from_slice(bytes: &[u8]) -> Result<TciMeasurement, DpeErrorCode> {
+        if bytes.len() != DPE_PROFILE.get_hash_size() {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+        Ok(Self(
+            bytes
+                .try_into()
+                .map_err(|_| DpeErrorCode::InvalidArgument)?,
+        ))
+    }
+}
+
+impl TryFrom<&[u8]> for TciMeasurement {
+    type Error = DpeErrorCode;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Self::try_from_slice(bytes)
+    }
+}
+
 impl Default for TciMeasurement {
     fn default() -> Self {
         Self([0; DPE_PROFILE.get_tci_size()])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::{CryptoBuf, CryptoError, Digest};
+
+    /// Folds its input by XOR into a TCI-sized digest
+    struct XorHasher([u8; DPE_PROFILE.get_tci_size()], usize);
+
+    impl Hasher for XorHasher {
+        fn update(&mut self, bytes: &[u8]) -> Result<(), CryptoError> {
+            for b in bytes {
+                self.0[self.1 % self.0.len()] ^= b;
+                self.1 += 1;
+            }
+            Ok(())
+        }
+
+        fn finish(self) -> Result<Digest, CryptoError> {
+            CryptoBuf::new(&self.0)
+        }
+    }
+
+    #[test]
+    fn test_from_parts() {
+        let current = [0x11; DPE_PROFILE.get_tci_size()];
+        let cumulative = [0x22; DPE_PROFILE.get_tci_size()];
+        let node = TciNodeData::from_parts(&current, &cumulative, 0x4B4C4D4E, 3).unwrap();
+        assert_eq!(node.tci_current, TciMeasurement(current));
+        assert_eq!(node.tci_cumulative, TciMeasurement(cumulative));
+        assert_eq!(node.tci_type, 0x4B4C4D4E);
+        assert_eq!(node.locality, 3);
+
+        assert!(matches!(
+            TciNodeData::from_parts(&current[1..], &cumulative, 0, 0),
+            Err(DpeErrorCode::InvalidArgument)
+        ));
+        assert!(matches!(
+            TciNodeData::from_parts(&current, &[0x22; DPE_PROFILE.get_tci_size() + 1], 0, 0),
+            Err(DpeErrorCode::InvalidArgument)
+        ));
+    }
+
+    #[test]
+    fn test_try_from_slice() {
+        let bytes = [0x5A; DPE_PROFILE.get_hash_size() + 1];
+        assert_eq!(
+            TciMeasurement::try_from_slice(&bytes[..DPE_PROFILE.get_hash_size()]),
+            Ok(TciMeasurement([0x5A; DPE_PROFILE.get_hash_size()]))
+        );
+
+        for len in [0, DPE_PROFILE.get_hash_size() - 1, bytes.len()] {
+            assert_eq!(
+                TciMeasurement::try_from_slice(&bytes[..len]),
+                Err(DpeErrorCode::InvalidArgument)
+            );
+        }
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut node = TciNodeData::new();
+        node.tci_cumulative = TciMeasurement([0x0F; DPE_PROFILE.get_tci_size()]);
+        let measurement = TciMeasurement([0xF5; DPE_PROFILE.get_tci_size()]);
+
+        node.extend(XorHasher([0; DPE_PROFILE.get_tci_size()], 0), &measurement)
+            .unwrap();
+        assert_eq!(node.tci_current, measurement);
+        assert_eq!(
+            node.tci_cumulative,
+            TciMeasurement([0x0F ^ 0xF5; DPE_PROFILE.get_tci_size()])
+        );
+
+        // A digest of the wrong size is rejected
+        struct ShortHasher;
+        impl Hasher for ShortHasher {
+            fn update(&mut self, _bytes: &[u8]) -> Result<(), CryptoError> {
+                Ok(())
+            }
+
+            fn finish(self) -> Result<Digest, CryptoError> {
+                CryptoBuf::new(&[0; 4])
+            }
+        }
+        assert_eq!(
+            node.extend(ShortHasher, &measurement),
+            Err(DpeErrorCode::InternalError)
+        );
+    }
+}
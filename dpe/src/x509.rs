@@ -6,19 +6,53 @@
 //! this functionality for a no_std environment.
 
 use crate::{
+    dpe_instance::{DpeEnv, DpeTypes},
     response::DpeErrorCode,
     tci::{TciMeasurement, TciNodeData},
-    DpeProfile, DPE_PROFILE,
+    DpeProfile, DPE_PROFILE, MAX_CERT_SIZE, MAX_HANDLES,
 };
 use bitflags::bitflags;
-use crypto::{EcdsaPub, EcdsaSig};
+use core::str;
+use crypto::{Crypto, CryptoBuf, CryptoError, Digest, EcdsaPub, EcdsaSig, Hasher};
+use platform::{Platform, StringType, GENERALIZED_TIME_SIZE, MAX_CHUNK_SIZE, MAX_DEVICE_UEID_SIZE};
+
+/// Maximum number of TCI nodes in a tcg-dice-MultiTcbInfo. A certificate
+/// measures at most one chain of contexts, so this matches the number of
+/// contexts.
+pub const MAX_TCI_NODES: usize = MAX_HANDLES;
+
+/// Maximum size of the DER encoded issuer name placed in a TBS. Platforms
+/// return the issuer in a `MAX_CHUNK_SIZE` buffer, but a certificate must fit
+/// in `MAX_CERT_SIZE` alongside the issuer, so longer issuers are rejected
+/// before encoding starts.
+pub const MAX_ISSUER_NAME_SIZE: usize = MAX_CHUNK_SIZE / 2;
+
+/// Maximum number of content octets in a DER encoded serial number. RFC 5280
+/// section 4.1.2.2 forbids longer serial numbers, so platforms deriving one
+/// from a digest must truncate it.
+pub const MAX_SERIAL_NUMBER_SIZE: usize = 20;
+
+/// Subject commonName of the certificates and CSRs CertifyKey issues
+pub(crate) const CERTIFY_KEY_SUBJECT_CN: &[u8] = b"DPE Leaf";
 
 pub enum DirectoryString<'a> {
     PrintableString(&'a [u8]),
     Utf8String(&'a [u8]),
+    /// Only meaningful for the serialNumber attribute, which some verifiers
+    /// expect as an IA5String
+    Ia5String(&'a [u8]),
 }
 
-impl DirectoryString<'_> {
+impl<'a> DirectoryString<'a> {
+    /// Wrap `val` in the variant for `string_type`, e.g. the type reported by
+    /// `Platform::get_name_string_type`
+    pub fn new(string_type: StringType, val: &'a [u8]) -> Self {
+        match string_type {
+            StringType::PrintableString => Self::PrintableString(val),
+            StringType::Utf8String => Self::Utf8String(val),
+        }
+    }
+
     pub fn len(&self) -> usize {
         self.bytes().len()
     }
@@ -31,37 +65,795 @@ impl DirectoryString<'_> {
         match self {
             Self::PrintableString(val) => val,
             Self::Utf8String(val) => val,
+            Self::Ia5String(val) => val,
         }
     }
 }
 
+/// Order of the attributes of an encoded `Name`
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NameOrder {
+    /// commonName, then serialNumber
+    #[default]
+    CnFirst,
+    /// serialNumber, then commonName, for matching issuers whose DN is
+    /// ordered this way
+    SerialFirst,
+}
+
 /// Type for specifying an X.509 RelativeDistinguisedName
 ///
-/// `serial` is expected to hold a hex string of the hash of the public key
+/// `serial` is expected to hold a hex string of the hash of the public key.
+/// Since the hex string is plain ASCII, it may be encoded either as a
+/// PrintableString or, where the verifier requires it, an IA5String. Either
+/// way it must match the encoding used by the issuer's certificate exactly,
+/// as must `order`.
 pub struct Name<'a> {
     pub cn: DirectoryString<'a>,
     pub serial: DirectoryString<'a>,
+    pub order: NameOrder,
+}
+
+impl<'a> Name<'a> {
+    /// Build a name whose serialNumber is the uppercase hex string of the hash
+    /// of `pubkey` in uncompressed form, as CertifyKey does for its subject
+    ///
+    /// The hex string is written to `serial_buf`, which must hold twice the
+    /// profile's hash size. It is encoded with the same string type as `cn`.
+    pub fn with_pubkey_serial(
+        cn: DirectoryString<'a>,
+        crypto: &mut impl Crypto,
+        pubkey: &EcdsaPub,
+        serial_buf: &'a mut [u8],
+    ) -> Result<Self, DpeErrorCode> {
+        let mut hasher = crypto.hash_initialize(DPE_PROFILE.alg_len())?;
+        hasher.update(&[0x04])?;
+        hasher.update(pubkey.x.bytes())?;
+        hasher.update(pubkey.y.bytes())?;
+        let digest = hasher.finish()?;
+
+        let serial_len = to_hex(digest.bytes(), serial_buf, /*uppercase=*/ true)?;
+        let serial = serial_buf
+            .get(..serial_len)
+            .ok_or(DpeErrorCode::InternalError)?;
+        let serial = match cn {
+            DirectoryString::PrintableString(_) => DirectoryString::PrintableString(serial),
+            DirectoryString::Utf8String(_) => DirectoryString::Utf8String(serial),
+            DirectoryString::Ia5String(_) => DirectoryString::Ia5String(serial),
+        };
+        Ok(Self {
+            cn,
+            serial,
+            order: NameOrder::default(),
+        })
+    }
+
+    /// Get the attribute types and values in encoding order
+    fn attributes(&self) -> [(&'static [u8], &DirectoryString<'a>); 2] {
+        let cn: (&'static [u8], _) = (&CertWriter::RDN_COMMON_NAME_OID, &self.cn);
+        let serial: (&'static [u8], _) = (&CertWriter::RDN_SERIALNUMBER_OID, &self.serial);
+        match self.order {
+            NameOrder::CnFirst => [cn, serial],
+            NameOrder::SerialFirst => [serial, cn],
+        }
+    }
+
+    /// DER-encode the name into `out`, e.g. to reuse a certificate's subject
+    /// as the issuer of the certificates it signs
+    ///
+    /// Returns the number of bytes written to `out`
+    pub fn to_der(&self, out: &mut [u8]) -> Result<usize, DpeErrorCode> {
+        CertWriter::new(out, true).encode_rdn(self)
+    }
+}
+
+/// RFC 5280 PolicyConstraints. Fields that are `None` are omitted.
+#[derive(Default, Clone, Copy)]
+pub struct PolicyConstraints {
+    pub require_explicit_policy: Option<u32>,
+    pub inhibit_policy_mapping: Option<u32>,
+}
+
+/// Preset for the set, criticality and ordering of certificate extensions
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum CertProfile {
+    /// TCG DICE default. Extensions are ordered tcg-dice-MultiTcbInfo,
+    /// tcg-dice-Ueid, basicConstraints, keyUsage, extendedKeyUsage. The
+    /// tcg-dice-* extensions are critical if the CertWriter was built with
+    /// `crit_dice`.
+    #[default]
+    Dice,
+    /// Compatible with Android Keystore attestation chain verification, which
+    /// rejects certificates with critical extensions it does not recognize.
+    /// Deviations from `Dice`:
+    ///
+    /// * basicConstraints, keyUsage and extendedKeyUsage come first, followed
+    ///   by tcg-dice-MultiTcbInfo and tcg-dice-Ueid.
+    /// * tcg-dice-* extensions are never critical, regardless of `crit_dice`.
+    /// * extendedKeyUsage is not critical, as its TCG key purposes are not
+    ///   recognized.
+    ///
+    /// basicConstraints and keyUsage keep their `Dice` shape and criticality.
+    AndroidAttestation,
+}
+
+/// Preset for the key purposes in the extendedKeyUsage of leaf certificates.
+/// CA certificates always carry tcg-dice-kp-eca (2.23.133.5.4.100.12).
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum KeyPurposes {
+    /// tcg-dice-kp-attestLoc (2.23.133.5.4.100.9), for keys attesting to the
+    /// DICE layer that holds them
+    #[default]
+    Dice,
+    /// For attestation identity keys in TPM-style flows:
+    ///
+    /// * tcg-kp-AIKCertificate (2.23.133.8.3), marking the key as an
+    ///   attestation identity key (id-AK)
+    /// * tcg-dice-kp-attestLoc (2.23.133.5.4.100.9), as for `Dice`
+    AttestationKey,
+    /// The selected TCG DICE key purposes, in ascending OID order. At least
+    /// one must be selected.
+    Custom(DiceKeyPurposes),
+    /// No extendedKeyUsage extension, for keys the TCG DICE key purposes do
+    /// not describe. The key is then limited by keyUsage alone.
+    Omitted,
+}
+
+/// TCG DICE key purposes (2.23.133.5.4.100.*) for `KeyPurposes::Custom`
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct DiceKeyPurposes(u8);
+
+bitflags! {
+    impl DiceKeyPurposes: u8 {
+        /// tcg-dice-kp-identityInit (2.23.133.5.4.100.6)
+        const IDENTITY_INIT = 1 << 0;
+        /// tcg-dice-kp-identityLoc (2.23.133.5.4.100.7)
+        const IDENTITY_LOC = 1 << 1;
+        /// tcg-dice-kp-attestInit (2.23.133.5.4.100.8)
+        const ATTEST_INIT = 1 << 2;
+        /// tcg-dice-kp-attestLoc (2.23.133.5.4.100.9)
+        const ATTEST_LOC = 1 << 3;
+        /// tcg-dice-kp-assertInit (2.23.133.5.4.100.10)
+        const ASSERT_INIT = 1 << 4;
+        /// tcg-dice-kp-assertLoc (2.23.133.5.4.100.11)
+        const ASSERT_LOC = 1 << 5;
+        /// tcg-dice-kp-eca (2.23.133.5.4.100.12)
+        const ECA = 1 << 6;
+    }
+}
+
+/// Role of a certified DPE key. Each role has one consistent keyUsage,
+/// basicConstraints and extendedKeyUsage combination, see
+/// `CertRole::extensions`. `MeasurementData::is_ca` remains a shorthand for
+/// `AttestationLeaf` and `EmbeddedCa`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CertRole {
+    /// Leaf key signing evidence about the DICE layer that holds it
+    ///
+    /// * keyUsage: digitalSignature
+    /// * basicConstraints: cA FALSE
+    /// * extendedKeyUsage: tcg-dice-kp-attestLoc
+    AttestationLeaf,
+    /// CA embedded in a DICE layer, certifying the keys of the layers after
+    /// it. Those may be CAs themselves, so the path length is unconstrained.
+    ///
+    /// * keyUsage: digitalSignature, keyCertSign
+    /// * basicConstraints: cA TRUE, no pathLenConstraint
+    /// * extendedKeyUsage: tcg-dice-kp-eca
+    EmbeddedCa,
+    /// CA that cross-signs another CA, such as a DPE CA bridged into an
+    /// existing PKI. It signs only certificates and CRLs, and the CA it
+    /// certifies may issue leaf certificates but no further CAs.
+    ///
+    /// * keyUsage: keyCertSign, cRLSign
+    /// * basicConstraints: cA TRUE, pathLenConstraint 1
+    /// * extendedKeyUsage: tcg-dice-kp-eca
+    CrossSigningCa,
+    /// Leaf key used only for key agreement, such as ECDH
+    ///
+    /// * keyUsage: keyAgreement
+    /// * basicConstraints: cA FALSE
+    /// * extendedKeyUsage: omitted, as no TCG DICE key purpose applies
+    KeyAgreement,
+}
+
+/// Extension configuration of a `CertRole`, as applied to `MeasurementData`
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct RoleExtensions {
+    pub key_usage: KeyUsageFlags,
+    pub is_ca: bool,
+    pub path_len_constraint: Option<u8>,
+    pub key_purposes: KeyPurposes,
+}
+
+impl CertRole {
+    /// Get the keyUsage, basicConstraints and extendedKeyUsage of the role
+    pub fn extensions(self) -> RoleExtensions {
+        match self {
+            CertRole::AttestationLeaf => RoleExtensions {
+                key_usage: KeyUsageFlags::DIGITAL_SIGNATURE,
+                is_ca: false,
+                path_len_constraint: None,
+                key_purposes: KeyPurposes::Custom(DiceKeyPurposes::ATTEST_LOC),
+            },
+            CertRole::EmbeddedCa => RoleExtensions {
+                key_usage: KeyUsageFlags::DIGITAL_SIGNATURE | KeyUsageFlags::KEY_CERT_SIGN,
+                is_ca: true,
+                path_len_constraint: None,
+                key_purposes: KeyPurposes::Custom(DiceKeyPurposes::ECA),
+            },
+            CertRole::CrossSigningCa => RoleExtensions {
+                key_usage: KeyUsageFlags::KEY_CERT_SIGN | KeyUsageFlags::CRL_SIGN,
+                is_ca: true,
+                path_len_constraint: Some(1),
+                key_purposes: KeyPurposes::Custom(DiceKeyPurposes::ECA),
+            },
+            CertRole::KeyAgreement => RoleExtensions {
+                key_usage: KeyUsageFlags::KEY_AGREEMENT,
+                is_ca: false,
+                path_len_constraint: None,
+                key_purposes: KeyPurposes::Omitted,
+            },
+        }
+    }
+}
+
+/// Hash algorithm identified in DICE FWIDs or CMS digests, independent of the
+/// profile's hash. FWID digests of another size than the profile's TCIs are
+/// passed in `MeasurementData::fwid_digests`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HashAlg {
+    Sha256,
+    Sha384,
+    Sha512,
+    /// SHA-512 truncated to 256 bits
+    Sha512_256,
+}
+
+/// FWID digests of a TCI node, used in place of its TCIs
+///
+/// Lets FWIDs carry digests of `MeasurementData::fwid_hash_alg` whose size
+/// differs from the profile's TCIs, such as SHA-384 measurements in a P-256
+/// certificate.
+#[derive(Clone, Copy, Default)]
+pub struct FwidDigests<'a> {
+    /// Digest of the current measurement
+    pub current: &'a [u8],
+    /// Digest of the cumulative measurement. Only used if the node's TcbInfo
+    /// includes the cumulative TCI.
+    pub cumulative: &'a [u8],
+}
+
+/// CMS SignerIdentifier of the key that signed a SignedData
+///
+/// SignerIdentifier ::= CHOICE {
+///    issuerAndSerialNumber IssuerAndSerialNumber,
+///    subjectKeyIdentifier [0] SubjectKeyIdentifier
+/// }
+#[derive(Clone, Copy)]
+pub enum SignerId<'a> {
+    /// DER encoded issuer Name and big-endian serial number of the signer's
+    /// certificate. Encoded as CMS version 1.
+    IssuerAndSerialNumber {
+        issuer_name: &'a [u8],
+        serial_number: &'a [u8],
+    },
+    /// Key identifier from the subjectKeyIdentifier extension of the signer's
+    /// certificate. Encoded as CMS version 3.
+    SubjectKeyIdentifier(&'a [u8]),
+    /// Complete DER IssuerAndSerialNumber of the signer's certificate, as
+    /// written by `CertWriter::encode_issuer_and_serial_number`. Lets a signer
+    /// of many messages encode it once. Encoded as CMS version 1, identically
+    /// to `IssuerAndSerialNumber`.
+    IssuerAndSerialNumberDer(&'a [u8]),
+}
+
+impl SignerId<'_> {
+    /// Get the CMSVersion RFC 5652 requires for the SignerInfo, and for a
+    /// SignedData with id-data content and this single signer
+    fn get_cms_version(&self) -> u64 {
+        match self {
+            SignerId::IssuerAndSerialNumber { .. } | SignerId::IssuerAndSerialNumberDer(_) => {
+                CertWriter::CMS_V1
+            }
+            SignerId::SubjectKeyIdentifier(_) => CertWriter::CMS_V3,
+        }
+    }
+}
+
+/// Encoding of the certificate validity dates
+///
+/// RFC 5280 section 4.1.2.5 requires UTCTime for dates through 2049 and
+/// GeneralizedTime from 2050 onwards. DICE attestation profiles instead
+/// require GeneralizedTime for every date in a DPE certificate, so verifiers
+/// only need to parse a single time format. The two rules conflict for any
+/// date before 2050, and DPE follows DICE unless asked otherwise.
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum TimeEncoding {
+    /// GeneralizedTime regardless of the year. DPE never emits UTCTime.
+    #[default]
+    AlwaysGeneralized,
+    /// The RFC 5280 year-based rule. UTCTime for years 1950 through 2049,
+    /// GeneralizedTime otherwise.
+    Rfc5280,
+}
+
+/// X.509 certificate version
+///
+/// Only v3 certificates have Extensions, so v1 and v2 certificates cannot
+/// carry TCI nodes or any other extension input, other than the default
+/// `include_ueid`, which they ignore. v2 added the issuer and subject unique
+/// identifiers.
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum CertVersion {
+    V1,
+    V2,
+    #[default]
+    V3,
+}
+
+impl CertVersion {
+    /// The value of the TBS version field
+    fn value(self) -> u64 {
+        match self {
+            CertVersion::V1 => 0,
+            CertVersion::V2 => 1,
+            CertVersion::V3 => 2,
+        }
+    }
+}
+
+/// Encoding of the EC point in an ECDSA SubjectPublicKeyInfo
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum PointFormat {
+    /// `0x04 || x || y`. Many verifiers only accept this form.
+    #[default]
+    Uncompressed,
+    /// `0x02 || x` if y is even, `0x03 || x` if it is odd
+    Compressed,
+}
+
+/// Encoding of the tcg-dice-Ueid extension value
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum UeidEncoding {
+    /// The TcgUeid SEQUENCE wrapping the ueid OCTET STRING, as defined by the
+    /// TCG DICE Attestation Architecture. Verifiers following the
+    /// specification expect this form.
+    #[default]
+    Sequence,
+    /// The ueid OCTET STRING alone. Only for older verifiers that read the
+    /// extension value as the UEID itself and reject the SEQUENCE.
+    OctetString,
+}
+
+/// Certificate validity period. Both dates are GeneralizedTime strings of the
+/// form `YYYYMMDDHHMMSSZ`.
+#[derive(Clone, Copy)]
+pub struct Validity<'a> {
+    pub not_before: &'a str,
+    pub not_after: &'a str,
+}
+
+impl<'a> Validity<'a> {
+    /// Build a validity period from the raw dates returned by
+    /// `Platform::get_cert_validity`
+    ///
+    /// Returns `InvalidArgument` if either date is not ASCII.
+    pub fn from_platform(
+        not_before: &'a [u8; GENERALIZED_TIME_SIZE],
+        not_after: &'a [u8; GENERALIZED_TIME_SIZE],
+    ) -> Result<Self, DpeErrorCode> {
+        Ok(Self {
+            not_before: str::from_utf8(not_before).map_err(|_| DpeErrorCode::InvalidArgument)?,
+            not_after: str::from_utf8(not_after).map_err(|_| DpeErrorCode::InvalidArgument)?,
+        })
+    }
+}
+
+/// What an issuance log records about an encoded TBSCertificate, see
+/// `CertWriter::encode_ecdsa_tbs_record`
+pub struct TbsRecord {
+    /// Number of bytes written for the TBSCertificate
+    pub tbs_len: usize,
+    serial: [u8; MAX_SERIAL_NUMBER_SIZE],
+    serial_len: usize,
+    /// Digest of the TBSCertificate with the profile's hash, which is what
+    /// the issuer signs
+    pub tbs_digest: Digest,
+}
+
+impl TbsRecord {
+    /// The content octets of the serialNumber INTEGER as encoded, with
+    /// leading zeros stripped and a zero byte prepended if needed
+    pub fn serial(&self) -> &[u8] {
+        &self.serial[..self.serial_len]
+    }
+}
+
+/// Write `bytes` to `out` as a hex string, two characters per byte
+///
+/// Returns the number of characters written, or `BufferTooSmall` with the
+/// needed size if `out` cannot hold them.
+pub fn to_hex(bytes: &[u8], out: &mut [u8], uppercase: bool) -> Result<usize, DpeErrorCode> {
+    let hex_chars: &[u8; 16] = if uppercase {
+        b"0123456789ABCDEF"
+    } else {
+        b"0123456789abcdef"
+    };
+
+    let len = bytes.len() * 2;
+    let out = out
+        .get_mut(..len)
+        .ok_or(DpeErrorCode::BufferTooSmall(len as u32))?;
+    for (byte, chars) in bytes.iter().zip(out.chunks_exact_mut(2)) {
+        chars[0] = hex_chars[usize::from(byte >> 4)];
+        chars[1] = hex_chars[usize::from(byte & 0xF)];
+    }
+
+    Ok(len)
+}
+
+/// Format `unix_secs`, the number of seconds since 1970-01-01T00:00:00Z, as a
+/// GeneralizedTime string of the form `YYYYMMDDHHMMSSZ`
+///
+/// Returns `InvalidArgument` if the time is after the end of year 9999.
+pub fn format_generalized_time(
+    unix_secs: u64,
+    out: &mut [u8; GENERALIZED_TIME_SIZE],
+) -> Result<(), DpeErrorCode> {
+    const SECS_PER_DAY: u64 = 24 * 60 * 60;
+    // 9999-12-31T23:59:59Z
+    const MAX_UNIX_SECS: u64 = 253_402_300_799;
+
+    if unix_secs > MAX_UNIX_SECS {
+        return Err(DpeErrorCode::InvalidArgument);
+    }
+    let secs_of_day = unix_secs % SECS_PER_DAY;
+
+    // Convert days since the epoch to a proleptic Gregorian date. Years are
+    // counted from March so the leap day falls at the end of the year, and
+    // split into 400-year eras of 146097 days.
+    let days = unix_secs / SECS_PER_DAY + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+    let fields = [
+        (year, 4),
+        (month, 2),
+        (day, 2),
+        (secs_of_day / 3600, 2),
+        (secs_of_day / 60 % 60, 2),
+        (secs_of_day % 60, 2),
+    ];
+    let mut offset = 0;
+    for (mut value, width) in fields {
+        let field = out
+            .get_mut(offset..offset + width)
+            .ok_or(DpeErrorCode::InternalError)?;
+        for digit in field.iter_mut().rev() {
+            *digit = b'0' + (value % 10) as u8;
+            value /= 10;
+        }
+        offset += width;
+    }
+    *out.last_mut().ok_or(DpeErrorCode::InternalError)? = b'Z';
+
+    Ok(())
 }
 
 pub struct MeasurementData<'a> {
     pub label: &'a [u8],
     pub tci_nodes: &'a [TciNodeData],
     pub is_ca: bool,
+    /// Whether TcbInfos include the cumulative TCI, unless overridden in
+    /// `extend_tci_overrides`. Defaults to `DPE_PROFILE.supports_extend_tci()`.
     pub supports_extend_tci: bool,
+    /// Per-node overrides of `supports_extend_tci`. Entry `i` belongs to
+    /// `tci_nodes[i]`. Nodes without an entry, or whose entry is `None`,
+    /// follow `supports_extend_tci`.
+    pub extend_tci_overrides: &'a [Option<bool>],
+    /// Optional TBS issuerUniqueID. Only used for certificates.
+    pub issuer_unique_id: Option<&'a [u8]>,
+    /// Optional TBS subjectUniqueID. Only used for certificates.
+    pub subject_unique_id: Option<&'a [u8]>,
+    /// Additional FWIDs for each TCI node, appended after the current and
+    /// cumulative measurements. Entry `i` belongs to `tci_nodes[i]`. Nodes
+    /// without an entry have no additional FWIDs.
+    pub extra_fwids: &'a [&'a [TciMeasurement]],
+    /// Bitmask of the TCI nodes whose TcbInfo has no fwids, such as
+    /// configuration-only entries described by vendorInfo and type alone.
+    /// Bit `i` is `tci_nodes[i]`. These nodes cannot have additional FWIDs.
+    pub nodes_without_fwids: u32,
+    /// Complete DER encoded Extensions emitted verbatim after the DICE
+    /// extensions. Each entry must be a single SEQUENCE.
+    pub raw_extensions: &'a [&'a [u8]],
+    /// Optional critical policyConstraints extension. Only used for CA
+    /// certificates.
+    pub policy_constraints: Option<PolicyConstraints>,
+    /// Optional critical inhibitAnyPolicy extension SkipCerts. Only used for
+    /// CA certificates.
+    pub inhibit_any_policy: Option<u32>,
+    /// Optional PKCS #9 challengePassword attribute. Only used for CSRs.
+    pub challenge_password: Option<&'a [u8]>,
+    /// Extension preset
+    pub cert_profile: CertProfile,
+    /// Hash algorithm identified in FWIDs. The profile's hash if `None`.
+    pub fwid_hash_alg: Option<HashAlg>,
+    /// FWID digests used in place of the TCIs. Entry `i` belongs to
+    /// `tci_nodes[i]`. Nodes without an entry use their TCIs.
+    pub fwid_digests: &'a [FwidDigests<'a>],
+    /// Signature algorithm OID for the TBS signature field, for certificates
+    /// signed outside of DPE with `encode_certificate`. The profile's ECDSA
+    /// algorithm if `None`. Only used for certificates.
+    pub signature_alg_oid: Option<&'a [u8]>,
+    /// DER encoded SubjectPublicKeyInfo emitted in place of the ECDSA public
+    /// key, for keys DPE does not encode natively
+    pub subject_public_key_info: Option<&'a [u8]>,
+    /// Validity period. The default DPE validity period if `None`. Only used
+    /// for certificates.
+    pub validity: Option<Validity<'a>>,
+    /// Encoding of the validity dates
+    pub time_encoding: TimeEncoding,
+    /// Whether to include the tcg-dice-Ueid extension. Deployments that must
+    /// not expose a stable device identifier can leave it out. The
+    /// tcg-dice-MultiTcbInfo, basicConstraints and keyUsage extensions are
+    /// always included, so Extensions is never empty. Ignored for v1 and v2
+    /// certificates, which have no Extensions.
+    pub include_ueid: bool,
+    /// Whether basicConstraints is critical in leaf certificates. RFC 5280
+    /// allows it to be non-critical in end-entity certificates, which some
+    /// verifiers expect. It is always critical in CA certificates.
+    pub leaf_basic_constraints_critical: bool,
+    /// keyUsage bits. digitalSignature, plus keyCertSign for CA
+    /// certificates, if `None`.
+    pub key_usage: Option<KeyUsageFlags>,
+    /// basicConstraints pathLenConstraint. Only used for CA certificates.
+    pub path_len_constraint: Option<u8>,
+    /// Certificate version. Unique IDs need v2 or later, and everything
+    /// encoded as an extension needs v3. Only used for certificates.
+    pub version: CertVersion,
+    /// Encoding of the subject public key point. Not used with
+    /// `subject_public_key_info`.
+    pub point_format: PointFormat,
+    /// extendedKeyUsage key purposes. Only used for leaf certificates.
+    pub key_purposes: KeyPurposes,
+    /// Whether the TCI node is described by a tcg-dice-TcbInfo extension
+    /// instead of a tcg-dice-MultiTcbInfo, for verifiers that expect it.
+    /// Requires exactly one TCI node.
+    pub single_tcb_info: bool,
+    /// Encoding of the tcg-dice-Ueid extension value
+    pub ueid_encoding: UeidEncoding,
+    /// Whether the certificate is a self-signed root. The issuer must then be
+    /// byte-identical to the encoded subject. Only used for certificates.
+    pub self_signed: bool,
+}
+
+impl Default for MeasurementData<'_> {
+    fn default() -> Self {
+        Self {
+            label: &[],
+            tci_nodes: &[],
+            is_ca: false,
+            supports_extend_tci: DPE_PROFILE.supports_extend_tci(),
+            extend_tci_overrides: &[],
+            issuer_unique_id: None,
+            subject_unique_id: None,
+            extra_fwids: &[],
+            nodes_without_fwids: 0,
+            raw_extensions: &[],
+            policy_constraints: None,
+            inhibit_any_policy: None,
+            challenge_password: None,
+            cert_profile: CertProfile::default(),
+            fwid_hash_alg: None,
+            fwid_digests: &[],
+            signature_alg_oid: None,
+            subject_public_key_info: None,
+            validity: None,
+            time_encoding: TimeEncoding::default(),
+            include_ueid: true,
+            leaf_basic_constraints_critical: true,
+            key_usage: None,
+            path_len_constraint: None,
+            version: CertVersion::default(),
+            point_format: PointFormat::default(),
+            key_purposes: KeyPurposes::default(),
+            single_tcb_info: false,
+            ueid_encoding: UeidEncoding::default(),
+            self_signed: false,
+        }
+    }
+}
+
+impl MeasurementData<'_> {
+    /// Set the keyUsage, basicConstraints and extendedKeyUsage of `role`,
+    /// replacing `is_ca`, `key_usage`, `path_len_constraint` and
+    /// `key_purposes`
+    pub fn apply_role(&mut self, role: CertRole) {
+        let extensions = role.extensions();
+        self.is_ca = extensions.is_ca;
+        self.key_usage = Some(extensions.key_usage);
+        self.path_len_constraint = extensions.path_len_constraint;
+        self.key_purposes = extensions.key_purposes;
+    }
+
+    /// Check that the measurements can be encoded for `profile`
+    ///
+    /// * `version` must be able to hold the unique IDs and extensions. The
+    ///   remaining checks only apply to v3, as the fields they cover feed
+    ///   extensions that earlier versions reject.
+    /// * There must be at least one TCI node, and at most `MAX_TCI_NODES`.
+    ///   There must be exactly one if `single_tcb_info` is set.
+    /// * Every entry of `extra_fwids` and `fwid_digests` must belong to a TCI
+    ///   node with fwids.
+    /// * `extend_tci_overrides` must not have more entries than `tci_nodes`.
+    /// * Every FWID digest must be the size of the FWID hash algorithm's
+    ///   digests.
+    /// * If `include_ueid`, the label must be a valid tcg-dice-Ueid.
+    /// * `key_usage` must have at least one bit set.
+    /// * Leaf certificates must have at least one key purpose.
+    ///
+    /// Fails with `MaxTcis` if there are too many TCI nodes and
+    /// `InvalidArgument` for everything else.
+    pub fn validate(&self, profile: DpeProfile) -> Result<(), DpeErrorCode> {
+        CertWriter::validate_cert_version(self)?;
+        if self.version != CertVersion::V3 {
+            return Ok(());
+        }
+
+        if self.tci_nodes.is_empty() {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+        if self.tci_nodes.len() > MAX_TCI_NODES {
+            return Err(DpeErrorCode::MaxTcis);
+        }
+        if self.single_tcb_info && self.tci_nodes.len() != 1 {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        if self.extra_fwids.len() > self.tci_nodes.len()
+            || self.fwid_digests.len() > self.tci_nodes.len()
+            || self.extend_tci_overrides.len() > self.tci_nodes.len()
+        {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+        let misplaced_fwids = self
+            .extra_fwids
+            .iter()
+            .enumerate()
+            .any(|(i, fwids)| !fwids.is_empty() && !self.has_fwids(i))
+            || (0..self.fwid_digests.len()).any(|i| !self.has_fwids(i));
+        if misplaced_fwids {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        // Without `fwid_hash_alg`, FWIDs use the profile's hash
+        let digest_size =
+            CertWriter::get_hash_oid_digest_size(CertWriter::get_hash_oid(self.fwid_hash_alg))?;
+        for (i, node) in self.tci_nodes.iter().enumerate() {
+            if !self.has_fwids(i) {
+                continue;
+            }
+            let (current, cumulative) =
+                CertWriter::resolve_fwid_digests(node, self.get_fwid_digests(i));
+            let mut digests = core::iter::once(current)
+                .chain(self.supports_extend_tci(i).then_some(cumulative))
+                .chain(self.get_extra_fwids(i).iter().map(|fwid| &fwid.0[..]));
+            if digests.any(|digest| digest.len() != digest_size) {
+                return Err(DpeErrorCode::InvalidArgument);
+            }
+        }
+
+        if self.include_ueid && (self.label.is_empty() || self.label.len() > profile.ueid_size()) {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        if self.key_usage.is_some_and(|key_usage| key_usage.is_empty()) {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        if !self.is_ca && self.key_purposes == KeyPurposes::Custom(DiceKeyPurposes::empty()) {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
+    /// Get the additional FWIDs of the TCI node at `index`
+    fn get_extra_fwids(&self, index: usize) -> &[TciMeasurement] {
+        self.extra_fwids.get(index).copied().unwrap_or(&[])
+    }
+
+    /// Get the FWID digests that replace the TCIs of the TCI node at `index`
+    fn get_fwid_digests(&self, index: usize) -> Option<&FwidDigests> {
+        self.fwid_digests.get(index)
+    }
+
+    /// Whether the TcbInfo of the TCI node at `index` includes the cumulative
+    /// TCI
+    fn supports_extend_tci(&self, index: usize) -> bool {
+        self.extend_tci_overrides
+            .get(index)
+            .copied()
+            .flatten()
+            .unwrap_or(self.supports_extend_tci)
+    }
+
+    /// Whether the TcbInfo of the TCI node at `index` has fwids
+    fn has_fwids(&self, index: usize) -> bool {
+        u32::try_from(index)
+            .ok()
+            .and_then(|index| self.nodes_without_fwids.checked_shr(index))
+            .map_or(true, |bits| bits & 1 == 0)
+    }
+
+    /// Get the keyUsage bits of the certificate
+    fn get_key_usage(&self) -> KeyUsageFlags {
+        self.key_usage.unwrap_or(if self.is_ca {
+            KeyUsageFlags::DIGITAL_SIGNATURE | KeyUsageFlags::KEY_CERT_SIGN
+        } else {
+            KeyUsageFlags::DIGITAL_SIGNATURE
+        })
+    }
+
+    /// Get the OID of the TBS signature algorithm
+    fn get_signature_alg_oid(&self) -> &[u8] {
+        self.signature_alg_oid.unwrap_or(CertWriter::ECDSA_OID)
+    }
+
+    /// Get the validity period of the certificate
+    fn get_validity(&self) -> Validity {
+        self.validity.unwrap_or(Validity {
+            not_before: CertWriter::NOT_BEFORE,
+            not_after: CertWriter::NOT_AFTER,
+        })
+    }
 }
 
 pub struct CertWriter<'a> {
     certificate: &'a mut [u8],
     offset: usize,
     crit_dice: bool,
+    /// Offset of the first TcbInfo of an in-progress incremental MultiTcbInfo
+    multi_tcb_info_start: Option<usize>,
+    /// Offset of the eContent of an in-progress incremental CMS message
+    cms_econtent_start: Option<usize>,
+    /// Whether top-level structures back-patch their sizes instead of
+    /// computing them up-front
+    single_pass: bool,
+    /// Test hook that corrupts top-level structures before they are checked
+    /// by `debug_check_der`
+    #[cfg(test)]
+    corrupt_der: bool,
 }
 
-pub struct KeyUsageFlags(u8);
+/// KeyUsage bits, laid out as the big-endian value bytes of the KeyUsage BIT
+/// STRING
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct KeyUsageFlags(u16);
 
 bitflags! {
-    impl KeyUsageFlags: u8 {
-        const DIGITAL_SIGNATURE = 0b1000_0000;
-        const KEY_CERT_SIGN = 0b0000_0100;
+    impl KeyUsageFlags: u16 {
+        const DIGITAL_SIGNATURE = 0b1000_0000_0000_0000;
+        const NON_REPUDIATION = 0b0100_0000_0000_0000;
+        const KEY_ENCIPHERMENT = 0b0010_0000_0000_0000;
+        const DATA_ENCIPHERMENT = 0b0001_0000_0000_0000;
+        const KEY_AGREEMENT = 0b0000_1000_0000_0000;
+        const KEY_CERT_SIGN = 0b0000_0100_0000_0000;
+        const CRL_SIGN = 0b0000_0010_0000_0000;
+        const ENCIPHER_ONLY = 0b0000_0001_0000_0000;
+        const DECIPHER_ONLY = 0b0000_0000_1000_0000;
     }
 }
 
@@ -73,6 +865,8 @@ impl CertWriter<'_> {
     const OID_TAG: u8 = 0x6;
     const UTF8_STRING_TAG: u8 = 0xC;
     const PRINTABLE_STRING_TAG: u8 = 0x13;
+    const IA5_STRING_TAG: u8 = 0x16;
+    const UTC_TIME_TAG: u8 = 0x17;
     const GENERALIZE_TIME_TAG: u8 = 0x18;
     const SEQUENCE_TAG: u8 = 0x30;
     const SEQUENCE_OF_TAG: u8 = 0x30;
@@ -84,8 +878,8 @@ impl CertWriter<'_> {
     const CONTEXT_SPECIFIC: u8 = 0x80; // Used for Implicit/Explicit tags
     const CONSTRUCTED: u8 = 0x20; // SET{OF} and SEQUENCE{OF} have this bit set
 
-    const X509_V3: u64 = 2;
     const CMS_V1: u64 = 1;
+    const CMS_V3: u64 = 3;
     const CSR_V0: u64 = 0;
 
     const ECDSA_OID: &[u8] = match DPE_PROFILE {
@@ -105,27 +899,69 @@ impl CertWriter<'_> {
     };
 
     const HASH_OID: &[u8] = match DPE_PROFILE {
-        // SHA256
-        DpeProfile::P256Sha256 => &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01],
-        // SHA384
-        DpeProfile::P384Sha384 => &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02],
+        DpeProfile::P256Sha256 => Self::SHA256_OID,
+        DpeProfile::P384Sha384 => Self::SHA384_OID,
     };
 
+    // SHA256 2.16.840.1.101.3.4.2.1
+    const SHA256_OID: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+
+    // SHA384 2.16.840.1.101.3.4.2.2
+    const SHA384_OID: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02];
+
+    // SHA512 2.16.840.1.101.3.4.2.3
+    const SHA512_OID: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03];
+
+    // SHA512/256 2.16.840.1.101.3.4.2.6
+    const SHA512_256_OID: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x06];
+
     const RDN_COMMON_NAME_OID: [u8; 3] = [0x55, 0x04, 0x03];
     const RDN_SERIALNUMBER_OID: [u8; 3] = [0x55, 0x04, 0x05];
 
+    // tcg-dice-TcbInfo 2.23.133.5.4.1
+    const TCBINFO_OID: &[u8] = &[0x67, 0x81, 0x05, 0x05, 0x04, 0x01];
+
     // tcg-dice-MultiTcbInfo 2.23.133.5.4.5
     const MULTI_TCBINFO_OID: &[u8] = &[0x67, 0x81, 0x05, 0x05, 0x04, 0x05];
 
     // tcg-dice-Ueid 2.23.133.5.4.4
     const UEID_OID: &[u8] = &[0x67, 0x81, 0x05, 0x05, 0x04, 0x04];
 
-    // tcg-dice-kp-eca 2.23.133.5.4.100.12
-    const ECA_OID: &[u8] = &[0x67, 0x81, 0x05, 0x05, 0x04, 0x64, 0x0C];
+    // tcg-dice-kp-identityInit 2.23.133.5.4.100.6
+    const IDENTITY_INIT_OID: &[u8] = &[0x67, 0x81, 0x05, 0x05, 0x04, 0x64, 0x06];
+
+    // tcg-dice-kp-identityLoc 2.23.133.5.4.100.7
+    const IDENTITY_LOC_OID: &[u8] = &[0x67, 0x81, 0x05, 0x05, 0x04, 0x64, 0x07];
+
+    // tcg-dice-kp-attestInit 2.23.133.5.4.100.8
+    const ATTEST_INIT_OID: &[u8] = &[0x67, 0x81, 0x05, 0x05, 0x04, 0x64, 0x08];
 
     // tcg-dice-kp-attestLoc 2.23.133.5.4.100.9
     const ATTEST_LOC_OID: &[u8] = &[0x67, 0x81, 0x05, 0x05, 0x04, 0x64, 0x09];
 
+    // tcg-dice-kp-assertInit 2.23.133.5.4.100.10
+    const ASSERT_INIT_OID: &[u8] = &[0x67, 0x81, 0x05, 0x05, 0x04, 0x64, 0x0A];
+
+    // tcg-dice-kp-assertLoc 2.23.133.5.4.100.11
+    const ASSERT_LOC_OID: &[u8] = &[0x67, 0x81, 0x05, 0x05, 0x04, 0x64, 0x0B];
+
+    // tcg-dice-kp-eca 2.23.133.5.4.100.12
+    const ECA_OID: &[u8] = &[0x67, 0x81, 0x05, 0x05, 0x04, 0x64, 0x0C];
+
+    // OIDs of each DiceKeyPurposes flag, in ascending OID order
+    const DICE_KEY_PURPOSE_OIDS: &'static [(DiceKeyPurposes, &'static [u8])] = &[
+        (DiceKeyPurposes::IDENTITY_INIT, Self::IDENTITY_INIT_OID),
+        (DiceKeyPurposes::IDENTITY_LOC, Self::IDENTITY_LOC_OID),
+        (DiceKeyPurposes::ATTEST_INIT, Self::ATTEST_INIT_OID),
+        (DiceKeyPurposes::ATTEST_LOC, Self::ATTEST_LOC_OID),
+        (DiceKeyPurposes::ASSERT_INIT, Self::ASSERT_INIT_OID),
+        (DiceKeyPurposes::ASSERT_LOC, Self::ASSERT_LOC_OID),
+        (DiceKeyPurposes::ECA, Self::ECA_OID),
+    ];
+
+    // tcg-kp-AIKCertificate 2.23.133.8.3
+    const AIK_CERTIFICATE_OID: &[u8] = &[0x67, 0x81, 0x05, 0x08, 0x03];
+
     // RFC 5280 2.5.29.19
     const BASIC_CONSTRAINTS_OID: &[u8] = &[0x55, 0x1D, 0x13];
 
@@ -135,6 +971,12 @@ impl CertWriter<'_> {
     // RFC 5280 2.5.29.37
     const EXTENDED_KEY_USAGE_OID: &[u8] = &[0x55, 0x1D, 0x25];
 
+    // RFC 5280 2.5.29.36
+    const POLICY_CONSTRAINTS_OID: &[u8] = &[0x55, 0x1D, 0x24];
+
+    // RFC 5280 2.5.29.54
+    const INHIBIT_ANY_POLICY_OID: &[u8] = &[0x55, 0x1D, 0x36];
+
     // RFC 5652 1.2.840.113549.1.7.2
     const ID_SIGNED_DATA_OID: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x07, 0x02];
 
@@ -144,6 +986,36 @@ impl CertWriter<'_> {
     // RFC 2985 1.2.840.113549.1.9.14
     const EXTENSION_REQUEST_OID: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x09, 0x0E];
 
+    // RFC 2985 1.2.840.113549.1.9.7
+    const CHALLENGE_PASSWORD_OID: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x09, 0x07];
+
+    // DPE holder reference 2.25.186599487716995261381330353687416476417
+    //
+    // There is no standard extension linking a certificate to another
+    // certificate by issuer and serial number, so this is a UUID-based OID
+    // (ITU-T X.667, UUID 8c61c10c-b511-4dce-ac5f-13a128a71301), which does
+    // not need to be registered.
+    const HOLDER_REFERENCE_OID: &[u8] = &[
+        0x69, 0x82, 0x98, 0xE1, 0xE0, 0xC3, 0x96, 0xD1, 0x8A, 0xB7, 0x9D, 0xAC, 0xAF, 0xC4, 0xF4,
+        0x92, 0xC5, 0x9C, 0xA6, 0x01,
+    ];
+
+    // DPE EAT claims 2.25.184124198755465424310751390438906145320
+    //
+    // A UUID-based OID (ITU-T X.667, UUID 8a8507e1-a73d-4e38-9f91-65ac1bb82a28)
+    // for an extension whose value is a CBOR encoded Entity Attestation Token
+    // claims set (RFC 9711), carried as an OCTET STRING.
+    const EAT_CLAIMS_OID: &[u8] = &[
+        0x69, 0x82, 0x95, 0x85, 0x83, 0xF8, 0xB4, 0xF3, 0xEA, 0xB8, 0xF1, 0x9F, 0xC8, 0xD9, 0xB5,
+        0xC1, 0xDD, 0xE0, 0xD4, 0x28,
+    ];
+
+    // ITU-T X.509 (10/2019) altSignatureAlgorithm 2.5.29.73
+    const ALT_SIGNATURE_ALGORITHM_OID: &[u8] = &[0x55, 0x1D, 0x49];
+
+    // ITU-T X.509 (10/2019) altSignatureValue 2.5.29.74
+    const ALT_SIGNATURE_VALUE_OID: &[u8] = &[0x55, 0x1D, 0x4A];
+
     // All DPE certs are valid from January 1st, 2023 00:00:00 until
     // December 31st, 9999 23:59:59
     const NOT_BEFORE: &str = "20230227000000Z";
@@ -158,9 +1030,36 @@ impl CertWriter<'_> {
             certificate: cert,
             offset: 0,
             crit_dice,
+            multi_tcb_info_start: None,
+            cms_econtent_start: None,
+            single_pass: false,
+            #[cfg(test)]
+            corrupt_der: false,
+        }
+    }
+
+    /// Build new CertWriter that writes output to `cert` in a single pass
+    ///
+    /// Instead of computing the size of certificates, TBSs, CSRs and their
+    /// extensions before encoding them, a maximum-width size placeholder is
+    /// reserved, the contents are encoded and the size is then back-patched.
+    /// The output is identical to `CertWriter::new`, but `cert` needs up to
+    /// two bytes of slack past the end of the encoding for the placeholder.
+    ///
+    /// See `CertWriter::new` for `crit_dice`.
+    pub fn new_single_pass(cert: &mut [u8], crit_dice: bool) -> CertWriter {
+        CertWriter {
+            single_pass: true,
+            ..Self::new(cert, crit_dice)
         }
     }
 
+    /// Get the bytes encoded so far, so callers do not need to track the
+    /// number of bytes written by each encode call
+    pub fn written(&self) -> &[u8] {
+        &self.certificate[..self.offset]
+    }
+
     /// Calculate the number of bytes the ASN.1 size field will be
     fn get_size_width(size: usize) -> Result<usize, DpeErrorCode> {
         if size <= 127 {
@@ -174,11 +1073,173 @@ impl CertWriter<'_> {
         }
     }
 
+    /// Decode the tag and size field at the start of a DER blob
+    ///
+    /// Returns the tag, the length of the tag and size field, and the
+    /// declared content length.
+    fn decode_header(der: &[u8]) -> Result<(u8, usize, usize), DpeErrorCode> {
+        let (&tag, rest) = der.split_first().ok_or(DpeErrorCode::InvalidArgument)?;
+        let (&first, rest) = rest.split_first().ok_or(DpeErrorCode::InvalidArgument)?;
+
+        // Short form
+        if first & 0x80 == 0 {
+            return Ok((tag, 2, first as usize));
+        }
+
+        // Long form. Only sizes the size field can encode are accepted.
+        let width = (first & 0x7f) as usize;
+        if width == 0 || width > 2 || rest.len() < width {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+        let size = rest[..width]
+            .iter()
+            .fold(0usize, |acc, &b| (acc << 8) | b as usize);
+
+        Ok((tag, 2 + width, size))
+    }
+
+    /// Check that a header decoded by `decode_header` is in DER form: a
+    /// low-tag-number tag and a size field of minimal width
+    fn is_der_header(tag: u8, header_len: usize, size: usize) -> bool {
+        tag & 0x1f != 0x1f && Self::get_size_width(size).map_or(false, |w| header_len == 1 + w)
+    }
+
+    /// Check that caller-supplied `der` is exactly one well-formed DER
+    /// element before it is spliced into the output
+    ///
+    /// The element must span the whole slice, and it and every element nested
+    /// in it must have a DER header and fit within its parent. The contents of
+    /// primitive elements are not checked.
+    fn validate_der_well_formed_single_element(der: &[u8]) -> Result<(), DpeErrorCode> {
+        let (_, header_len, size) = Self::decode_header(der)?;
+        if header_len.checked_add(size) != Some(der.len()) {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        Self::validate_der(der).map_err(|_| DpeErrorCode::InvalidArgument)
+    }
+
+    /// Check that `der` is a single DER SEQUENCE spanning the whole slice,
+    /// as expected of an encoded Name
+    fn validate_name_der(der: &[u8]) -> Result<(), DpeErrorCode> {
+        Self::validate_der_well_formed_single_element(der)?;
+        if der[0] != Self::SEQUENCE_TAG {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
+    /// Check that `der` is a sequence of well-formed DER elements, recursing
+    /// into constructed ones. The contents of primitive elements, such as
+    /// encapsulated OCTET STRINGs, are not checked.
+    ///
+    /// Returns the offset of the first malformed element on failure.
+    fn validate_der(der: &[u8]) -> Result<(), usize> {
+        let mut offset = 0;
+        while let Some(element) = der.get(offset..).filter(|rest| !rest.is_empty()) {
+            let (tag, header_len, size) = Self::decode_header(element).map_err(|_| offset)?;
+            if !Self::is_der_header(tag, header_len, size) {
+                return Err(offset);
+            }
+            let contents = header_len
+                .checked_add(size)
+                .and_then(|end| element.get(header_len..end))
+                .ok_or(offset)?;
+            if tag & Self::CONSTRUCTED != 0 {
+                Self::validate_der(contents).map_err(|inner| offset + header_len + inner)?;
+            }
+            offset += header_len + size;
+        }
+
+        Ok(())
+    }
+
+    /// Debug-only check that the top-level structure of `len` bytes written
+    /// at `start` is well-formed DER, to catch encoder bugs early. Panics with
+    /// the offset of the first malformed element. Does nothing without
+    /// `debug_assertions`.
+    fn debug_check_der(&mut self, start: usize, len: usize) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+
+        #[cfg(test)]
+        if self.corrupt_der {
+            // Grow the first nested element past the end of its parent
+            let (_, header_len, _) = Self::decode_header(&self.certificate[start..]).unwrap();
+            self.certificate[start + header_len + 1] += 1;
+        }
+
+        if let Err(offset) = Self::validate_der(&self.certificate[start..start + len]) {
+            panic!("malformed DER at offset {offset}");
+        }
+    }
+
+    /// Split the DER element at the start of `der` from the rest
+    ///
+    /// Returns the tag, the element's contents and the bytes that follow it.
+    fn split_element(der: &[u8]) -> Result<(u8, &[u8], &[u8]), DpeErrorCode> {
+        let (tag, header_len, size) = Self::decode_header(der)?;
+        let end = header_len
+            .checked_add(size)
+            .filter(|&end| end <= der.len())
+            .ok_or(DpeErrorCode::InvalidArgument)?;
+        Ok((tag, &der[header_len..end], &der[end..]))
+    }
+
+    /// Check that `der` is a single SubjectPublicKeyInfo SEQUENCE
+    ///
+    /// SubjectPublicKeyInfo  ::=  SEQUENCE  {
+    ///     algorithm            AlgorithmIdentifier,
+    ///     subjectPublicKey     BIT STRING  }
+    fn validate_spki_der(der: &[u8]) -> Result<(), DpeErrorCode> {
+        Self::validate_der_well_formed_single_element(der)?;
+        let (tag, spki, rest) = Self::split_element(der)?;
+        if tag != Self::SEQUENCE_TAG || !rest.is_empty() {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        let (alg_tag, _, rest) = Self::split_element(spki)?;
+        let (key_tag, _, rest) = Self::split_element(rest)?;
+        if alg_tag != Self::SEQUENCE_TAG || key_tag != Self::BIT_STRING_TAG || !rest.is_empty() {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
+    /// Check that `der` is a single DER IssuerAndSerialNumber, without
+    /// parsing the issuer Name
+    fn validate_issuer_serial_der(der: &[u8]) -> Result<(), DpeErrorCode> {
+        Self::validate_der_well_formed_single_element(der)?;
+        let (tag, issuer_serial, rest) = Self::split_element(der)?;
+        if tag != Self::SEQUENCE_TAG || !rest.is_empty() {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        let (issuer_tag, _, rest) = Self::split_element(issuer_serial)?;
+        let (serial_tag, _, rest) = Self::split_element(rest)?;
+        if issuer_tag != Self::SEQUENCE_TAG || serial_tag != Self::INTEGER_TAG || !rest.is_empty() {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
+    /// Add up `sizes`, failing instead of wrapping on overflow
+    fn checked_sum(sizes: &[usize]) -> Result<usize, DpeErrorCode> {
+        sizes
+            .iter()
+            .try_fold(0usize, |acc, &size| acc.checked_add(size))
+            .ok_or(DpeErrorCode::InternalError)
+    }
+
     /// Get the size of an ASN.1 structure
     /// If tagged, includes the tag and size
     fn get_structure_size(data_size: usize, tagged: bool) -> Result<usize, DpeErrorCode> {
         let size = if tagged {
-            1 + Self::get_size_width(data_size)? + data_size
+            Self::checked_sum(&[1, Self::get_size_width(data_size)?, data_size])?
         } else {
             data_size
         };
@@ -204,6 +1265,21 @@ impl CertWriter<'_> {
         Self::get_structure_size(len, tagged)
     }
 
+    /// Check that `serial_number` encodes to a non-empty INTEGER of at most
+    /// `MAX_SERIAL_NUMBER_SIZE` content octets. Leading zeros are stripped and
+    /// a zero byte is prepended to keep the value positive, as in
+    /// `encode_integer_bytes`.
+    fn validate_serial_number(serial_number: &[u8]) -> Result<(), DpeErrorCode> {
+        if serial_number.is_empty()
+            || Self::get_integer_bytes_size(serial_number, /*tagged=*/ false)?
+                > MAX_SERIAL_NUMBER_SIZE
+        {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
     /// Calculate the number of bytes the ASN.1 INTEGER will be
     /// If `tagged`, include the tag and size fields
     fn get_integer_size(integer: u64, tagged: bool) -> Result<usize, DpeErrorCode> {
@@ -220,80 +1296,175 @@ impl CertWriter<'_> {
 
     /// If `tagged`, include the tag and size fields
     fn get_rdn_size(name: &Name, tagged: bool) -> Result<usize, DpeErrorCode> {
-        let cn_seq_size = Self::get_structure_size(
-            Self::get_bytes_size(&Self::RDN_COMMON_NAME_OID, /*tagged=*/ true)?
-                + Self::get_bytes_size(name.cn.bytes(), true)?,
-            /*tagged=*/ true,
-        )?;
-        let serialnumber_seq_size = Self::get_structure_size(
-            Self::get_bytes_size(&Self::RDN_COMMON_NAME_OID, /*tagged=*/ true)?
-                + Self::get_bytes_size(name.serial.bytes(), /*tagged=*/ true)?,
+        let mut size = 0;
+        for (oid, val) in name.attributes() {
+            size = Self::checked_sum(&[
+                size,
+                Self::get_rdn_attribute_size(oid, val, /*tagged=*/ true)?,
+            ])?;
+        }
+
+        Self::get_structure_size(size, tagged)
+    }
+
+    /// Get the size of the single-attribute RelativeDistinguishedName SET
+    /// holding `oid` and `val`
+    /// If `tagged`, include the tag and size fields
+    fn get_rdn_attribute_size(
+        oid: &[u8],
+        val: &DirectoryString,
+        tagged: bool,
+    ) -> Result<usize, DpeErrorCode> {
+        let attribute_size = Self::get_structure_size(
+            Self::checked_sum(&[
+                Self::get_bytes_size(oid, /*tagged=*/ true)?,
+                Self::get_bytes_size(val.bytes(), /*tagged=*/ true)?,
+            ])?,
             /*tagged=*/ true,
         )?;
 
-        let cn_set_size = Self::get_structure_size(cn_seq_size, /*tagged=*/ true)?;
-        let serialnumber_set_size =
-            Self::get_structure_size(serialnumber_seq_size, /*tagged=*/ true)?;
-
-        Self::get_structure_size(cn_set_size + serialnumber_set_size, tagged)
+        Self::get_structure_size(attribute_size, tagged)
     }
 
     /// Calculate the number of bytes for an ECC Public Key AlgorithmIdentifier
     /// If `tagged`, include the tag and size fields
     fn get_ec_pub_alg_id_size(tagged: bool) -> Result<usize, DpeErrorCode> {
-        let len = Self::get_bytes_size(Self::EC_PUB_OID, true)?
-            + Self::get_bytes_size(Self::CURVE_OID, true)?;
+        let len = Self::checked_sum(&[
+            Self::get_bytes_size(Self::EC_PUB_OID, true)?,
+            Self::get_bytes_size(Self::CURVE_OID, true)?,
+        ])?;
         Self::get_structure_size(len, tagged)
     }
 
     /// Calculate the number of bytes for an ECDSA signature AlgorithmIdentifier
     /// If `tagged`, include the tag and size fields
     fn get_ecdsa_sig_alg_id_size(tagged: bool) -> Result<usize, DpeErrorCode> {
-        let len = Self::get_bytes_size(Self::ECDSA_OID, true)?;
-        Self::get_structure_size(len, tagged)
+        Self::get_sig_alg_id_size(Self::ECDSA_OID, tagged)
     }
 
-    /// Calculate the number of bytes for a Hash AlgorithmIdentifier
+    /// Calculate the number of bytes for a signature AlgorithmIdentifier with
+    /// algorithm `oid` and absent parameters
     /// If `tagged`, include the tag and size fields
-    fn get_hash_alg_id_size(tagged: bool) -> Result<usize, DpeErrorCode> {
-        let len = Self::get_bytes_size(Self::HASH_OID, true)?;
+    fn get_sig_alg_id_size(oid: &[u8], tagged: bool) -> Result<usize, DpeErrorCode> {
+        let len = Self::get_bytes_size(oid, true)?;
         Self::get_structure_size(len, tagged)
     }
 
+    /// Calculate the number of bytes for a Hash AlgorithmIdentifier
     /// If `tagged`, include the tag and size fields
-    fn get_validity_size(tagged: bool) -> Result<usize, DpeErrorCode> {
-        let len = Self::get_bytes_size(Self::NOT_BEFORE.as_bytes(), true)?
-            + Self::get_bytes_size(Self::NOT_AFTER.as_bytes(), true)?;
+    fn get_hash_alg_id_size(hash_oid: &[u8], tagged: bool) -> Result<usize, DpeErrorCode> {
+        let len = Self::get_bytes_size(hash_oid, true)?;
         Self::get_structure_size(len, tagged)
     }
 
-    /// Calculate the number of bytes an ECC SubjectPublicKeyInfo will be
-    /// If `tagged`, include the tag and size fields
-    fn get_ecdsa_subject_pubkey_info_size(
-        pubkey: &EcdsaPub,
-        tagged: bool,
-    ) -> Result<usize, DpeErrorCode> {
-        let point_size = 1 + pubkey.x.len() + pubkey.y.len();
-        let bitstring_size = 1 + point_size;
-        let seq_size = Self::get_structure_size(bitstring_size, /*tagged=*/ true)?
-            + Self::get_ec_pub_alg_id_size(/*tagged=*/ true)?;
-
+    /// Get the tag and value used to encode the GeneralizedTime string
+    /// `time` with `encoding`
+    ///
+    /// Returns `InvalidArgument` if `time` is not of the form
+    /// `YYYYMMDDHHMMSSZ`
+    fn get_time_value(time: &str, encoding: TimeEncoding) -> Result<(u8, &[u8]), DpeErrorCode> {
+        let bytes = time.as_bytes();
+        let Some((b'Z', digits)) = bytes.split_last() else {
+            return Err(DpeErrorCode::InvalidArgument);
+        };
+        if digits.len() != 14 || !digits.iter().all(u8::is_ascii_digit) {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        let year = digits[..4]
+            .iter()
+            .fold(0u32, |year, digit| year * 10 + u32::from(digit - b'0'));
+        match encoding {
+            // UTCTime drops the century from the year
+            TimeEncoding::Rfc5280 if (1950..2050).contains(&year) => {
+                Ok((Self::UTC_TIME_TAG, &bytes[2..]))
+            }
+            _ => Ok((Self::GENERALIZE_TIME_TAG, bytes)),
+        }
+    }
+
+    /// If `tagged`, include the tag and size fields
+    fn get_validity_size(
+        measurements: &MeasurementData,
+        tagged: bool,
+    ) -> Result<usize, DpeErrorCode> {
+        let validity = measurements.get_validity();
+        let (_, not_before) =
+            Self::get_time_value(validity.not_before, measurements.time_encoding)?;
+        let (_, not_after) = Self::get_time_value(validity.not_after, measurements.time_encoding)?;
+        let len = Self::checked_sum(&[
+            Self::get_bytes_size(not_before, true)?,
+            Self::get_bytes_size(not_after, true)?,
+        ])?;
+        Self::get_structure_size(len, tagged)
+    }
+
+    /// Calculate the number of bytes an ECC SubjectPublicKeyInfo will be
+    /// If `tagged`, include the tag and size fields
+    fn get_ecdsa_subject_pubkey_info_size(
+        pubkey: &EcdsaPub,
+        point_format: PointFormat,
+        tagged: bool,
+    ) -> Result<usize, DpeErrorCode> {
+        let point_size = Self::get_ec_point_size(pubkey, point_format)?;
+        let bitstring_size = Self::checked_sum(&[1, point_size])?;
+        let seq_size = Self::checked_sum(&[
+            Self::get_structure_size(bitstring_size, /*tagged=*/ true)?,
+            Self::get_ec_pub_alg_id_size(/*tagged=*/ true)?,
+        ])?;
+
         Self::get_structure_size(seq_size, tagged)
     }
 
+    /// Get the size of the SubjectPublicKeyInfo for `pubkey`, or of the raw
+    /// one in `measurements` if present
+    fn get_spki_size(
+        pubkey: &EcdsaPub,
+        measurements: &MeasurementData,
+    ) -> Result<usize, DpeErrorCode> {
+        match measurements.subject_public_key_info {
+            Some(spki_der) => Ok(spki_der.len()),
+            None => Self::get_ecdsa_subject_pubkey_info_size(
+                pubkey,
+                measurements.point_format,
+                /*tagged=*/ true,
+            ),
+        }
+    }
+
+    /// Get the size of an EC point. Each coordinate is padded to the
+    /// profile's integer size, so shorter coordinates are accepted but longer
+    /// ones are not.
+    fn get_ec_point_size(
+        pubkey: &EcdsaPub,
+        point_format: PointFormat,
+    ) -> Result<usize, DpeErrorCode> {
+        const ECC_INT_SIZE: usize = DPE_PROFILE.get_ecc_int_size();
+        if pubkey.x.len() > ECC_INT_SIZE || pubkey.y.len() > ECC_INT_SIZE {
+            return Err(DpeErrorCode::InternalError);
+        }
+
+        match point_format {
+            PointFormat::Uncompressed => Ok(1 + 2 * ECC_INT_SIZE),
+            PointFormat::Compressed => Ok(1 + ECC_INT_SIZE),
+        }
+    }
+
     /// If `tagged`, include the tag and size fields
     fn get_ecdsa_signature_bit_string_size(
         sig: &EcdsaSig,
         tagged: bool,
     ) -> Result<usize, DpeErrorCode> {
         let seq_size = Self::get_structure_size(
-            Self::get_integer_bytes_size(sig.r.bytes(), /*tagged=*/ true)?
-                + Self::get_integer_bytes_size(sig.s.bytes(), /*tagged=*/ true)?,
+            Self::checked_sum(&[
+                Self::get_integer_bytes_size(sig.r.bytes(), /*tagged=*/ true)?,
+                Self::get_integer_bytes_size(sig.s.bytes(), /*tagged=*/ true)?,
+            ])?,
             /*tagged=*/ true,
         )?;
 
         // Wrapping structure size
-        Self::get_structure_size(1 + seq_size, tagged)
+        Self::get_structure_size(Self::checked_sum(&[1, seq_size])?, tagged)
     }
 
     /// If `tagged`, include the tag and size fields
@@ -302,8 +1473,10 @@ impl CertWriter<'_> {
         tagged: bool,
     ) -> Result<usize, DpeErrorCode> {
         let seq_size = Self::get_structure_size(
-            Self::get_integer_bytes_size(sig.r.bytes(), /*tagged=*/ true)?
-                + Self::get_integer_bytes_size(sig.s.bytes(), /*tagged=*/ true)?,
+            Self::checked_sum(&[
+                Self::get_integer_bytes_size(sig.r.bytes(), /*tagged=*/ true)?,
+                Self::get_integer_bytes_size(sig.s.bytes(), /*tagged=*/ true)?,
+            ])?,
             /*tagged=*/ true,
         )?;
 
@@ -311,41 +1484,208 @@ impl CertWriter<'_> {
         Self::get_structure_size(seq_size, tagged)
     }
 
-    /// version is marked as EXPLICIT [0]
+    /// version is marked as EXPLICIT [0]. v1 is the DEFAULT, so it is
+    /// omitted and has size 0.
     /// If `tagged`, include the explicit tag and size fields
-    fn get_version_size(tagged: bool) -> Result<usize, DpeErrorCode> {
-        let integer_size = Self::get_integer_size(Self::X509_V3, /*tagged=*/ true)?;
+    fn get_version_size(version: CertVersion, tagged: bool) -> Result<usize, DpeErrorCode> {
+        if version == CertVersion::V1 {
+            return Ok(0);
+        }
+        let integer_size = Self::get_integer_size(version.value(), /*tagged=*/ true)?;
 
         // If tagged, also add explicit wrapping
         Self::get_structure_size(integer_size, tagged)
     }
 
-    /// Get the size of a DICE FWID structure
-    fn get_fwid_size(digest: &[u8], tagged: bool) -> Result<usize, DpeErrorCode> {
-        let size = Self::get_structure_size(Self::HASH_OID.len(), /*tagged=*/ true)?
-            + Self::get_structure_size(digest.len(), /*tagged=*/ true)?;
+    /// Get the size of a TBS UniqueIdentifier. Returns 0 if `unique_id` is
+    /// not present.
+    /// If `tagged`, include the implicit tag and size fields
+    fn get_unique_id_size(unique_id: Option<&[u8]>, tagged: bool) -> Result<usize, DpeErrorCode> {
+        match unique_id {
+            // BIT STRING with an additional byte for the unused bits
+            Some(id) => Self::get_structure_size(Self::checked_sum(&[1, id.len()])?, tagged),
+            None => Ok(0),
+        }
+    }
+
+    /// Get the size of a DICE FWID structure with a `digest_len` byte digest
+    fn get_fwid_size(
+        hash_oid: &[u8],
+        digest_len: usize,
+        tagged: bool,
+    ) -> Result<usize, DpeErrorCode> {
+        let size = Self::checked_sum(&[
+            Self::get_structure_size(hash_oid.len(), /*tagged=*/ true)?,
+            Self::get_structure_size(digest_len, /*tagged=*/ true)?,
+        ])?;
 
         Self::get_structure_size(size, tagged)
     }
 
+    /// Get the digest size of the hash algorithm identified by `hash_oid`
+    fn get_hash_oid_digest_size(hash_oid: &[u8]) -> Result<usize, DpeErrorCode> {
+        match hash_oid {
+            Self::SHA256_OID => Ok(32),
+            Self::SHA384_OID => Ok(48),
+            Self::SHA512_OID => Ok(64),
+            Self::SHA512_256_OID => Ok(32),
+            _ => Err(DpeErrorCode::InvalidArgument),
+        }
+    }
+
+    /// Get the OID of the hash algorithm `alg`, defaulting to the profile's
+    /// hash
+    fn get_hash_oid(alg: Option<HashAlg>) -> &'static [u8] {
+        match alg {
+            None => Self::HASH_OID,
+            Some(HashAlg::Sha256) => Self::SHA256_OID,
+            Some(HashAlg::Sha384) => Self::SHA384_OID,
+            Some(HashAlg::Sha512) => Self::SHA512_OID,
+            Some(HashAlg::Sha512_256) => Self::SHA512_256_OID,
+        }
+    }
+
     /// Get the size of a tcg-dice-TcbInfo structure. For DPE, this is only used
     /// as part of a MultiTcbInfo. For this reason, do not include the standard
     /// extension fields. Only include the size of the structure itself.
+    ///
+    /// If not `has_fwids`, the optional fwids field is omitted.
     fn get_tcb_info_size(
         node: &TciNodeData,
+        has_fwids: bool,
         supports_extend_tci: bool,
+        extra_fwids: &[TciMeasurement],
+        fwid_digests: Option<&FwidDigests>,
+        hash_oid: &[u8],
         tagged: bool,
     ) -> Result<usize, DpeErrorCode> {
-        let fwid0_size = Self::get_fwid_size(&node.tci_current.0, /*tagged=*/ true)?;
-        let fwid1_size = if supports_extend_tci {
-            Self::get_fwid_size(&node.tci_cumulative.0, /*tagged=*/ true)?
-        } else {
+        let fwids_size = if has_fwids {
+            Self::get_structure_size(
+                Self::get_fwids_size(
+                    node,
+                    supports_extend_tci,
+                    extra_fwids,
+                    fwid_digests,
+                    hash_oid,
+                )?,
+                /*tagged=*/ true,
+            )?
+        } else if extra_fwids.is_empty() && fwid_digests.is_none() {
             0
+        } else {
+            return Err(DpeErrorCode::InvalidArgument);
+        };
+
+        let size = Self::checked_sum(&[
+            fwids_size,
+            Self::get_structure_size(core::mem::size_of::<u32>(), /*tagged=*/ true)?, // vendorInfo
+            Self::get_structure_size(core::mem::size_of::<u32>(), /*tagged=*/ true)?, // type
+        ])?;
+
+        Self::get_structure_size(size, tagged)
+    }
+
+    /// Get the size of all FWIDs in a tcg-dice-TcbInfo, excluding the fwids
+    /// SEQUENCE OF tag and size.
+    fn get_fwids_size(
+        node: &TciNodeData,
+        supports_extend_tci: bool,
+        extra_fwids: &[TciMeasurement],
+        fwid_digests: Option<&FwidDigests>,
+        hash_oid: &[u8],
+    ) -> Result<usize, DpeErrorCode> {
+        let (current, cumulative) = Self::resolve_fwid_digests(node, fwid_digests);
+        let mut size = Self::get_fwid_size(hash_oid, current.len(), /*tagged=*/ true)?;
+        if supports_extend_tci {
+            size = Self::checked_sum(&[
+                size,
+                Self::get_fwid_size(hash_oid, cumulative.len(), /*tagged=*/ true)?,
+            ])?;
+        }
+        for fwid in extra_fwids {
+            size = Self::checked_sum(&[
+                size,
+                Self::get_fwid_size(hash_oid, fwid.0.len(), /*tagged=*/ true)?,
+            ])?;
+        }
+
+        Ok(size)
+    }
+
+    /// Get the current and cumulative FWID digests of `node`, taken from
+    /// `fwid_digests` if set and from its TCIs otherwise
+    fn resolve_fwid_digests<'b>(
+        node: &'b TciNodeData,
+        fwid_digests: Option<&FwidDigests<'b>>,
+    ) -> (&'b [u8], &'b [u8]) {
+        match fwid_digests {
+            Some(digests) => (digests.current, digests.cumulative),
+            None => (&node.tci_current.0, &node.tci_cumulative.0),
+        }
+    }
+
+    /// Get the size of all TcbInfos in a tcg-dice-MultiTcbInfo, excluding the
+    /// SEQUENCE OF tag and size.
+    fn get_tcb_infos_size(measurements: &MeasurementData) -> Result<usize, DpeErrorCode> {
+        if measurements.tci_nodes.len() > MAX_TCI_NODES {
+            return Err(DpeErrorCode::MaxTcis);
+        }
+
+        let mut size = 0;
+        for (i, node) in measurements.tci_nodes.iter().enumerate() {
+            let tcb_info_size = Self::get_tcb_info_size(
+                node,
+                measurements.has_fwids(i),
+                measurements.supports_extend_tci(i),
+                measurements.get_extra_fwids(i),
+                measurements.get_fwid_digests(i),
+                Self::get_hash_oid(measurements.fwid_hash_alg),
+                /*tagged=*/ true,
+            )?;
+            size = Self::checked_sum(&[size, tcb_info_size])?;
+        }
+
+        Ok(size)
+    }
+
+    /// Get the size of the extension describing the TCI nodes, either a
+    /// tcg-dice-TcbInfo or a tcg-dice-MultiTcbInfo, including the extension OID
+    /// and critical bits.
+    fn get_dice_tcb_info_size(
+        measurements: &MeasurementData,
+        tagged: bool,
+    ) -> Result<usize, DpeErrorCode> {
+        if measurements.single_tcb_info {
+            Self::get_single_tcb_info_size(measurements, tagged)
+        } else {
+            Self::get_multi_tcb_info_size(measurements, tagged)
+        }
+    }
+
+    /// Get the size of a tcg-dice-TcbInfo extension for the only TCI node,
+    /// including the extension OID and critical bits.
+    fn get_single_tcb_info_size(
+        measurements: &MeasurementData,
+        tagged: bool,
+    ) -> Result<usize, DpeErrorCode> {
+        let [node] = measurements.tci_nodes else {
+            return Err(DpeErrorCode::InvalidArgument);
         };
-        let fwids_size = Self::get_structure_size(fwid0_size + fwid1_size, /*tagged=*/ true)?;
+        let tcb_info_size = Self::get_tcb_info_size(
+            node,
+            measurements.has_fwids(0),
+            measurements.supports_extend_tci(0),
+            measurements.get_extra_fwids(0),
+            measurements.get_fwid_digests(0),
+            Self::get_hash_oid(measurements.fwid_hash_alg),
+            /*tagged=*/ true,
+        )?;
 
-        let size = fwids_size
-            + (2 * Self::get_structure_size(core::mem::size_of::<u32>(), /*tagged=*/ true)?); // vendorInfo and type
+        let size = Self::checked_sum(&[
+            Self::get_structure_size(Self::TCBINFO_OID.len(), /*tagged=*/ true)?, // Extension OID
+            Self::get_structure_size(1, /*tagged=*/ true)?,                       // Critical bool
+            Self::get_structure_size(tcb_info_size, /*tagged=*/ true)?,           // OCTET STRING
+        ])?;
 
         Self::get_structure_size(size, tagged)
     }
@@ -361,63 +1701,210 @@ impl CertWriter<'_> {
         }
 
         // Size of concatenated tcb infos
-        let tcb_infos_size = measurements.tci_nodes.len()
-            * Self::get_tcb_info_size(
-                &measurements.tci_nodes[0],
-                measurements.supports_extend_tci,
-                /*tagged=*/ true,
-            )?;
+        let tcb_infos_size = Self::get_tcb_infos_size(measurements)?;
+
+        Self::get_multi_tcb_info_ext_size(tcb_infos_size, tagged)
+    }
 
+    /// Get the size of a tcg-dice-MultiTcbInfo extension wrapping
+    /// `tcb_infos_size` bytes of concatenated TcbInfos.
+    fn get_multi_tcb_info_ext_size(
+        tcb_infos_size: usize,
+        tagged: bool,
+    ) -> Result<usize, DpeErrorCode> {
         // Size of tcb infos including SEQUENCE OF tag/size
         let multi_tcb_info_size = Self::get_structure_size(tcb_infos_size, /*tagged=*/ true)?;
 
-        let size = Self::get_structure_size(Self::MULTI_TCBINFO_OID.len(), /*tagged=*/true)? // Extension OID
-            + Self::get_structure_size(1, /*tagged=*/true)? // Critical bool
-            + Self::get_structure_size(multi_tcb_info_size, /*tagged=*/true)?; // OCTET STRING
+        let size = Self::checked_sum(&[
+            Self::get_structure_size(Self::MULTI_TCBINFO_OID.len(), /*tagged=*/ true)?, // Extension OID
+            Self::get_structure_size(1, /*tagged=*/ true)?, // Critical bool
+            Self::get_structure_size(multi_tcb_info_size, /*tagged=*/ true)?, // OCTET STRING
+        ])?;
 
         Self::get_structure_size(size, tagged)
     }
 
     /// Get the size of a tcg-dice-Ueid extension, including the extension
-    /// OID and critical bits.
+    /// OID and critical bits. Zero if the extension is not included.
     fn get_ueid_size(measurements: &MeasurementData, tagged: bool) -> Result<usize, DpeErrorCode> {
-        // Extension data is sequence -> octet string. To compute size, wrap
-        // in tagging twice.
-        let ext_size = Self::get_structure_size(
-            Self::get_structure_size(measurements.label.len(), /*tagged=*/ true)?,
+        if !measurements.include_ueid {
+            return Ok(0);
+        }
+        Self::validate_ueid(measurements.label)?;
+
+        let ext_size = Self::get_ueid_ext_value_size(measurements)?;
+        let size = Self::checked_sum(&[
+            Self::get_structure_size(Self::UEID_OID.len(), /*tagged=*/ true)?, // Extension OID
+            Self::get_structure_size(1, /*tagged=*/ true)?,                    // Critical bool
+            Self::get_structure_size(ext_size, /*tagged=*/ true)?,             // OCTET STRING
+        ])?;
+
+        Self::get_structure_size(size, tagged)
+    }
+
+    /// Get the size of the tcg-dice-Ueid extension value in the encoding
+    /// selected by `measurements`
+    fn get_ueid_ext_value_size(measurements: &MeasurementData) -> Result<usize, DpeErrorCode> {
+        match measurements.ueid_encoding {
+            UeidEncoding::Sequence => Self::get_ueid_value_size(measurements.label),
+            UeidEncoding::OctetString => {
+                Self::get_structure_size(measurements.label.len(), /*tagged=*/ true)
+            }
+        }
+    }
+
+    /// Check that `label` can be used as a tcg-dice-Ueid. It must not be empty
+    /// or longer than the profile's UEID size.
+    fn validate_ueid(label: &[u8]) -> Result<(), DpeErrorCode> {
+        if label.is_empty() || label.len() > DPE_PROFILE.ueid_size() {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
+    /// Get the size of a standalone tcg-dice-Ueid value, as written by
+    /// `encode_ueid_value`.
+    pub fn get_ueid_value_size(label: &[u8]) -> Result<usize, DpeErrorCode> {
+        // Value is sequence -> octet string. To compute size, wrap in tagging
+        // twice.
+        Self::get_structure_size(
+            Self::get_structure_size(label.len(), /*tagged=*/ true)?,
             /*tagged=*/ true,
-        )?;
-        let size = Self::get_structure_size(Self::UEID_OID.len(), /*tagged=*/true)? // Extension OID
-            + Self::get_structure_size(1, /*tagged=*/true)? // Critical bool
-            + Self::get_structure_size(ext_size, /*tagged=*/true)?; // OCTET STRING
+        )
+    }
+
+    /// Get the size of a holder reference extension, as written by
+    /// `encode_holder_reference`.
+    /// If `tagged`, include the tag and size fields
+    ///
+    /// Fails if `issuer_serial_der` is not a DER IssuerAndSerialNumber.
+    pub fn get_holder_reference_size(
+        issuer_serial_der: &[u8],
+        tagged: bool,
+    ) -> Result<usize, DpeErrorCode> {
+        Self::validate_issuer_serial_der(issuer_serial_der)?;
+
+        let size = Self::checked_sum(&[
+            Self::get_structure_size(Self::HOLDER_REFERENCE_OID.len(), /*tagged=*/ true)?, // Extension OID
+            Self::get_structure_size(Self::BOOL_SIZE, /*tagged=*/ true)?, // Critical bool
+            Self::get_structure_size(issuer_serial_der.len(), /*tagged=*/ true)?, // OCTET STRING
+        ])?;
+
+        Self::get_structure_size(size, tagged)
+    }
+
+    /// Get the size of an EAT claims extension holding `cbor`, as written by
+    /// `encode_eat_claims_extension`.
+    /// If `tagged`, include the tag and size fields
+    ///
+    /// Fails if `cbor` is empty.
+    pub fn get_eat_claims_extension_size(cbor: &[u8], tagged: bool) -> Result<usize, DpeErrorCode> {
+        if cbor.is_empty() {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        let size = Self::checked_sum(&[
+            Self::get_structure_size(Self::EAT_CLAIMS_OID.len(), /*tagged=*/ true)?, // Extension OID
+            Self::get_structure_size(Self::BOOL_SIZE, /*tagged=*/ true)?, // Critical bool
+            Self::get_structure_size(cbor.len(), /*tagged=*/ true)?,      // OCTET STRING
+        ])?;
+
+        Self::get_structure_size(size, tagged)
+    }
+
+    /// Get the size of an altSignatureAlgorithm extension, as written by
+    /// `encode_alt_signature_algorithm`.
+    /// If `tagged`, include the tag and size fields
+    pub fn get_alt_signature_algorithm_size(tagged: bool) -> Result<usize, DpeErrorCode> {
+        let size = Self::checked_sum(&[
+            Self::get_structure_size(
+                Self::ALT_SIGNATURE_ALGORITHM_OID.len(),
+                /*tagged=*/ true,
+            )?, // Extension OID
+            Self::get_structure_size(Self::BOOL_SIZE, /*tagged=*/ true)?, // Critical bool
+            Self::get_structure_size(
+                Self::get_ecdsa_sig_alg_id_size(/*tagged=*/ true)?,
+                /*tagged=*/ true,
+            )?, // OCTET STRING
+        ])?;
+
+        Self::get_structure_size(size, tagged)
+    }
+
+    /// Get the size of an altSignatureValue extension holding `sig`, as
+    /// written by `encode_alt_signature_value`.
+    /// If `tagged`, include the tag and size fields
+    pub fn get_alt_signature_value_size(
+        sig: &EcdsaSig,
+        tagged: bool,
+    ) -> Result<usize, DpeErrorCode> {
+        let size = Self::checked_sum(&[
+            Self::get_structure_size(Self::ALT_SIGNATURE_VALUE_OID.len(), /*tagged=*/ true)?, // Extension OID
+            Self::get_structure_size(Self::BOOL_SIZE, /*tagged=*/ true)?, // Critical bool
+            Self::get_structure_size(
+                Self::get_ecdsa_signature_bit_string_size(sig, /*tagged=*/ true)?,
+                /*tagged=*/ true,
+            )?, // OCTET STRING
+        ])?;
 
         Self::get_structure_size(size, tagged)
     }
 
     /// Get the size of a basicConstraints extension, including the extension
     /// OID and critical bits.
-    fn get_basic_constraints_size(tagged: bool) -> Result<usize, DpeErrorCode> {
+    fn get_basic_constraints_size(
+        measurements: &MeasurementData,
+        tagged: bool,
+    ) -> Result<usize, DpeErrorCode> {
         // Extension data is sequence -> octet string. To compute size, wrap
         // in tagging twice.
         let ext_size = Self::get_structure_size(
-            Self::get_structure_size(Self::BOOL_SIZE, /*tagged=*/ true)?,
+            Self::get_basic_constraints_value_size(measurements)?,
             /*tagged=*/ true,
         )?;
-        let size = Self::get_structure_size(Self::BASIC_CONSTRAINTS_OID.len(), /*tagged=*/true)? // Extension OID
-            + Self::get_structure_size(Self::BOOL_SIZE, /*tagged=*/true)? // Critical bool
-            + Self::get_structure_size(ext_size, /*tagged=*/true)?; // OCTET STRING
+        let size = Self::checked_sum(&[
+            Self::get_structure_size(Self::BASIC_CONSTRAINTS_OID.len(), /*tagged=*/ true)?, // Extension OID
+            Self::get_structure_size(Self::BOOL_SIZE, /*tagged=*/ true)?, // Critical bool
+            Self::get_structure_size(ext_size, /*tagged=*/ true)?,        // OCTET STRING
+        ])?;
 
         Self::get_structure_size(size, tagged)
     }
 
+    /// Get the size of the BasicConstraints SEQUENCE contents
+    fn get_basic_constraints_value_size(
+        measurements: &MeasurementData,
+    ) -> Result<usize, DpeErrorCode> {
+        let path_len_size = match measurements.path_len_constraint {
+            Some(path_len) if measurements.is_ca => {
+                Self::get_integer_size(path_len.into(), /*tagged=*/ true)?
+            }
+            _ => 0,
+        };
+
+        Self::checked_sum(&[
+            Self::get_structure_size(Self::BOOL_SIZE, /*tagged=*/ true)?,
+            path_len_size,
+        ])
+    }
+
     /// Get the size of a keyUsage extension, including the extension
     /// OID and critical bits.
-    fn get_key_usage_size(tagged: bool) -> Result<usize, DpeErrorCode> {
-        // Extension data is a 2-byte BIT STRING
-        let ext_size = Self::get_structure_size(2, /*tagged=*/ true)?;
-        let size = Self::get_structure_size(Self::KEY_USAGE_OID.len(), /*tagged=*/true)? // Extension OID
-            + Self::get_structure_size(Self::BOOL_SIZE, /*tagged=*/true)? // Critical bool
-            + Self::get_structure_size(ext_size, /*tagged=*/true)?; // OCTET STRING
+    fn get_key_usage_size(
+        measurements: &MeasurementData,
+        tagged: bool,
+    ) -> Result<usize, DpeErrorCode> {
+        // Extension data is a BIT STRING of the unused bits count and the
+        // KeyUsage bits
+        let value_len = Self::get_key_usage_value_len(measurements.get_key_usage())?;
+        let ext_size =
+            Self::get_structure_size(Self::checked_sum(&[1, value_len])?, /*tagged=*/ true)?;
+        let size = Self::checked_sum(&[
+            Self::get_structure_size(Self::KEY_USAGE_OID.len(), /*tagged=*/ true)?, // Extension OID
+            Self::get_structure_size(Self::BOOL_SIZE, /*tagged=*/ true)?,           // Critical bool
+            Self::get_structure_size(ext_size, /*tagged=*/ true)?,                  // OCTET STRING
+        ])?;
 
         Self::get_structure_size(size, tagged)
     }
@@ -428,21 +1915,132 @@ impl CertWriter<'_> {
         measurements: &MeasurementData,
         tagged: bool,
     ) -> Result<usize, DpeErrorCode> {
-        let policy_oid_size = if measurements.is_ca {
-            Self::ECA_OID.len()
+        if !Self::has_extended_key_usage(measurements) {
+            return Ok(0);
+        }
+
+        // Extension data is sequence -> octet string
+        let ext_size = Self::get_key_purposes_size(measurements, /*tagged=*/ true)?;
+        let size = Self::checked_sum(&[
+            Self::get_structure_size(Self::EXTENDED_KEY_USAGE_OID.len(), /*tagged=*/ true)?, // Extension OID
+            Self::get_structure_size(Self::BOOL_SIZE, /*tagged=*/ true)?, // Critical bool
+            Self::get_structure_size(ext_size, /*tagged=*/ true)?,        // OCTET STRING
+        ])?;
+
+        Self::get_structure_size(size, tagged)
+    }
+
+    /// Get the size of the KeyPurposeIds SEQUENCE
+    /// If `tagged`, include the tag and size fields
+    fn get_key_purposes_size(
+        measurements: &MeasurementData,
+        tagged: bool,
+    ) -> Result<usize, DpeErrorCode> {
+        let mut size = 0;
+        for oid in Self::get_key_purpose_oids(measurements) {
+            size =
+                Self::checked_sum(&[size, Self::get_structure_size(oid.len(), /*tagged=*/ true)?])?;
+        }
+        // KeyPurposeIds has at least one entry
+        if size == 0 {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        Self::get_structure_size(size, tagged)
+    }
+
+    /// Whether the certificate has an extendedKeyUsage extension. CA
+    /// certificates always do.
+    fn has_extended_key_usage(measurements: &MeasurementData) -> bool {
+        measurements.is_ca || measurements.key_purposes != KeyPurposes::Omitted
+    }
+
+    /// Get the extendedKeyUsage key purposes of the certificate
+    fn get_key_purpose_oids(measurements: &MeasurementData) -> impl Iterator<Item = &'static [u8]> {
+        let (aik, dice) = if measurements.is_ca {
+            (false, DiceKeyPurposes::ECA)
         } else {
-            Self::ATTEST_LOC_OID.len()
+            match measurements.key_purposes {
+                KeyPurposes::Dice => (false, DiceKeyPurposes::ATTEST_LOC),
+                KeyPurposes::AttestationKey => (true, DiceKeyPurposes::ATTEST_LOC),
+                KeyPurposes::Custom(purposes) => (false, purposes),
+                KeyPurposes::Omitted => (false, DiceKeyPurposes::empty()),
+            }
+        };
+
+        aik.then_some(Self::AIK_CERTIFICATE_OID).into_iter().chain(
+            Self::DICE_KEY_PURPOSE_OIDS
+                .iter()
+                .filter(move |(purpose, _)| dice.contains(*purpose))
+                .map(|(_, oid)| *oid),
+        )
+    }
+
+    /// Get the size of the PolicyConstraints SEQUENCE contents
+    fn get_policy_constraints_value_size(
+        constraints: &PolicyConstraints,
+    ) -> Result<usize, DpeErrorCode> {
+        let mut size = 0;
+        if let Some(skip_certs) = constraints.require_explicit_policy {
+            size = Self::checked_sum(&[
+                size,
+                Self::get_integer_size(skip_certs.into(), /*tagged=*/ true)?,
+            ])?;
+        }
+        if let Some(skip_certs) = constraints.inhibit_policy_mapping {
+            size = Self::checked_sum(&[
+                size,
+                Self::get_integer_size(skip_certs.into(), /*tagged=*/ true)?,
+            ])?;
+        }
+
+        Ok(size)
+    }
+
+    /// Get the size of a policyConstraints extension, including the extension
+    /// OID and critical bits. Zero if the extension is not included.
+    fn get_policy_constraints_size(
+        measurements: &MeasurementData,
+        tagged: bool,
+    ) -> Result<usize, DpeErrorCode> {
+        let constraints = match measurements.policy_constraints {
+            Some(constraints) if measurements.is_ca => constraints,
+            _ => return Ok(0),
         };
 
         // Extension data is sequence -> octet string. To compute size, wrap
         // in tagging twice.
         let ext_size = Self::get_structure_size(
-            Self::get_structure_size(policy_oid_size, /*tagged=*/ true)?,
+            Self::get_policy_constraints_value_size(&constraints)?,
             /*tagged=*/ true,
         )?;
-        let size = Self::get_structure_size(Self::EXTENDED_KEY_USAGE_OID.len(), /*tagged=*/true)? // Extension OID
-            + Self::get_structure_size(Self::BOOL_SIZE, /*tagged=*/true)? // Critical bool
-            + Self::get_structure_size(ext_size, /*tagged=*/true)?; // OCTET STRING
+        let size = Self::checked_sum(&[
+            Self::get_structure_size(Self::POLICY_CONSTRAINTS_OID.len(), /*tagged=*/ true)?, // Extension OID
+            Self::get_structure_size(Self::BOOL_SIZE, /*tagged=*/ true)?, // Critical bool
+            Self::get_structure_size(ext_size, /*tagged=*/ true)?,        // OCTET STRING
+        ])?;
+
+        Self::get_structure_size(size, tagged)
+    }
+
+    /// Get the size of an inhibitAnyPolicy extension, including the extension
+    /// OID and critical bits. Zero if the extension is not included.
+    fn get_inhibit_any_policy_size(
+        measurements: &MeasurementData,
+        tagged: bool,
+    ) -> Result<usize, DpeErrorCode> {
+        let skip_certs = match measurements.inhibit_any_policy {
+            Some(skip_certs) if measurements.is_ca => skip_certs,
+            _ => return Ok(0),
+        };
+
+        // Extension data is an INTEGER
+        let ext_size = Self::get_integer_size(skip_certs.into(), /*tagged=*/ true)?;
+        let size = Self::checked_sum(&[
+            Self::get_structure_size(Self::INHIBIT_ANY_POLICY_OID.len(), /*tagged=*/ true)?, // Extension OID
+            Self::get_structure_size(Self::BOOL_SIZE, /*tagged=*/ true)?, // Critical bool
+            Self::get_structure_size(ext_size, /*tagged=*/ true)?,        // OCTET STRING
+        ])?;
 
         Self::get_structure_size(size, tagged)
     }
@@ -453,11 +2051,16 @@ impl CertWriter<'_> {
         tagged: bool,
         explicit: bool,
     ) -> Result<usize, DpeErrorCode> {
-        let mut size = Self::get_multi_tcb_info_size(measurements, /*tagged=*/ true)?
-            + Self::get_ueid_size(measurements, /*tagged=*/ true)?
-            + Self::get_basic_constraints_size(/*tagged=*/ true)?
-            + Self::get_key_usage_size(/*tagged=*/ true)?
-            + Self::get_extended_key_usage_size(measurements, /*tagged=*/ true)?;
+        let mut size = Self::checked_sum(&[
+            Self::get_dice_tcb_info_size(measurements, /*tagged=*/ true)?,
+            Self::get_ueid_size(measurements, /*tagged=*/ true)?,
+            Self::get_basic_constraints_size(measurements, /*tagged=*/ true)?,
+            Self::get_key_usage_size(measurements, /*tagged=*/ true)?,
+            Self::get_extended_key_usage_size(measurements, /*tagged=*/ true)?,
+            Self::get_policy_constraints_size(measurements, /*tagged=*/ true)?,
+            Self::get_inhibit_any_policy_size(measurements, /*tagged=*/ true)?,
+            Self::get_raw_extensions_size(measurements)?,
+        ])?;
 
         // Determine whether to include the explicit tag wrapping in the size calculation
         size = Self::get_structure_size(size, /*tagged=*/ explicit)?;
@@ -465,6 +2068,69 @@ impl CertWriter<'_> {
         Self::get_structure_size(size, tagged)
     }
 
+    /// Check that a caller-provided extension is a single well-formed DER
+    /// SEQUENCE
+    fn validate_raw_extension(ext: &[u8]) -> Result<(), DpeErrorCode> {
+        Self::validate_der_well_formed_single_element(ext)?;
+        if ext[0] != Self::SEQUENCE_TAG {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
+    /// Get the size of the caller-provided DER encoded extensions
+    ///
+    /// Fails if any of them is not a single well-formed DER SEQUENCE.
+    fn get_raw_extensions_size(measurements: &MeasurementData) -> Result<usize, DpeErrorCode> {
+        let mut size = 0;
+        for ext in measurements.raw_extensions {
+            Self::validate_raw_extension(ext)?;
+            size = Self::checked_sum(&[size, ext.len()])?;
+        }
+
+        Ok(size)
+    }
+
+    /// Check that the certificate version in `measurements` can hold the
+    /// optional TBS fields that were asked for
+    ///
+    /// Unique IDs need v2 or later. v1 and v2 certificates have no
+    /// Extensions, so anything that would only be encoded in one is rejected
+    /// rather than dropped: TCI nodes, the UEID, basicConstraints and
+    /// caller-provided extensions.
+    fn validate_cert_version(measurements: &MeasurementData) -> Result<(), DpeErrorCode> {
+        let has_unique_ids =
+            measurements.issuer_unique_id.is_some() || measurements.subject_unique_id.is_some();
+        let has_extensions = !measurements.tci_nodes.is_empty()
+            || !measurements.extra_fwids.is_empty()
+            || !measurements.extend_tci_overrides.is_empty()
+            || measurements.single_tcb_info
+            || measurements.is_ca
+            || measurements.path_len_constraint.is_some()
+            || measurements.key_purposes != KeyPurposes::default()
+            || !measurements.raw_extensions.is_empty()
+            || measurements.policy_constraints.is_some()
+            || measurements.inhibit_any_policy.is_some()
+            || measurements.key_usage.is_some();
+
+        match measurements.version {
+            CertVersion::V1 if has_unique_ids || has_extensions => {
+                Err(DpeErrorCode::InvalidArgument)
+            }
+            CertVersion::V2 if has_extensions => Err(DpeErrorCode::InvalidArgument),
+            _ => Ok(()),
+        }
+    }
+
+    /// Get the size of the TBS extensions field, which is absent before v3
+    fn get_tbs_extensions_size(measurements: &MeasurementData) -> Result<usize, DpeErrorCode> {
+        if measurements.version != CertVersion::V3 {
+            return Ok(0);
+        }
+        Self::get_extensions_size(measurements, /*tagged=*/ true, /*explicit=*/ true)
+    }
+
     /// Get the size of the ASN.1 TBSCertificate structure
     /// If `tagged`, include the tag and size fields
     fn get_tbs_size(
@@ -475,79 +2141,275 @@ impl CertWriter<'_> {
         measurements: &MeasurementData,
         tagged: bool,
     ) -> Result<usize, DpeErrorCode> {
-        let tbs_size = Self::get_version_size(/*tagged=*/ true)?
-            + Self::get_integer_bytes_size(serial_number, /*tagged=*/ true)?
-            + Self::get_ecdsa_sig_alg_id_size(/*tagged=*/ true)?
-            + issuer_der.len()
-            + Self::get_validity_size(/*tagged=*/ true)?
-            + Self::get_rdn_size(subject_name, /*tagged=*/ true)?
-            + Self::get_ecdsa_subject_pubkey_info_size(pubkey, /*tagged=*/ true)?
-            + Self::get_extensions_size(
-                measurements,
-                /*tagged=*/ true,
-                /*explicit=*/ true,
-            )?;
+        let tbs_size = Self::checked_sum(&[
+            Self::get_version_size(measurements.version, /*tagged=*/ true)?,
+            Self::get_integer_bytes_size(serial_number, /*tagged=*/ true)?,
+            Self::get_sig_alg_id_size(measurements.get_signature_alg_oid(), /*tagged=*/ true)?,
+            issuer_der.len(),
+            Self::get_validity_size(measurements, /*tagged=*/ true)?,
+            Self::get_rdn_size(subject_name, /*tagged=*/ true)?,
+            Self::get_spki_size(pubkey, measurements)?,
+            Self::get_unique_id_size(measurements.issuer_unique_id, /*tagged=*/ true)?,
+            Self::get_unique_id_size(measurements.subject_unique_id, /*tagged=*/ true)?,
+            Self::get_tbs_extensions_size(measurements)?,
+        ])?;
 
         Self::get_structure_size(tbs_size, tagged)
     }
 
-    /// Get the size of the ASN.1 CertificationRequestInfo structure
+    /// Get the size of an ECDSA Certificate wrapping a `tbs_len` byte
+    /// TBSCertificate
     /// If `tagged`, include the tag and size fields
-    fn get_certification_request_info_size(
-        subject_name: &Name,
-        pubkey: &EcdsaPub,
-        measurements: &MeasurementData,
+    fn get_ecdsa_certificate_size(
+        tbs_len: usize,
+        sig: &EcdsaSig,
         tagged: bool,
     ) -> Result<usize, DpeErrorCode> {
-        let cert_req_info_size = Self::get_integer_size(Self::CSR_V0, true)?
-            + Self::get_rdn_size(subject_name, /*tagged=*/ true)?
-            + Self::get_ecdsa_subject_pubkey_info_size(pubkey, /*tagged=*/ true)?
-            + Self::get_attributes_size(measurements, /*tagged=*/ true)?;
+        let cert_size = Self::checked_sum(&[
+            tbs_len,
+            Self::get_ecdsa_sig_alg_id_size(/*tagged=*/ true)?,
+            Self::get_ecdsa_signature_bit_string_size(sig, /*tagged=*/ true)?,
+        ])?;
 
-        Self::get_structure_size(cert_req_info_size, tagged)
+        Self::get_structure_size(cert_size, tagged)
     }
 
-    /// Get the size of the ASN.1 SignerInfo structure
+    /// Get the size of a PKCS #10 CertificateRequest wrapping a
+    /// `cert_req_info_len` byte CertificationRequestInfo
     /// If `tagged`, include the tag and size fields
-    fn get_signer_info_size(
-        serial_number: &[u8],
-        issuer_der: &[u8],
+    fn get_csr_size(
+        cert_req_info_len: usize,
         sig: &EcdsaSig,
         tagged: bool,
     ) -> Result<usize, DpeErrorCode> {
-        let signer_info_size = Self::get_integer_size(Self::CMS_V1, true)?
-            + Self::get_issuer_and_serial_number_size(
-                serial_number,
-                issuer_der,
-                /*tagged=*/ true,
-            )?
-            + Self::get_hash_alg_id_size(/*tagged=*/ true)?
-            + Self::get_ecdsa_sig_alg_id_size(/*tagged=*/ true)?
-            + Self::get_ecdsa_signature_octet_string_size(sig, /*tagged=*/ true)?;
+        let csr_size = Self::checked_sum(&[
+            cert_req_info_len,
+            Self::get_ecdsa_sig_alg_id_size(/*tagged=*/ true)?,
+            Self::get_ecdsa_signature_bit_string_size(sig, /*tagged=*/ true)?,
+        ])?;
 
-        Self::get_structure_size(signer_info_size, tagged)
+        Self::get_structure_size(csr_size, tagged)
     }
 
-    /// Get the size of the ASN.1 SignedData structure
-    /// If `tagged`, include the tag and size fields
-    fn get_signed_data_size(
-        csr: &[u8],
-        serial_number: &[u8],
-        issuer_der: &[u8],
-        sig: &EcdsaSig,
+    /// Get an upper bound on the size of the PKCS #10 CertificateRequest
+    /// CertifyKey issues for a context chain of `tci_count` TCI nodes
+    ///
+    /// Follows the CSR size helpers for the largest inputs CertifyKey gives
+    /// them: a full-length label, the cumulative TCI of every node and
+    /// signature integers that need a leading zero octet. CA and leaf keys
+    /// only differ in their key purpose OID, which has the same length.
+    pub(crate) const fn get_max_csr_size(tci_count: usize) -> usize {
+        /// `get_structure_size` of a tagged element, for constant expressions
+        const fn der(size: usize) -> usize {
+            let width = if size <= 127 {
+                1
+            } else if size <= 255 {
+                2
+            } else if size <= 65535 {
+                3
+            } else {
+                4
+            };
+            1 + width + size
+        }
+        const HASH_SIZE: usize = DPE_PROFILE.get_hash_size();
+        const ECC_INT_SIZE: usize = DPE_PROFILE.get_ecc_int_size();
+        const BOOL_SIZE: usize = CertWriter::BOOL_SIZE;
+
+        // tcg-dice-MultiTcbInfo with current and cumulative FWIDs
+        let fwid = der(der(Self::HASH_OID.len()) + der(HASH_SIZE));
+        let tcb_info = der(der(2 * fwid) + 2 * der(core::mem::size_of::<u32>()));
+        let multi_tcb_info = der(der(Self::MULTI_TCBINFO_OID.len())
+            + der(BOOL_SIZE)
+            + der(der(tci_count * tcb_info)));
+
+        // tcg-dice-Ueid
+        let ueid = der(der(Self::UEID_OID.len())
+            + der(BOOL_SIZE)
+            + der(der(der(DPE_PROFILE.ueid_size()))));
+
+        // basicConstraints without pathLenConstraint
+        let basic_constraints =
+            der(der(Self::BASIC_CONSTRAINTS_OID.len()) + der(BOOL_SIZE) + der(der(der(BOOL_SIZE))));
+
+        // keyUsage with one octet of bits
+        let key_usage = der(der(Self::KEY_USAGE_OID.len()) + der(BOOL_SIZE) + der(der(2)));
+
+        // extendedKeyUsage with the leaf or the CA key purpose
+        let key_purpose = if Self::ATTEST_LOC_OID.len() > Self::ECA_OID.len() {
+            Self::ATTEST_LOC_OID.len()
+        } else {
+            Self::ECA_OID.len()
+        };
+        let extended_key_usage = der(der(Self::EXTENDED_KEY_USAGE_OID.len())
+            + der(BOOL_SIZE)
+            + der(der(der(key_purpose))));
+
+        let extensions =
+            der(multi_tcb_info + ueid + basic_constraints + key_usage + extended_key_usage);
+        let attributes = der(der(der(Self::EXTENSION_REQUEST_OID.len()) + der(extensions)));
+
+        let subject = der(der(der(
+            der(Self::RDN_COMMON_NAME_OID.len()) + der(CERTIFY_KEY_SUBJECT_CN.len())
+        )) + der(der(
+            der(Self::RDN_SERIALNUMBER_OID.len()) + der(2 * HASH_SIZE)
+        )));
+        let spki = der(der(1 + 1 + 2 * ECC_INT_SIZE)
+            + der(der(Self::EC_PUB_OID.len()) + der(Self::CURVE_OID.len())));
+        let cert_req_info = der(der(1) + subject + spki + attributes);
+
+        let sig_alg_id = der(der(Self::ECDSA_OID.len()));
+        let sig = der(1 + der(2 * der(ECC_INT_SIZE + 1)));
+
+        der(cert_req_info + sig_alg_id + sig)
+    }
+
+    /// Get the size of the ASN.1 CertificationRequestInfo structure
+    /// If `tagged`, include the tag and size fields
+    fn get_certification_request_info_size(
+        subject_name: Option<&Name>,
+        pubkey: &EcdsaPub,
+        measurements: &MeasurementData,
+        tagged: bool,
+    ) -> Result<usize, DpeErrorCode> {
+        let subject_size = match subject_name {
+            Some(name) => Self::get_rdn_size(name, /*tagged=*/ true)?,
+            None => Self::get_structure_size(0, /*tagged=*/ true)?,
+        };
+        let cert_req_info_size = Self::checked_sum(&[
+            Self::get_integer_size(Self::CSR_V0, true)?,
+            subject_size,
+            Self::get_spki_size(pubkey, measurements)?,
+            Self::get_attributes_size(measurements, /*tagged=*/ true)?,
+        ])?;
+
+        Self::get_structure_size(cert_req_info_size, tagged)
+    }
+
+    /// Get the size of the ASN.1 SignerInfo structure
+    /// If `tagged`, include the tag and size fields
+    fn get_signer_info_size(
+        signer_id: &SignerId,
+        sig: &EcdsaSig,
+        hash_oid: &[u8],
+        tagged: bool,
+    ) -> Result<usize, DpeErrorCode> {
+        let signer_info_size = Self::checked_sum(&[
+            Self::get_integer_size(signer_id.get_cms_version(), true)?,
+            Self::get_signer_identifier_size(signer_id)?,
+            Self::get_hash_alg_id_size(hash_oid, /*tagged=*/ true)?,
+            Self::get_ecdsa_sig_alg_id_size(/*tagged=*/ true)?,
+            Self::get_ecdsa_signature_octet_string_size(sig, /*tagged=*/ true)?,
+        ])?;
+
+        Self::get_structure_size(signer_info_size, tagged)
+    }
+
+    /// Get the size of the signerInfos SET holding a single SignerInfo,
+    /// including the tag and size fields
+    fn get_signer_infos_size(
+        signer_id: &SignerId,
+        sig: &EcdsaSig,
+        hash_oid: &[u8],
+    ) -> Result<usize, DpeErrorCode> {
+        Self::get_structure_size(
+            Self::get_signer_info_size(signer_id, sig, hash_oid, /*tagged=*/ true)?,
+            /*tagged=*/ true,
+        )
+    }
+
+    /// Get the size of the CMS ContentInfo structure
+    /// If `tagged`, include the tag and size fields
+    fn get_cms_size(
+        csr_len: usize,
+        signer_id: &SignerId,
+        sig: &EcdsaSig,
+        hash_oid: &[u8],
+        tagged: bool,
+    ) -> Result<usize, DpeErrorCode> {
+        let size = Self::checked_sum(&[
+            Self::get_structure_size(Self::ID_SIGNED_DATA_OID.len(), /*tagged=*/ true)?,
+            Self::get_signed_data_size(
+                csr_len, signer_id, sig, hash_oid, /*tagged=*/ true, /*explicit=*/ true,
+            )?,
+        ])?;
+
+        Self::get_structure_size(size, tagged)
+    }
+
+    /// Get the size of the certificates-only CMS ContentInfo structure
+    /// If `tagged`, include the tag and size fields
+    fn get_certs_only_cms_size(certs: &[u8], tagged: bool) -> Result<usize, DpeErrorCode> {
+        let size = Self::checked_sum(&[
+            Self::get_structure_size(Self::ID_SIGNED_DATA_OID.len(), /*tagged=*/ true)?,
+            Self::get_certs_only_signed_data_size(
+                certs, /*tagged=*/ true, /*explicit=*/ true,
+            )?,
+        ])?;
+
+        Self::get_structure_size(size, tagged)
+    }
+
+    /// Get the size of the ASN.1 SignedData structure
+    /// If `tagged`, include the tag and size fields
+    fn get_signed_data_size(
+        csr_len: usize,
+        signer_id: &SignerId,
+        sig: &EcdsaSig,
+        hash_oid: &[u8],
         tagged: bool,
         explicit: bool,
     ) -> Result<usize, DpeErrorCode> {
-        let signed_data_size = Self::get_integer_size(Self::CMS_V1, true)?
-            + Self::get_structure_size(
-                Self::get_hash_alg_id_size(/*tagged=*/ true)?,
+        let signed_data_size = Self::checked_sum(&[
+            Self::get_integer_size(signer_id.get_cms_version(), true)?,
+            Self::get_structure_size(
+                Self::get_hash_alg_id_size(hash_oid, /*tagged=*/ true)?,
                 /*tagged=*/ true,
-            )?
-            + Self::get_encap_content_info_size(csr, /*tagged=*/ true)?
-            + Self::get_structure_size(
-                Self::get_signer_info_size(serial_number, issuer_der, sig, /*tagged=*/ true)?,
-                /*tagged=*/ true,
-            )?;
+            )?,
+            Self::get_encap_content_info_size(Some(csr_len), /*tagged=*/ true)?,
+            Self::get_signer_infos_size(signer_id, sig, hash_oid)?,
+        ])?;
+
+        // Determine whether to include the explicit tag wrapping in the size calculation
+        let explicit_signed_data_size = Self::get_structure_size(signed_data_size, explicit)?;
+
+        Self::get_structure_size(explicit_signed_data_size, tagged)
+    }
+
+    /// Check that `certs` is a concatenation of single well-formed DER
+    /// SEQUENCEs, one per certificate
+    fn validate_certs(certs: &[u8]) -> Result<(), DpeErrorCode> {
+        let mut rest = certs;
+        while !rest.is_empty() {
+            let (_, _, next) = Self::split_element(rest)?;
+            let cert = &rest[..rest.len() - next.len()];
+            Self::validate_der_well_formed_single_element(cert)?;
+            if cert[0] != Self::SEQUENCE_TAG {
+                return Err(DpeErrorCode::InvalidArgument);
+            }
+            rest = next;
+        }
+
+        Ok(())
+    }
+
+    /// Get the size of a certificates-only ASN.1 SignedData structure
+    /// If `tagged`, include the tag and size fields
+    ///
+    /// Fails if `certs` is not a concatenation of well-formed DER SEQUENCEs.
+    fn get_certs_only_signed_data_size(
+        certs: &[u8],
+        tagged: bool,
+        explicit: bool,
+    ) -> Result<usize, DpeErrorCode> {
+        Self::validate_certs(certs)?;
+        let signed_data_size = Self::checked_sum(&[
+            Self::get_integer_size(Self::CMS_V1, true)?,
+            Self::get_structure_size(0, /*tagged=*/ true)?, // digestAlgorithms
+            Self::get_encap_content_info_size(None, /*tagged=*/ true)?,
+            Self::get_structure_size(certs.len(), /*tagged=*/ true)?, // certificates
+            Self::get_structure_size(0, /*tagged=*/ true)?,           // signerInfos
+        ])?;
 
         // Determine whether to include the explicit tag wrapping in the size calculation
         let explicit_signed_data_size = Self::get_structure_size(signed_data_size, explicit)?;
@@ -555,6 +2417,28 @@ impl CertWriter<'_> {
         Self::get_structure_size(explicit_signed_data_size, tagged)
     }
 
+    /// Get the size of the ASN.1 SignerIdentifier structure, including the
+    /// tag and size fields
+    fn get_signer_identifier_size(signer_id: &SignerId) -> Result<usize, DpeErrorCode> {
+        match *signer_id {
+            SignerId::IssuerAndSerialNumber {
+                issuer_name,
+                serial_number,
+            } => Self::get_issuer_and_serial_number_size(
+                serial_number,
+                issuer_name,
+                /*tagged=*/ true,
+            ),
+            SignerId::SubjectKeyIdentifier(key_id) => {
+                Self::get_structure_size(key_id.len(), /*tagged=*/ true)
+            }
+            SignerId::IssuerAndSerialNumberDer(issuer_serial_der) => {
+                Self::validate_issuer_serial_der(issuer_serial_der)?;
+                Ok(issuer_serial_der.len())
+            }
+        }
+    }
+
     /// Get the size of the ASN.1 IssuerAndSerialNumber structure
     /// If `tagged`, include the tag and size fields
     fn get_issuer_and_serial_number_size(
@@ -562,19 +2446,21 @@ impl CertWriter<'_> {
         issuer_der: &[u8],
         tagged: bool,
     ) -> Result<usize, DpeErrorCode> {
-        let issuer_and_serial_number_size =
-            Self::get_integer_bytes_size(serial_number, /*tagged=*/ true)? + issuer_der.len();
+        Self::validate_serial_number(serial_number)?;
+        Self::validate_name_der(issuer_der)?;
+        let issuer_and_serial_number_size = Self::checked_sum(&[
+            Self::get_integer_bytes_size(serial_number, /*tagged=*/ true)?,
+            issuer_der.len(),
+        ])?;
 
         Self::get_structure_size(issuer_and_serial_number_size, tagged)
     }
 
     fn get_econtent_size(
-        bytes: &[u8],
+        bytes_size: usize,
         tagged: bool,
         explicit: bool,
     ) -> Result<usize, DpeErrorCode> {
-        let bytes_size = bytes.len();
-
         // Determine whether to include the explicit tag wrapping in the size calculation
         let explicit_bytes_size = Self::get_structure_size(bytes_size, explicit)?;
 
@@ -583,30 +2469,66 @@ impl CertWriter<'_> {
 
     /// Get the size of the ASN.1 EncapsulatedContentInfo structure
     /// If `tagged`, include the tag and size fields
-    fn get_encap_content_info_size(csr: &[u8], tagged: bool) -> Result<usize, DpeErrorCode> {
-        let encap_content_info_size =
-            Self::get_structure_size(Self::ID_DATA_OID.len(), /*tagged=*/ true)?
-                + Self::get_econtent_size(csr, /*tagged=*/ true, /*explicit=*/ true)?;
+    /// `econtent_len` is the length of the eContent, if present.
+    fn get_encap_content_info_size(
+        econtent_len: Option<usize>,
+        tagged: bool,
+    ) -> Result<usize, DpeErrorCode> {
+        let mut encap_content_info_size =
+            Self::get_structure_size(Self::ID_DATA_OID.len(), /*tagged=*/ true)?;
+        if let Some(econtent_len) = econtent_len {
+            encap_content_info_size = Self::checked_sum(&[
+                encap_content_info_size,
+                Self::get_econtent_size(
+                    econtent_len,
+                    /*tagged=*/ true,
+                    /*explicit=*/ true,
+                )?,
+            ])?;
+        }
 
         Self::get_structure_size(encap_content_info_size, tagged)
     }
 
-    /// Get the size of the ASN.1 Attribute structure
+    /// Get the size of the ASN.1 extensionRequest Attribute structure
     /// If `tagged`, include the tag and size fields
-    fn get_attribute_size(
+    fn get_extension_request_size(
         measurements: &MeasurementData,
         tagged: bool,
     ) -> Result<usize, DpeErrorCode> {
-        let attribute_size =
-            Self::get_structure_size(Self::ID_DATA_OID.len(), /*tagged=*/ true)?
-                + Self::get_structure_size(
-                    Self::get_extensions_size(
-                        measurements,
-                        /*tagged=*/ true,
-                        /*explicit=*/ false,
-                    )?,
+        let attribute_size = Self::checked_sum(&[
+            Self::get_structure_size(Self::EXTENSION_REQUEST_OID.len(), /*tagged=*/ true)?,
+            Self::get_structure_size(
+                Self::get_extensions_size(
+                    measurements,
                     /*tagged=*/ true,
-                )?;
+                    /*explicit=*/ false,
+                )?,
+                /*tagged=*/ true,
+            )?,
+        ])?;
+
+        Self::get_structure_size(attribute_size, tagged)
+    }
+
+    /// Get the size of the ASN.1 challengePassword Attribute structure. Zero
+    /// if there is no challenge password.
+    /// If `tagged`, include the tag and size fields
+    fn get_challenge_password_size(
+        measurements: &MeasurementData,
+        tagged: bool,
+    ) -> Result<usize, DpeErrorCode> {
+        let Some(password) = measurements.challenge_password else {
+            return Ok(0);
+        };
+
+        let attribute_size = Self::checked_sum(&[
+            Self::get_structure_size(Self::CHALLENGE_PASSWORD_OID.len(), /*tagged=*/ true)?,
+            Self::get_structure_size(
+                Self::get_structure_size(password.len(), /*tagged=*/ true)?,
+                /*tagged=*/ true,
+            )?,
+        ])?;
 
         Self::get_structure_size(attribute_size, tagged)
     }
@@ -617,18 +2539,70 @@ impl CertWriter<'_> {
         measurements: &MeasurementData,
         tagged: bool,
     ) -> Result<usize, DpeErrorCode> {
-        let attribute_size = Self::get_attribute_size(measurements, /*tagged=*/ true)?;
+        let attributes_size = Self::checked_sum(&[
+            Self::get_extension_request_size(measurements, /*tagged=*/ true)?,
+            Self::get_challenge_password_size(measurements, /*tagged=*/ true)?,
+        ])?;
 
-        Self::get_structure_size(attribute_size, tagged)
+        Self::get_structure_size(attributes_size, tagged)
+    }
+
+    /// Sort the DER elements written since `start` into canonical SET OF
+    /// order, ascending by their encodings.
+    ///
+    /// The elements are sorted in place so no scratch buffer is needed.
+    fn sort_set_of(&mut self, start: usize) -> Result<(), DpeErrorCode> {
+        let set = self
+            .certificate
+            .get_mut(start..self.offset)
+            .ok_or(DpeErrorCode::InternalError)?;
+
+        // Selection sort: move the smallest remaining element to `sorted`
+        let mut sorted = 0;
+        while sorted < set.len() {
+            let mut min = sorted..sorted;
+            let mut next = sorted;
+            while next < set.len() {
+                let (_, header_len, size) =
+                    Self::decode_header(&set[next..]).map_err(|_| DpeErrorCode::InternalError)?;
+                let end = next + header_len + size;
+                let element = set.get(next..end).ok_or(DpeErrorCode::InternalError)?;
+                if min.is_empty() || element < &set[min.clone()] {
+                    min = next..end;
+                }
+                next = end;
+            }
+
+            let min_len = min.len();
+            set[sorted..min.end].rotate_right(min_len);
+            sorted += min_len;
+        }
+
+        Ok(())
+    }
+
+    /// Check that `size` more bytes fit in the certificate buffer
+    ///
+    /// Fails with `BufferTooSmall` holding the total buffer size needed.
+    fn check_space(&self, size: usize) -> Result<(), DpeErrorCode> {
+        let needed = self
+            .offset
+            .checked_add(size)
+            .ok_or(DpeErrorCode::InternalError)?;
+        if needed > self.certificate.len() {
+            return Err(DpeErrorCode::BufferTooSmall(
+                u32::try_from(needed).map_err(|_| DpeErrorCode::InternalError)?,
+            ));
+        }
+
+        Ok(())
     }
 
     /// Write all of `bytes` to the certificate buffer
     fn encode_bytes(&mut self, bytes: &[u8]) -> Result<usize, DpeErrorCode> {
         let size = bytes.len();
 
-        if self.offset >= self.certificate.len() || self.offset + size > self.certificate.len() {
-            return Err(DpeErrorCode::InternalError);
-        }
+        self.check_space(size)?;
 
         self.certificate
             .get_mut(self.offset..self.offset + size)
@@ -641,15 +2615,62 @@ impl CertWriter<'_> {
 
     /// Write a single `byte` to be certificate buffer
     fn encode_byte(&mut self, byte: u8) -> Result<usize, DpeErrorCode> {
-        if self.offset >= self.certificate.len() {
-            return Err(DpeErrorCode::InternalError);
-        }
+        self.check_space(1)?;
 
         self.certificate[self.offset] = byte;
         self.offset += 1;
         Ok(1)
     }
 
+    /// DER-encodes a structure with `tag` whose contents are written by
+    /// `encode_contents`.
+    ///
+    /// In two-pass mode the size field is written from `get_size` before the
+    /// contents. In single-pass mode `get_size` is not called. Instead a
+    /// maximum-width size field is reserved and back-patched once the
+    /// contents have been written, shifting the contents if the size field
+    /// turns out to be narrower.
+    fn encode_structure(
+        &mut self,
+        tag: u8,
+        get_size: impl FnOnce() -> Result<usize, DpeErrorCode>,
+        encode_contents: impl FnOnce(&mut Self) -> Result<usize, DpeErrorCode>,
+    ) -> Result<usize, DpeErrorCode> {
+        let mut bytes_written = self.encode_tag_field(tag)?;
+
+        if !self.single_pass {
+            bytes_written += self.encode_size_field(get_size()?)?;
+            bytes_written += encode_contents(self)?;
+            return Ok(bytes_written);
+        }
+
+        // Reserve a size field wide enough for any supported size
+        const MAX_SIZE_WIDTH: usize = 3;
+        let size_offset = self.offset;
+        for _ in 0..MAX_SIZE_WIDTH {
+            self.encode_byte(0)?;
+        }
+
+        let contents_size = encode_contents(self)?;
+        let contents_offset = size_offset + MAX_SIZE_WIDTH;
+        if self.offset != contents_offset + contents_size {
+            return Err(DpeErrorCode::InternalError);
+        }
+
+        let size_width = Self::get_size_width(contents_size)?;
+        if size_width < MAX_SIZE_WIDTH {
+            self.certificate
+                .copy_within(contents_offset..self.offset, size_offset + size_width);
+        }
+
+        self.offset = size_offset;
+        bytes_written += self.encode_size_field(contents_size)?;
+        self.offset += contents_size;
+        bytes_written += contents_size;
+
+        Ok(bytes_written)
+    }
+
     /// DER-encodes the tag field of an ASN.1 type
     fn encode_tag_field(&mut self, tag: u8) -> Result<usize, DpeErrorCode> {
         self.encode_byte(tag)
@@ -673,6 +2694,27 @@ impl CertWriter<'_> {
         Ok(size_width)
     }
 
+    /// DER-encodes the tag and size fields of an ASN.1 SEQUENCE whose
+    /// contents are `content_len` bytes
+    fn encode_sequence_header(&mut self, content_len: usize) -> Result<usize, DpeErrorCode> {
+        let bytes_written = self.encode_tag_field(Self::SEQUENCE_TAG)?;
+        Ok(bytes_written + self.encode_size_field(content_len)?)
+    }
+
+    /// DER-encodes the tag and size fields of an ASN.1 SET or SET OF whose
+    /// contents are `content_len` bytes
+    fn encode_set_header(&mut self, content_len: usize) -> Result<usize, DpeErrorCode> {
+        let bytes_written = self.encode_tag_field(Self::SET_OF_TAG)?;
+        Ok(bytes_written + self.encode_size_field(content_len)?)
+    }
+
+    /// DER-encodes the tag and size fields of an ASN.1 OCTET STRING whose
+    /// contents are `content_len` bytes
+    fn encode_octet_string_header(&mut self, content_len: usize) -> Result<usize, DpeErrorCode> {
+        let bytes_written = self.encode_tag_field(Self::OCTET_STRING_TAG)?;
+        Ok(bytes_written + self.encode_size_field(content_len)?)
+    }
+
     /// DER-encodes a big-endian integer buffer as an ASN.1 INTEGER
     fn encode_integer_bytes(&mut self, integer: &[u8]) -> Result<usize, DpeErrorCode> {
         let mut bytes_written = self.encode_tag_field(Self::INTEGER_TAG)?;
@@ -701,8 +2743,87 @@ impl CertWriter<'_> {
         self.encode_integer_bytes(&integer.to_be_bytes())
     }
 
+    /// Get the size of an INTEGER written by `encode_integer_fixed_width`
+    /// If `tagged`, include the tag and size fields
+    pub fn get_integer_fixed_width_size(width: usize, tagged: bool) -> Result<usize, DpeErrorCode> {
+        Self::get_structure_size(width, tagged)
+    }
+
+    /// Encodes `integer` as an ASN.1 INTEGER of exactly `width` content bytes,
+    /// for fields such as boot counters that must keep the same size whatever
+    /// their value
+    ///
+    /// The value is zero-padded on the left. It must leave the high bit of
+    /// the first byte clear so it stays positive, so `width` is between 1 and
+    /// 9. Padding that does not come from the high bit is not minimal DER, so
+    /// use this only inside extension values whose verifier expects it.
+    pub fn encode_integer_fixed_width(
+        &mut self,
+        integer: u64,
+        width: usize,
+    ) -> Result<usize, DpeErrorCode> {
+        const MAX_WIDTH: usize = core::mem::size_of::<u64>() + 1;
+        if width == 0 || width > MAX_WIDTH {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+        // The value needs `width * 8 - 1` bits or fewer
+        let value_bits = u64::BITS - integer.leading_zeros();
+        if value_bits as usize >= width * 8 {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        let mut bytes_written = self.encode_tag_field(Self::INTEGER_TAG)?;
+        bytes_written += self.encode_size_field(width)?;
+        let be_bytes = integer.to_be_bytes();
+        let pad = width.saturating_sub(be_bytes.len());
+        for _ in 0..pad {
+            bytes_written += self.encode_byte(0)?;
+        }
+        let start = be_bytes.len() - (width - pad);
+        bytes_written += self.encode_bytes(&be_bytes[start..])?;
+
+        Ok(bytes_written)
+    }
+
+    /// DER-encodes `integer` as an ASN.1 INTEGER with an IMPLICIT context-specific
+    /// tag `field_number`
+    fn encode_implicit_integer(
+        &mut self,
+        field_number: u8,
+        integer: u64,
+    ) -> Result<usize, DpeErrorCode> {
+        let tag_offset = self.offset;
+        let bytes_written = self.encode_integer(integer)?;
+        self.certificate[tag_offset] = Self::CONTEXT_SPECIFIC | field_number;
+
+        Ok(bytes_written)
+    }
+
+    /// Check that `oid` is a well-formed OBJECT IDENTIFIER value. Each
+    /// base-128 subidentifier must be minimally encoded, so it cannot start
+    /// with 0x80, and must end with a byte whose high bit is clear.
+    fn validate_oid(oid: &[u8]) -> Result<(), DpeErrorCode> {
+        if oid.is_empty() {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        let mut subidentifier_start = true;
+        for &byte in oid {
+            if subidentifier_start && byte == 0x80 {
+                return Err(DpeErrorCode::InvalidArgument);
+            }
+            subidentifier_start = byte & 0x80 == 0;
+        }
+        if !subidentifier_start {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
     /// DER-encodes `oid` as an ASN.1 ObjectIdentifier
     fn encode_oid(&mut self, oid: &[u8]) -> Result<usize, DpeErrorCode> {
+        Self::validate_oid(oid)?;
         let mut bytes_written = self.encode_tag_field(Self::OID_TAG)?;
         bytes_written += self.encode_size_field(oid.len())?;
         bytes_written += self.encode_bytes(oid)?;
@@ -717,6 +2838,7 @@ impl CertWriter<'_> {
         let (val, tag) = match s {
             DirectoryString::PrintableString(val) => (val, Self::PRINTABLE_STRING_TAG),
             DirectoryString::Utf8String(val) => (val, Self::UTF8_STRING_TAG),
+            DirectoryString::Ia5String(val) => (val, Self::IA5_STRING_TAG),
         };
         let mut bytes_written = self.encode_tag_field(tag)?;
         bytes_written += self.encode_size_field(val.len())?;
@@ -725,6 +2847,18 @@ impl CertWriter<'_> {
         Ok(bytes_written)
     }
 
+    /// Encode a DER encoded Name, such as the subject of an existing
+    /// certificate
+    ///
+    /// The Name is copied verbatim rather than re-encoded, so the output is
+    /// byte-for-byte identical to `name_der`. This guarantees that issuer and
+    /// subject names match exactly when chaining to an existing certificate.
+    /// Only the outer SEQUENCE of `name_der` is validated.
+    pub fn encode_name_from_der(&mut self, name_der: &[u8]) -> Result<usize, DpeErrorCode> {
+        Self::validate_name_der(name_der)?;
+        self.encode_bytes(name_der)
+    }
+
     /// DER-encodes a RelativeDistinguishedName with CommonName and SerialNumber
     /// fields.
     ///
@@ -744,42 +2878,24 @@ impl CertWriter<'_> {
     ///     ...
     ///     }
     pub fn encode_rdn(&mut self, name: &Name) -> Result<usize, DpeErrorCode> {
-        let cn_size =
-            Self::get_structure_size(Self::RDN_COMMON_NAME_OID.len(), /*tagged=*/ true)?
-                + Self::get_structure_size(name.cn.len(), /*tagged=*/ true)?;
-        let serialnumber_size =
-            Self::get_structure_size(Self::RDN_SERIALNUMBER_OID.len(), /*tagged=*/ true)?
-                + Self::get_structure_size(name.serial.len(), /*tagged=*/ true)?;
-
-        let rdn_name_set_size = Self::get_structure_size(cn_size, /*tagged=*/ true)?;
-        let rnd_serial_set_size =
-            Self::get_structure_size(serialnumber_size, /*tagged=*/ true)?;
-        let rdn_seq_size = Self::get_structure_size(rdn_name_set_size, /*tagged=*/ true)?
-            + Self::get_structure_size(rnd_serial_set_size, /*tagged=*/ true)?;
+        self.check_space(Self::get_rdn_size(name, /*tagged=*/ true)?)?;
 
         // Encode RDN SEQUENCE OF
-        let mut bytes_written = self.encode_tag_field(Self::SEQUENCE_OF_TAG)?;
-        bytes_written += self.encode_size_field(rdn_seq_size)?;
-
-        // Encode RDN SET
-        bytes_written += self.encode_tag_field(Self::SET_OF_TAG)?;
-        bytes_written += self.encode_size_field(rdn_name_set_size)?;
-
-        // Encode CN SEQUENCE
-        bytes_written += self.encode_tag_field(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(cn_size)?;
-        bytes_written += self.encode_oid(&Self::RDN_COMMON_NAME_OID)?;
-        bytes_written += self.encode_rdn_string(&name.cn)?;
-
-        // Encode RDN SET
-        bytes_written += self.encode_tag_field(Self::SET_OF_TAG)?;
-        bytes_written += self.encode_size_field(rnd_serial_set_size)?;
+        let mut bytes_written =
+            self.encode_sequence_header(Self::get_rdn_size(name, /*tagged=*/ false)?)?;
 
-        // Encode SERIALNUMBER SEQUENCE
-        bytes_written += self.encode_tag_field(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(serialnumber_size)?;
-        bytes_written += self.encode_oid(&Self::RDN_SERIALNUMBER_OID)?;
-        bytes_written += self.encode_rdn_string(&name.serial)?;
+        // Encode each attribute in its own RDN SET, in the name's order
+        for (oid, val) in name.attributes() {
+            bytes_written += self.encode_set_header(Self::get_rdn_attribute_size(
+                oid, val, /*tagged=*/ false,
+            )?)?;
+            bytes_written += self.encode_sequence_header(Self::checked_sum(&[
+                Self::get_bytes_size(oid, /*tagged=*/ true)?,
+                Self::get_bytes_size(val.bytes(), /*tagged=*/ true)?,
+            ])?)?;
+            bytes_written += self.encode_oid(oid)?;
+            bytes_written += self.encode_rdn_string(val)?;
+        }
 
         Ok(bytes_written)
     }
@@ -800,8 +2916,7 @@ impl CertWriter<'_> {
     fn encode_ec_pub_alg_id(&mut self) -> Result<usize, DpeErrorCode> {
         let seq_size = Self::get_ec_pub_alg_id_size(/*tagged=*/ false)?;
 
-        let mut bytes_written = self.encode_tag_field(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(seq_size)?;
+        let mut bytes_written = self.encode_sequence_header(seq_size)?;
         bytes_written += self.encode_oid(Self::EC_PUB_OID)?;
         bytes_written += self.encode_oid(Self::CURVE_OID)?;
 
@@ -816,46 +2931,58 @@ impl CertWriter<'_> {
     ///     parameters  ECParameters
     ///     }
     fn encode_ecdsa_sig_alg_id(&mut self) -> Result<usize, DpeErrorCode> {
-        let seq_size = Self::get_ecdsa_sig_alg_id_size(/*tagged=*/ false)?;
+        self.encode_sig_alg_id(Self::ECDSA_OID)
+    }
+
+    /// DER-encodes a signature AlgorithmIdentifier for algorithm `oid`. The
+    /// parameters are absent.
+    fn encode_sig_alg_id(&mut self, oid: &[u8]) -> Result<usize, DpeErrorCode> {
+        let seq_size = Self::get_sig_alg_id_size(oid, /*tagged=*/ false)?;
 
-        let mut bytes_written = self.encode_tag_field(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(seq_size)?;
-        bytes_written += self.encode_oid(Self::ECDSA_OID)?;
+        let mut bytes_written = self.encode_sequence_header(seq_size)?;
+        bytes_written += self.encode_oid(oid)?;
 
         Ok(bytes_written)
     }
 
-    /// DER-encodes the AlgorithmIdentifier for the hash algorithm
-    /// used by the active DPE profile.
+    /// DER-encodes the AlgorithmIdentifier for the hash algorithm `hash_oid`
     ///
     /// AlgorithmIdentifier  ::=  SEQUENCE  {
     ///     algorithm   OBJECT IDENTIFIER,
     ///     parameters  ECParameters
     ///     }
-    fn encode_hash_alg_id(&mut self) -> Result<usize, DpeErrorCode> {
-        let seq_size = Self::get_hash_alg_id_size(/*tagged=*/ false)?;
+    fn encode_hash_alg_id(&mut self, hash_oid: &[u8]) -> Result<usize, DpeErrorCode> {
+        let seq_size = Self::get_hash_alg_id_size(hash_oid, /*tagged=*/ false)?;
 
-        let mut bytes_written = self.encode_tag_field(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(seq_size)?;
-        bytes_written += self.encode_oid(Self::HASH_OID)?;
+        let mut bytes_written = self.encode_sequence_header(seq_size)?;
+        bytes_written += self.encode_oid(hash_oid)?;
 
         Ok(bytes_written)
     }
 
-    // Encode ASN.1 Validity which never expires
-    fn encode_validity(&mut self) -> Result<usize, DpeErrorCode> {
-        let seq_size = Self::get_validity_size(/*tagged=*/ false)?;
+    // Encode ASN.1 Validity, which never expires unless the caller supplies
+    // its own dates
+    fn encode_validity(&mut self, measurements: &MeasurementData) -> Result<usize, DpeErrorCode> {
+        let seq_size = Self::get_validity_size(measurements, /*tagged=*/ false)?;
+        let validity = measurements.get_validity();
+
+        let mut bytes_written = self.encode_sequence_header(seq_size)?;
+        bytes_written += self.encode_time(validity.not_before, measurements.time_encoding)?;
+        bytes_written += self.encode_time(validity.not_after, measurements.time_encoding)?;
 
-        let mut bytes_written = self.encode_tag_field(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(seq_size)?;
+        Ok(bytes_written)
+    }
 
-        bytes_written += self.encode_tag_field(Self::GENERALIZE_TIME_TAG)?;
-        bytes_written += self.encode_size_field(Self::NOT_BEFORE.len())?;
-        bytes_written += self.encode_bytes(Self::NOT_BEFORE.as_bytes())?;
+    /// Encode the GeneralizedTime string `time` as a UTCTime or
+    /// GeneralizedTime, depending on `encoding`
+    ///
+    /// Returns number of bytes written to `remaining_cert`
+    fn encode_time(&mut self, time: &str, encoding: TimeEncoding) -> Result<usize, DpeErrorCode> {
+        let (tag, value) = Self::get_time_value(time, encoding)?;
 
-        bytes_written += self.encode_tag_field(Self::GENERALIZE_TIME_TAG)?;
-        bytes_written += self.encode_size_field(Self::NOT_AFTER.len())?;
-        bytes_written += self.encode_bytes(Self::NOT_AFTER.as_bytes())?;
+        let mut bytes_written = self.encode_tag_field(tag)?;
+        bytes_written += self.encode_size_field(value.len())?;
+        bytes_written += self.encode_bytes(value)?;
 
         Ok(bytes_written)
     }
@@ -869,7 +2996,7 @@ impl CertWriter<'_> {
     ///        subjectPublicKey     BIT STRING  }
     ///
     /// subjectPublicKey is a BIT STRING containing an ECPoint
-    /// in uncompressed format.
+    /// in `point_format`.
     ///
     /// ECPoint ::= OCTET STRING
     ///
@@ -878,14 +3005,16 @@ impl CertWriter<'_> {
     fn encode_ecdsa_subject_pubkey_info(
         &mut self,
         pubkey: &EcdsaPub,
+        point_format: PointFormat,
     ) -> Result<usize, DpeErrorCode> {
-        let point_size = 1 + pubkey.x.len() + pubkey.y.len();
-        let bitstring_size = 1 + point_size;
-        let seq_size = Self::get_structure_size(bitstring_size, /*tagged=*/ true)?
-            + Self::get_ec_pub_alg_id_size(/*tagged=*/ true)?;
-
-        let mut bytes_written = self.encode_tag_field(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(seq_size)?;
+        let point_size = Self::get_ec_point_size(pubkey, point_format)?;
+        let bitstring_size = Self::checked_sum(&[1, point_size])?;
+        let seq_size = Self::checked_sum(&[
+            Self::get_structure_size(bitstring_size, /*tagged=*/ true)?,
+            Self::get_ec_pub_alg_id_size(/*tagged=*/ true)?,
+        ])?;
+
+        let mut bytes_written = self.encode_sequence_header(seq_size)?;
         bytes_written += self.encode_ec_pub_alg_id()?;
 
         bytes_written += self.encode_tag_field(Self::BIT_STRING_TAG)?;
@@ -894,9 +3023,59 @@ impl CertWriter<'_> {
         // are used.
         bytes_written += self.encode_byte(0)?;
 
-        bytes_written += self.encode_byte(0x4)?;
-        bytes_written += self.encode_bytes(pubkey.x.bytes())?;
-        bytes_written += self.encode_bytes(pubkey.y.bytes())?;
+        match point_format {
+            PointFormat::Uncompressed => {
+                bytes_written += self.encode_byte(0x4)?;
+                bytes_written += self.encode_ec_coordinate(pubkey.x.bytes())?;
+                bytes_written += self.encode_ec_coordinate(pubkey.y.bytes())?;
+            }
+            PointFormat::Compressed => {
+                // The prefix holds the parity of y, which is big-endian
+                let y_is_odd = pubkey.y.bytes().last().is_some_and(|b| b & 1 == 1);
+                bytes_written += self.encode_byte(if y_is_odd { 0x03 } else { 0x02 })?;
+                bytes_written += self.encode_ec_coordinate(pubkey.x.bytes())?;
+            }
+        }
+
+        Ok(bytes_written)
+    }
+
+    /// Encode a DER encoded SubjectPublicKeyInfo, such as one for an
+    /// algorithm DPE does not encode natively
+    ///
+    /// The SubjectPublicKeyInfo is copied verbatim after checking that it is
+    /// a single SEQUENCE holding an AlgorithmIdentifier and a BIT STRING.
+    pub fn encode_spki_raw(&mut self, spki_der: &[u8]) -> Result<usize, DpeErrorCode> {
+        Self::validate_spki_der(spki_der)?;
+        self.encode_bytes(spki_der)
+    }
+
+    /// Encode the SubjectPublicKeyInfo for `pubkey`, or the raw one in
+    /// `measurements` if present
+    fn encode_spki(
+        &mut self,
+        pubkey: &EcdsaPub,
+        measurements: &MeasurementData,
+    ) -> Result<usize, DpeErrorCode> {
+        match measurements.subject_public_key_info {
+            Some(spki_der) => self.encode_spki_raw(spki_der),
+            None => self.encode_ecdsa_subject_pubkey_info(pubkey, measurements.point_format),
+        }
+    }
+
+    /// Encode an EC point coordinate left-padded with zeros to the profile's
+    /// integer size
+    fn encode_ec_coordinate(&mut self, coord: &[u8]) -> Result<usize, DpeErrorCode> {
+        const ECC_INT_SIZE: usize = DPE_PROFILE.get_ecc_int_size();
+        let padding = ECC_INT_SIZE
+            .checked_sub(coord.len())
+            .ok_or(DpeErrorCode::InternalError)?;
+
+        let mut bytes_written = 0;
+        for _ in 0..padding {
+            bytes_written += self.encode_byte(0)?;
+        }
+        bytes_written += self.encode_bytes(coord)?;
 
         Ok(bytes_written)
     }
@@ -908,21 +3087,22 @@ impl CertWriter<'_> {
     ///     s  INTEGER
     ///   }
     fn encode_ecdsa_signature_bit_string(&mut self, sig: &EcdsaSig) -> Result<usize, DpeErrorCode> {
-        let seq_size = Self::get_integer_bytes_size(sig.r.bytes(), /*tagged=*/ true)?
-            + Self::get_integer_bytes_size(sig.s.bytes(), /*tagged=*/ true)?;
+        let seq_size = Self::checked_sum(&[
+            Self::get_integer_bytes_size(sig.r.bytes(), /*tagged=*/ true)?,
+            Self::get_integer_bytes_size(sig.s.bytes(), /*tagged=*/ true)?,
+        ])?;
 
         // Encode BIT STRING
         let mut bytes_written = self.encode_tag_field(Self::BIT_STRING_TAG)?;
         bytes_written += self.encode_size_field(Self::get_structure_size(
-            1 + seq_size,
+            Self::checked_sum(&[1, seq_size])?,
             /*tagged=*/ true,
         )?)?;
         // Unused bits
         bytes_written += self.encode_byte(0)?;
 
         // Encode SEQUENCE
-        bytes_written += self.encode_tag_field(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(seq_size)?;
+        bytes_written += self.encode_sequence_header(seq_size)?;
         bytes_written += self.encode_integer_bytes(sig.r.bytes())?;
         bytes_written += self.encode_integer_bytes(sig.s.bytes())?;
 
@@ -939,93 +3119,198 @@ impl CertWriter<'_> {
         &mut self,
         sig: &EcdsaSig,
     ) -> Result<usize, DpeErrorCode> {
-        let seq_size = Self::get_integer_bytes_size(sig.r.bytes(), /*tagged=*/ true)?
-            + Self::get_integer_bytes_size(sig.s.bytes(), /*tagged=*/ true)?;
+        let seq_size = Self::checked_sum(&[
+            Self::get_integer_bytes_size(sig.r.bytes(), /*tagged=*/ true)?,
+            Self::get_integer_bytes_size(sig.s.bytes(), /*tagged=*/ true)?,
+        ])?;
 
         // Encode OCTET STRING
-        let mut bytes_written = self.encode_tag_field(Self::OCTET_STRING_TAG)?;
-        bytes_written +=
-            self.encode_size_field(Self::get_structure_size(seq_size, /*tagged=*/ true)?)?;
+        let mut bytes_written = self.encode_octet_string_header(Self::get_structure_size(
+            seq_size, /*tagged=*/ true,
+        )?)?;
 
         // Encode SEQUENCE
-        bytes_written += self.encode_tag_field(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(seq_size)?;
+        bytes_written += self.encode_sequence_header(seq_size)?;
         bytes_written += self.encode_integer_bytes(sig.r.bytes())?;
         bytes_written += self.encode_integer_bytes(sig.s.bytes())?;
 
         Ok(bytes_written)
     }
 
-    pub fn encode_version(&mut self) -> Result<usize, DpeErrorCode> {
+    /// Encode the TBS version. Nothing is written for v1, the DEFAULT.
+    pub fn encode_version(&mut self, version: CertVersion) -> Result<usize, DpeErrorCode> {
+        if version == CertVersion::V1 {
+            return Ok(0);
+        }
+        self.check_space(Self::get_version_size(version, /*tagged=*/ true)?)?;
+
         // Version is EXPLICIT field number 0
         let mut bytes_written = self.encode_byte(Self::CONTEXT_SPECIFIC | Self::CONSTRUCTED)?;
         bytes_written += self.encode_size_field(Self::get_integer_size(
-            Self::X509_V3,
+            version.value(),
             /*tagged=*/ true,
         )?)?;
-        bytes_written += self.encode_integer(Self::X509_V3)?;
+        bytes_written += self.encode_integer(version.value())?;
 
         Ok(bytes_written)
     }
 
-    fn encode_fwid(&mut self, tci: &TciMeasurement) -> Result<usize, DpeErrorCode> {
-        let mut bytes_written = self.encode_byte(Self::SEQUENCE_TAG)?;
-        bytes_written +=
-            self.encode_size_field(Self::get_fwid_size(&tci.0, /*tagged=*/ false)?)?;
-
-        // hashAlg OID
-        bytes_written += self.encode_byte(Self::OID_TAG)?;
-        bytes_written += self.encode_size_field(Self::HASH_OID.len())?;
-        bytes_written += self.encode_bytes(Self::HASH_OID)?;
+    /// Encode a TBS UniqueIdentifier if it is present
+    ///
+    /// UniqueIdentifier  ::=  BIT STRING
+    ///
+    /// issuerUniqueID is IMPLICIT field number 1 and subjectUniqueID is
+    /// IMPLICIT field number 2.
+    fn encode_unique_id(
+        &mut self,
+        field_number: u8,
+        unique_id: Option<&[u8]>,
+    ) -> Result<usize, DpeErrorCode> {
+        let Some(id) = unique_id else {
+            return Ok(0);
+        };
 
-        // digest OCTET STRING
-        bytes_written += self.encode_byte(Self::OCTET_STRING_TAG)?;
-        bytes_written += self.encode_size_field(tci.0.len())?;
-        bytes_written += self.encode_bytes(&tci.0)?;
+        let mut bytes_written = self.encode_byte(Self::CONTEXT_SPECIFIC | field_number)?;
+        bytes_written +=
+            self.encode_size_field(Self::get_unique_id_size(unique_id, /*tagged=*/ false)?)?;
+        // Unused bits
+        bytes_written += self.encode_byte(0)?;
+        bytes_written += self.encode_bytes(id)?;
 
         Ok(bytes_written)
     }
 
-    /// Encode a tcg-dice-TcbInfo structure
-    ///
-    /// https://trustedcomputinggroup.org/wp-content/uploads/TCG_DICE_Attestation_Architecture_r22_02dec2020.pdf
-    ///
-    /// TcbInfo makes use of implicitly encoded types. This means the tag
-    /// denotes that the type is implicit (8th bit set) and number of the
-    /// field. For example, "Implicit tag number 2" would be encoded with
-    /// the tag 0x82 for primitive types.
+    /// Encode a DICE FWID holding the leading `digest_len` bytes of `tci`,
+    /// for devices that store truncated measurements such as 128-bit tags
     ///
-    /// For constructed types (SEQUENCE, SEQUENCE OF, SET, SET OF) the 6th
-    /// bit is also set. For example, "Implicit tag number 2" would be encoded
-    /// with tag 0xA2 for constructed types.
-    fn encode_tcb_info(
+    /// `digest_len` must not be 0 or exceed the digest size of `hash_oid`.
+    pub fn encode_fwid(
         &mut self,
-        node: &TciNodeData,
-        supports_extend_tci: bool,
+        tci: &TciMeasurement,
+        hash_oid: &[u8],
+        digest_len: usize,
     ) -> Result<usize, DpeErrorCode> {
-        let tcb_info_size =
-            Self::get_tcb_info_size(node, supports_extend_tci, /*tagged=*/ false)?;
+        if digest_len == 0 || digest_len > Self::get_hash_oid_digest_size(hash_oid)? {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+        let digest = tci
+            .0
+            .get(..digest_len)
+            .ok_or(DpeErrorCode::InvalidArgument)?;
+        self.encode_fwid_digest(hash_oid, digest)
+    }
+
+    /// Encode a DICE FWID from a raw digest
+    ///
+    /// FWID ::= SEQUENCE {
+    ///     hashAlg OBJECT IDENTIFIER,
+    ///     digest OCTET STRING
+    /// }
+    ///
+    /// # Arguments
+    ///
+    /// * `hash_oid` - DER encoded OID of the SHA-2 hash algorithm that
+    ///   produced `digest`, without the tag and size.
+    /// * `digest` - The digest. Must match the size of `hash_oid`'s digests.
+    pub fn encode_fwid_bytes(
+        &mut self,
+        hash_oid: &[u8],
+        digest: &[u8],
+    ) -> Result<usize, DpeErrorCode> {
+        if digest.len() != Self::get_hash_oid_digest_size(hash_oid)? {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+        self.encode_fwid_digest(hash_oid, digest)
+    }
+
+    /// Encode a DICE FWID whose digest size has already been checked
+    fn encode_fwid_digest(
+        &mut self,
+        hash_oid: &[u8],
+        digest: &[u8],
+    ) -> Result<usize, DpeErrorCode> {
+        self.check_space(Self::get_fwid_size(
+            hash_oid,
+            digest.len(),
+            /*tagged=*/ true,
+        )?)?;
+
+        let mut bytes_written = self.encode_sequence_header(Self::get_fwid_size(
+            hash_oid,
+            digest.len(),
+            /*tagged=*/ false,
+        )?)?;
+
+        // hashAlg OID
+        bytes_written += self.encode_oid(hash_oid)?;
+
+        // digest OCTET STRING
+        bytes_written += self.encode_octet_string_header(digest.len())?;
+        bytes_written += self.encode_bytes(digest)?;
+
+        Ok(bytes_written)
+    }
+
+    /// Encode a tcg-dice-TcbInfo structure
+    ///
+    /// https://trustedcomputinggroup.org/wp-content/uploads/TCG_DICE_Attestation_Architecture_r22_02dec2020.pdf
+    ///
+    /// TcbInfo makes use of implicitly encoded types. This means the tag
+    /// denotes that the type is implicit (8th bit set) and number of the
+    /// field. For example, "Implicit tag number 2" would be encoded with
+    /// the tag 0x82 for primitive types.
+    ///
+    /// For constructed types (SEQUENCE, SEQUENCE OF, SET, SET OF) the 6th
+    /// bit is also set. For example, "Implicit tag number 2" would be encoded
+    /// with tag 0xA2 for constructed types.
+    fn encode_tcb_info(
+        &mut self,
+        node: &TciNodeData,
+        has_fwids: bool,
+        supports_extend_tci: bool,
+        extra_fwids: &[TciMeasurement],
+        fwid_digests: Option<&FwidDigests>,
+        hash_oid: &[u8],
+    ) -> Result<usize, DpeErrorCode> {
+        let tcb_info_size = Self::get_tcb_info_size(
+            node,
+            has_fwids,
+            supports_extend_tci,
+            extra_fwids,
+            fwid_digests,
+            hash_oid,
+            /*tagged=*/ false,
+        )?;
         // TcbInfo sequence
-        let mut bytes_written = self.encode_byte(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(tcb_info_size)?;
+        let mut bytes_written = self.encode_sequence_header(tcb_info_size)?;
 
         // fwids SEQUENCE OF
         // IMPLICIT [6] Constructed
-        let fwid_size = Self::get_fwid_size(&node.tci_current.0, /*tagged=*/ true)?;
-        bytes_written += self.encode_byte(Self::CONTEXT_SPECIFIC | Self::CONSTRUCTED | 0x06)?;
-        if supports_extend_tci {
-            bytes_written += self.encode_size_field(fwid_size * 2)?;
-        } else {
-            bytes_written += self.encode_size_field(fwid_size)?;
-        }
+        // Omitted entirely for nodes without measurements
+        if has_fwids {
+            bytes_written += self.encode_byte(Self::CONTEXT_SPECIFIC | Self::CONSTRUCTED | 0x06)?;
+            bytes_written += self.encode_size_field(Self::get_fwids_size(
+                node,
+                supports_extend_tci,
+                extra_fwids,
+                fwid_digests,
+                hash_oid,
+            )?)?;
+            let (current, cumulative) = Self::resolve_fwid_digests(node, fwid_digests);
 
-        // fwid[0] current measurement
-        bytes_written += self.encode_fwid(&node.tci_current)?;
+            // fwid[0] current measurement
+            bytes_written += self.encode_fwid_bytes(hash_oid, current)?;
 
-        // fwid[1] journey measurement
-        // Omit fwid[1] from tcb_info if DPE_PROFILE does not support extend_tci
-        if supports_extend_tci {
-            bytes_written += self.encode_fwid(&node.tci_cumulative)?;
+            // fwid[1] journey measurement
+            // Omit fwid[1] from tcb_info if DPE_PROFILE does not support extend_tci
+            if supports_extend_tci {
+                bytes_written += self.encode_fwid_bytes(hash_oid, cumulative)?;
+            }
+
+            // Any additional measurements
+            for fwid in extra_fwids {
+                bytes_written += self.encode_fwid_bytes(hash_oid, &fwid.0)?;
+            }
         }
 
         // vendorInfo OCTET STRING
@@ -1044,91 +3329,435 @@ impl CertWriter<'_> {
         Ok(bytes_written)
     }
 
+    /// Encode the extension describing the TCI nodes, a tcg-dice-TcbInfo if
+    /// `measurements.single_tcb_info` is set and a tcg-dice-MultiTcbInfo
+    /// otherwise
+    fn encode_dice_tcb_info(
+        &mut self,
+        measurements: &MeasurementData,
+    ) -> Result<usize, DpeErrorCode> {
+        if measurements.single_tcb_info {
+            self.encode_single_tcb_info(measurements)
+        } else {
+            self.encode_multi_tcb_info(measurements)
+        }
+    }
+
+    /// Encode a tcg-dice-TcbInfo extension for the only TCI node
+    ///
+    /// https://trustedcomputinggroup.org/wp-content/uploads/TCG_DICE_Attestation_Architecture_r22_02dec2020.pdf
+    fn encode_single_tcb_info(
+        &mut self,
+        measurements: &MeasurementData,
+    ) -> Result<usize, DpeErrorCode> {
+        let [node] = measurements.tci_nodes else {
+            return Err(DpeErrorCode::InvalidArgument);
+        };
+        let has_fwids = measurements.has_fwids(0);
+        let supports_extend_tci = measurements.supports_extend_tci(0);
+        let extra_fwids = measurements.get_extra_fwids(0);
+        let fwid_digests = measurements.get_fwid_digests(0);
+        let hash_oid = Self::get_hash_oid(measurements.fwid_hash_alg);
+
+        // Encode Extension
+        let mut bytes_written = self.encode_sequence_header(Self::get_single_tcb_info_size(
+            measurements,
+            /*tagged=*/ false,
+        )?)?;
+        bytes_written += self.encode_oid(Self::TCBINFO_OID)?;
+
+        let crit = if self.crit_dice && measurements.cert_profile == CertProfile::Dice {
+            0xFF
+        } else {
+            0x00
+        };
+        bytes_written += self.encode_byte(Self::BOOL_TAG)?;
+        bytes_written += self.encode_size_field(Self::BOOL_SIZE)?;
+        bytes_written += self.encode_byte(crit)?;
+
+        bytes_written += self.encode_octet_string_header(Self::get_tcb_info_size(
+            node,
+            has_fwids,
+            supports_extend_tci,
+            extra_fwids,
+            fwid_digests,
+            hash_oid,
+            /*tagged=*/ true,
+        )?)?;
+        bytes_written += self.encode_tcb_info(
+            node,
+            has_fwids,
+            supports_extend_tci,
+            extra_fwids,
+            fwid_digests,
+            hash_oid,
+        )?;
+
+        Ok(bytes_written)
+    }
+
     /// Encode a tcg-dice-MultiTcbInfo extension
     ///
     /// https://trustedcomputinggroup.org/wp-content/uploads/TCG_DICE_Attestation_Architecture_r22_02dec2020.pdf
     fn encode_multi_tcb_info(
         &mut self,
         measurements: &MeasurementData,
+    ) -> Result<usize, DpeErrorCode> {
+        if measurements.tci_nodes.is_empty() {
+            return Err(DpeErrorCode::InternalError);
+        }
+
+        let tcb_infos_size = Self::get_tcb_infos_size(measurements)?;
+
+        let crit = self.crit_dice && measurements.cert_profile == CertProfile::Dice;
+        let mut bytes_written = self.encode_multi_tcb_info_header(tcb_infos_size, crit)?;
+
+        // Encode multiple tcg-dice-TcbInfos
+        for (i, node) in measurements.tci_nodes.iter().enumerate() {
+            bytes_written += self.encode_tcb_info(
+                node,
+                measurements.has_fwids(i),
+                measurements.supports_extend_tci(i),
+                measurements.get_extra_fwids(i),
+                measurements.get_fwid_digests(i),
+                Self::get_hash_oid(measurements.fwid_hash_alg),
+            )?;
+        }
+
+        Ok(bytes_written)
+    }
+
+    /// Encode everything in a tcg-dice-MultiTcbInfo extension that precedes
+    /// the concatenated TcbInfos, which are `tcb_infos_size` bytes long.
+    fn encode_multi_tcb_info_header(
+        &mut self,
+        tcb_infos_size: usize,
+        crit: bool,
     ) -> Result<usize, DpeErrorCode> {
         let multi_tcb_info_size =
-            Self::get_multi_tcb_info_size(measurements, /*tagged=*/ false)?;
+            Self::get_multi_tcb_info_ext_size(tcb_infos_size, /*tagged=*/ false)?;
 
         // Encode Extension
-        let mut bytes_written = self.encode_byte(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(multi_tcb_info_size)?;
+        let mut bytes_written = self.encode_sequence_header(multi_tcb_info_size)?;
         bytes_written += self.encode_oid(Self::MULTI_TCBINFO_OID)?;
 
-        let crit = if self.crit_dice { 0xFF } else { 0x00 };
+        let crit = if crit { 0xFF } else { 0x00 };
         bytes_written += self.encode_byte(Self::BOOL_TAG)?;
         bytes_written += self.encode_size_field(Self::BOOL_SIZE)?;
         bytes_written += self.encode_byte(crit)?;
 
-        let tcb_infos_size = if !measurements.tci_nodes.is_empty() {
-            Self::get_tcb_info_size(
-                &measurements.tci_nodes[0],
-                measurements.supports_extend_tci,
-                /*tagged=*/ true,
-            )? * measurements.tci_nodes.len()
-        } else {
-            0
-        };
-        bytes_written += self.encode_byte(Self::OCTET_STRING_TAG)?;
-        bytes_written += self.encode_size_field(Self::get_structure_size(
+        bytes_written += self.encode_octet_string_header(Self::get_structure_size(
             tcb_infos_size,
             /*tagged=*/ true,
         )?)?;
 
         // Encode MultiTcbInfo
-        bytes_written += self.encode_byte(Self::SEQUENCE_OF_TAG)?;
-        bytes_written += self.encode_size_field(tcb_infos_size)?;
+        bytes_written += self.encode_sequence_header(tcb_infos_size)?;
 
-        // Encode multiple tcg-dice-TcbInfos
-        for node in measurements.tci_nodes {
-            bytes_written += self.encode_tcb_info(node, measurements.supports_extend_tci)?;
+        Ok(bytes_written)
+    }
+
+    /// Start building a tcg-dice-MultiTcbInfo extension one TcbInfo at a time
+    ///
+    /// TcbInfos are added with `append_tcb_info` and the extension is
+    /// completed with `finish_multi_tcb_info`. No other encoding may be done
+    /// with this writer in between.
+    pub fn begin_multi_tcb_info(&mut self) -> Result<(), DpeErrorCode> {
+        if self.multi_tcb_info_start.is_some() {
+            return Err(DpeErrorCode::InvalidArgument);
         }
+        self.multi_tcb_info_start = Some(self.offset);
 
-        Ok(bytes_written)
+        Ok(())
+    }
+
+    /// Append a tcg-dice-TcbInfo for `node` to the MultiTcbInfo started by
+    /// `begin_multi_tcb_info`
+    ///
+    /// `supports_extend_tci` and `extend_tci_override` are resolved as for
+    /// `MeasurementData::supports_extend_tci` and the node's entry in
+    /// `MeasurementData::extend_tci_overrides`.
+    pub fn append_tcb_info(
+        &mut self,
+        node: &TciNodeData,
+        supports_extend_tci: bool,
+        extend_tci_override: Option<bool>,
+        extra_fwids: &[TciMeasurement],
+        fwid_hash_alg: Option<HashAlg>,
+    ) -> Result<usize, DpeErrorCode> {
+        if self.multi_tcb_info_start.is_none() {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        self.encode_tcb_info(
+            node,
+            /*has_fwids=*/ true,
+            extend_tci_override.unwrap_or(supports_extend_tci),
+            extra_fwids,
+            /*fwid_digests=*/ None,
+            Self::get_hash_oid(fwid_hash_alg),
+        )
+    }
+
+    /// Complete the MultiTcbInfo started by `begin_multi_tcb_info`
+    ///
+    /// The appended TcbInfos are shifted to make room for the extension
+    /// header, whose length fields are only known now. Returns the size of
+    /// the whole extension.
+    ///
+    /// Fails with `BufferTooSmall` if the header does not fit, leaving the
+    /// appended TcbInfos in place.
+    pub fn finish_multi_tcb_info(&mut self) -> Result<usize, DpeErrorCode> {
+        let start = self
+            .multi_tcb_info_start
+            .ok_or(DpeErrorCode::InvalidArgument)?;
+        let tcb_infos_size = self.offset - start;
+        if tcb_infos_size == 0 {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        let ext_size = Self::get_multi_tcb_info_ext_size(tcb_infos_size, /*tagged=*/ true)?;
+        let header_size = ext_size - tcb_infos_size;
+        self.check_space(header_size)?;
+
+        self.multi_tcb_info_start = None;
+        self.certificate
+            .copy_within(start..self.offset, start + header_size);
+
+        self.offset = start;
+        self.encode_multi_tcb_info_header(tcb_infos_size, self.crit_dice)?;
+        self.offset = start + ext_size;
+
+        Ok(ext_size)
     }
 
-    /// Encode a tcg-dice-Ueid extension
+    /// Encode a tcg-dice-Ueid extension if included
     ///
     /// https://trustedcomputinggroup.org/wp-content/uploads/TCG_DICE_Attestation_Architecture_r22_02dec2020.pdf
     fn encode_ueid(&mut self, measurements: &MeasurementData) -> Result<usize, DpeErrorCode> {
+        if !measurements.include_ueid {
+            return Ok(0);
+        }
         let ueid_size = Self::get_ueid_size(measurements, /*tagged=*/ false)?;
 
         // Encode Extension
-        let mut bytes_written = self.encode_byte(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(ueid_size)?;
+        let mut bytes_written = self.encode_sequence_header(ueid_size)?;
         bytes_written += self.encode_oid(Self::UEID_OID)?;
 
-        let crit = if self.crit_dice { 0xFF } else { 0x00 };
+        let crit = if self.crit_dice && measurements.cert_profile == CertProfile::Dice {
+            0xFF
+        } else {
+            0x00
+        };
         bytes_written += self.encode_byte(Self::BOOL_TAG)?;
         bytes_written += self.encode_size_field(Self::BOOL_SIZE)?;
         bytes_written += self.encode_byte(crit)?;
 
-        // Extension data is sequence -> octet string. To compute size, wrap
-        // in tagging twice.
-        bytes_written += self.encode_byte(Self::OCTET_STRING_TAG)?;
-        bytes_written += self.encode_size_field(Self::get_structure_size(
-            Self::get_structure_size(measurements.label.len(), /*tagged=*/ true)?,
+        // Extension data is sequence -> octet string, or just the octet
+        // string for older verifiers
+        bytes_written +=
+            self.encode_octet_string_header(Self::get_ueid_ext_value_size(measurements)?)?;
+        bytes_written += match measurements.ueid_encoding {
+            UeidEncoding::Sequence => self.encode_ueid_value(measurements.label)?,
+            UeidEncoding::OctetString => {
+                self.encode_octet_string_header(measurements.label.len())?
+                    + self.encode_bytes(measurements.label)?
+            }
+        };
+
+        Ok(bytes_written)
+    }
+
+    /// Encode just the value of a tcg-dice-Ueid extension, without the
+    /// extension OID and critical bits
+    ///
+    /// TcgUeid ::= SEQUENCE {
+    ///     ueid OCTET STRING
+    /// }
+    ///
+    /// Returns number of bytes written
+    pub fn encode_ueid_value(&mut self, label: &[u8]) -> Result<usize, DpeErrorCode> {
+        self.check_space(Self::get_ueid_value_size(label)?)?;
+
+        // Sequence size to just a tagged OCTET_STRING
+        let mut bytes_written = self.encode_sequence_header(Self::get_structure_size(
+            label.len(),
             /*tagged=*/ true,
         )?)?;
 
-        // Sequence size to just a tagged OCTET_STRING
-        bytes_written += self.encode_byte(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(Self::get_structure_size(
-            measurements.label.len(),
+        bytes_written += self.encode_octet_string_header(label.len())?;
+        bytes_written += self.encode_bytes(label)?;
+
+        Ok(bytes_written)
+    }
+
+    /// Hash the TCI node and tcg-dice-Ueid extensions of
+    /// `measurements` with the hash of `profile` and write the digest to `out`
+    ///
+    /// The digest is a compact fingerprint for detecting measurement changes
+    /// without comparing whole certificates. The extensions are always encoded
+    /// as critical, so it does not depend on how a CertWriter is configured.
+    /// They are encoded into `scratch` before hashing, which fails with
+    /// `BufferTooSmall` if they do not fit.
+    ///
+    /// Returns the number of bytes written to `out`
+    pub fn extensions_digest(
+        crypto: &mut impl Crypto,
+        profile: DpeProfile,
+        measurements: &MeasurementData,
+        scratch: &mut [u8],
+        out: &mut [u8],
+    ) -> Result<usize, DpeErrorCode> {
+        let algs = profile.alg_len();
+        measurements.validate(profile)?;
+
+        let mut w = CertWriter::new(scratch, /*crit_dice=*/ true);
+        w.encode_dice_tcb_info(measurements)?;
+        w.encode_ueid(measurements)?;
+
+        let digest = crypto.hash(algs, w.written())?;
+        let out = out
+            .get_mut(..digest.len())
+            .ok_or(DpeErrorCode::BufferTooSmall(
+                u32::try_from(digest.len()).map_err(|_| DpeErrorCode::InternalError)?,
+            ))?;
+        out.copy_from_slice(digest.bytes());
+
+        Ok(digest.len())
+    }
+
+    /// Encode a non-critical holder reference extension, linking the
+    /// certificate to an external certificate identified by
+    /// `issuer_serial_der`
+    ///
+    /// The output is a complete Extension for `MeasurementData::raw_extensions`.
+    /// The extension value is the DER IssuerAndSerialNumber of the referenced
+    /// certificate.
+    ///
+    /// IssuerAndSerialNumber  ::=  SEQUENCE  {
+    ///    issuer Name,
+    ///    serialNumber CertificateSerialNumber
+    /// }
+    ///
+    /// See `HOLDER_REFERENCE_OID` for the extension OID.
+    pub fn encode_holder_reference(
+        &mut self,
+        issuer_serial_der: &[u8],
+    ) -> Result<usize, DpeErrorCode> {
+        self.check_space(Self::get_holder_reference_size(
+            issuer_serial_der,
             /*tagged=*/ true,
         )?)?;
+        let size = Self::get_holder_reference_size(issuer_serial_der, /*tagged=*/ false)?;
 
-        bytes_written += self.encode_byte(Self::OCTET_STRING_TAG)?;
-        bytes_written += self.encode_size_field(Self::get_structure_size(
-            measurements.label.len(),
-            /*tagged=*/ false,
+        // Encode Extension
+        let mut bytes_written = self.encode_sequence_header(size)?;
+        bytes_written += self.encode_oid(Self::HOLDER_REFERENCE_OID)?;
+
+        bytes_written += self.encode_byte(Self::BOOL_TAG)?;
+        bytes_written += self.encode_size_field(Self::BOOL_SIZE)?;
+        bytes_written += self.encode_byte(0x00)?;
+
+        // Extension data is the IssuerAndSerialNumber wrapped in an OCTET STRING
+        bytes_written += self.encode_octet_string_header(issuer_serial_der.len())?;
+        bytes_written += self.encode_bytes(issuer_serial_der)?;
+
+        Ok(bytes_written)
+    }
+
+    /// Encode a non-critical extension carrying the CBOR encoded EAT claims
+    /// `cbor` alongside the DICE extensions
+    ///
+    /// The output is a complete Extension for `MeasurementData::raw_extensions`.
+    /// The claims are copied verbatim into the extension OCTET STRING without
+    /// being parsed.
+    ///
+    /// See `EAT_CLAIMS_OID` for the extension OID.
+    pub fn encode_eat_claims_extension(&mut self, cbor: &[u8]) -> Result<usize, DpeErrorCode> {
+        self.check_space(Self::get_eat_claims_extension_size(
+            cbor, /*tagged=*/ true,
+        )?)?;
+        let size = Self::get_eat_claims_extension_size(cbor, /*tagged=*/ false)?;
+
+        // Encode Extension
+        let mut bytes_written = self.encode_sequence_header(size)?;
+        bytes_written += self.encode_oid(Self::EAT_CLAIMS_OID)?;
+
+        bytes_written += self.encode_byte(Self::BOOL_TAG)?;
+        bytes_written += self.encode_size_field(Self::BOOL_SIZE)?;
+        bytes_written += self.encode_byte(0x00)?;
+
+        // Extension data is the CBOR wrapped in an OCTET STRING
+        bytes_written += self.encode_octet_string_header(cbor.len())?;
+        bytes_written += self.encode_bytes(cbor)?;
+
+        Ok(bytes_written)
+    }
+
+    /// Encode a non-critical altSignatureAlgorithm extension naming the
+    /// profile's ECDSA algorithm
+    ///
+    /// The output is a complete Extension for `MeasurementData::raw_extensions`.
+    /// Together with `encode_alt_signature_value` this carries a second,
+    /// independent signature following the ITU-T X.509 multiple signature
+    /// conventions. The alternative signature is computed over the
+    /// preTBSCertificate: the TBS with this extension but without
+    /// altSignatureValue.
+    ///
+    /// altSignatureAlgorithm ::= AlgorithmIdentifier
+    ///
+    /// See `ALT_SIGNATURE_ALGORITHM_OID` for the extension OID.
+    pub fn encode_alt_signature_algorithm(&mut self) -> Result<usize, DpeErrorCode> {
+        self.check_space(Self::get_alt_signature_algorithm_size(
+            /*tagged=*/ true,
+        )?)?;
+        let size = Self::get_alt_signature_algorithm_size(/*tagged=*/ false)?;
+
+        // Encode Extension
+        let mut bytes_written = self.encode_sequence_header(size)?;
+        bytes_written += self.encode_oid(Self::ALT_SIGNATURE_ALGORITHM_OID)?;
+
+        bytes_written += self.encode_byte(Self::BOOL_TAG)?;
+        bytes_written += self.encode_size_field(Self::BOOL_SIZE)?;
+        bytes_written += self.encode_byte(0x00)?;
+
+        // Extension data is the AlgorithmIdentifier wrapped in an OCTET STRING
+        bytes_written += self
+            .encode_octet_string_header(Self::get_ecdsa_sig_alg_id_size(/*tagged=*/ true)?)?;
+        bytes_written += self.encode_ecdsa_sig_alg_id()?;
+
+        Ok(bytes_written)
+    }
+
+    /// Encode a non-critical altSignatureValue extension holding the
+    /// alternative signature `sig`
+    ///
+    /// The output is a complete Extension for `MeasurementData::raw_extensions`.
+    /// `sig` must be computed over the preTBSCertificate, see
+    /// `encode_alt_signature_algorithm`.
+    ///
+    /// altSignatureValue ::= BIT STRING
+    ///
+    /// See `ALT_SIGNATURE_VALUE_OID` for the extension OID.
+    pub fn encode_alt_signature_value(&mut self, sig: &EcdsaSig) -> Result<usize, DpeErrorCode> {
+        self.check_space(Self::get_alt_signature_value_size(
+            sig, /*tagged=*/ true,
         )?)?;
+        let size = Self::get_alt_signature_value_size(sig, /*tagged=*/ false)?;
+
+        // Encode Extension
+        let mut bytes_written = self.encode_sequence_header(size)?;
+        bytes_written += self.encode_oid(Self::ALT_SIGNATURE_VALUE_OID)?;
+
+        bytes_written += self.encode_byte(Self::BOOL_TAG)?;
+        bytes_written += self.encode_size_field(Self::BOOL_SIZE)?;
+        bytes_written += self.encode_byte(0x00)?;
 
-        bytes_written += self.encode_bytes(measurements.label)?;
+        // Extension data is the signature BIT STRING wrapped in an OCTET STRING
+        bytes_written += self.encode_octet_string_header(
+            Self::get_ecdsa_signature_bit_string_size(sig, /*tagged=*/ true)?,
+        )?;
+        bytes_written += self.encode_ecdsa_signature_bit_string(sig)?;
 
         Ok(bytes_written)
     }
@@ -1140,31 +3769,31 @@ impl CertWriter<'_> {
         &mut self,
         measurements: &MeasurementData,
     ) -> Result<usize, DpeErrorCode> {
-        let basic_constraints_size = Self::get_basic_constraints_size(/*tagged=*/ false)?;
+        let basic_constraints_size =
+            Self::get_basic_constraints_size(measurements, /*tagged=*/ false)?;
+        let value_size = Self::get_basic_constraints_value_size(measurements)?;
 
         // Encode Extension
-        let mut bytes_written = self.encode_byte(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(basic_constraints_size)?;
+        let mut bytes_written = self.encode_sequence_header(basic_constraints_size)?;
         bytes_written += self.encode_oid(Self::BASIC_CONSTRAINTS_OID)?;
 
+        let crit = if measurements.is_ca || measurements.leaf_basic_constraints_critical {
+            0xFF
+        } else {
+            0x00
+        };
         bytes_written += self.encode_byte(Self::BOOL_TAG)?;
         bytes_written += self.encode_size_field(Self::BOOL_SIZE)?;
-        bytes_written += self.encode_byte(0xFF)?;
+        bytes_written += self.encode_byte(crit)?;
 
         // Extension data is sequence -> octet string. To compute size, wrap
         // in tagging twice.
-        bytes_written += self.encode_byte(Self::OCTET_STRING_TAG)?;
-        bytes_written += self.encode_size_field(Self::get_structure_size(
-            Self::get_structure_size(1, /*tagged=*/ true)?,
-            /*tagged=*/ true,
+        bytes_written += self.encode_octet_string_header(Self::get_structure_size(
+            value_size, /*tagged=*/ true,
         )?)?;
 
-        // Sequence size to just a tagged bool
-        bytes_written += self.encode_byte(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(Self::get_structure_size(
-            Self::BOOL_SIZE,
-            /*tagged=*/ true,
-        )?)?;
+        // Sequence of the cA bool and the optional pathLenConstraint
+        bytes_written += self.encode_sequence_header(value_size)?;
 
         bytes_written += self.encode_byte(Self::BOOL_TAG)?;
         bytes_written += self.encode_size_field(Self::BOOL_SIZE)?;
@@ -1174,18 +3803,27 @@ impl CertWriter<'_> {
             bytes_written += self.encode_byte(0x00)?;
         }
 
+        if let Some(path_len) = measurements
+            .path_len_constraint
+            .filter(|_| measurements.is_ca)
+        {
+            bytes_written += self.encode_integer(path_len.into())?;
+        }
+
         Ok(bytes_written)
     }
 
     /// Encode a KeyUsage extension
     ///
     /// https://datatracker.ietf.org/doc/html/rfc5280
-    fn encode_key_usage(&mut self, is_ca: bool) -> Result<usize, DpeErrorCode> {
-        let key_usage_size = Self::get_key_usage_size(/*tagged=*/ false)?;
+    fn encode_key_usage(&mut self, measurements: &MeasurementData) -> Result<usize, DpeErrorCode> {
+        let key_usage_size = Self::get_key_usage_size(measurements, /*tagged=*/ false)?;
+        let key_usage = measurements.get_key_usage();
+        let value_len = Self::get_key_usage_value_len(key_usage)?;
+        let value = &key_usage.bits().to_be_bytes()[..value_len];
 
         // Encode Extension
-        let mut bytes_written = self.encode_byte(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(key_usage_size)?;
+        let mut bytes_written = self.encode_sequence_header(key_usage_size)?;
         bytes_written += self.encode_oid(Self::KEY_USAGE_OID)?;
 
         bytes_written += self.encode_byte(Self::BOOL_TAG)?;
@@ -1194,80 +3832,88 @@ impl CertWriter<'_> {
 
         // Extension data is sequence -> octet string. To compute size, wrap
         // in tagging twice.
-        bytes_written += self.encode_byte(Self::OCTET_STRING_TAG)?;
-        bytes_written +=
-            self.encode_size_field(Self::get_structure_size(2, /*tagged=*/ true)?)?;
-
-        bytes_written += self.encode_byte(Self::BIT_STRING_TAG)?;
+        bytes_written += self.encode_octet_string_header(Self::get_structure_size(
+            1 + value_len,
+            /*tagged=*/ true,
+        )?)?;
 
-        // Bit string is 2 bytes:
+        // Bit string is:
         // * Unused bits
-        // * KeyUsage bits
+        // * KeyUsage bits, without trailing zero bytes
         //
-        // To simplify encoding, no bits are marked as unused, they are just
-        // set to zero.
-        bytes_written += self.encode_size_field(2)?;
-
-        // Unused bits
-        bytes_written += self.encode_byte(0)?;
+        // DER requires trailing zero bits to be removed, so the bits after the
+        // last set one are marked as unused.
+        bytes_written += self.encode_byte(Self::BIT_STRING_TAG)?;
+        bytes_written += self.encode_size_field(1 + value_len)?;
+        let unused_bits = value.last().map_or(0, |last| last.trailing_zeros());
+        bytes_written += self.encode_byte(unused_bits as u8)?;
+        bytes_written += self.encode_bytes(value)?;
 
-        let key_usage = if is_ca {
-            KeyUsageFlags::DIGITAL_SIGNATURE | KeyUsageFlags::KEY_CERT_SIGN
-        } else {
-            KeyUsageFlags::DIGITAL_SIGNATURE
-        };
+        Ok(bytes_written)
+    }
 
-        bytes_written += self.encode_byte(key_usage.0)?;
+    /// Get the number of KeyUsage BIT STRING value bytes for `key_usage`.
+    /// decipherOnly is the only bit in the second byte, which is left out if
+    /// it is not set.
+    ///
+    /// Fails if no bits are set, as RFC 5280 requires at least one.
+    fn get_key_usage_value_len(key_usage: KeyUsageFlags) -> Result<usize, DpeErrorCode> {
+        if key_usage.is_empty() {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
 
-        Ok(bytes_written)
+        if key_usage.contains(KeyUsageFlags::DECIPHER_ONLY) {
+            Ok(2)
+        } else {
+            Ok(1)
+        }
     }
 
     /// Encode ExtendedKeyUsage extension
     ///
     /// The included EKU OIDs is as follows based on whether or not this certificate is for a CA:
     ///
-    /// is_ca = true: id-tcg-kp-identityLoc (2.23.133.8.7)
-    /// is_ca = false: id-tcg-kp-attestLoc (2.23.133.8.9)
+    /// is_ca = true: tcg-dice-kp-eca (2.23.133.5.4.100.12)
+    /// is_ca = false: the OIDs of `measurements.key_purposes`, or no extension
+    /// if they are `KeyPurposes::Omitted`
     ///
     /// https://datatracker.ietf.org/doc/html/rfc5280
     fn encode_extended_key_usage(
         &mut self,
         measurements: &MeasurementData,
     ) -> Result<usize, DpeErrorCode> {
-        let policy_oid = if measurements.is_ca {
-            Self::ECA_OID
-        } else {
-            Self::ATTEST_LOC_OID
-        };
+        if !Self::has_extended_key_usage(measurements) {
+            return Ok(0);
+        }
 
-        // Assumes only one certificate policy is supported.
         let extended_key_usage_size = Self::get_extended_key_usage_size(measurements, false)?;
 
         // Encode Extension
-        let mut bytes_written = self.encode_byte(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(extended_key_usage_size)?;
+        let mut bytes_written = self.encode_sequence_header(extended_key_usage_size)?;
         bytes_written += self.encode_oid(Self::EXTENDED_KEY_USAGE_OID)?;
 
+        let crit = match measurements.cert_profile {
+            CertProfile::Dice => 0xFF,
+            CertProfile::AndroidAttestation => 0x00,
+        };
         bytes_written += self.encode_byte(Self::BOOL_TAG)?;
         bytes_written += self.encode_size_field(Self::BOOL_SIZE)?;
-        bytes_written += self.encode_byte(0xFF)?;
+        bytes_written += self.encode_byte(crit)?;
 
-        // Extension data is sequence -> octet string. To compute size, wrap
-        // in tagging twice.
-        bytes_written += self.encode_byte(Self::OCTET_STRING_TAG)?;
-        bytes_written += self.encode_size_field(Self::get_structure_size(
-            Self::get_structure_size(policy_oid.len(), /*tagged=*/ true)?,
+        // Extension data is sequence -> octet string
+        bytes_written += self.encode_octet_string_header(Self::get_key_purposes_size(
+            measurements,
             /*tagged=*/ true,
         )?)?;
 
         // Sequence size is the size of all the EKU OIDs.
-        bytes_written += self.encode_byte(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(Self::get_structure_size(
-            policy_oid.len(),
-            /*tagged=*/ true,
+        bytes_written += self.encode_sequence_header(Self::get_key_purposes_size(
+            measurements,
+            /*tagged=*/ false,
         )?)?;
-
-        bytes_written += self.encode_oid(policy_oid)?;
+        for oid in Self::get_key_purpose_oids(measurements) {
+            bytes_written += self.encode_oid(oid)?;
+        }
 
         Ok(bytes_written)
     }
@@ -1277,35 +3923,145 @@ impl CertWriter<'_> {
         measurements: &MeasurementData,
         explicit: bool,
     ) -> Result<usize, DpeErrorCode> {
-        let mut bytes_written = 0;
         if explicit {
             // Extensions is EXPLICIT field number 3
-            bytes_written += self.encode_byte(Self::CONTEXT_SPECIFIC | Self::CONSTRUCTED | 0x03)?;
-            bytes_written += self.encode_size_field(Self::get_extensions_size(
-                measurements,
-                /*tagged=*/ true,
-                /*explicit=*/ false,
-            )?)?;
+            return self.encode_structure(
+                Self::CONTEXT_SPECIFIC | Self::CONSTRUCTED | 0x03,
+                || {
+                    Self::get_extensions_size(
+                        measurements,
+                        /*tagged=*/ true,
+                        /*explicit=*/ false,
+                    )
+                },
+                |w| w.encode_extensions(measurements, /*explicit=*/ false),
+            );
         }
 
         // SEQUENCE OF Extension
-        bytes_written += self.encode_byte(Self::SEQUENCE_OF_TAG)?;
-        bytes_written += self.encode_size_field(Self::get_extensions_size(
-            measurements,
-            /*tagged=*/ false,
-            /*explicit=*/ false,
+        self.encode_structure(
+            Self::SEQUENCE_OF_TAG,
+            || {
+                Self::get_extensions_size(
+                    measurements,
+                    /*tagged=*/ false,
+                    /*explicit=*/ false,
+                )
+            },
+            |w| {
+                let mut bytes_written = match measurements.cert_profile {
+                    CertProfile::Dice => {
+                        let mut bytes_written = w.encode_dice_tcb_info(measurements)?;
+                        bytes_written += w.encode_ueid(measurements)?;
+                        bytes_written += w.encode_basic_constraints(measurements)?;
+                        bytes_written += w.encode_key_usage(measurements)?;
+                        bytes_written += w.encode_extended_key_usage(measurements)?;
+                        bytes_written
+                    }
+                    CertProfile::AndroidAttestation => {
+                        let mut bytes_written = w.encode_basic_constraints(measurements)?;
+                        bytes_written += w.encode_key_usage(measurements)?;
+                        bytes_written += w.encode_extended_key_usage(measurements)?;
+                        bytes_written += w.encode_dice_tcb_info(measurements)?;
+                        bytes_written += w.encode_ueid(measurements)?;
+                        bytes_written
+                    }
+                };
+                bytes_written += w.encode_policy_constraints(measurements)?;
+                bytes_written += w.encode_inhibit_any_policy(measurements)?;
+                for ext in measurements.raw_extensions {
+                    Self::validate_raw_extension(ext)?;
+                    bytes_written += w.encode_bytes(ext)?;
+                }
+
+                Ok(bytes_written)
+            },
+        )
+    }
+
+    /// Encode a PolicyConstraints extension if included
+    ///
+    /// PolicyConstraints ::= SEQUENCE {
+    ///     requireExplicitPolicy           [0] SkipCerts OPTIONAL,
+    ///     inhibitPolicyMapping            [1] SkipCerts OPTIONAL }
+    ///
+    /// SkipCerts ::= INTEGER (0..MAX)
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc5280
+    fn encode_policy_constraints(
+        &mut self,
+        measurements: &MeasurementData,
+    ) -> Result<usize, DpeErrorCode> {
+        let constraints = match measurements.policy_constraints {
+            Some(constraints) if measurements.is_ca => constraints,
+            _ => return Ok(0),
+        };
+        let policy_constraints_size =
+            Self::get_policy_constraints_size(measurements, /*tagged=*/ false)?;
+        let value_size = Self::get_policy_constraints_value_size(&constraints)?;
+
+        // Encode Extension
+        let mut bytes_written = self.encode_sequence_header(policy_constraints_size)?;
+        bytes_written += self.encode_oid(Self::POLICY_CONSTRAINTS_OID)?;
+
+        bytes_written += self.encode_byte(Self::BOOL_TAG)?;
+        bytes_written += self.encode_size_field(Self::BOOL_SIZE)?;
+        bytes_written += self.encode_byte(0xFF)?;
+
+        // Extension data is sequence -> octet string. To compute size, wrap
+        // in tagging twice.
+        bytes_written += self.encode_octet_string_header(Self::get_structure_size(
+            value_size, /*tagged=*/ true,
         )?)?;
 
-        bytes_written += self.encode_multi_tcb_info(measurements)?;
-        bytes_written += self.encode_ueid(measurements)?;
-        bytes_written += self.encode_basic_constraints(measurements)?;
-        bytes_written += self.encode_key_usage(measurements.is_ca)?;
-        bytes_written += self.encode_extended_key_usage(measurements)?;
+        bytes_written += self.encode_sequence_header(value_size)?;
+
+        if let Some(skip_certs) = constraints.require_explicit_policy {
+            bytes_written += self.encode_implicit_integer(0x00, skip_certs.into())?;
+        }
+        if let Some(skip_certs) = constraints.inhibit_policy_mapping {
+            bytes_written += self.encode_implicit_integer(0x01, skip_certs.into())?;
+        }
+
+        Ok(bytes_written)
+    }
+
+    /// Encode an InhibitAnyPolicy extension if included
+    ///
+    /// InhibitAnyPolicy ::= SkipCerts
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc5280
+    fn encode_inhibit_any_policy(
+        &mut self,
+        measurements: &MeasurementData,
+    ) -> Result<usize, DpeErrorCode> {
+        let skip_certs = match measurements.inhibit_any_policy {
+            Some(skip_certs) if measurements.is_ca => skip_certs,
+            _ => return Ok(0),
+        };
+        let inhibit_any_policy_size =
+            Self::get_inhibit_any_policy_size(measurements, /*tagged=*/ false)?;
+
+        // Encode Extension
+        let mut bytes_written = self.encode_sequence_header(inhibit_any_policy_size)?;
+        bytes_written += self.encode_oid(Self::INHIBIT_ANY_POLICY_OID)?;
+
+        bytes_written += self.encode_byte(Self::BOOL_TAG)?;
+        bytes_written += self.encode_size_field(Self::BOOL_SIZE)?;
+        bytes_written += self.encode_byte(0xFF)?;
+
+        bytes_written += self.encode_octet_string_header(Self::get_integer_size(
+            skip_certs.into(),
+            /*tagged=*/ true,
+        )?)?;
+        bytes_written += self.encode_integer(skip_certs.into())?;
 
         Ok(bytes_written)
     }
 
-    /// Encode a SignedData
+    /// Encode everything in a SignedData that precedes the eContent bytes,
+    /// which are `csr_len` bytes long. The SignedData is completed by the
+    /// eContent and `encode_signer_infos`.
     ///
     /// This function does not populate the certificates or crls fields.
     ///
@@ -1317,58 +4073,97 @@ impl CertWriter<'_> {
     ///    crls [1] IMPLICIT RevocationInfoChoices OPTIONAL,
     ///    signerInfos SignerInfos
     /// }
+    ///
+    /// The version is 3 if the signer is identified by subjectKeyIdentifier
+    /// and 1 otherwise.
     #[allow(clippy::identity_op)]
-    fn encode_signed_data(
+    fn encode_signed_data_header(
         &mut self,
-        serial_number: &[u8],
-        issuer_name: &[u8],
-        csr: &[u8],
+        signer_id: &SignerId,
+        csr_len: usize,
         sig: &EcdsaSig,
+        hash_oid: &[u8],
     ) -> Result<usize, DpeErrorCode> {
         // SignedData is EXPLICIT field number 0
         let mut bytes_written =
             self.encode_byte(Self::CONTEXT_SPECIFIC | Self::CONSTRUCTED | 0x0)?;
         bytes_written += self.encode_size_field(Self::get_signed_data_size(
-            csr,
-            serial_number,
-            issuer_name,
-            sig,
-            /*tagged=*/ true,
-            /*explicit=*/ false,
+            csr_len, signer_id, sig, hash_oid, /*tagged=*/ true, /*explicit=*/ false,
         )?)?;
 
         // SignedData sequence
-        bytes_written += self.encode_tag_field(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(Self::get_signed_data_size(
-            csr,
-            serial_number,
-            issuer_name,
-            sig,
-            /*tagged=*/ false,
-            /*explicit=*/ false,
+        bytes_written += self.encode_sequence_header(Self::get_signed_data_size(
+            csr_len, signer_id, sig, hash_oid, /*tagged=*/ false, /*explicit=*/ false,
         )?)?;
 
         // CMS version
-        bytes_written += self.encode_integer(Self::CMS_V1)?;
+        bytes_written += self.encode_integer(signer_id.get_cms_version())?;
 
         // digestAlgorithms
-        bytes_written += self.encode_tag_field(Self::SET_OF_TAG)?;
         bytes_written +=
-            self.encode_size_field(Self::get_hash_alg_id_size(/*tagged=*/ true)?)?;
-        bytes_written += self.encode_hash_alg_id()?;
+            self.encode_set_header(Self::get_hash_alg_id_size(hash_oid, /*tagged=*/ true)?)?;
+        bytes_written += self.encode_hash_alg_id(hash_oid)?;
 
         // encapContentInfo
-        bytes_written += self.encode_encapsulated_content_info(csr)?;
+        bytes_written += self.encode_encapsulated_content_info_header(Some(csr_len))?;
 
-        // signerInfos
-        bytes_written += self.encode_tag_field(Self::SET_OF_TAG)?;
-        bytes_written += self.encode_size_field(Self::get_signer_info_size(
-            serial_number,
-            issuer_name,
-            sig,
-            /*tagged=*/ true,
+        Ok(bytes_written)
+    }
+
+    /// Encode the SignedData signerInfos SET holding a single SignerInfo
+    fn encode_signer_infos(
+        &mut self,
+        signer_id: &SignerId,
+        sig: &EcdsaSig,
+        hash_oid: &[u8],
+    ) -> Result<usize, DpeErrorCode> {
+        let mut bytes_written = self.encode_set_header(Self::get_signer_info_size(
+            signer_id, sig, hash_oid, /*tagged=*/ true,
         )?)?;
-        bytes_written += self.encode_signer_info(serial_number, issuer_name, sig)?;
+        bytes_written += self.encode_signer_info_with_hash(signer_id, sig, hash_oid)?;
+
+        Ok(bytes_written)
+    }
+
+    /// Encode a certificates-only SignedData
+    ///
+    /// This is the degenerate case of SignedData, used to convey
+    /// certificates. There are no digestAlgorithms, eContent or signerInfos.
+    ///
+    /// `certs` holds the concatenated DER encoded certificates. Each one
+    /// must be a single well-formed DER SEQUENCE.
+    #[allow(clippy::identity_op)]
+    fn encode_certs_only_signed_data(&mut self, certs: &[u8]) -> Result<usize, DpeErrorCode> {
+        Self::validate_certs(certs)?;
+
+        // SignedData is EXPLICIT field number 0
+        let mut bytes_written =
+            self.encode_byte(Self::CONTEXT_SPECIFIC | Self::CONSTRUCTED | 0x0)?;
+        bytes_written += self.encode_size_field(Self::get_certs_only_signed_data_size(
+            certs, /*tagged=*/ true, /*explicit=*/ false,
+        )?)?;
+
+        // SignedData sequence
+        bytes_written += self.encode_sequence_header(Self::get_certs_only_signed_data_size(
+            certs, /*tagged=*/ false, /*explicit=*/ false,
+        )?)?;
+
+        // CMS version
+        bytes_written += self.encode_integer(Self::CMS_V1)?;
+
+        // digestAlgorithms
+        bytes_written += self.encode_set_header(0)?;
+
+        // encapContentInfo
+        bytes_written += self.encode_encapsulated_content_info_header(None)?;
+
+        // certificates is IMPLICIT field number 0
+        bytes_written += self.encode_tag_field(Self::CONTEXT_SPECIFIC | Self::CONSTRUCTED | 0x0)?;
+        bytes_written += self.encode_size_field(certs.len())?;
+        bytes_written += self.encode_bytes(certs)?;
+
+        // signerInfos
+        bytes_written += self.encode_set_header(0)?;
 
         Ok(bytes_written)
     }
@@ -1386,31 +4181,85 @@ impl CertWriter<'_> {
     #[allow(clippy::identity_op)]
     fn encode_attributes(&mut self, measurements: &MeasurementData) -> Result<usize, DpeErrorCode> {
         // Attributes is EXPLICIT field number 0
-        let mut bytes_written =
-            self.encode_byte(Self::CONTEXT_SPECIFIC | Self::CONSTRUCTED | 0x0)?;
-        bytes_written += self.encode_size_field(Self::get_attributes_size(
-            measurements,
-            /*tagged=*/ false,
-        )?)?;
+        self.encode_structure(
+            Self::CONTEXT_SPECIFIC | Self::CONSTRUCTED | 0x0,
+            || Self::get_attributes_size(measurements, /*tagged=*/ false),
+            |w| {
+                let start = w.offset;
+                let mut bytes_written = w.encode_extension_request(measurements)?;
+                bytes_written += w.encode_challenge_password(measurements)?;
+
+                // Attributes is a SET OF, so must be in DER order
+                w.sort_set_of(start)?;
+
+                Ok(bytes_written)
+            },
+        )
+    }
 
+    /// Encode an extensionRequest Attribute
+    ///
+    /// Attribute ::= SEQUENCE {
+    ///     type   OBJECT IDENTIFIER,
+    ///     values SET SIZE(1..MAX) OF Extensions
+    /// }
+    fn encode_extension_request(
+        &mut self,
+        measurements: &MeasurementData,
+    ) -> Result<usize, DpeErrorCode> {
         // Attribute Sequence
-        bytes_written += self.encode_tag_field(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(Self::get_attribute_size(
+        self.encode_structure(
+            Self::SEQUENCE_TAG,
+            || Self::get_extension_request_size(measurements, /*tagged=*/ false),
+            |w| {
+                let mut bytes_written = w.encode_oid(Self::EXTENSION_REQUEST_OID)?;
+
+                // attrValues SET OF
+                bytes_written += w.encode_structure(
+                    Self::SET_OF_TAG,
+                    || {
+                        Self::get_extensions_size(
+                            measurements,
+                            /*tagged=*/ true,
+                            /*explicit=*/ false,
+                        )
+                    },
+                    // extensions
+                    |w| w.encode_extensions(measurements, /*explicit=*/ false),
+                )?;
+
+                Ok(bytes_written)
+            },
+        )
+    }
+
+    /// Encode a challengePassword Attribute if there is a challenge password
+    ///
+    /// Attribute ::= SEQUENCE {
+    ///     type   OBJECT IDENTIFIER,
+    ///     values SET SIZE(1) OF UTF8String
+    /// }
+    fn encode_challenge_password(
+        &mut self,
+        measurements: &MeasurementData,
+    ) -> Result<usize, DpeErrorCode> {
+        let Some(password) = measurements.challenge_password else {
+            return Ok(0);
+        };
+
+        let mut bytes_written = self.encode_sequence_header(Self::get_challenge_password_size(
             measurements,
             /*tagged=*/ false,
         )?)?;
-        bytes_written += self.encode_oid(Self::EXTENSION_REQUEST_OID)?;
+        bytes_written += self.encode_oid(Self::CHALLENGE_PASSWORD_OID)?;
 
-        // attrValues SET OF
-        bytes_written += self.encode_tag_field(Self::SET_OF_TAG)?;
-        bytes_written += self.encode_size_field(Self::get_extensions_size(
-            measurements,
+        bytes_written += self.encode_set_header(Self::get_structure_size(
+            password.len(),
             /*tagged=*/ true,
-            /*explicit=*/ false,
         )?)?;
-
-        // extensions
-        bytes_written += self.encode_extensions(measurements, /*explicit=*/ false)?;
+        bytes_written += self.encode_tag_field(Self::UTF8_STRING_TAG)?;
+        bytes_written += self.encode_size_field(password.len())?;
+        bytes_written += self.encode_bytes(password)?;
 
         Ok(bytes_written)
     }
@@ -1426,27 +4275,46 @@ impl CertWriter<'_> {
     ///    signature SignatureValue,
     ///    unsignedAttrs [1] IMPLICIT UnsignedAttributes OPTIONAL
     /// }
+    ///
+    /// `digest_alg` is the algorithm `sig` was computed over. The profile's
+    /// hash if `None`.
     pub fn encode_signer_info(
         &mut self,
-        serial_number: &[u8],
-        issuer_name: &[u8],
+        signer_id: &SignerId,
         sig: &EcdsaSig,
+        digest_alg: Option<HashAlg>,
+    ) -> Result<usize, DpeErrorCode> {
+        self.encode_signer_info_with_hash(signer_id, sig, Self::get_hash_oid(digest_alg))
+    }
+
+    /// Encode a SignerInfo whose digestAlgorithm is `hash_oid`
+    ///
+    /// The version is 3 if the signer is identified by subjectKeyIdentifier
+    /// and 1 otherwise.
+    fn encode_signer_info_with_hash(
+        &mut self,
+        signer_id: &SignerId,
+        sig: &EcdsaSig,
+        hash_oid: &[u8],
     ) -> Result<usize, DpeErrorCode> {
         let signer_info_size =
-            Self::get_signer_info_size(serial_number, issuer_name, sig, /*tagged=*/ false)?;
+            Self::get_signer_info_size(signer_id, sig, hash_oid, /*tagged=*/ false)?;
+        self.check_space(Self::get_structure_size(
+            signer_info_size,
+            /*tagged=*/ true,
+        )?)?;
 
         // SignerInfo Sequence
-        let mut bytes_written = self.encode_tag_field(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(signer_info_size)?;
+        let mut bytes_written = self.encode_sequence_header(signer_info_size)?;
 
         // CMS version
-        bytes_written += self.encode_integer(Self::CMS_V1)?;
+        bytes_written += self.encode_integer(signer_id.get_cms_version())?;
 
         // SignerIdentifier
-        bytes_written += self.encode_issuer_and_serial_number(serial_number, issuer_name)?;
+        bytes_written += self.encode_signer_identifier(signer_id)?;
 
         // digestAlgorithm
-        bytes_written += self.encode_hash_alg_id()?;
+        bytes_written += self.encode_hash_alg_id(hash_oid)?;
 
         // Alg ID
         bytes_written += self.encode_ecdsa_sig_alg_id()?;
@@ -1457,17 +4325,55 @@ impl CertWriter<'_> {
         Ok(bytes_written)
     }
 
+    /// Encode a SignerIdentifier
+    ///
+    /// SignerIdentifier ::= CHOICE {
+    ///    issuerAndSerialNumber IssuerAndSerialNumber,
+    ///    subjectKeyIdentifier [0] SubjectKeyIdentifier
+    /// }
+    ///
+    /// SubjectKeyIdentifier ::= OCTET STRING
+    fn encode_signer_identifier(&mut self, signer_id: &SignerId) -> Result<usize, DpeErrorCode> {
+        match *signer_id {
+            SignerId::IssuerAndSerialNumber {
+                issuer_name,
+                serial_number,
+            } => self.encode_issuer_and_serial_number(serial_number, issuer_name),
+            SignerId::SubjectKeyIdentifier(key_id) => {
+                // subjectKeyIdentifier is IMPLICIT field number 0
+                let mut bytes_written = self.encode_tag_field(Self::CONTEXT_SPECIFIC)?;
+                bytes_written += self.encode_size_field(key_id.len())?;
+                bytes_written += self.encode_bytes(key_id)?;
+
+                Ok(bytes_written)
+            }
+            SignerId::IssuerAndSerialNumberDer(issuer_serial_der) => {
+                Self::validate_issuer_serial_der(issuer_serial_der)?;
+                self.encode_bytes(issuer_serial_der)
+            }
+        }
+    }
+
     /// Encode an IssuerAndSerialNumber
     ///
     /// IssuerAndSerialNumber  ::=  SEQUENCE  {
     ///    issuer Name,
     ///    serialNumber CertificateSerialNumber
     /// }
-    fn encode_issuer_and_serial_number(
+    ///
+    /// The output can be reused as `SignerId::IssuerAndSerialNumberDer`.
+    ///
+    /// Returns number of bytes written
+    pub fn encode_issuer_and_serial_number(
         &mut self,
         serial_number: &[u8],
         issuer_name: &[u8],
     ) -> Result<usize, DpeErrorCode> {
+        self.check_space(Self::get_issuer_and_serial_number_size(
+            serial_number,
+            issuer_name,
+            /*tagged=*/ true,
+        )?)?;
         let issuer_and_serial_number_size = Self::get_issuer_and_serial_number_size(
             serial_number,
             issuer_name,
@@ -1475,8 +4381,7 @@ impl CertWriter<'_> {
         )?;
 
         // IssuerAndSerialNumber sequence
-        let mut bytes_written = self.encode_tag_field(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(issuer_and_serial_number_size)?;
+        let mut bytes_written = self.encode_sequence_header(issuer_and_serial_number_size)?;
 
         // issuer
         bytes_written += self.encode_bytes(issuer_name)?;
@@ -1487,44 +4392,48 @@ impl CertWriter<'_> {
         Ok(bytes_written)
     }
 
-    /// Encode an eContent
+    /// Encode the tag and size fields of an eContent that is `len` bytes
+    /// long
     ///
     /// eContent [0] EXPLICIT OCTET STRING OPTIONAL
     #[allow(clippy::identity_op)]
-    fn encode_econtent(&mut self, bytes: &[u8]) -> Result<usize, DpeErrorCode> {
+    fn encode_econtent_header(&mut self, len: usize) -> Result<usize, DpeErrorCode> {
         // eContent is EXPLICIT field number 0
         let mut bytes_written =
             self.encode_byte(Self::CONTEXT_SPECIFIC | Self::CONSTRUCTED | 0x0)?;
         bytes_written += self.encode_size_field(Self::get_econtent_size(
-            bytes, /*tagged=*/ true, /*explicit=*/ false,
+            len, /*tagged=*/ true, /*explicit=*/ false,
         )?)?;
 
         // eContent OCTET STRING
-        bytes_written += self.encode_byte(Self::OCTET_STRING_TAG)?;
-        bytes_written += self.encode_size_field(Self::get_econtent_size(
-            bytes, /*tagged=*/ false, /*explicit=*/ false,
+        bytes_written += self.encode_octet_string_header(Self::get_econtent_size(
+            len, /*tagged=*/ false, /*explicit=*/ false,
         )?)?;
-        bytes_written += self.encode_bytes(bytes)?;
 
         Ok(bytes_written)
     }
 
-    /// Encode an EncapsulatedContentInfo
+    /// Encode everything in an EncapsulatedContentInfo that precedes the
+    /// eContent bytes, which are `econtent_len` bytes long if present
     ///
     /// EncapsulatedContentInfo  ::=  SEQUENCE  {
     ///    eContentType ContentType,
     ///    eContent [0] EXPLICIT OCTET STRING OPTIONAL
     /// }
-    fn encode_encapsulated_content_info(&mut self, csr: &[u8]) -> Result<usize, DpeErrorCode> {
+    fn encode_encapsulated_content_info_header(
+        &mut self,
+        econtent_len: Option<usize>,
+    ) -> Result<usize, DpeErrorCode> {
         let encap_content_info_size =
-            Self::get_encap_content_info_size(csr, /*tagged=*/ false)?;
+            Self::get_encap_content_info_size(econtent_len, /*tagged=*/ false)?;
 
         // EncapsulatedContentInfo Sequence
-        let mut bytes_written = self.encode_byte(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(encap_content_info_size)?;
+        let mut bytes_written = self.encode_sequence_header(encap_content_info_size)?;
         bytes_written += self.encode_oid(Self::ID_DATA_OID)?;
 
-        bytes_written += self.encode_econtent(csr)?;
+        if let Some(econtent_len) = econtent_len {
+            bytes_written += self.encode_econtent_header(econtent_len)?;
+        }
 
         Ok(bytes_written)
     }
@@ -1550,10 +4459,13 @@ impl CertWriter<'_> {
     /// # Arguments
     ///
     /// * `serial_number` - A byte slice holding the serial number.
-    /// * `issuer_name` - A DER encoded issuer RDN.
+    /// * `issuer_name` - A DER encoded issuer RDN. Must be a single SEQUENCE
+    ///   spanning the whole slice and at most `MAX_ISSUER_NAME_SIZE` bytes.
+    ///   If `measurements.self_signed`, it must match the encoded subject.
     /// * `subject_name` - The subject name RDN struct to encode.
     /// * `pubkey` - ECDSA Public key.
-    /// * `measurements` - DPE measurement data.
+    /// * `measurements` - DPE measurement data. Also holds the optional
+    ///   issuerUniqueID and subjectUniqueID.
     pub fn encode_ecdsa_tbs(
         &mut self,
         serial_number: &[u8],
@@ -1562,44 +4474,159 @@ impl CertWriter<'_> {
         pubkey: &EcdsaPub,
         measurements: &MeasurementData,
     ) -> Result<usize, DpeErrorCode> {
-        let tbs_size = Self::get_tbs_size(
+        measurements.validate(DPE_PROFILE)?;
+        Self::validate_serial_number(serial_number)?;
+        if issuer_name.len() > MAX_ISSUER_NAME_SIZE {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+        Self::validate_name_der(issuer_name)?;
+
+        // TBS sequence
+        self.encode_structure(
+            Self::SEQUENCE_TAG,
+            || {
+                Self::get_tbs_size(
+                    serial_number,
+                    issuer_name,
+                    subject_name,
+                    pubkey,
+                    measurements,
+                    /*tagged=*/ false,
+                )
+            },
+            |w| {
+                // version
+                let mut bytes_written = w.encode_version(measurements.version)?;
+
+                // serialNumber
+                bytes_written += w.encode_integer_bytes(serial_number)?;
+
+                // signature
+                bytes_written += w.encode_sig_alg_id(measurements.get_signature_alg_oid())?;
+
+                // issuer
+                bytes_written += w.encode_bytes(issuer_name)?;
+
+                // validity
+                bytes_written += w.encode_validity(measurements)?;
+
+                // subject
+                let subject_start = w.offset;
+                bytes_written += w.encode_rdn(subject_name)?;
+                if measurements.self_signed
+                    && w.certificate.get(subject_start..w.offset) != Some(issuer_name)
+                {
+                    return Err(DpeErrorCode::InvalidArgument);
+                }
+
+                // subjectPublicKeyInfo
+                bytes_written += w.encode_spki(pubkey, measurements)?;
+
+                // issuerUniqueID
+                bytes_written += w.encode_unique_id(0x01, measurements.issuer_unique_id)?;
+
+                // subjectUniqueID
+                bytes_written += w.encode_unique_id(0x02, measurements.subject_unique_id)?;
+
+                // extensions
+                if measurements.version == CertVersion::V3 {
+                    bytes_written += w.encode_extensions(measurements, /*explicit=*/ true)?;
+                }
+
+                Ok(bytes_written)
+            },
+        )
+    }
+
+    /// Encode the TBSCertificate of a self-signed root, using `name` as both
+    /// issuer and subject
+    ///
+    /// `name` is encoded once for the issuer, and the subject is checked
+    /// against those bytes, so the two cannot differ.
+    ///
+    /// Returns number of bytes written
+    pub fn encode_self_signed_ecdsa_tbs(
+        &mut self,
+        serial_number: &[u8],
+        name: &Name,
+        pubkey: &EcdsaPub,
+        measurements: &MeasurementData,
+    ) -> Result<usize, DpeErrorCode> {
+        let mut issuer_name = [0u8; MAX_ISSUER_NAME_SIZE];
+        let issuer_len = name.to_der(&mut issuer_name)?;
+        let issuer_name = issuer_name
+            .get(..issuer_len)
+            .ok_or(DpeErrorCode::InternalError)?;
+
+        self.encode_ecdsa_tbs(
+            serial_number,
+            issuer_name,
+            name,
+            pubkey,
+            &MeasurementData {
+                self_signed: true,
+                ..*measurements
+            },
+        )
+    }
+
+    /// Encode an ECDSA TBSCertificate as `encode_ecdsa_tbs` does, and return
+    /// its length, serial number and digest for an issuance log
+    ///
+    /// The digest is computed over the bytes just written, so it can also be
+    /// signed directly without hashing the TBSCertificate again.
+    ///
+    /// # Arguments
+    ///
+    /// * `crypto` - Crypto implementation used to hash the TBSCertificate.
+    /// * The rest are as for `encode_ecdsa_tbs`.
+    pub fn encode_ecdsa_tbs_record(
+        &mut self,
+        crypto: &mut impl Crypto,
+        serial_number: &[u8],
+        issuer_name: &[u8],
+        subject_name: &Name,
+        pubkey: &EcdsaPub,
+        measurements: &MeasurementData,
+    ) -> Result<TbsRecord, DpeErrorCode> {
+        let start = self.offset;
+        let tbs_len = self.encode_ecdsa_tbs(
             serial_number,
             issuer_name,
             subject_name,
             pubkey,
             measurements,
-            /*tagged=*/ false,
         )?;
+        let tbs = self
+            .certificate
+            .get(start..start + tbs_len)
+            .ok_or(DpeErrorCode::InternalError)?;
+        let tbs_digest = crypto.hash(DPE_PROFILE.alg_len(), tbs)?;
+
+        // Same content octets as encode_integer_bytes writes
+        let serial_len = Self::get_integer_bytes_size(serial_number, /*tagged=*/ false)?;
+        let mut serial = [0u8; MAX_SERIAL_NUMBER_SIZE];
+        let dst = serial
+            .get_mut(..serial_len)
+            .ok_or(DpeErrorCode::InternalError)?;
+        if serial_len > serial_number.len() {
+            dst.get_mut(1..)
+                .ok_or(DpeErrorCode::InternalError)?
+                .copy_from_slice(serial_number);
+        } else {
+            dst.copy_from_slice(
+                serial_number
+                    .get(serial_number.len() - serial_len..)
+                    .ok_or(DpeErrorCode::InternalError)?,
+            );
+        }
 
-        // TBS sequence
-        let mut bytes_written = self.encode_tag_field(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(tbs_size)?;
-
-        // version
-        bytes_written += self.encode_version()?;
-
-        // serialNumber
-        bytes_written += self.encode_integer_bytes(serial_number)?;
-
-        // signature
-        bytes_written += self.encode_ecdsa_sig_alg_id()?;
-
-        // issuer
-        bytes_written += self.encode_bytes(issuer_name)?;
-
-        // validity
-        bytes_written += self.encode_validity()?;
-
-        // subject
-        bytes_written += self.encode_rdn(subject_name)?;
-
-        // subjectPublicKeyInfo
-        bytes_written += self.encode_ecdsa_subject_pubkey_info(pubkey)?;
-
-        // extensions
-        bytes_written += self.encode_extensions(measurements, /*explicit=*/ true)?;
-
-        Ok(bytes_written)
+        Ok(TbsRecord {
+            tbs_len,
+            serial,
+            serial_len,
+            tbs_digest,
+        })
     }
 
     /// Encode an ECDSA X.509 certificate
@@ -1615,72 +4642,196 @@ impl CertWriter<'_> {
         tbs: &[u8],
         sig: &EcdsaSig,
     ) -> Result<usize, DpeErrorCode> {
-        let cert_size = tbs.len()
-            + Self::get_ecdsa_sig_alg_id_size(/*tagged=*/ true)?
-            + Self::get_ecdsa_signature_bit_string_size(sig, /*tagged=*/ true)?;
+        Self::validate_tbs_sig_alg(tbs, Self::ECDSA_OID)?;
 
-        // Certificate sequence
-        let mut bytes_written = self.encode_tag_field(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(cert_size)?;
-
-        // TBS
-        bytes_written += self.encode_bytes(tbs)?;
+        // Fail before writing the header if the certificate cannot fit
+        self.check_space(Self::get_ecdsa_certificate_size(
+            tbs.len(),
+            sig,
+            /*tagged=*/ true,
+        )?)?;
 
-        // Alg ID
-        bytes_written += self.encode_ecdsa_sig_alg_id()?;
+        // Certificate sequence
+        let start = self.offset;
+        let bytes_written = self.encode_structure(
+            Self::SEQUENCE_TAG,
+            || Self::get_ecdsa_certificate_size(tbs.len(), sig, /*tagged=*/ false),
+            |w| {
+                // TBS
+                let mut bytes_written = w.encode_bytes(tbs)?;
+
+                // Alg ID
+                bytes_written += w.encode_ecdsa_sig_alg_id()?;
+
+                // Signature
+                bytes_written += w.encode_ecdsa_signature_bit_string(sig)?;
+
+                Ok(bytes_written)
+            },
+        )?;
 
-        // Signature
-        bytes_written += self.encode_ecdsa_signature_bit_string(sig)?;
+        self.debug_check_der(start, bytes_written);
 
         Ok(bytes_written)
     }
 
-    /// Encode a certification request info
+    /// Encode an X.509 certificate with a signature produced outside of DPE,
+    /// such as a PQC or composite signature
     ///
     /// Returns number of bytes written to `scratch`
     ///
-    /// CertificationRequestInfo  ::=  SEQUENCE  {
-    ///    version       INTEGER { v1(0) } (v1,...),
-    ///    subject       Name,
-    ///    subjectPKInfo SubjectPublicKeyInfo{{ PKInfoAlgorithms }},
-    ///    attributes    [0] Attributes{{ CRIAttributes }}}  
-    /// }
+    /// Certificate  ::=  SEQUENCE  {
+    ///    tbsCertificate       TBSCertificate,
+    ///    signatureAlgorithm   AlgorithmIdentifier,
+    ///    signatureValue       BIT STRING  }
     ///
     /// # Arguments
     ///
-    /// * `pubkey` - ECDSA Public key.
-    /// * `subject_name` - The subject name RDN struct to encode.
-    /// * `measurements` - DPE measurement data.
-    pub fn encode_certification_request_info(
+    /// * `tbs` - The DER encoded TBSCertificate. Its signature field must
+    ///   identify `sig_alg_oid`, see `MeasurementData::signature_alg_oid`.
+    /// * `sig_alg_oid` - The signature algorithm OID. The AlgorithmIdentifier
+    ///   parameters are absent.
+    /// * `sig` - The raw signature, written to the signatureValue BIT STRING
+    ///   as-is.
+    pub fn encode_certificate(
         &mut self,
-        pub_key: &EcdsaPub,
-        subject_name: &Name,
-        measurements: &MeasurementData,
+        tbs: &[u8],
+        sig_alg_oid: &[u8],
+        sig: &[u8],
     ) -> Result<usize, DpeErrorCode> {
-        let cert_req_info_size = Self::get_certification_request_info_size(
-            subject_name,
-            pub_key,
-            measurements,
-            /*tagged=*/ false,
-        )?;
+        Self::validate_tbs_sig_alg(tbs, sig_alg_oid)?;
 
-        // CertificationRequestInfo Sequence
-        let mut bytes_written = self.encode_tag_field(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(cert_req_info_size)?;
+        // Certificate sequence
+        self.encode_structure(
+            Self::SEQUENCE_TAG,
+            || {
+                Self::checked_sum(&[
+                    tbs.len(),
+                    Self::get_sig_alg_id_size(sig_alg_oid, /*tagged=*/ true)?,
+                    Self::get_structure_size(
+                        Self::checked_sum(&[1, sig.len()])?,
+                        /*tagged=*/ true,
+                    )?,
+                ])
+            },
+            |w| {
+                // TBS
+                let mut bytes_written = w.encode_bytes(tbs)?;
+
+                // Alg ID
+                bytes_written += w.encode_sig_alg_id(sig_alg_oid)?;
+
+                // Signature
+                bytes_written += w.encode_tag_field(Self::BIT_STRING_TAG)?;
+                bytes_written += w.encode_size_field(1 + sig.len())?;
+                // Unused bits
+                bytes_written += w.encode_byte(0)?;
+                bytes_written += w.encode_bytes(sig)?;
+
+                Ok(bytes_written)
+            },
+        )
+    }
 
-        // version
-        bytes_written += self.encode_integer(Self::CSR_V0)?;
+    /// Append a complete DER encoded certificate after the data written so
+    /// far, e.g. to build `leaf || intermediate || root` in one buffer
+    ///
+    /// Fails with `InvalidArgument` if `cert_der` is not a single SEQUENCE
+    /// spanning the whole slice.
+    ///
+    /// Returns number of bytes written
+    pub fn append_certificate(&mut self, cert_der: &[u8]) -> Result<usize, DpeErrorCode> {
+        Self::validate_der_well_formed_single_element(cert_der)?;
+        if cert_der[0] != Self::SEQUENCE_TAG {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
 
-        // subject
-        bytes_written += self.encode_rdn(subject_name)?;
+        self.encode_bytes(cert_der)
+    }
 
-        // subjectPublicKeyInfo
-        bytes_written += self.encode_ecdsa_subject_pubkey_info(pub_key)?;
+    /// Check that the signature field of the DER encoded TBSCertificate `tbs`
+    /// identifies `sig_alg_oid` with absent parameters. RFC 5280 requires it
+    /// to equal the outer signatureAlgorithm.
+    fn validate_tbs_sig_alg(tbs: &[u8], sig_alg_oid: &[u8]) -> Result<(), DpeErrorCode> {
+        Self::validate_der_well_formed_single_element(tbs)?;
+        let (tag, tbs_contents, _) = Self::split_element(tbs)?;
+        if tag != Self::SEQUENCE_TAG {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
 
-        // attributes
-        bytes_written += self.encode_attributes(measurements)?;
+        // Skip the optional version and the serialNumber
+        let (tag, _, mut rest) = Self::split_element(tbs_contents)?;
+        if tag == Self::CONTEXT_SPECIFIC | Self::CONSTRUCTED {
+            (_, _, rest) = Self::split_element(rest)?;
+        }
 
-        Ok(bytes_written)
+        let (tag, alg_id, _) = Self::split_element(rest)?;
+        if tag != Self::SEQUENCE_TAG {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+        let (tag, oid, params) = Self::split_element(alg_id)?;
+        if tag != Self::OID_TAG || oid != sig_alg_oid || !params.is_empty() {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
+    /// Encode a certification request info
+    ///
+    /// Returns number of bytes written to `scratch`
+    ///
+    /// CertificationRequestInfo  ::=  SEQUENCE  {
+    ///    version       INTEGER { v1(0) } (v1,...),
+    ///    subject       Name,
+    ///    subjectPKInfo SubjectPublicKeyInfo{{ PKInfoAlgorithms }},
+    ///    attributes    [0] Attributes{{ CRIAttributes }}}  
+    /// }
+    ///
+    /// # Arguments
+    ///
+    /// * `pubkey` - ECDSA Public key.
+    /// * `subject_name` - The subject name RDN struct to encode. If `None`,
+    ///   the subject is an empty RDNSequence and left for the CA to assign.
+    /// * `measurements` - DPE measurement data.
+    pub fn encode_certification_request_info(
+        &mut self,
+        pub_key: &EcdsaPub,
+        subject_name: Option<&Name>,
+        measurements: &MeasurementData,
+    ) -> Result<usize, DpeErrorCode> {
+        measurements.validate(DPE_PROFILE)?;
+
+        // CertificationRequestInfo Sequence
+        self.encode_structure(
+            Self::SEQUENCE_TAG,
+            || {
+                Self::get_certification_request_info_size(
+                    subject_name,
+                    pub_key,
+                    measurements,
+                    /*tagged=*/ false,
+                )
+            },
+            |w| {
+                // version
+                let mut bytes_written = w.encode_integer(Self::CSR_V0)?;
+
+                // subject
+                bytes_written += match subject_name {
+                    Some(name) => w.encode_rdn(name)?,
+                    None => w.encode_sequence_header(0)?,
+                };
+
+                // subjectPublicKeyInfo
+                bytes_written += w.encode_spki(pub_key, measurements)?;
+
+                // attributes
+                bytes_written += w.encode_attributes(measurements)?;
+
+                Ok(bytes_written)
+            },
+        )
     }
 
     /// Encode an PKCS #10 CSR
@@ -1697,68 +4848,736 @@ impl CertWriter<'_> {
         cert_req_info: &[u8],
         sig: &EcdsaSig,
     ) -> Result<usize, DpeErrorCode> {
-        let csr_size = cert_req_info.len()
-            + Self::get_ecdsa_sig_alg_id_size(/*tagged=*/ true)?
-            + Self::get_ecdsa_signature_bit_string_size(sig, /*tagged=*/ true)?;
-
         // CertificateRequest sequence
-        let mut bytes_written = self.encode_tag_field(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(csr_size)?;
-
-        // CertificationRequestInfo
-        bytes_written += self.encode_bytes(cert_req_info)?;
-
-        // Alg ID
-        bytes_written += self.encode_ecdsa_sig_alg_id()?;
+        let start = self.offset;
+        let bytes_written = self.encode_structure(
+            Self::SEQUENCE_TAG,
+            || Self::get_csr_size(cert_req_info.len(), sig, /*tagged=*/ false),
+            |w| {
+                // CertificationRequestInfo
+                let mut bytes_written = w.encode_bytes(cert_req_info)?;
+
+                // Alg ID
+                bytes_written += w.encode_ecdsa_sig_alg_id()?;
+
+                // Signature
+                bytes_written += w.encode_ecdsa_signature_bit_string(sig)?;
+
+                Ok(bytes_written)
+            },
+        )?;
 
-        // Signature
-        bytes_written += self.encode_ecdsa_signature_bit_string(sig)?;
+        self.debug_check_der(start, bytes_written);
 
         Ok(bytes_written)
     }
 
+    /// Get the size of the CMS ContentInfo message `encode_cms` writes for
+    /// the same arguments
+    pub fn cms_size(
+        csr: &[u8],
+        signer_id: &SignerId,
+        sig: &EcdsaSig,
+        digest_alg: Option<HashAlg>,
+    ) -> Result<usize, DpeErrorCode> {
+        Self::get_cms_size(
+            csr.len(),
+            signer_id,
+            sig,
+            Self::get_hash_oid(digest_alg),
+            /*tagged=*/ true,
+        )
+    }
+
     /// Encode a CMS ContentInfo message
     ///
     /// ContentInfo  ::=  SEQUENCE  {
     ///    contentType ContentType,
     ///    content [0] EXPLICIT ANY DEFINED BY contentType
     /// }
+    ///
+    /// `signer_id` identifies the certificate of the key `sig` was made with,
+    /// and selects the CMS version. `digest_alg` is the algorithm `sig` was
+    /// computed over. It is used for both the SignedData digestAlgorithms and
+    /// the SignerInfo digestAlgorithm. The profile's hash if `None`.
     pub fn encode_cms(
         &mut self,
         csr: &[u8],
-        serial_number: &[u8],
-        issuer_name: &[u8],
+        signer_id: &SignerId,
         sig: &EcdsaSig,
+        digest_alg: Option<HashAlg>,
     ) -> Result<usize, DpeErrorCode> {
-        let size = Self::get_structure_size(Self::ID_SIGNED_DATA_OID.len(), /*tagged=*/ true)?
-            + Self::get_signed_data_size(
-                csr,
-                serial_number,
-                issuer_name,
-                sig,
-                /*tagged=*/ true,
-                /*explicit=*/ true,
-            )?;
+        let hash_oid = Self::get_hash_oid(digest_alg);
+
+        let start = self.offset;
+        let mut bytes_written = self.encode_cms_header(csr.len(), signer_id, sig, hash_oid)?;
+        bytes_written += self.encode_bytes(csr)?;
+        bytes_written += self.encode_signer_infos(signer_id, sig, hash_oid)?;
+
+        self.debug_check_der(start, bytes_written);
+
+        Ok(bytes_written)
+    }
+
+    /// Encode everything in a CMS ContentInfo message that precedes the
+    /// eContent bytes, which are `csr_len` bytes long
+    fn encode_cms_header(
+        &mut self,
+        csr_len: usize,
+        signer_id: &SignerId,
+        sig: &EcdsaSig,
+        hash_oid: &[u8],
+    ) -> Result<usize, DpeErrorCode> {
+        let cms_size =
+            Self::get_cms_size(csr_len, signer_id, sig, hash_oid, /*tagged=*/ false)?;
+
+        let mut bytes_written = self.encode_sequence_header(cms_size)?;
+        bytes_written += self.encode_oid(Self::ID_SIGNED_DATA_OID)?;
+        bytes_written += self.encode_signed_data_header(signer_id, csr_len, sig, hash_oid)?;
+
+        Ok(bytes_written)
+    }
+
+    /// Start building a CMS ContentInfo message whose eContent is written in
+    /// chunks, so the CSR does not need to be buffered separately from the
+    /// output
+    ///
+    /// The eContent is added with `append_cms_econtent` and the message is
+    /// completed with `finish_cms`. No other encoding may be done with this
+    /// writer in between.
+    pub fn begin_cms(&mut self) -> Result<(), DpeErrorCode> {
+        if self.cms_econtent_start.is_some() {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+        self.cms_econtent_start = Some(self.offset);
+
+        Ok(())
+    }
+
+    /// Append `chunk` to the eContent of the CMS message started by
+    /// `begin_cms`
+    pub fn append_cms_econtent(&mut self, chunk: &[u8]) -> Result<usize, DpeErrorCode> {
+        if self.cms_econtent_start.is_none() {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
 
-        let cms_size = Self::get_structure_size(size, false)?;
+        self.encode_bytes(chunk)
+    }
+
+    /// Complete the CMS message started by `begin_cms`
+    ///
+    /// The appended eContent is shifted to make room for the message header,
+    /// whose length fields are only known now, and the SignerInfo is written
+    /// after it. The output is identical to `encode_cms` over the whole
+    /// eContent. Returns the size of the whole message.
+    ///
+    /// If the message does not fit, the writer is left as it was so the
+    /// failure can be reported without losing the eContent.
+    ///
+    /// See `encode_cms` for `signer_id`, `sig` and `digest_alg`.
+    pub fn finish_cms(
+        &mut self,
+        signer_id: &SignerId,
+        sig: &EcdsaSig,
+        digest_alg: Option<HashAlg>,
+    ) -> Result<usize, DpeErrorCode> {
+        let start = self
+            .cms_econtent_start
+            .ok_or(DpeErrorCode::InvalidArgument)?;
+        let csr_len = self.offset - start;
+        let hash_oid = Self::get_hash_oid(digest_alg);
+
+        let cms_size =
+            Self::get_cms_size(csr_len, signer_id, sig, hash_oid, /*tagged=*/ true)?;
+        let signer_infos_size = Self::get_signer_infos_size(signer_id, sig, hash_oid)?;
+        let header_size = cms_size - csr_len - signer_infos_size;
+        // The header and SignerInfo must fit after the eContent is moved back
+        // to `start`
+        self.check_space(cms_size - csr_len)?;
+
+        self.cms_econtent_start = None;
+        self.offset = start;
+        self.certificate
+            .copy_within(start..start + csr_len, start + header_size);
+
+        self.encode_cms_header(csr_len, signer_id, sig, hash_oid)?;
+        self.offset += csr_len;
+        self.encode_signer_infos(signer_id, sig, hash_oid)?;
+
+        Ok(cms_size)
+    }
+
+    /// Get the size of the CMS ContentInfo message `encode_certs_only_cms`
+    /// writes for `certs`
+    pub fn certs_only_cms_size(certs: &[u8]) -> Result<usize, DpeErrorCode> {
+        Self::get_certs_only_cms_size(certs, /*tagged=*/ true)
+    }
+
+    /// Encode a certificates-only CMS ContentInfo message, also known as a
+    /// PKCS #7 certificate bag
+    ///
+    /// `certs` holds the concatenated DER encoded certificates, such as a
+    /// certificate chain. Fails with `InvalidArgument`, before anything is
+    /// written, if any of them is not a single well-formed DER SEQUENCE.
+    pub fn encode_certs_only_cms(&mut self, certs: &[u8]) -> Result<usize, DpeErrorCode> {
+        let cms_size = Self::get_certs_only_cms_size(certs, /*tagged=*/ false)?;
 
-        let mut bytes_written = self.encode_byte(Self::SEQUENCE_TAG)?;
-        bytes_written += self.encode_size_field(cms_size)?;
+        let mut bytes_written = self.encode_sequence_header(cms_size)?;
         bytes_written += self.encode_oid(Self::ID_SIGNED_DATA_OID)?;
 
-        bytes_written += self.encode_signed_data(serial_number, issuer_name, csr, sig)?;
+        bytes_written += self.encode_certs_only_signed_data(certs)?;
 
         Ok(bytes_written)
     }
+
+    /// Check the structure of a certificate issued by `run_cert_self_test`
+    ///
+    /// The certificate must be a single SEQUENCE holding the TBS, an ECDSA
+    /// signatureAlgorithm matching the TBS signature field and a BIT STRING
+    /// signature. The TBS must carry the tcg-dice-MultiTcbInfo and
+    /// tcg-dice-Ueid extensions.
+    fn validate_self_test_cert(cert: &[u8]) -> Result<(), DpeErrorCode> {
+        let (tag, cert_contents, rest) = Self::split_element(cert)?;
+        if tag != Self::SEQUENCE_TAG || !rest.is_empty() {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        let (_, tbs_contents, rest) = Self::split_element(cert_contents)?;
+        let tbs = &cert_contents[..cert_contents.len() - rest.len()];
+        Self::validate_tbs_sig_alg(tbs, Self::ECDSA_OID)?;
+
+        let (tag, alg_id, rest) = Self::split_element(rest)?;
+        let (oid_tag, oid, params) = Self::split_element(alg_id)?;
+        if tag != Self::SEQUENCE_TAG
+            || oid_tag != Self::OID_TAG
+            || oid != Self::ECDSA_OID
+            || !params.is_empty()
+        {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        let (tag, _, rest) = Self::split_element(rest)?;
+        if tag != Self::BIT_STRING_TAG || !rest.is_empty() {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        // Find the EXPLICIT [3] extensions, which come last in the TBS
+        let mut rest = tbs_contents;
+        let extensions = loop {
+            let (tag, contents, next) = Self::split_element(rest)?;
+            if tag == Self::CONTEXT_SPECIFIC | Self::CONSTRUCTED | 0x3 {
+                break contents;
+            }
+            rest = next;
+        };
+
+        let (tag, mut rest, _) = Self::split_element(extensions)?;
+        if tag != Self::SEQUENCE_TAG {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+        let mut has_multi_tcb_info = false;
+        let mut has_ueid = false;
+        while !rest.is_empty() {
+            let (_, extension, next) = Self::split_element(rest)?;
+            let (tag, oid, _) = Self::split_element(extension)?;
+            if tag == Self::OID_TAG {
+                has_multi_tcb_info |= oid == Self::MULTI_TCBINFO_OID;
+                has_ueid |= oid == Self::UEID_OID;
+            }
+            rest = next;
+        }
+        if !has_multi_tcb_info || !has_ueid {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
+    /// Split an ECDSA certificate into its DER encoded TBSCertificate and
+    /// its signature
+    fn decode_ecdsa_certificate(cert: &[u8]) -> Result<(&[u8], EcdsaSig), DpeErrorCode> {
+        let (tag, cert_contents, _) = Self::split_element(cert)?;
+        if tag != Self::SEQUENCE_TAG {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+        let (_, _, rest) = Self::split_element(cert_contents)?;
+        let tbs = &cert_contents[..cert_contents.len() - rest.len()];
+
+        // Skip the signatureAlgorithm
+        let (_, _, rest) = Self::split_element(rest)?;
+        let (tag, sig_bits, _) = Self::split_element(rest)?;
+        let (&unused_bits, sig_value) = sig_bits
+            .split_first()
+            .ok_or(DpeErrorCode::InvalidArgument)?;
+        if tag != Self::BIT_STRING_TAG || unused_bits != 0 {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        // Ecdsa-Sig-Value  ::=  SEQUENCE  {
+        //     r     INTEGER,
+        //     s     INTEGER  }
+        let (tag, ints, _) = Self::split_element(sig_value)?;
+        let (r_tag, r, rest) = Self::split_element(ints)?;
+        let (s_tag, s, _) = Self::split_element(rest)?;
+        if tag != Self::SEQUENCE_TAG || r_tag != Self::INTEGER_TAG || s_tag != Self::INTEGER_TAG {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        let sig = EcdsaSig {
+            r: Self::decode_ecc_int(r)?,
+            s: Self::decode_ecc_int(s)?,
+        };
+        Ok((tbs, sig))
+    }
+
+    /// Left-pad the contents of a DER INTEGER to the ECC integer size of
+    /// `DPE_PROFILE`
+    fn decode_ecc_int(int: &[u8]) -> Result<CryptoBuf, DpeErrorCode> {
+        let mut padded = [0u8; DPE_PROFILE.get_ecc_int_size()];
+        let first = int.iter().position(|&b| b != 0).unwrap_or(int.len());
+        let int = &int[first..];
+        let start = padded
+            .len()
+            .checked_sub(int.len())
+            .ok_or(DpeErrorCode::InvalidArgument)?;
+        padded[start..].copy_from_slice(int);
+        Ok(CryptoBuf::new(&padded)?)
+    }
+
+    /// Read the uncompressed EC point from the subjectPublicKey of `cert`
+    fn decode_ecdsa_pubkey(cert: &[u8]) -> Result<EcdsaPub, DpeErrorCode> {
+        const ECC_INT_SIZE: usize = DPE_PROFILE.get_ecc_int_size();
+
+        let (_, cert_contents, _) = Self::split_element(cert)?;
+        let (_, tbs_contents, _) = Self::split_element(cert_contents)?;
+
+        // Skip the optional EXPLICIT [0] version, then serialNumber,
+        // signature, issuer, validity and subject
+        let (tag, _, mut rest) = Self::split_element(tbs_contents)?;
+        if tag != Self::CONTEXT_SPECIFIC | Self::CONSTRUCTED {
+            rest = tbs_contents;
+        }
+        for _ in 0..5 {
+            (_, _, rest) = Self::split_element(rest)?;
+        }
+
+        let (tag, spki, _) = Self::split_element(rest)?;
+        let (_, _, rest) = Self::split_element(spki)?;
+        let (key_tag, key, _) = Self::split_element(rest)?;
+        if tag != Self::SEQUENCE_TAG || key_tag != Self::BIT_STRING_TAG {
+            return Err(DpeErrorCode::InvalidArgument);
+        }
+
+        // No unused bits, then an uncompressed point
+        match key {
+            [0x00, 0x04, point @ ..] if point.len() == 2 * ECC_INT_SIZE => {
+                let (x, y) = point.split_at(ECC_INT_SIZE);
+                Ok(EcdsaPub::from_coords(x, y, DPE_PROFILE.alg_len())?)
+            }
+            _ => Err(DpeErrorCode::InvalidArgument),
+        }
+    }
+}
+
+/// Maximum number of certificates a `RingCertWriter` keeps track of
+pub const MAX_RING_CERTS: usize = 16;
+
+/// Writes certificates into a fixed circular buffer, such as a continuous
+/// attestation log
+///
+/// Each certificate is encoded in place, with a single-pass CertWriter over
+/// the free space after the newest one, and stored contiguously. If it does
+/// not fit before the end of the buffer, it is placed at the start instead
+/// and the unused tail is skipped. The oldest certificates are dropped to
+/// make room, as is the oldest once `MAX_RING_CERTS` are held, but only once
+/// the new certificate is committed.
+pub struct RingCertWriter<'a> {
+    buf: &'a mut [u8],
+    crit_dice: bool,
+    /// (offset, length) of each certificate, oldest first starting at `head`
+    entries: [(usize, usize); MAX_RING_CERTS],
+    head: usize,
+    count: usize,
+}
+
+impl RingCertWriter<'_> {
+    pub fn new(buf: &mut [u8], crit_dice: bool) -> RingCertWriter {
+        RingCertWriter {
+            buf,
+            crit_dice,
+            entries: [(0, 0); MAX_RING_CERTS],
+            head: 0,
+            count: 0,
+        }
+    }
+
+    /// Encode a certificate with `encode` and append it as the newest entry
+    ///
+    /// `encode` writes the certificate with the given CertWriter and returns
+    /// the number of bytes written. It is first called without any space to
+    /// learn the certificate size from `BufferTooSmall`, which the top-level
+    /// CertWriter encoders report before writing anything. A certificate
+    /// larger than the whole buffer then fails with the ring intact.
+    ///
+    /// Otherwise `encode` is given the free space, grown by dropping the
+    /// oldest certificates until the certificate fits. Failures leave the
+    /// ring intact unless `encode` had already overwritten a dropped
+    /// certificate.
+    ///
+    /// Returns the number of bytes written
+    pub fn write_cert(
+        &mut self,
+        mut encode: impl FnMut(&mut CertWriter) -> Result<usize, DpeErrorCode>,
+    ) -> Result<usize, DpeErrorCode> {
+        let mut needed = match encode(&mut CertWriter::new_single_pass(&mut [], self.crit_dice)) {
+            Ok(cert_len) => cert_len,
+            Err(DpeErrorCode::BufferTooSmall(needed)) if needed as usize <= self.buf.len() => {
+                needed as usize
+            }
+            Err(e) => return Err(e),
+        };
+
+        // Drop certificates from copies of `head` and `count`, which are only
+        // stored once the new certificate is committed
+        let (mut head, mut count) = (self.head, self.count);
+        let mut start = match count {
+            0 => 0,
+            _ => {
+                let (offset, len) = self.entry(head, count - 1)?;
+                offset + len
+            }
+        };
+        let mut clobbered = false;
+
+        let cert_len = loop {
+            // The oldest certificate bounds the free space if it lies ahead
+            let oldest = match count {
+                0 => None,
+                _ => Some(self.entry(head, 0)?.0).filter(|&offset| offset >= start),
+            };
+            let end = oldest.unwrap_or(self.buf.len());
+
+            if end - start < needed {
+                if oldest.is_some() {
+                    head = (head + 1) % MAX_RING_CERTS;
+                    count -= 1;
+                } else if start != 0 {
+                    start = 0;
+                } else {
+                    return Err(DpeErrorCode::InternalError);
+                }
+                continue;
+            }
+
+            let free = self
+                .buf
+                .get_mut(start..end)
+                .ok_or(DpeErrorCode::InternalError)?;
+            let mut w = CertWriter::new_single_pass(free, self.crit_dice);
+            let result = encode(&mut w);
+            clobbered |= !w.written().is_empty();
+            match result {
+                Ok(cert_len) => break cert_len,
+                // The certificate is larger than `encode` first reported
+                Err(DpeErrorCode::BufferTooSmall(more)) if more as usize <= self.buf.len() => {
+                    needed = more as usize
+                }
+                Err(e) => {
+                    // Dropped certificates that were overwritten stay dropped
+                    if clobbered {
+                        self.head = head;
+                        self.count = count;
+                    }
+                    return Err(e);
+                }
+            }
+        };
+
+        if count == MAX_RING_CERTS {
+            head = (head + 1) % MAX_RING_CERTS;
+            count -= 1;
+        }
+        self.entries[(head + count) % MAX_RING_CERTS] = (start, cert_len);
+        self.head = head;
+        self.count = count + 1;
+
+        Ok(cert_len)
+    }
+
+    /// Number of complete certificates in the ring
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Iterate over the certificates in the ring, oldest first
+    pub fn certs(&self) -> impl Iterator<Item = &[u8]> {
+        (0..self.count).filter_map(|i| {
+            let (offset, len) = self.entry(self.head, i).ok()?;
+            self.buf.get(offset..offset + len)
+        })
+    }
+
+    /// Get the (offset, length) of the `i`th certificate after `head`
+    fn entry(&self, head: usize, i: usize) -> Result<(usize, usize), DpeErrorCode> {
+        self.entries
+            .get((head + i) % MAX_RING_CERTS)
+            .copied()
+            .ok_or(DpeErrorCode::InternalError)
+    }
+}
+
+/// Power-on self-test of the certificate issuing pipeline
+///
+/// Issues a leaf certificate from fixed measurements, a fixed public key and
+/// the platform's issuer name and device UEID, signed with the alias key.
+/// Then checks that the certificate is well-formed, that its signature
+/// algorithms agree, that the DICE extensions are present and that the
+/// signature over the encoded TBSCertificate verifies against the public key
+/// of the last certificate in the platform certificate chain. The signature
+/// check is skipped if `crypto` does not implement `ecdsa_verify`. Returns
+/// `InternalError` if any check fails.
+pub fn run_cert_self_test(
+    crypto: &mut impl Crypto,
+    platform: &mut impl Platform,
+) -> Result<(), DpeErrorCode> {
+    let mut cert = [0u8; MAX_CERT_SIZE];
+    let cert_len = issue_self_test_cert(crypto, platform, &mut cert)?;
+    let cert = &cert[..cert_len];
+
+    CertWriter::validate_self_test_cert(cert).map_err(|_| DpeErrorCode::InternalError)?;
+    verify_self_test_cert(crypto, platform, cert).map_err(|_| DpeErrorCode::InternalError)
+}
+
+/// Check the signature of `cert` against the platform alias key
+fn verify_self_test_cert(
+    crypto: &mut impl Crypto,
+    platform: &mut impl Platform,
+    cert: &[u8],
+) -> Result<(), DpeErrorCode> {
+    let mut alias_cert = [0u8; MAX_CERT_SIZE];
+    let alias_cert_len = read_alias_cert(platform, &mut alias_cert)?;
+    let pub_key = CertWriter::decode_ecdsa_pubkey(&alias_cert[..alias_cert_len])?;
+
+    let (tbs, sig) = CertWriter::decode_ecdsa_certificate(cert)?;
+    let algs = DPE_PROFILE.alg_len();
+    let digest = crypto.hash(algs, tbs)?;
+    match crypto.ecdsa_verify(algs, &digest, &sig, &pub_key) {
+        Ok(true) | Err(CryptoError::NotImplemented) => Ok(()),
+        Ok(false) => Err(DpeErrorCode::InternalError),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Read the last certificate of the platform certificate chain, the one
+/// certifying the alias key, into `out`
+///
+/// Returns the number of bytes written to `out`
+fn read_alias_cert(platform: &mut impl Platform, out: &mut [u8]) -> Result<usize, DpeErrorCode> {
+    // Long enough for a tag and a two octet long form size
+    const MAX_HEADER_SIZE: u32 = 4;
+
+    let chain_len = platform.get_certificate_chain_len()?;
+    let mut chunk = [0u8; MAX_CHUNK_SIZE];
+
+    // Walk the certificate headers to find where the last one starts
+    let mut start = 0;
+    loop {
+        let len = platform.get_certificate_chain(start, MAX_HEADER_SIZE, &mut chunk)?;
+        let header = chunk
+            .get(..len as usize)
+            .ok_or(DpeErrorCode::InternalError)?;
+        let (_, header_len, size) = CertWriter::decode_header(header)?;
+        let end = u32::try_from(header_len + size)
+            .ok()
+            .and_then(|cert_len| start.checked_add(cert_len))
+            .ok_or(DpeErrorCode::InternalError)?;
+        match end.cmp(&chain_len) {
+            core::cmp::Ordering::Less => start = end,
+            core::cmp::Ordering::Equal => break,
+            core::cmp::Ordering::Greater => return Err(DpeErrorCode::InternalError),
+        }
+    }
+
+    let cert_len = (chain_len - start) as usize;
+    let mut written = 0;
+    while written < cert_len {
+        let len = platform.get_certificate_chain(
+            start + written as u32,
+            MAX_CHUNK_SIZE as u32,
+            &mut chunk,
+        )? as usize;
+        // Guard against a platform that stops making progress
+        if len == 0 {
+            return Err(DpeErrorCode::InternalError);
+        }
+        out.get_mut(written..written + len)
+            .ok_or(DpeErrorCode::InternalError)?
+            .copy_from_slice(chunk.get(..len).ok_or(DpeErrorCode::InternalError)?);
+        written += len;
+    }
+
+    Ok(cert_len)
+}
+
+/// Issue the self-test certificate into `cert`, signed with the alias key
+///
+/// Returns the number of bytes written to `cert`
+fn issue_self_test_cert(
+    crypto: &mut impl Crypto,
+    platform: &mut impl Platform,
+    cert: &mut [u8],
+) -> Result<usize, DpeErrorCode> {
+    const ECC_INT_SIZE: usize = DPE_PROFILE.get_ecc_int_size();
+    const SUBJECT_NAME: Name = Name {
+        cn: DirectoryString::PrintableString(b"DPE Self Test"),
+        serial: DirectoryString::PrintableString(&[b'0'; DPE_PROFILE.get_hash_size() * 2]),
+        order: NameOrder::CnFirst,
+    };
+
+    let mut issuer_name = [0u8; MAX_CHUNK_SIZE];
+    let issuer_len = platform.get_issuer_name(&mut issuer_name)?;
+    if issuer_len > MAX_ISSUER_NAME_SIZE {
+        return Err(DpeErrorCode::InvalidArgument);
+    }
+    let issuer_name = issuer_name
+        .get(..issuer_len)
+        .ok_or(DpeErrorCode::InternalError)?;
+
+    let pubkey = EcdsaPub::from_coords(
+        &[0x5A; ECC_INT_SIZE],
+        &[0xA5; ECC_INT_SIZE],
+        DPE_PROFILE.alg_len(),
+    )?;
+    let mut ueid = [0u8; MAX_DEVICE_UEID_SIZE];
+    let ueid_len = platform.get_device_ueid(&mut ueid)?;
+    let mut not_before = [0u8; GENERALIZED_TIME_SIZE];
+    let mut not_after = [0u8; GENERALIZED_TIME_SIZE];
+    platform.get_cert_validity(&mut not_before, &mut not_after)?;
+    let measurements = MeasurementData {
+        label: ueid.get(..ueid_len).ok_or(DpeErrorCode::InternalError)?,
+        tci_nodes: &[TciNodeData::new()],
+        supports_extend_tci: true,
+        validity: Some(Validity::from_platform(&not_before, &not_after)?),
+        ..Default::default()
+    };
+
+    let mut tbs = [0u8; MAX_CERT_SIZE];
+    let mut tbs_writer = CertWriter::new(&mut tbs, true);
+    let tbs_len = tbs_writer.encode_ecdsa_tbs(
+        &[0x01; 20],
+        issuer_name,
+        &SUBJECT_NAME,
+        &pubkey,
+        &measurements,
+    )?;
+
+    let algs = DPE_PROFILE.alg_len();
+    let digest = crypto.hash(algs, &tbs[..tbs_len])?;
+    let sig = crypto.ecdsa_sign_with_alias(algs, &digest)?;
+
+    let mut cert_writer = CertWriter::new(cert, true);
+    cert_writer.encode_ecdsa_certificate(&tbs[..tbs_len], &sig)
+}
+
+/// Issue a DPE certificate for `pubkey` with `writer`
+///
+/// The issuer name is read from the platform, as is the validity period if
+/// `measurements` does not set one. The DICE extensions are marked critical
+/// if `writer` was created with `crit_dice`. The TBSCertificate is hashed with
+/// `DPE_PROFILE` and `sign_fn` signs the digest, typically with the alias key.
+///
+/// Returns the number of bytes written by `writer`
+///
+/// # Arguments
+///
+/// * `env` - DPE environment providing the platform and the crypto used to
+///   hash the TBSCertificate.
+/// * `serial_number` - The certificate serialNumber, at most
+///   `MAX_SERIAL_NUMBER_SIZE` octets once encoded.
+/// * `subject_name` - The subject name RDN struct to encode.
+/// * `pubkey` - ECDSA Public key.
+/// * `measurements` - DPE measurement data.
+/// * `sign_fn` - Signs the TBSCertificate digest.
+/// * `writer` - Writer the DER encoded certificate is written with.
+pub fn build_dpe_cert<'a, T: DpeTypes + 'a>(
+    env: &mut DpeEnv<'a, T>,
+    serial_number: &[u8],
+    subject_name: &Name,
+    pubkey: &EcdsaPub,
+    measurements: &MeasurementData,
+    sign_fn: impl FnOnce(&mut T::Crypto<'a>, &Digest) -> Result<EcdsaSig, CryptoError>,
+    writer: &mut CertWriter,
+) -> Result<usize, DpeErrorCode> {
+    let mut issuer_name = [0u8; MAX_CHUNK_SIZE];
+    let issuer_len = env.platform.get_issuer_name(&mut issuer_name)?;
+    if issuer_len > MAX_ISSUER_NAME_SIZE {
+        return Err(DpeErrorCode::InvalidArgument);
+    }
+    let issuer_name = issuer_name
+        .get(..issuer_len)
+        .ok_or(DpeErrorCode::InternalError)?;
+
+    let mut not_before = [0u8; GENERALIZED_TIME_SIZE];
+    let mut not_after = [0u8; GENERALIZED_TIME_SIZE];
+    let validity = match measurements.validity {
+        Some(validity) => validity,
+        None => {
+            env.platform
+                .get_cert_validity(&mut not_before, &mut not_after)?;
+            Validity::from_platform(&not_before, &not_after)?
+        }
+    };
+    let measurements = MeasurementData {
+        validity: Some(validity),
+        ..*measurements
+    };
+
+    let algs = DPE_PROFILE.alg_len();
+
+    let mut tbs = [0u8; MAX_CERT_SIZE];
+    let mut tbs_writer = CertWriter::new(&mut tbs, writer.crit_dice);
+    let tbs_len = tbs_writer.encode_ecdsa_tbs(
+        serial_number,
+        issuer_name,
+        subject_name,
+        pubkey,
+        &measurements,
+    )?;
+    let tbs = tbs.get(..tbs_len).ok_or(DpeErrorCode::InternalError)?;
+
+    let tbs_digest = env.crypto.hash(algs, tbs)?;
+    let sig = sign_fn(&mut env.crypto, &tbs_digest)?;
+
+    writer.encode_ecdsa_certificate(tbs, &sig)
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::dpe_instance::{
+        tests::{TestPlatform, TestTypes},
+        DpeEnv,
+    };
+    use crate::response::DpeErrorCode;
     use crate::tci::{TciMeasurement, TciNodeData};
-    use crate::x509::{CertWriter, DirectoryString, MeasurementData, Name};
-    use crate::DPE_PROFILE;
-    use crypto::{CryptoBuf, EcdsaPub, EcdsaSig};
+    use crate::x509::{
+        build_dpe_cert, format_generalized_time, issue_self_test_cert, run_cert_self_test, to_hex,
+        verify_self_test_cert, CertProfile, CertRole, CertVersion, CertWriter, DiceKeyPurposes,
+        DirectoryString, FwidDigests, HashAlg, KeyPurposes, KeyUsageFlags, MeasurementData, Name,
+        NameOrder, PointFormat, PolicyConstraints, RingCertWriter, SignerId, TimeEncoding,
+        UeidEncoding, Validity, CERTIFY_KEY_SUBJECT_CN, MAX_ISSUER_NAME_SIZE,
+        MAX_SERIAL_NUMBER_SIZE, MAX_TCI_NODES,
+    };
+    use crate::{DpeProfile, DPE_PROFILE};
+    use crypto::{AlgLen, Crypto, CryptoBuf, CryptoError, Digest, EcdsaPub, EcdsaSig, HmacSig};
+    use der::Decode;
+    use platform::default::{DefaultPlatform, TEST_CERT_CHAIN};
+    use platform::{Platform, GENERALIZED_TIME_SIZE, MAX_CHUNK_SIZE, MAX_VENDOR_OID_ARC_SIZE};
     use std::str;
+    use strum::IntoEnumIterator;
     use x509_parser::certificate::X509CertificateParser;
     use x509_parser::nom::Parser;
     use x509_parser::oid_registry::asn1_rs::oid;
@@ -1799,9 +5618,107 @@ mod tests {
         pub(crate) ueid: &'a [u8],
     }
 
+    /// Owned form of the `MeasurementData` a certificate was built from
+    #[derive(PartialEq, Eq)]
+    struct OwnedMeasurementData {
+        label: Vec<u8>,
+        tci_nodes: Vec<TciNodeData>,
+        is_ca: bool,
+        supports_extend_tci: bool,
+    }
+
+    /// Reconstruct the `MeasurementData` a DPE certificate was built from.
+    ///
+    /// The TCI nodes come from the tcg-dice-MultiTcbInfo extension, the label
+    /// from tcg-dice-Ueid and `is_ca` from basicConstraints. Each TcbInfo is
+    /// expected to carry the current TCI followed by the cumulative TCI when
+    /// `supports_extend_tci` is set, and no other FWIDs. The cumulative TCI of
+    /// nodes without one is left zeroed.
+    fn measurement_data_from_cert(cert_der: &[u8]) -> Result<OwnedMeasurementData, DpeErrorCode> {
+        // Parse with the RustCrypto parser since x509-parser does not handle
+        // unique IDs correctly.
+        let cert = cms::cert::x509::Certificate::from_der(cert_der)
+            .map_err(|_| DpeErrorCode::InvalidArgument)?;
+        let extensions = cert
+            .tbs_certificate
+            .extensions
+            .ok_or(DpeErrorCode::InvalidArgument)?;
+
+        let mut label = None;
+        let mut nodes = None;
+        let mut is_ca = None;
+        for ext in extensions {
+            let ext_der = der::Encode::to_der(&ext).map_err(|_| DpeErrorCode::InvalidArgument)?;
+            let (_, ext) =
+                X509Extension::from_der(&ext_der).map_err(|_| DpeErrorCode::InvalidArgument)?;
+            match ext.oid.to_id_string().as_str() {
+                "2.23.133.5.4.5" => {
+                    let tcb_infos = asn1::parse_single::<asn1::SequenceOf<TcbInfo>>(ext.value)
+                        .map_err(|_| DpeErrorCode::InvalidArgument)?;
+                    let mut parsed = vec![];
+                    for tcb_info in tcb_infos {
+                        let fwids: Vec<Vec<u8>> = tcb_info
+                            .fwids
+                            .ok_or(DpeErrorCode::InvalidArgument)?
+                            .map(|fwid| fwid.digest.to_vec())
+                            .collect();
+                        let to_u32 = |bytes: Option<&[u8]>| -> Result<u32, DpeErrorCode> {
+                            Ok(u32::from_be_bytes(
+                                bytes
+                                    .ok_or(DpeErrorCode::InvalidArgument)?
+                                    .try_into()
+                                    .map_err(|_| DpeErrorCode::InvalidArgument)?,
+                            ))
+                        };
+                        parsed.push((
+                            fwids,
+                            to_u32(tcb_info.tci_type)?,
+                            to_u32(tcb_info.vendor_info)?,
+                        ));
+                    }
+                    nodes = Some(parsed);
+                }
+                "2.23.133.5.4.4" => {
+                    let ueid = asn1::parse_single::<Ueid>(ext.value)
+                        .map_err(|_| DpeErrorCode::InvalidArgument)?;
+                    label = Some(ueid.ueid.to_vec());
+                }
+                _ => {
+                    if let ParsedExtension::BasicConstraints(basic_constraints) =
+                        ext.parsed_extension()
+                    {
+                        is_ca = Some(basic_constraints.ca);
+                    }
+                }
+            }
+        }
+
+        let nodes = nodes.ok_or(DpeErrorCode::InvalidArgument)?;
+        let supports_extend_tci = nodes.iter().all(|(fwids, _, _)| fwids.len() == 2);
+        let tci_nodes = nodes
+            .iter()
+            .map(|(fwids, tci_type, locality)| {
+                let tci_cumulative = match supports_extend_tci {
+                    true => &fwids[1][..],
+                    false => &[0; DPE_PROFILE.get_tci_size()],
+                };
+                let tci_current = fwids.first().ok_or(DpeErrorCode::InvalidArgument)?;
+                TciNodeData::from_parts(tci_current, tci_cumulative, *tci_type, *locality)
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(OwnedMeasurementData {
+            label: label.ok_or(DpeErrorCode::InvalidArgument)?,
+            tci_nodes,
+            is_ca: is_ca.ok_or(DpeErrorCode::InvalidArgument)?,
+            supports_extend_tci,
+        })
+    }
+
     const TEST_ISSUER: Name = Name {
         cn: DirectoryString::PrintableString(b"Caliptra Alias"),
         serial: DirectoryString::PrintableString(&[0x00; DPE_PROFILE.get_hash_size() * 2]),
+        order: NameOrder::CnFirst,
     };
 
     fn encode_test_issuer() -> Vec<u8> {
@@ -1813,24 +5730,73 @@ mod tests {
     }
 
     #[test]
-    fn test_integers() {
-        let buffer_cases = [
-            [0; 8],
-            [0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00],
-            [0x01, 0x02, 0x03, 0x04, 0x00, 0x00, 0x00, 0x00],
-            [0x00, 0x00, 0xFF, 0x04, 0x00, 0x00, 0x00, 0x00],
-            [0x01, 0x02, 0x03, 0x04, 0x00, 0x00, 0x00, 0x00],
+    fn test_integer_fixed_width() {
+        let cases: [(u64, usize, &[u8]); 6] = [
+            (0, 4, &[0x00, 0x00, 0x00, 0x00]),
+            (0x2A, 4, &[0x00, 0x00, 0x00, 0x2A]),
+            (0x7FFF_FFFF, 4, &[0x7F, 0xFF, 0xFF, 0xFF]),
+            // The high bit needs a leading 0x00 to keep the value positive
+            (0x80, 2, &[0x00, 0x80]),
+            (0x80, 1, &[]),
+            (
+                u64::MAX,
+                9,
+                &[0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+            ),
         ];
 
-        for c in buffer_cases {
-            let mut cert = [0u8; 128];
+        for (value, width, contents) in cases {
+            let mut cert = [0u8; 16];
             let mut w = CertWriter::new(&mut cert, true);
-            let byte_count = w.encode_integer_bytes(&c).unwrap();
-            let n = asn1::parse_single::<u64>(&cert[..byte_count]).unwrap();
-            assert_eq!(n, u64::from_be_bytes(c));
+            let result = w.encode_integer_fixed_width(value, width);
+            if contents.is_empty() {
+                assert_eq!(result, Err(DpeErrorCode::InvalidArgument));
+                continue;
+            }
+            let bytes_written = result.unwrap();
             assert_eq!(
-                CertWriter::get_integer_bytes_size(&c, true).unwrap(),
-                byte_count
+                bytes_written,
+                CertWriter::get_integer_fixed_width_size(width, /*tagged=*/ true).unwrap()
+            );
+            assert_eq!(cert[..2], [CertWriter::INTEGER_TAG, width as u8]);
+            assert_eq!(&cert[2..bytes_written], contents);
+        }
+
+        // Values that need the whole width are also minimal DER
+        let mut cert = [0u8; 16];
+        let mut w = CertWriter::new(&mut cert, true);
+        let bytes_written = w.encode_integer_fixed_width(0x80, 2).unwrap();
+        assert_eq!(asn1::parse_single::<u64>(&cert[..bytes_written]), Ok(0x80));
+
+        let mut w = CertWriter::new(&mut cert, true);
+        for (value, width) in [(0x8000_0000, 4), (0, 0), (0, 10)] {
+            assert_eq!(
+                w.encode_integer_fixed_width(value, width),
+                Err(DpeErrorCode::InvalidArgument)
+            );
+        }
+        assert!(w.written().is_empty());
+    }
+
+    #[test]
+    fn test_integers() {
+        let buffer_cases = [
+            [0; 8],
+            [0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00],
+            [0x01, 0x02, 0x03, 0x04, 0x00, 0x00, 0x00, 0x00],
+            [0x00, 0x00, 0xFF, 0x04, 0x00, 0x00, 0x00, 0x00],
+            [0x01, 0x02, 0x03, 0x04, 0x00, 0x00, 0x00, 0x00],
+        ];
+
+        for c in buffer_cases {
+            let mut cert = [0u8; 128];
+            let mut w = CertWriter::new(&mut cert, true);
+            let byte_count = w.encode_integer_bytes(&c).unwrap();
+            let n = asn1::parse_single::<u64>(&cert[..byte_count]).unwrap();
+            assert_eq!(n, u64::from_be_bytes(c));
+            assert_eq!(
+                CertWriter::get_integer_bytes_size(&c, true).unwrap(),
+                byte_count
             );
         }
 
@@ -1846,12 +5812,94 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_encode_headers() {
+        type EncodeHeader = fn(&mut CertWriter, usize) -> Result<usize, DpeErrorCode>;
+        let helpers: [(u8, EncodeHeader); 3] = [
+            (CertWriter::SEQUENCE_TAG, |w, len| {
+                w.encode_sequence_header(len)
+            }),
+            (CertWriter::SET_OF_TAG, |w, len| w.encode_set_header(len)),
+            (CertWriter::OCTET_STRING_TAG, |w, len| {
+                w.encode_octet_string_header(len)
+            }),
+        ];
+
+        for (tag, encode_header) in helpers {
+            for content_len in [0, 0x7F, 0x80, 0xFF, 0x100, 0x1234, 0xFFFF] {
+                let mut expected = [0u8; 8];
+                let mut w = CertWriter::new(&mut expected, true);
+                let mut expected_len = w.encode_tag_field(tag).unwrap();
+                expected_len += w.encode_size_field(content_len).unwrap();
+
+                let mut actual = [0u8; 8];
+                let mut w = CertWriter::new(&mut actual, true);
+                let actual_len = encode_header(&mut w, content_len).unwrap();
+
+                assert_eq!(actual_len, expected_len);
+                assert_eq!(actual, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_size_field_boundaries() {
+        for (content_len, size_width) in [(0x7F, 1), (0x80, 2), (0xFF, 2), (0x100, 3), (0xFFFF, 3)]
+        {
+            assert_eq!(CertWriter::get_size_width(content_len), Ok(size_width));
+
+            let mut size_field = [0u8; 4];
+            let mut w = CertWriter::new(&mut size_field, true);
+            assert_eq!(w.encode_size_field(content_len), Ok(size_width));
+            match size_width {
+                1 => assert_eq!(size_field[0], content_len as u8),
+                _ => assert_eq!(size_field[0], 0x80 | (size_width - 1) as u8),
+            }
+
+            // An OCTET STRING with `content_len` bytes of content parses back
+            // with the same length
+            let content = vec![0x5A; content_len];
+            let total_len = CertWriter::get_structure_size(content_len, /*tagged=*/ true).unwrap();
+            assert_eq!(total_len, 1 + size_width + content_len);
+            let mut buf = vec![0u8; total_len];
+            let mut w = CertWriter::new(&mut buf, true);
+            let mut bytes_written = w.encode_octet_string_header(content_len).unwrap();
+            bytes_written += w.encode_bytes(&content).unwrap();
+            assert_eq!(bytes_written, total_len);
+
+            let parsed = der::asn1::OctetString::from_der(&buf).unwrap();
+            assert_eq!(parsed.as_bytes(), content);
+            assert_eq!(
+                CertWriter::decode_header(&buf),
+                Ok((CertWriter::OCTET_STRING_TAG, 1 + size_width, content_len))
+            );
+        }
+
+        // The size field is at most three bytes
+        assert_eq!(
+            CertWriter::get_size_width(0x10000),
+            Err(DpeErrorCode::InternalError)
+        );
+        assert_eq!(
+            CertWriter::get_structure_size(0x10000, /*tagged=*/ true),
+            Err(DpeErrorCode::InternalError)
+        );
+        let mut size_field = [0u8; 4];
+        let mut w = CertWriter::new(&mut size_field, true);
+        assert_eq!(
+            w.encode_size_field(0x10000),
+            Err(DpeErrorCode::InternalError)
+        );
+        assert_eq!(w.offset, 0);
+    }
+
     #[test]
     fn test_rdn() {
         let mut cert = [0u8; 256];
         let test_name = Name {
             cn: DirectoryString::PrintableString(b"Caliptra Alias"),
             serial: DirectoryString::PrintableString(&[0x0u8; DPE_PROFILE.get_hash_size() * 2]),
+            order: NameOrder::CnFirst,
         };
 
         let mut w = CertWriter::new(&mut cert, true);
@@ -1877,169 +5925,4901 @@ mod tests {
     }
 
     #[test]
-    fn test_subject_pubkey() {
-        let mut cert = [0u8; 256];
-        let test_key = EcdsaPub::default(DPE_PROFILE.alg_len());
-
-        let mut w = CertWriter::new(&mut cert, true);
-        let bytes_written = w.encode_ecdsa_subject_pubkey_info(&test_key).unwrap();
-
-        SubjectPublicKeyInfo::from_der(&cert[..bytes_written]).unwrap();
+    fn test_rdn_ia5_serial() {
+        let mut cert = [0u8; 256];
+        let serial = [b'A'; DPE_PROFILE.get_hash_size() * 2];
+        let test_name = Name {
+            cn: DirectoryString::PrintableString(b"DPE Leaf"),
+            serial: DirectoryString::Ia5String(&serial),
+            order: NameOrder::CnFirst,
+        };
+
+        let mut w = CertWriter::new(&mut cert, true);
+        let bytes_written = w.encode_rdn(&test_name).unwrap();
+        assert_eq!(
+            CertWriter::get_rdn_size(&test_name, true).unwrap(),
+            bytes_written
+        );
+
+        let (_, name) = X509Name::from_der(&cert[..bytes_written]).unwrap();
+        let attr = name
+            .iter_attributes()
+            .find(|attr| attr.attr_type() == &oid!(2.5.4 .5))
+            .unwrap();
+        assert_eq!(
+            attr.attr_value().tag(),
+            x509_parser::der_parser::asn1_rs::Tag::Ia5String
+        );
+        assert_eq!(attr.as_str().unwrap().as_bytes(), serial);
+    }
+
+    #[test]
+    fn test_rdn_order() {
+        use cms::cert::x509::{attr::AttributeTypeAndValue, name};
+
+        let serial = [b'A'; DPE_PROFILE.get_hash_size() * 2];
+        for (order, expected_oids) in [
+            (NameOrder::CnFirst, [oid!(2.5.4 .3), oid!(2.5.4 .5)]),
+            (NameOrder::SerialFirst, [oid!(2.5.4 .5), oid!(2.5.4 .3)]),
+        ] {
+            let test_name = Name {
+                cn: DirectoryString::PrintableString(b"DPE Leaf"),
+                serial: DirectoryString::PrintableString(&serial),
+                order,
+            };
+            let mut cert = [0u8; 256];
+            let mut w = CertWriter::new(&mut cert, true);
+            let bytes_written = w.encode_rdn(&test_name).unwrap();
+            assert_eq!(
+                CertWriter::get_rdn_size(&test_name, true).unwrap(),
+                bytes_written
+            );
+            let der = &cert[..bytes_written];
+
+            let (_, parsed) = X509Name::from_der(der).unwrap();
+            let oids: Vec<_> = parsed
+                .iter_attributes()
+                .map(|attr| attr.attr_type().clone())
+                .collect();
+            assert_eq!(oids, expected_oids);
+
+            // Re-encoding the parsed attributes in the same order with an
+            // independent encoder gives the same bytes
+            let rdns = parsed
+                .iter_rdn()
+                .map(|rdn| {
+                    let attrs: Vec<_> = rdn
+                        .iter()
+                        .map(|attr| AttributeTypeAndValue {
+                            oid: der::asn1::ObjectIdentifier::from_bytes(
+                                attr.attr_type().as_bytes(),
+                            )
+                            .unwrap(),
+                            value: der::asn1::Any::new(
+                                der::Tag::try_from(attr.attr_value().tag().0 as u8).unwrap(),
+                                attr.attr_value().data,
+                            )
+                            .unwrap(),
+                        })
+                        .collect();
+                    name::RelativeDistinguishedName(attrs.try_into().unwrap())
+                })
+                .collect();
+            let reencoded = der::Encode::to_der(&name::RdnSequence(rdns)).unwrap();
+            assert_eq!(reencoded, der);
+        }
+    }
+
+    #[test]
+    fn test_rdn_tags() {
+        let mut buf = [0u8; 256];
+        let mut w = CertWriter::new(&mut buf, true);
+        let bytes_written = w.encode_rdn(&TEST_SUBJECT_NAME).unwrap();
+
+        // Compare against literal tags rather than the CertWriter constants,
+        // since SEQUENCE and SET OF are easily swapped.
+        // RDNSequence ::= SEQUENCE OF RelativeDistinguishedName
+        let (tag, mut rdns, rem) = CertWriter::split_element(&buf[..bytes_written]).unwrap();
+        assert_eq!(tag, 0x30);
+        assert!(rem.is_empty());
+
+        // RelativeDistinguishedName ::= SET OF AttributeTypeAndValue
+        let mut rdn_count = 0;
+        while !rdns.is_empty() {
+            let (tag, rdn, rest) = CertWriter::split_element(rdns).unwrap();
+            assert_eq!(tag, 0x31);
+
+            // AttributeTypeAndValue ::= SEQUENCE
+            let (tag, _, rem) = CertWriter::split_element(rdn).unwrap();
+            assert_eq!(tag, 0x30);
+            assert!(rem.is_empty());
+
+            rdns = rest;
+            rdn_count += 1;
+        }
+        assert_eq!(rdn_count, 2);
+    }
+
+    #[test]
+    fn test_name_to_der() {
+        let mut issuer_der = [0u8; 256];
+        let issuer_len = TEST_ISSUER.to_der(&mut issuer_der).unwrap();
+        let issuer_der = &issuer_der[..issuer_len];
+        assert_eq!(issuer_der, encode_test_issuer());
+
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let node = TciNodeData::new();
+        let measurements = MeasurementData {
+            label: &[0; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[node],
+            ..Default::default()
+        };
+        let mut cert = [0u8; 1024];
+        let mut w = CertWriter::new(&mut cert, true);
+        let bytes_written = w
+            .encode_ecdsa_tbs(
+                TEST_SERIAL,
+                issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+            )
+            .unwrap();
+
+        let mut parser = TbsCertificateParser::new().with_deep_parse_extensions(false);
+        let tbs = parser.parse(&cert[..bytes_written]).unwrap().1;
+        assert_eq!(tbs.issuer.as_raw(), issuer_der);
+
+        let mut out = [0u8; 8];
+        assert_eq!(
+            TEST_ISSUER.to_der(&mut out),
+            Err(DpeErrorCode::BufferTooSmall(issuer_len as u32))
+        );
+    }
+
+    #[test]
+    fn test_subject_pubkey() {
+        let mut cert = [0u8; 256];
+        let test_key = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+
+        let mut w = CertWriter::new(&mut cert, true);
+        let bytes_written = w
+            .encode_ecdsa_subject_pubkey_info(&test_key, PointFormat::Uncompressed)
+            .unwrap();
+
+        SubjectPublicKeyInfo::from_der(&cert[..bytes_written]).unwrap();
+
+        assert_eq!(
+            CertWriter::get_ecdsa_subject_pubkey_info_size(
+                &test_key,
+                PointFormat::Uncompressed,
+                true
+            )
+            .unwrap(),
+            bytes_written
+        );
+    }
+
+    #[test]
+    fn test_default_pubkey_size() {
+        for profile in [DpeProfile::P256Sha256, DpeProfile::P384Sha384] {
+            let key = EcdsaPub::default(profile.alg_len());
+            assert_eq!(key.x.len(), profile.get_ecc_int_size());
+            assert_eq!(key.y.len(), profile.get_ecc_int_size());
+        }
+    }
+
+    #[test]
+    fn test_tcb_info() {
+        let mut node = TciNodeData::new();
+
+        node.tci_type = 0x11223344;
+        node.tci_cumulative = TciMeasurement([0xaau8; DPE_PROFILE.get_hash_size()]);
+        node.tci_current = TciMeasurement([0xbbu8; DPE_PROFILE.get_hash_size()]);
+        node.locality = 0xFFFFFFFF;
+
+        let mut cert = [0u8; 256];
+        let mut w = CertWriter::new(&mut cert, true);
+        let mut supports_extend_tci = true;
+        let mut bytes_written = w
+            .encode_tcb_info(
+                &node,
+                /*has_fwids=*/ true,
+                supports_extend_tci,
+                &[],
+                /*fwid_digests=*/ None,
+                CertWriter::HASH_OID,
+            )
+            .unwrap();
+
+        let mut parsed_tcb_info = asn1::parse_single::<TcbInfo>(&cert[..bytes_written]).unwrap();
+
+        assert_eq!(
+            bytes_written,
+            CertWriter::get_tcb_info_size(
+                &node,
+                /*has_fwids=*/ true,
+                supports_extend_tci,
+                &[],
+                /*fwid_digests=*/ None,
+                CertWriter::HASH_OID,
+                true
+            )
+            .unwrap()
+        );
+
+        // FWIDs
+        let mut fwid_itr = parsed_tcb_info.fwids.unwrap();
+        let expected_current = fwid_itr.next().unwrap().digest;
+        let expected_cumulative = fwid_itr.next().unwrap().digest;
+        assert_eq!(expected_current, node.tci_current.0);
+        assert_eq!(expected_cumulative, node.tci_cumulative.0);
+
+        assert_eq!(
+            parsed_tcb_info.tci_type.unwrap(),
+            node.tci_type.to_be_bytes()
+        );
+        assert_eq!(
+            parsed_tcb_info.vendor_info.unwrap(),
+            node.locality.to_be_bytes()
+        );
+
+        // test tbs_info with supports_extend_tci = false
+        supports_extend_tci = false;
+        w = CertWriter::new(&mut cert, true);
+        bytes_written = w
+            .encode_tcb_info(
+                &node,
+                /*has_fwids=*/ true,
+                supports_extend_tci,
+                &[],
+                /*fwid_digests=*/ None,
+                CertWriter::HASH_OID,
+            )
+            .unwrap();
+
+        parsed_tcb_info = asn1::parse_single::<TcbInfo>(&cert[..bytes_written]).unwrap();
+
+        assert_eq!(
+            bytes_written,
+            CertWriter::get_tcb_info_size(
+                &node,
+                /*has_fwids=*/ true,
+                supports_extend_tci,
+                &[],
+                /*fwid_digests=*/ None,
+                CertWriter::HASH_OID,
+                true
+            )
+            .unwrap()
+        );
+
+        // Check that only FWID[0] is present
+        let mut fwid_itr = parsed_tcb_info.fwids.unwrap();
+        let expected_current = fwid_itr.next().unwrap().digest;
+        assert!(fwid_itr.next().is_none());
+        assert_eq!(expected_current, node.tci_current.0);
+    }
+
+    #[test]
+    fn test_profile_supports_extend_tci() {
+        let mut node = TciNodeData::new();
+        node.tci_current = TciMeasurement([0xbb; DPE_PROFILE.get_hash_size()]);
+        node.tci_cumulative = TciMeasurement([0xaa; DPE_PROFILE.get_hash_size()]);
+
+        let fwid_count = |measurements: &MeasurementData| {
+            let mut cert = [0u8; 512];
+            let mut w = CertWriter::new(&mut cert, true);
+            let bytes_written = w.encode_multi_tcb_info(measurements).unwrap();
+            let mut parser = X509ExtensionParser::new().with_deep_parse_extensions(false);
+            let ext = parser.parse(&cert[..bytes_written]).unwrap().1;
+            let mut tcb_infos = asn1::parse_single::<asn1::SequenceOf<TcbInfo>>(ext.value).unwrap();
+            tcb_infos.next().unwrap().fwids.unwrap().count()
+        };
+
+        // The default follows the profile, as test_tcb_info does for each value
+        let measurements = MeasurementData {
+            tci_nodes: &[node],
+            ..Default::default()
+        };
+        assert!(DPE_PROFILE.supports_extend_tci());
+        assert_eq!(
+            measurements.supports_extend_tci,
+            DPE_PROFILE.supports_extend_tci()
+        );
+        assert_eq!(fwid_count(&measurements), 2);
+
+        let measurements = MeasurementData {
+            supports_extend_tci: false,
+            ..measurements
+        };
+        assert_eq!(fwid_count(&measurements), 1);
+    }
+
+    #[test]
+    fn test_incremental_multi_tcb_info() {
+        let mut nodes = [TciNodeData::new(), TciNodeData::new(), TciNodeData::new()];
+        for (i, node) in nodes.iter_mut().enumerate() {
+            node.tci_type = i as u32;
+            node.tci_current = TciMeasurement([i as u8; DPE_PROFILE.get_hash_size()]);
+            node.tci_cumulative = TciMeasurement([0x10 + i as u8; DPE_PROFILE.get_hash_size()]);
+            node.locality = 0x1000 + i as u32;
+        }
+        let measurements = MeasurementData {
+            tci_nodes: &nodes,
+            supports_extend_tci: true,
+            ..Default::default()
+        };
+
+        let mut expected = [0u8; 1024];
+        let mut w = CertWriter::new(&mut expected, true);
+        let expected_len = w.encode_multi_tcb_info(&measurements).unwrap();
+
+        let mut actual = [0u8; 1024];
+        let mut w = CertWriter::new(&mut actual, true);
+        // Encoding must not start at the beginning of the buffer
+        let prefix_len = w.encode_integer(7).unwrap();
+        w.begin_multi_tcb_info().unwrap();
+        for node in nodes.iter() {
+            w.append_tcb_info(node, measurements.supports_extend_tci, None, &[], None)
+                .unwrap();
+        }
+        let actual_len = w.finish_multi_tcb_info().unwrap();
+
+        assert_eq!(actual_len, expected_len);
+        assert_eq!(
+            &actual[prefix_len..prefix_len + actual_len],
+            &expected[..expected_len]
+        );
+
+        // The header must fit in front of the TcbInfos
+        let mut small = vec![0u8; expected_len - 1];
+        let mut w = CertWriter::new(&mut small, true);
+        w.begin_multi_tcb_info().unwrap();
+        for node in nodes.iter() {
+            w.append_tcb_info(node, measurements.supports_extend_tci, None, &[], None)
+                .unwrap();
+        }
+        for _ in 0..2 {
+            assert_eq!(
+                w.finish_multi_tcb_info(),
+                Err(DpeErrorCode::BufferTooSmall(expected_len as u32))
+            );
+        }
+
+        // Appending without an in-progress MultiTcbInfo fails
+        let mut w = CertWriter::new(&mut actual, true);
+        assert_eq!(
+            w.append_tcb_info(&nodes[0], true, None, &[], None),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+        assert_eq!(
+            w.finish_multi_tcb_info(),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_encode_name_from_der() {
+        let mut issuer_name = [0u8; MAX_CHUNK_SIZE];
+        let issuer_len = DefaultPlatform.get_issuer_name(&mut issuer_name).unwrap();
+        let issuer_name = &issuer_name[..issuer_len];
+
+        let mut buf = [0u8; MAX_CHUNK_SIZE];
+        let mut w = CertWriter::new(&mut buf, true);
+        let bytes_written = w.encode_name_from_der(issuer_name).unwrap();
+        assert_eq!(&buf[..bytes_written], issuer_name);
+
+        let mut w = CertWriter::new(&mut buf, true);
+        assert_eq!(
+            w.encode_name_from_der(&issuer_name[1..]),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_validate_name_der() {
+        let issuer_der = encode_test_issuer();
+        assert!(CertWriter::validate_name_der(&issuer_der).is_ok());
+
+        // Truncated
+        assert_eq!(
+            CertWriter::validate_name_der(&issuer_der[..issuer_der.len() - 1]),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+        assert_eq!(
+            CertWriter::validate_name_der(&issuer_der[..1]),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+        assert_eq!(
+            CertWriter::validate_name_der(&[]),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+
+        // Trailing bytes
+        let mut trailing = issuer_der.clone();
+        trailing.push(0);
+        assert_eq!(
+            CertWriter::validate_name_der(&trailing),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+
+        // Not a SEQUENCE
+        let mut not_seq = issuer_der.clone();
+        not_seq[0] = CertWriter::SET_OF_TAG;
+        assert_eq!(
+            CertWriter::validate_name_der(&not_seq),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+
+        // The TBS encoder rejects a bad issuer before writing anything
+        let mut cert = [0u8; 1024];
+        let mut w = CertWriter::new(&mut cert, true);
+        let node = TciNodeData::new();
+        let measurements = MeasurementData {
+            tci_nodes: &[node],
+            ..Default::default()
+        };
+        assert_eq!(
+            w.encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &issuer_der[..issuer_der.len() - 1],
+                &TEST_SUBJECT_NAME,
+                &EcdsaPub::zeroed(DPE_PROFILE.alg_len()),
+                &measurements,
+            ),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+    }
+
+    /// Small deterministic xorshift generator so the fuzz tests below are
+    /// reproducible
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    /// Encode a random well-formed DER element, independently of CertWriter
+    fn random_der_element(rng: &mut XorShift, depth: usize) -> Vec<u8> {
+        const PRIMITIVE_TAGS: [u8; 6] = [0x02, 0x04, 0x05, 0x06, 0x0c, 0x80];
+        const CONSTRUCTED_TAGS: [u8; 3] = [0x30, 0x31, 0xa0];
+
+        let (tag, contents) = if depth == 0 || rng.below(2) == 0 {
+            let len = match rng.below(4) {
+                0 => 0,
+                1 => rng.below(300),
+                _ => rng.below(16),
+            };
+            let contents: Vec<u8> = (0..len).map(|_| rng.next() as u8).collect();
+            (PRIMITIVE_TAGS[rng.below(PRIMITIVE_TAGS.len())], contents)
+        } else {
+            let contents = (0..rng.below(4))
+                .flat_map(|_| random_der_element(rng, depth - 1))
+                .collect();
+            (
+                CONSTRUCTED_TAGS[rng.below(CONSTRUCTED_TAGS.len())],
+                contents,
+            )
+        };
+
+        let len: Vec<u8> = match contents.len() {
+            len @ 0..=127 => vec![len as u8],
+            len @ 128..=255 => vec![0x81, len as u8],
+            len => vec![0x82, (len >> 8) as u8, len as u8],
+        };
+        [&[tag], len.as_slice(), contents.as_slice()].concat()
+    }
+
+    #[test]
+    fn test_validate_der_well_formed_single_element() {
+        let validate = CertWriter::validate_der_well_formed_single_element;
+
+        for der in [
+            &[0x05, 0x00][..],
+            &[0x30, 0x00],
+            &[0x30, 0x05, 0x31, 0x03, 0x02, 0x01, 0x07],
+            &[&[0x04, 0x81, 0x80][..], &[0; 128]].concat(),
+            &[&[0x04, 0x82, 0x01, 0x00][..], &[0; 256]].concat(),
+        ] {
+            assert_eq!(validate(der), Ok(()), "{der:02x?}");
+        }
+
+        for der in [
+            // Empty, or a truncated header or contents
+            &[][..],
+            &[0x30],
+            &[0x30, 0x81],
+            &[0x30, 0x02, 0x05],
+            // Trailing bytes, or two elements
+            &[0x05, 0x00, 0x00],
+            &[0x05, 0x00, 0x05, 0x00],
+            // Non-minimal and indefinite lengths
+            &[0x04, 0x81, 0x01, 0x00],
+            &[&[0x04, 0x82, 0x00, 0x80][..], &[0; 128]].concat(),
+            &[0x30, 0x80, 0x00, 0x00],
+            // High-tag-number form
+            &[0x1f, 0x01, 0x00],
+            // A nested element that overruns its parent or is malformed
+            &[0x30, 0x04, 0x04, 0x03, 0x00, 0x00],
+            &[0x30, 0x04, 0x04, 0x81, 0x01, 0x00],
+        ] {
+            assert_eq!(
+                validate(der),
+                Err(DpeErrorCode::InvalidArgument),
+                "{der:02x?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_der_fuzz() {
+        let validate = CertWriter::validate_der_well_formed_single_element;
+        let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+
+        // Well-formed elements are accepted, and every truncation or extension
+        // of them is rejected
+        for _ in 0..500 {
+            let der = random_der_element(&mut rng, 4);
+            assert_eq!(validate(&der), Ok(()), "{der:02x?}");
+            for len in 0..der.len() {
+                assert!(validate(&der[..len]).is_err(), "{der:02x?}");
+            }
+            let mut extended = der.clone();
+            extended.push(rng.next() as u8);
+            assert!(validate(&extended).is_err(), "{extended:02x?}");
+        }
+
+        // Random and randomly corrupted bytes never panic, and anything that
+        // is accepted starts with a minimal header spanning the whole input
+        for _ in 0..20000 {
+            let mut der = if rng.below(2) == 0 {
+                random_der_element(&mut rng, 3)
+            } else {
+                (0..rng.below(32)).map(|_| rng.next() as u8).collect()
+            };
+            if !der.is_empty() {
+                let i = rng.below(der.len());
+                der[i] = rng.next() as u8;
+            }
+
+            if validate(&der).is_ok() {
+                let (_, header_len, size) = CertWriter::decode_header(&der).unwrap();
+                assert_eq!(header_len + size, der.len(), "{der:02x?}");
+                assert_eq!(
+                    header_len,
+                    1 + CertWriter::get_size_width(size).unwrap(),
+                    "{der:02x?}"
+                );
+            }
+        }
+
+        // Every splice point rejects what the shared check rejects
+        let malformed = [0x30, 0x03, 0x05, 0x00];
+        assert_eq!(
+            CertWriter::validate_name_der(&malformed),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+        assert_eq!(
+            CertWriter::validate_spki_der(&malformed),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+        assert_eq!(
+            CertWriter::validate_issuer_serial_der(&malformed),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+        let raw_extensions = [&malformed[..]];
+        let measurements = MeasurementData {
+            raw_extensions: &raw_extensions,
+            ..Default::default()
+        };
+        assert_eq!(
+            CertWriter::get_raw_extensions_size(&measurements),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+        let mut buf = [0u8; 16];
+        let mut w = CertWriter::new(&mut buf, true);
+        assert_eq!(
+            w.append_certificate(&malformed),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+        assert_eq!(
+            CertWriter::validate_tbs_sig_alg(&malformed, CertWriter::ECDSA_OID),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_subject_pubkey_info_padding() {
+        // A coordinate whose big-endian form has a leading zero byte
+        let mut x = [0xAA; ECC_INT_SIZE];
+        x[0] = 0;
+        let pubkey = EcdsaPub {
+            x: CryptoBuf::new(&x[1..]).unwrap(),
+            y: CryptoBuf::new(&[0xBB; ECC_INT_SIZE]).unwrap(),
+        };
+
+        let mut cert = [0u8; 256];
+        let mut w = CertWriter::new(&mut cert, true);
+        let bytes_written = w
+            .encode_ecdsa_subject_pubkey_info(&pubkey, PointFormat::Uncompressed)
+            .unwrap();
+        assert_eq!(
+            bytes_written,
+            CertWriter::get_ecdsa_subject_pubkey_info_size(
+                &pubkey,
+                PointFormat::Uncompressed,
+                true
+            )
+            .unwrap()
+        );
+
+        let (_, spki) = SubjectPublicKeyInfo::from_der(&cert[..bytes_written]).unwrap();
+        let point = spki.subject_public_key.data;
+        // 97 bytes for P384, 65 bytes for P256
+        assert_eq!(point.len(), 1 + 2 * ECC_INT_SIZE);
+        assert_eq!(point[0], 0x04);
+        assert_eq!(&point[1..1 + ECC_INT_SIZE], x);
+        assert_eq!(&point[1 + ECC_INT_SIZE..], [0xBB; ECC_INT_SIZE]);
+    }
+
+    #[test]
+    fn test_validate_oid() {
+        // ecdsa-with-SHA256 1.2.840.10045.4.3.2
+        let valid = [0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x04, 0x03, 0x02];
+        assert_eq!(CertWriter::validate_oid(&valid), Ok(()));
+
+        let mut buf = [0u8; 16];
+        let mut w = CertWriter::new(&mut buf, true);
+        assert_eq!(w.encode_oid(&valid), Ok(2 + valid.len()));
+
+        // A subidentifier padded with a leading 0x80 byte
+        let leading_continuation = [0x2A, 0x80, 0x86, 0x48];
+        // The last subidentifier is cut off mid-encoding
+        let truncated = &valid[..2];
+        for oid in [&leading_continuation[..], truncated, &[]] {
+            assert_eq!(
+                CertWriter::validate_oid(oid),
+                Err(DpeErrorCode::InvalidArgument)
+            );
+            let mut w = CertWriter::new(&mut buf, true);
+            assert_eq!(w.encode_oid(oid), Err(DpeErrorCode::InvalidArgument));
+        }
+    }
+
+    #[test]
+    fn test_self_signed_tbs() {
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let measurements = MeasurementData {
+            label: &[0; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[TciNodeData::new()],
+            is_ca: true,
+            ..Default::default()
+        };
+
+        let mut tbs = [0u8; 1024];
+        let mut w = CertWriter::new(&mut tbs, true);
+        let bytes_written = w
+            .encode_self_signed_ecdsa_tbs(TEST_SERIAL, &TEST_SUBJECT_NAME, &test_pub, &measurements)
+            .unwrap();
+        let mut parser = TbsCertificateParser::new().with_deep_parse_extensions(false);
+        let parsed = parser.parse(&tbs[..bytes_written]).unwrap().1;
+        assert_eq!(parsed.issuer().as_raw(), parsed.subject().as_raw());
+
+        // A separately supplied issuer must match the subject
+        let mut subject_der = [0u8; 256];
+        let subject_len = TEST_SUBJECT_NAME.to_der(&mut subject_der).unwrap();
+        let self_signed = MeasurementData {
+            self_signed: true,
+            ..measurements
+        };
+        let mut w = CertWriter::new(&mut tbs, true);
+        assert_eq!(
+            w.encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &subject_der[..subject_len],
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &self_signed,
+            ),
+            Ok(bytes_written)
+        );
+
+        let issuer_der = encode_test_issuer();
+        for single_pass in [false, true] {
+            let mut w = if single_pass {
+                CertWriter::new_single_pass(&mut tbs, true)
+            } else {
+                CertWriter::new(&mut tbs, true)
+            };
+            assert_eq!(
+                w.encode_ecdsa_tbs(
+                    TEST_SERIAL,
+                    &issuer_der,
+                    &TEST_SUBJECT_NAME,
+                    &test_pub,
+                    &self_signed,
+                ),
+                Err(DpeErrorCode::InvalidArgument)
+            );
+        }
+    }
+
+    #[test]
+    fn test_ecdsa_tbs_record() {
+        let mut crypto = crypto::OpensslCrypto::new();
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let measurements = MeasurementData {
+            label: &[0; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[TciNodeData::new()],
+            ..Default::default()
+        };
+        let md = match DPE_PROFILE {
+            DpeProfile::P256Sha256 => openssl::hash::MessageDigest::sha256(),
+            DpeProfile::P384Sha384 => openssl::hash::MessageDigest::sha384(),
+        };
+
+        // As given, with leading zeros, and needing a sign byte
+        let mut sign_byte = [0x80; MAX_SERIAL_NUMBER_SIZE - 1];
+        sign_byte[1] = 0x01;
+        for serial in [TEST_SERIAL, &[0x00, 0x00, 0x12, 0x34], &sign_byte] {
+            let mut tbs = [0u8; 1024];
+            let mut w = CertWriter::new(&mut tbs, true);
+            let record = w
+                .encode_ecdsa_tbs_record(
+                    &mut crypto,
+                    serial,
+                    &issuer_der,
+                    &TEST_SUBJECT_NAME,
+                    &test_pub,
+                    &measurements,
+                )
+                .unwrap();
+            assert_eq!(record.tbs_len, w.written().len());
+            let tbs = &tbs[..record.tbs_len];
+
+            let mut parser = TbsCertificateParser::new().with_deep_parse_extensions(false);
+            let parsed = parser.parse(tbs).unwrap().1;
+            assert_eq!(record.serial(), parsed.raw_serial());
+            assert_eq!(
+                record.tbs_digest.bytes(),
+                &openssl::hash::hash(md, tbs).unwrap()[..]
+            );
+        }
+    }
+
+    #[test]
+    fn test_ecdsa_certificate_size_bound() {
+        let test_sig = EcdsaSig {
+            r: CryptoBuf::new(&[0xCC; ECC_INT_SIZE]).unwrap(),
+            s: CryptoBuf::new(&[0xDD; ECC_INT_SIZE]).unwrap(),
+        };
+
+        // TBS lengths that cannot be encoded or overflow the sum
+        for tbs_len in [usize::MAX / 2, usize::MAX - 1] {
+            assert_eq!(
+                CertWriter::get_ecdsa_certificate_size(tbs_len, &test_sig, /*tagged=*/ true),
+                Err(DpeErrorCode::InternalError)
+            );
+        }
+
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let measurements = MeasurementData {
+            label: &[0; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[TciNodeData::new()],
+            ..Default::default()
+        };
+        let mut tbs = [0u8; 1024];
+        let mut w = CertWriter::new(&mut tbs, true);
+        let tbs_len = w
+            .encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+            )
+            .unwrap();
+        let tbs = &tbs[..tbs_len];
+        let cert_size =
+            CertWriter::get_ecdsa_certificate_size(tbs_len, &test_sig, /*tagged=*/ true).unwrap();
+
+        // A buffer one byte short fails before anything is written
+        for single_pass in [false, true] {
+            let mut cert = vec![0u8; cert_size - 1];
+            let mut w = if single_pass {
+                CertWriter::new_single_pass(&mut cert, true)
+            } else {
+                CertWriter::new(&mut cert, true)
+            };
+            assert_eq!(
+                w.encode_ecdsa_certificate(tbs, &test_sig),
+                Err(DpeErrorCode::BufferTooSmall(cert_size as u32))
+            );
+            assert!(w.written().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_serial_number_size() {
+        let mut buf = [0u8; 4096];
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let measurements = MeasurementData {
+            label: &[0xCC; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[TciNodeData::new()],
+            ..Default::default()
+        };
+
+        let mut padded = [0x1F; MAX_SERIAL_NUMBER_SIZE + 1];
+        padded[0] = 0;
+        // Leading zeros do not count towards the limit
+        for serial in [&[0x1F; MAX_SERIAL_NUMBER_SIZE][..], &padded] {
+            let mut w = CertWriter::new(&mut buf, true);
+            w.encode_ecdsa_tbs(
+                serial,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+            )
+            .unwrap();
+            let mut w = CertWriter::new(&mut buf, true);
+            w.encode_issuer_and_serial_number(serial, &issuer_der)
+                .unwrap();
+        }
+
+        // Too long, needs a sign byte, or empty
+        for serial in [
+            &[0x1F; MAX_SERIAL_NUMBER_SIZE + 1][..],
+            &[0x80; MAX_SERIAL_NUMBER_SIZE],
+            &[],
+        ] {
+            // The single-pass writer never computes the TBS size up front
+            for single_pass in [false, true] {
+                let mut w = if single_pass {
+                    CertWriter::new_single_pass(&mut buf, true)
+                } else {
+                    CertWriter::new(&mut buf, true)
+                };
+                assert_eq!(
+                    w.encode_ecdsa_tbs(
+                        serial,
+                        &issuer_der,
+                        &TEST_SUBJECT_NAME,
+                        &test_pub,
+                        &measurements,
+                    ),
+                    Err(DpeErrorCode::InvalidArgument)
+                );
+            }
+            let mut w = CertWriter::new(&mut buf, true);
+            assert_eq!(
+                w.encode_issuer_and_serial_number(serial, &issuer_der),
+                Err(DpeErrorCode::InvalidArgument)
+            );
+        }
+    }
+
+    /// Split a concatenation of DER certificates
+    fn split_cert_chain(mut chain: &[u8]) -> Vec<&[u8]> {
+        let mut certs = vec![];
+        while !chain.is_empty() {
+            let (_, _, rest) = CertWriter::split_element(chain).unwrap();
+            let (cert, rest) = chain.split_at(chain.len() - rest.len());
+            certs.push(cert);
+            chain = rest;
+        }
+        certs
+    }
+
+    #[test]
+    fn test_append_certificate() {
+        let mut leaf = [0u8; 1024];
+        let (leaf_len, _) = build_test_cert(/*is_ca=*/ false, &mut leaf);
+        let leaf = &leaf[..leaf_len];
+
+        let mut chain = [0u8; 4096];
+        let mut w = CertWriter::new(&mut chain, true);
+        let mut bytes_written = w.append_certificate(leaf).unwrap();
+        bytes_written += w.append_certificate(TEST_CERT_CHAIN).unwrap();
+        assert_eq!(bytes_written, leaf.len() + TEST_CERT_CHAIN.len());
+
+        let certs = split_cert_chain(&chain[..bytes_written]);
+        assert_eq!(certs, [leaf, TEST_CERT_CHAIN]);
+        for cert in certs {
+            assert!(openssl::x509::X509::from_der(cert).is_ok());
+        }
+
+        // Truncated and trailing data are rejected
+        let mut w = CertWriter::new(&mut chain, true);
+        assert_eq!(
+            w.append_certificate(&leaf[..leaf.len() - 1]),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+        let mut trailing = leaf.to_vec();
+        trailing.push(0);
+        assert_eq!(
+            w.append_certificate(&trailing),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+        assert!(w.written().is_empty());
+    }
+
+    #[test]
+    fn test_compressed_subject_pubkey_info() {
+        let (key_der, nid) = match DPE_PROFILE {
+            DpeProfile::P256Sha256 => (
+                &include_bytes!("../../platform/src/test_data/key_256.der")[..],
+                openssl::nid::Nid::X9_62_PRIME256V1,
+            ),
+            DpeProfile::P384Sha384 => (
+                &include_bytes!("../../platform/src/test_data/key_384.der")[..],
+                openssl::nid::Nid::SECP384R1,
+            ),
+        };
+        let key = openssl::ec::EcKey::private_key_from_der(key_der).unwrap();
+        let group = openssl::ec::EcGroup::from_curve_name(nid).unwrap();
+        let mut ctx = openssl::bn::BigNumContext::new().unwrap();
+        let mut x = openssl::bn::BigNum::new().unwrap();
+        let mut y = openssl::bn::BigNum::new().unwrap();
+        key.public_key()
+            .affine_coordinates(&group, &mut x, &mut y, &mut ctx)
+            .unwrap();
+        let pubkey = EcdsaPub::from_coords(
+            &x.to_vec_padded(ECC_INT_SIZE as i32).unwrap(),
+            &y.to_vec_padded(ECC_INT_SIZE as i32).unwrap(),
+            DPE_PROFILE.alg_len(),
+        )
+        .unwrap();
+
+        let mut cert = [0u8; 256];
+        let mut w = CertWriter::new(&mut cert, true);
+        let bytes_written = w
+            .encode_ecdsa_subject_pubkey_info(&pubkey, PointFormat::Compressed)
+            .unwrap();
+        assert_eq!(
+            bytes_written,
+            CertWriter::get_ecdsa_subject_pubkey_info_size(&pubkey, PointFormat::Compressed, true)
+                .unwrap()
+        );
+
+        let (_, spki) = SubjectPublicKeyInfo::from_der(&cert[..bytes_written]).unwrap();
+        let point = spki.subject_public_key.data;
+        assert_eq!(point.len(), 1 + ECC_INT_SIZE);
+        let expected_prefix = if y.is_bit_set(0) { 0x03 } else { 0x02 };
+        assert_eq!(point[0], expected_prefix);
+
+        // Decompressing the point recovers the original y
+        let point = openssl::ec::EcPoint::from_bytes(&group, &point, &mut ctx).unwrap();
+        let mut decompressed_x = openssl::bn::BigNum::new().unwrap();
+        let mut decompressed_y = openssl::bn::BigNum::new().unwrap();
+        point
+            .affine_coordinates(&group, &mut decompressed_x, &mut decompressed_y, &mut ctx)
+            .unwrap();
+        assert_eq!(decompressed_x, x);
+        assert_eq!(decompressed_y, y);
+
+        // OpenSSL accepts the whole SubjectPublicKeyInfo
+        let parsed = openssl::pkey::PKey::public_key_from_der(&cert[..bytes_written]).unwrap();
+        let expected = openssl::pkey::PKey::from_ec_key(key).unwrap();
+        assert!(parsed.public_eq(&expected));
+    }
+
+    #[test]
+    fn test_policy_extensions() {
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let node = TciNodeData::new();
+
+        for (constraints, inhibit_any_policy) in [
+            (
+                PolicyConstraints {
+                    require_explicit_policy: Some(0),
+                    inhibit_policy_mapping: Some(300),
+                },
+                0,
+            ),
+            (
+                PolicyConstraints {
+                    require_explicit_policy: None,
+                    inhibit_policy_mapping: Some(u32::MAX),
+                },
+                128,
+            ),
+            (
+                PolicyConstraints {
+                    require_explicit_policy: Some(2),
+                    inhibit_policy_mapping: None,
+                },
+                u32::MAX,
+            ),
+        ] {
+            let measurements = MeasurementData {
+                label: &[0; DPE_PROFILE.get_hash_size()],
+                tci_nodes: &[node],
+                is_ca: true,
+                policy_constraints: Some(constraints),
+                inhibit_any_policy: Some(inhibit_any_policy),
+                ..Default::default()
+            };
+
+            let mut cert = [0u8; 1024];
+            let mut w = CertWriter::new(&mut cert, true);
+            let bytes_written = w
+                .encode_ecdsa_tbs(
+                    TEST_SERIAL,
+                    &issuer_der,
+                    &TEST_SUBJECT_NAME,
+                    &test_pub,
+                    &measurements,
+                )
+                .unwrap();
+
+            let mut parser = TbsCertificateParser::new().with_deep_parse_extensions(true);
+            let tbs = parser.parse(&cert[..bytes_written]).unwrap().1;
+
+            let ext = tbs.policy_constraints().unwrap().unwrap();
+            assert!(ext.critical);
+            assert_eq!(
+                ext.value.require_explicit_policy,
+                constraints.require_explicit_policy
+            );
+            assert_eq!(
+                ext.value.inhibit_policy_mapping,
+                constraints.inhibit_policy_mapping
+            );
+
+            let ext = tbs.inhibit_anypolicy().unwrap().unwrap();
+            assert!(ext.critical);
+            assert_eq!(ext.value.skip_certs, inhibit_any_policy);
+
+            // Not included for leaf certs
+            let measurements = MeasurementData {
+                is_ca: false,
+                ..measurements
+            };
+            let mut w = CertWriter::new(&mut cert, true);
+            let bytes_written = w
+                .encode_ecdsa_tbs(
+                    TEST_SERIAL,
+                    &issuer_der,
+                    &TEST_SUBJECT_NAME,
+                    &test_pub,
+                    &measurements,
+                )
+                .unwrap();
+            let tbs = parser.parse(&cert[..bytes_written]).unwrap().1;
+            assert!(tbs.policy_constraints().unwrap().is_none());
+            assert!(tbs.inhibit_anypolicy().unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn test_holder_reference() {
+        const SERIAL: [u8; 20] = [0x3C; 20];
+        let issuer_der = encode_test_issuer();
+
+        let mut issuer_serial = [0u8; 256];
+        let mut w = CertWriter::new(&mut issuer_serial, true);
+        let issuer_serial_len = w
+            .encode_issuer_and_serial_number(&SERIAL, &issuer_der)
+            .unwrap();
+        let issuer_serial = &issuer_serial[..issuer_serial_len];
+
+        let mut ext = [0u8; 256];
+        let mut w = CertWriter::new(&mut ext, true);
+        let ext_len = w.encode_holder_reference(issuer_serial).unwrap();
+        assert_eq!(
+            ext_len,
+            CertWriter::get_holder_reference_size(issuer_serial, /*tagged=*/ true).unwrap()
+        );
+
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let node = TciNodeData::new();
+        let raw_extensions: [&[u8]; 1] = [&ext[..ext_len]];
+        let measurements = MeasurementData {
+            label: &[0; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[node],
+            raw_extensions: &raw_extensions,
+            ..Default::default()
+        };
+
+        let mut cert = [0u8; 1024];
+        let mut w = CertWriter::new(&mut cert, true);
+        let bytes_written = w
+            .encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+            )
+            .unwrap();
+
+        let mut parser = TbsCertificateParser::new().with_deep_parse_extensions(false);
+        let tbs = parser.parse(&cert[..bytes_written]).unwrap().1;
+        let holder_reference = tbs
+            .extensions()
+            .iter()
+            .find(|ext| ext.oid.as_bytes() == CertWriter::HOLDER_REFERENCE_OID)
+            .unwrap();
+        assert!(!holder_reference.critical);
+
+        let parsed = cms::cert::IssuerAndSerialNumber::from_der(holder_reference.value).unwrap();
+        assert_eq!(der::Encode::to_der(&parsed.issuer).unwrap(), issuer_der);
+        assert_eq!(parsed.serial_number.as_bytes(), SERIAL);
+
+        // The reference must be a single IssuerAndSerialNumber
+        let mut w = CertWriter::new(&mut ext, true);
+        for bad in [
+            &issuer_serial[1..],
+            &issuer_der,
+            &[issuer_serial, &[0x05, 0x00]].concat(),
+        ] {
+            assert_eq!(
+                w.encode_holder_reference(bad),
+                Err(DpeErrorCode::InvalidArgument)
+            );
+        }
+    }
+
+    #[test]
+    fn test_alt_signature() {
+        let (key_der, md) = match DPE_PROFILE {
+            DpeProfile::P256Sha256 => (
+                &include_bytes!("../../platform/src/test_data/key_256.der")[..],
+                openssl::hash::MessageDigest::sha256(),
+            ),
+            DpeProfile::P384Sha384 => (
+                &include_bytes!("../../platform/src/test_data/key_384.der")[..],
+                openssl::hash::MessageDigest::sha384(),
+            ),
+        };
+        let alt_key = openssl::ec::EcKey::private_key_from_der(key_der).unwrap();
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let node = TciNodeData::new();
+
+        let mut alg_ext = [0u8; 64];
+        let mut w = CertWriter::new(&mut alg_ext, true);
+        let alg_ext_len = w.encode_alt_signature_algorithm().unwrap();
+        assert_eq!(
+            alg_ext_len,
+            CertWriter::get_alt_signature_algorithm_size(/*tagged=*/ true).unwrap()
+        );
+        let alg_ext = &alg_ext[..alg_ext_len];
+
+        // The alternative signature covers the preTBSCertificate
+        let pre_extensions: [&[u8]; 1] = [alg_ext];
+        let measurements = MeasurementData {
+            label: &[0; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[node],
+            raw_extensions: &pre_extensions,
+            ..Default::default()
+        };
+        let mut pre_tbs = [0u8; 1024];
+        let mut w = CertWriter::new(&mut pre_tbs, true);
+        let pre_tbs_len = w
+            .encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+            )
+            .unwrap();
+        let digest = openssl::hash::hash(md, &pre_tbs[..pre_tbs_len]).unwrap();
+        let alt_sig = openssl::ecdsa::EcdsaSig::sign(&digest, &alt_key).unwrap();
+        let sig = EcdsaSig {
+            r: CryptoBuf::new(&alt_sig.r().to_vec()).unwrap(),
+            s: CryptoBuf::new(&alt_sig.s().to_vec()).unwrap(),
+        };
+
+        let mut value_ext = [0u8; 256];
+        let mut w = CertWriter::new(&mut value_ext, true);
+        let value_ext_len = w.encode_alt_signature_value(&sig).unwrap();
+        assert_eq!(
+            value_ext_len,
+            CertWriter::get_alt_signature_value_size(&sig, /*tagged=*/ true).unwrap()
+        );
+
+        let extensions: [&[u8]; 2] = [alg_ext, &value_ext[..value_ext_len]];
+        let measurements = MeasurementData {
+            raw_extensions: &extensions,
+            ..measurements
+        };
+        let mut tbs = [0u8; 1024];
+        let mut w = CertWriter::new(&mut tbs, true);
+        let tbs_len = w
+            .encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+            )
+            .unwrap();
+
+        let primary_sig = EcdsaSig {
+            r: CryptoBuf::new(&[0xCC; ECC_INT_SIZE]).unwrap(),
+            s: CryptoBuf::new(&[0xDD; ECC_INT_SIZE]).unwrap(),
+        };
+        let mut cert = [0u8; 2048];
+        let mut w = CertWriter::new(&mut cert, true);
+        let cert_len = w
+            .encode_ecdsa_certificate(&tbs[..tbs_len], &primary_sig)
+            .unwrap();
+
+        let (_, parsed_cert) = X509Certificate::from_der(&cert[..cert_len]).unwrap();
+
+        // The primary signature is untouched
+        let primary =
+            openssl::ecdsa::EcdsaSig::from_der(&parsed_cert.signature_value.data).unwrap();
+        assert_eq!(primary.r().to_vec(), [0xCC; ECC_INT_SIZE]);
+        assert_eq!(primary.s().to_vec(), [0xDD; ECC_INT_SIZE]);
+
+        let find_ext = |oid: &[u8]| {
+            parsed_cert
+                .extensions()
+                .iter()
+                .find(|ext| ext.oid.as_bytes() == oid)
+                .unwrap()
+        };
+
+        let alg = find_ext(CertWriter::ALT_SIGNATURE_ALGORITHM_OID);
+        assert!(!alg.critical);
+        let (_, alg_id) = x509_parser::x509::AlgorithmIdentifier::from_der(alg.value).unwrap();
+        assert_eq!(alg_id.algorithm.as_bytes(), CertWriter::ECDSA_OID);
+
+        // The alternative signature verifies over the preTBSCertificate
+        let value = find_ext(CertWriter::ALT_SIGNATURE_VALUE_OID);
+        assert!(!value.critical);
+        let bit_string = der::asn1::BitString::from_der(value.value).unwrap();
+        let parsed_sig = openssl::ecdsa::EcdsaSig::from_der(bit_string.raw_bytes()).unwrap();
+        assert!(parsed_sig.verify(&digest, &alt_key).unwrap());
+    }
+
+    #[test]
+    fn test_vendor_oid_arc_extension() {
+        let mut arc = [0u8; MAX_VENDOR_OID_ARC_SIZE];
+        let arc_len = DefaultPlatform.get_vendor_oid_arc(&mut arc).unwrap();
+        let arc = &arc[..arc_len];
+
+        // A vendor extension with OID <arc>.1 and a NULL value
+        let mut oid = arc.to_vec();
+        oid.push(0x01);
+        let mut ext = vec![0x06, oid.len() as u8];
+        ext.extend_from_slice(&oid);
+        ext.extend_from_slice(&[0x04, 0x02, 0x05, 0x00]);
+        ext.splice(0..0, [CertWriter::SEQUENCE_TAG, ext.len() as u8]);
+
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let raw_extensions: [&[u8]; 1] = [&ext];
+        let measurements = MeasurementData {
+            label: &[0; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[TciNodeData::new()],
+            raw_extensions: &raw_extensions,
+            ..Default::default()
+        };
+
+        let mut cert = [0u8; 1024];
+        let mut w = CertWriter::new(&mut cert, true);
+        let bytes_written = w
+            .encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+            )
+            .unwrap();
+
+        let mut parser = TbsCertificateParser::new().with_deep_parse_extensions(false);
+        let tbs = parser.parse(&cert[..bytes_written]).unwrap().1;
+        let vendor_exts: Vec<String> = tbs
+            .extensions()
+            .iter()
+            .filter(|ext| ext.oid.as_bytes().starts_with(arc))
+            .map(|ext| ext.oid.to_id_string())
+            .collect();
+        assert_eq!(vendor_exts, ["1.3.6.1.4.1.32473.1"]);
+    }
+
+    #[test]
+    fn test_eat_claims_extension() {
+        // {10: h'0102030405060708'}, an EAT with just an eat_nonce claim
+        let cbor = [
+            0xA1, 0x0A, 0x48, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+        ];
+        let mut ext = [0u8; 64];
+        let mut w = CertWriter::new(&mut ext, true);
+        let ext_len = w.encode_eat_claims_extension(&cbor).unwrap();
+        assert_eq!(
+            ext_len,
+            CertWriter::get_eat_claims_extension_size(&cbor, /*tagged=*/ true).unwrap()
+        );
+
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let raw_extensions: [&[u8]; 1] = [&ext[..ext_len]];
+        let measurements = MeasurementData {
+            label: &[0; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[TciNodeData::new()],
+            raw_extensions: &raw_extensions,
+            ..Default::default()
+        };
+
+        let mut cert = [0u8; 1024];
+        let mut w = CertWriter::new(&mut cert, true);
+        let bytes_written = w
+            .encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+            )
+            .unwrap();
+
+        let mut parser = TbsCertificateParser::new().with_deep_parse_extensions(false);
+        let tbs = parser.parse(&cert[..bytes_written]).unwrap().1;
+        let eat = tbs
+            .get_extension_unique(&oid!(2.25.184124198755465424310751390438906145320))
+            .unwrap()
+            .unwrap();
+        assert!(!eat.critical);
+        assert_eq!(eat.value, cbor);
+        // The DICE extensions are still present
+        assert!(tbs
+            .get_extension_unique(&oid!(2.23.133 .5 .4 .5))
+            .unwrap()
+            .is_some());
+
+        let mut w = CertWriter::new(&mut ext, true);
+        assert_eq!(
+            w.encode_eat_claims_extension(&[]),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_raw_extensions() {
+        // subjectKeyIdentifier extension
+        let mut ski = vec![
+            0x30, 0x1D, 0x06, 0x03, 0x55, 0x1D, 0x0E, 0x04, 0x16, 0x04, 0x14,
+        ];
+        ski.extend_from_slice(&[0x5A; 20]);
+
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let node = TciNodeData::new();
+        let raw_extensions: [&[u8]; 1] = [&ski];
+        let measurements = MeasurementData {
+            label: &[0; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[node],
+            raw_extensions: &raw_extensions,
+            ..Default::default()
+        };
+
+        let mut cert = [0u8; 1024];
+        let mut w = CertWriter::new(&mut cert, true);
+        let bytes_written = w
+            .encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+            )
+            .unwrap();
+
+        let mut parser = TbsCertificateParser::new().with_deep_parse_extensions(true);
+        let tbs = parser.parse(&cert[..bytes_written]).unwrap().1;
+        let oids: Vec<String> = tbs
+            .extensions()
+            .iter()
+            .map(|ext| ext.oid.to_id_string())
+            .collect();
+        assert!(oids.contains(&"2.23.133.5.4.5".to_string()));
+        assert!(oids.contains(&"2.5.29.15".to_string()));
+        match tbs
+            .get_extension_unique(&x509_parser::oid_registry::OID_X509_EXT_SUBJECT_KEY_IDENTIFIER)
+            .unwrap()
+            .unwrap()
+            .parsed_extension()
+        {
+            ParsedExtension::SubjectKeyIdentifier(id) => assert_eq!(id.0, [0x5A; 20]),
+            _ => panic!("unexpected subjectKeyIdentifier"),
+        }
+
+        // Extensions must be DER SEQUENCEs
+        let bad: [&[u8]; 1] = [&ski[1..]];
+        let measurements = MeasurementData {
+            raw_extensions: &bad,
+            ..measurements
+        };
+        for single_pass in [false, true] {
+            let mut w = if single_pass {
+                CertWriter::new_single_pass(&mut cert, true)
+            } else {
+                CertWriter::new(&mut cert, true)
+            };
+            assert_eq!(
+                w.encode_ecdsa_tbs(
+                    TEST_SERIAL,
+                    &issuer_der,
+                    &TEST_SUBJECT_NAME,
+                    &test_pub,
+                    &measurements,
+                ),
+                Err(DpeErrorCode::InvalidArgument)
+            );
+        }
+    }
+
+    #[test]
+    fn test_cms_size() {
+        let issuer_der = encode_test_issuer();
+        let sig = EcdsaSig {
+            r: CryptoBuf::new(&[0xCC; ECC_INT_SIZE]).unwrap(),
+            s: CryptoBuf::new(&[0xDD; ECC_INT_SIZE]).unwrap(),
+        };
+        // Any DER works as the encapsulated content
+        let mut csr = vec![0x04, 0x81, 0xC8];
+        csr.extend_from_slice(&[0xA5; 200]);
+
+        let signer_id = SignerId::IssuerAndSerialNumber {
+            issuer_name: &issuer_der,
+            serial_number: TEST_SERIAL,
+        };
+        let size = CertWriter::cms_size(&csr, &signer_id, &sig, None).unwrap();
+
+        let mut buf = vec![0u8; size];
+        let mut w = CertWriter::new(&mut buf, true);
+        let bytes_written = w.encode_cms(&csr, &signer_id, &sig, None).unwrap();
+        assert_eq!(bytes_written, size);
+        cms::content_info::ContentInfo::from_der(&buf).unwrap();
+    }
+
+    #[test]
+    fn test_cms_digest_alg() {
+        // Sign with a stronger digest than the profile's hash
+        let (digest_alg, md, hash_alg_oid) = match DPE_PROFILE {
+            DpeProfile::P256Sha256 => (
+                HashAlg::Sha384,
+                openssl::hash::MessageDigest::sha384(),
+                "2.16.840.1.101.3.4.2.2",
+            ),
+            DpeProfile::P384Sha384 => (
+                HashAlg::Sha512,
+                openssl::hash::MessageDigest::sha512(),
+                "2.16.840.1.101.3.4.2.3",
+            ),
+        };
+
+        let signer_cert = openssl::x509::X509::from_pem(include_bytes!(
+            "../../platform/src/test_data/cert_256.pem"
+        ))
+        .unwrap();
+        let signer_key = openssl::ec::EcKey::private_key_from_der(include_bytes!(
+            "../../platform/src/test_data/key_256.der"
+        ))
+        .unwrap();
+        let issuer_der = signer_cert.issuer_name().to_der().unwrap();
+        let serial = signer_cert.serial_number().to_bn().unwrap().to_vec();
+
+        let content = [0xA5; 200];
+        let digest = openssl::hash::hash(md, &content).unwrap();
+        let ecdsa_sig = openssl::ecdsa::EcdsaSig::sign(&digest, &signer_key).unwrap();
+        let sig = EcdsaSig {
+            r: CryptoBuf::new(&ecdsa_sig.r().to_vec()).unwrap(),
+            s: CryptoBuf::new(&ecdsa_sig.s().to_vec()).unwrap(),
+        };
+
+        let signer_id = SignerId::IssuerAndSerialNumber {
+            issuer_name: &issuer_der,
+            serial_number: &serial,
+        };
+        let size = CertWriter::cms_size(&content, &signer_id, &sig, Some(digest_alg)).unwrap();
+        let mut buf = vec![0u8; size];
+        let mut w = CertWriter::new(&mut buf, true);
+        let bytes_written = w
+            .encode_cms(&content, &signer_id, &sig, Some(digest_alg))
+            .unwrap();
+        assert_eq!(bytes_written, size);
+
+        // Both digest algorithm fields reflect the choice
+        let content_info = cms::content_info::ContentInfo::from_der(&buf).unwrap();
+        let signed_data = content_info
+            .content
+            .decode_as::<cms::signed_data::SignedData>()
+            .unwrap();
+        let digest_algs: Vec<String> = signed_data
+            .digest_algorithms
+            .iter()
+            .map(|alg| alg.oid.to_string())
+            .collect();
+        assert_eq!(digest_algs, [hash_alg_oid]);
+        let signer_info = signed_data.signer_infos.0.get(0).unwrap();
+        assert_eq!(signer_info.digest_alg.oid.to_string(), hash_alg_oid);
+
+        let pkcs7 = openssl::pkcs7::Pkcs7::from_der(&buf).unwrap();
+        let mut certs = openssl::stack::Stack::new().unwrap();
+        certs.push(signer_cert).unwrap();
+        let store = openssl::x509::store::X509StoreBuilder::new()
+            .unwrap()
+            .build();
+        let mut verified = vec![];
+        pkcs7
+            .verify(
+                &certs,
+                &store,
+                None,
+                Some(&mut verified),
+                openssl::pkcs7::Pkcs7Flags::NOVERIFY,
+            )
+            .unwrap();
+        assert_eq!(verified, content);
+    }
+
+    #[test]
+    fn test_cms_openssl_round_trip() {
+        let (cert_pem, key_der, md) = match DPE_PROFILE {
+            DpeProfile::P256Sha256 => (
+                &include_bytes!("../../platform/src/test_data/cert_256.pem")[..],
+                &include_bytes!("../../platform/src/test_data/key_256.der")[..],
+                openssl::hash::MessageDigest::sha256(),
+            ),
+            DpeProfile::P384Sha384 => (
+                &include_bytes!("../../platform/src/test_data/cert_384.pem")[..],
+                &include_bytes!("../../platform/src/test_data/key_384.der")[..],
+                openssl::hash::MessageDigest::sha384(),
+            ),
+        };
+        let signer_cert = openssl::x509::X509::from_pem(cert_pem).unwrap();
+        let signer_key = openssl::ec::EcKey::private_key_from_der(key_der).unwrap();
+        let issuer_der = signer_cert.issuer_name().to_der().unwrap();
+        let serial = signer_cert.serial_number().to_bn().unwrap();
+        let serial_bytes = serial.to_vec();
+
+        let sign = |data: &[u8]| {
+            let digest = openssl::hash::hash(md, data).unwrap();
+            let sig = openssl::ecdsa::EcdsaSig::sign(&digest, &signer_key).unwrap();
+            EcdsaSig {
+                r: CryptoBuf::new(&sig.r().to_vec()).unwrap(),
+                s: CryptoBuf::new(&sig.s().to_vec()).unwrap(),
+            }
+        };
+
+        // A CSR for the signer's own key
+        let mut ctx = openssl::bn::BigNumContext::new().unwrap();
+        let mut x = openssl::bn::BigNum::new().unwrap();
+        let mut y = openssl::bn::BigNum::new().unwrap();
+        signer_key
+            .public_key()
+            .affine_coordinates(signer_key.group(), &mut x, &mut y, &mut ctx)
+            .unwrap();
+        let pub_key = EcdsaPub::from_coords(
+            &x.to_vec_padded(ECC_INT_SIZE as i32).unwrap(),
+            &y.to_vec_padded(ECC_INT_SIZE as i32).unwrap(),
+            DPE_PROFILE.alg_len(),
+        )
+        .unwrap();
+        let node = TciNodeData::new();
+        let measurements = MeasurementData {
+            label: &[0xCC; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[node],
+            ..Default::default()
+        };
+
+        let mut cri = [0u8; 1024];
+        let mut w = CertWriter::new(&mut cri, true);
+        let cri_len = w
+            .encode_certification_request_info(&pub_key, Some(&TEST_SUBJECT_NAME), &measurements)
+            .unwrap();
+        let mut csr = [0u8; 1024];
+        let mut w = CertWriter::new(&mut csr, true);
+        let csr_len = w
+            .encode_csr(&cri[..cri_len], &sign(&cri[..cri_len]))
+            .unwrap();
+        let csr = &csr[..csr_len];
+
+        let mut cms_buf = [0u8; 2048];
+        let mut w = CertWriter::new(&mut cms_buf, true);
+        let cms_len = w
+            .encode_cms(
+                csr,
+                &SignerId::IssuerAndSerialNumber {
+                    issuer_name: &issuer_der,
+                    serial_number: &serial_bytes,
+                },
+                &sign(csr),
+                None,
+            )
+            .unwrap();
+        let cms_der = &cms_buf[..cms_len];
+
+        // OpenSSL parses the CMS and verifies its signature over the CSR
+        let mut cms = openssl::cms::CmsContentInfo::from_der(cms_der).unwrap();
+        let mut certs = openssl::stack::Stack::new().unwrap();
+        certs.push(signer_cert).unwrap();
+        let store = openssl::x509::store::X509StoreBuilder::new()
+            .unwrap()
+            .build();
+        let mut content = vec![];
+        cms.verify(
+            Some(&certs),
+            Some(&store),
+            None,
+            Some(&mut content),
+            openssl::cms::CMSOptions::NOVERIFY,
+        )
+        .unwrap();
+        assert_eq!(content, csr);
+        let signer_pkey = openssl::pkey::PKey::from_ec_key(signer_key.clone()).unwrap();
+        assert!(openssl::x509::X509Req::from_der(&content)
+            .unwrap()
+            .verify(&signer_pkey)
+            .unwrap());
+
+        let content_info = cms::content_info::ContentInfo::from_der(cms_der).unwrap();
+        let signed_data = content_info
+            .content
+            .decode_as::<cms::signed_data::SignedData>()
+            .unwrap();
+        assert_eq!(
+            signed_data.encap_content_info.econtent_type.to_string(),
+            "1.2.840.113549.1.7.1"
+        );
+        let signer_info = signed_data.signer_infos.0.get(0).unwrap();
+        let cms::signed_data::SignerIdentifier::IssuerAndSerialNumber(issuer_and_serial) =
+            &signer_info.sid
+        else {
+            panic!("unexpected SignerIdentifier");
+        };
+        assert_eq!(
+            der::Encode::to_der(&issuer_and_serial.issuer).unwrap(),
+            issuer_der
+        );
+        assert_eq!(
+            openssl::bn::BigNum::from_slice(issuer_and_serial.serial_number.as_bytes()).unwrap(),
+            serial
+        );
+    }
+
+    #[test]
+    fn test_cms_subject_key_identifier() {
+        let (cert_pem, key_der, md) = match DPE_PROFILE {
+            DpeProfile::P256Sha256 => (
+                &include_bytes!("../../platform/src/test_data/cert_256.pem")[..],
+                &include_bytes!("../../platform/src/test_data/key_256.der")[..],
+                openssl::hash::MessageDigest::sha256(),
+            ),
+            DpeProfile::P384Sha384 => (
+                &include_bytes!("../../platform/src/test_data/cert_384.pem")[..],
+                &include_bytes!("../../platform/src/test_data/key_384.der")[..],
+                openssl::hash::MessageDigest::sha384(),
+            ),
+        };
+        let signer_cert = openssl::x509::X509::from_pem(cert_pem).unwrap();
+        let signer_key = openssl::ec::EcKey::private_key_from_der(key_der).unwrap();
+        let key_id = signer_cert.subject_key_id().unwrap().as_slice().to_vec();
+
+        let content = [0x5A; 100];
+        let digest = openssl::hash::hash(md, &content).unwrap();
+        let ecdsa_sig = openssl::ecdsa::EcdsaSig::sign(&digest, &signer_key).unwrap();
+        let sig = EcdsaSig {
+            r: CryptoBuf::new(&ecdsa_sig.r().to_vec()).unwrap(),
+            s: CryptoBuf::new(&ecdsa_sig.s().to_vec()).unwrap(),
+        };
+
+        let signer_id = SignerId::SubjectKeyIdentifier(&key_id);
+        let size = CertWriter::cms_size(&content, &signer_id, &sig, None).unwrap();
+        let mut buf = vec![0u8; size];
+        let mut w = CertWriter::new(&mut buf, true);
+        assert_eq!(
+            w.encode_cms(&content, &signer_id, &sig, None).unwrap(),
+            size
+        );
+
+        // Both SignedData and SignerInfo are version 3
+        let content_info = cms::content_info::ContentInfo::from_der(&buf).unwrap();
+        let signed_data = content_info
+            .content
+            .decode_as::<cms::signed_data::SignedData>()
+            .unwrap();
+        assert_eq!(signed_data.version, cms::content_info::CmsVersion::V3);
+        let signer_info = signed_data.signer_infos.0.get(0).unwrap();
+        assert_eq!(signer_info.version, cms::content_info::CmsVersion::V3);
+        let cms::signed_data::SignerIdentifier::SubjectKeyIdentifier(parsed_key_id) =
+            &signer_info.sid
+        else {
+            panic!("unexpected SignerIdentifier");
+        };
+        assert_eq!(parsed_key_id.0.as_bytes(), key_id);
+
+        // OpenSSL finds the signer certificate by its key identifier
+        let mut cms = openssl::cms::CmsContentInfo::from_der(&buf).unwrap();
+        let mut certs = openssl::stack::Stack::new().unwrap();
+        certs.push(signer_cert).unwrap();
+        let store = openssl::x509::store::X509StoreBuilder::new()
+            .unwrap()
+            .build();
+        let mut verified = vec![];
+        cms.verify(
+            Some(&certs),
+            Some(&store),
+            None,
+            Some(&mut verified),
+            openssl::cms::CMSOptions::NOVERIFY,
+        )
+        .unwrap();
+        assert_eq!(verified, content);
+    }
+
+    #[test]
+    fn test_signer_info_subject_key_identifier() {
+        let key_id = [0x3C; 20];
+        let sig = EcdsaSig {
+            r: CryptoBuf::new(&[0xCC; ECC_INT_SIZE]).unwrap(),
+            s: CryptoBuf::new(&[0xDD; ECC_INT_SIZE]).unwrap(),
+        };
+        let signer_id = SignerId::SubjectKeyIdentifier(&key_id);
+        let size = CertWriter::get_signer_info_size(
+            &signer_id,
+            &sig,
+            CertWriter::HASH_OID,
+            /*tagged=*/ true,
+        )
+        .unwrap();
+
+        let mut buf = vec![0u8; size];
+        let mut w = CertWriter::new(&mut buf, true);
+        assert_eq!(w.encode_signer_info(&signer_id, &sig, None).unwrap(), size);
+
+        let signer_info = cms::signed_data::SignerInfo::from_der(&buf).unwrap();
+        assert_eq!(signer_info.version, cms::content_info::CmsVersion::V3);
+        let cms::signed_data::SignerIdentifier::SubjectKeyIdentifier(parsed_key_id) =
+            &signer_info.sid
+        else {
+            panic!("unexpected SignerIdentifier");
+        };
+        assert_eq!(parsed_key_id.0.as_bytes(), key_id);
+    }
+
+    #[test]
+    fn test_cms_pre_encoded_issuer_and_serial_number() {
+        let issuer_der = encode_test_issuer();
+        let sig = EcdsaSig {
+            r: CryptoBuf::new(&[0xCC; ECC_INT_SIZE]).unwrap(),
+            s: CryptoBuf::new(&[0xDD; ECC_INT_SIZE]).unwrap(),
+        };
+        let content = [0xA5; 100];
+
+        let mut issuer_serial = [0u8; 256];
+        let mut w = CertWriter::new(&mut issuer_serial, true);
+        let len = w
+            .encode_issuer_and_serial_number(TEST_SERIAL, &issuer_der)
+            .unwrap();
+        let issuer_serial = &issuer_serial[..len];
+        cms::cert::IssuerAndSerialNumber::from_der(issuer_serial).unwrap();
+
+        // The pre-encoded signer produces the same message as the derived one
+        let derived = SignerId::IssuerAndSerialNumber {
+            issuer_name: &issuer_der,
+            serial_number: TEST_SERIAL,
+        };
+        let pre_encoded = SignerId::IssuerAndSerialNumberDer(issuer_serial);
+        let size = CertWriter::cms_size(&content, &derived, &sig, None).unwrap();
+        assert_eq!(
+            CertWriter::cms_size(&content, &pre_encoded, &sig, None),
+            Ok(size)
+        );
+
+        let mut derived_buf = vec![0u8; size];
+        let mut w = CertWriter::new(&mut derived_buf, true);
+        assert_eq!(w.encode_cms(&content, &derived, &sig, None), Ok(size));
+        let mut pre_encoded_buf = vec![0u8; size];
+        let mut w = CertWriter::new(&mut pre_encoded_buf, true);
+        assert_eq!(w.encode_cms(&content, &pre_encoded, &sig, None), Ok(size));
+        assert_eq!(pre_encoded_buf, derived_buf);
+
+        // Anything but a single IssuerAndSerialNumber SEQUENCE is rejected
+        let mut not_seq = issuer_serial.to_vec();
+        not_seq[0] = CertWriter::SET_OF_TAG;
+        let mut trailing = issuer_serial.to_vec();
+        trailing.push(0);
+        for bad in [&issuer_serial[..len - 1], &not_seq, &trailing, &issuer_der] {
+            let signer_id = SignerId::IssuerAndSerialNumberDer(bad);
+            assert_eq!(
+                CertWriter::cms_size(&content, &signer_id, &sig, None),
+                Err(DpeErrorCode::InvalidArgument)
+            );
+            let mut buf = vec![0u8; size + 1];
+            let mut w = CertWriter::new(&mut buf, true);
+            assert_eq!(
+                w.encode_cms(&content, &signer_id, &sig, None),
+                Err(DpeErrorCode::InvalidArgument)
+            );
+        }
+    }
+
+    #[test]
+    fn test_streamed_cms() {
+        let issuer_der = encode_test_issuer();
+        let sig = EcdsaSig {
+            r: CryptoBuf::new(&[0xCC; ECC_INT_SIZE]).unwrap(),
+            s: CryptoBuf::new(&[0xDD; ECC_INT_SIZE]).unwrap(),
+        };
+        let signer_id = SignerId::IssuerAndSerialNumber {
+            issuer_name: &issuer_der,
+            serial_number: TEST_SERIAL,
+        };
+        let csr: Vec<u8> = (0..300u32).map(|i| i as u8).collect();
+
+        let size = CertWriter::cms_size(&csr, &signer_id, &sig, None).unwrap();
+        let mut expected = vec![0u8; size];
+        let mut w = CertWriter::new(&mut expected, true);
+        w.encode_cms(&csr, &signer_id, &sig, None).unwrap();
+
+        let mut streamed = vec![0u8; size];
+        let mut w = CertWriter::new(&mut streamed, true);
+        w.begin_cms().unwrap();
+        for chunk in csr.chunks(7) {
+            assert_eq!(w.append_cms_econtent(chunk), Ok(chunk.len()));
+        }
+        assert_eq!(w.finish_cms(&signer_id, &sig, None), Ok(size));
+        assert_eq!(w.written(), expected);
+
+        // The message must fit in the buffer once the header is added
+        let mut buf = vec![0u8; size - 1];
+        let mut w = CertWriter::new(&mut buf, true);
+        w.begin_cms().unwrap();
+        w.append_cms_econtent(&csr).unwrap();
+        assert_eq!(
+            w.finish_cms(&signer_id, &sig, None),
+            Err(DpeErrorCode::BufferTooSmall(size as u32))
+        );
+        // The started message is kept on failure
+        assert_eq!(w.written(), csr);
+        assert_eq!(
+            w.finish_cms(&signer_id, &sig, None),
+            Err(DpeErrorCode::BufferTooSmall(size as u32))
+        );
+
+        // Chunks can only be appended to a started message
+        let mut w = CertWriter::new(&mut buf, true);
+        assert_eq!(
+            w.append_cms_econtent(&csr),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+        assert_eq!(
+            w.finish_cms(&signer_id, &sig, None),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_certs_only_cms() {
+        let mut leaf = [0u8; 1024];
+        let (leaf_len, _) = build_test_cert(false, &mut leaf);
+        let mut ca = [0u8; 1024];
+        let (ca_len, _) = build_test_cert(true, &mut ca);
+        let certs = [&ca[..ca_len], &leaf[..leaf_len]].concat();
+
+        let size = CertWriter::certs_only_cms_size(&certs).unwrap();
+        let mut buf = vec![0u8; size];
+        let mut w = CertWriter::new(&mut buf, true);
+        assert_eq!(w.encode_certs_only_cms(&certs).unwrap(), size);
+
+        // OpenSSL accepts the bag
+        let pkcs7 = openssl::pkcs7::Pkcs7::from_der(&buf).unwrap();
+        assert_eq!(pkcs7.to_der().unwrap(), buf);
+
+        let content_info = cms::content_info::ContentInfo::from_der(&buf).unwrap();
+        let signed_data = content_info
+            .content
+            .decode_as::<cms::signed_data::SignedData>()
+            .unwrap();
+        assert!(signed_data.digest_algorithms.is_empty());
+        assert!(signed_data.encap_content_info.econtent.is_none());
+        assert!(signed_data.signer_infos.0.is_empty());
+
+        let embedded: Vec<Vec<u8>> = signed_data
+            .certificates
+            .unwrap()
+            .0
+            .iter()
+            .map(|cert| der::Encode::to_der(cert).unwrap())
+            .collect();
+        assert_eq!(embedded.len(), 2);
+        for cert in embedded {
+            openssl::x509::X509::from_der(&cert).unwrap();
+            assert!(certs.windows(cert.len()).any(|w| w == cert));
+        }
+
+        // Malformed certificates are rejected before anything is written
+        let mut truncated = certs.clone();
+        truncated.pop();
+        let mut not_a_sequence = certs.clone();
+        not_a_sequence[0] = CertWriter::SET_OF_TAG;
+        let mut bad_length = certs.clone();
+        bad_length[ca_len + 1] = 0x84;
+        let trailing_garbage = [&certs[..], &[0x00][..]].concat();
+        for bad_certs in [truncated, not_a_sequence, bad_length, trailing_garbage] {
+            assert_eq!(
+                CertWriter::certs_only_cms_size(&bad_certs),
+                Err(DpeErrorCode::InvalidArgument)
+            );
+            let mut buf = [0u8; 2048];
+            let mut w = CertWriter::new(&mut buf, true);
+            assert_eq!(
+                w.encode_certs_only_cms(&bad_certs),
+                Err(DpeErrorCode::InvalidArgument)
+            );
+            assert!(w.written().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_buffer_too_small() {
+        let version_size = CertWriter::get_version_size(CertVersion::V3, /*tagged=*/ true).unwrap();
+
+        let mut buf = [0u8; 2];
+        let mut w = CertWriter::new(&mut buf, true);
+        assert_eq!(
+            w.encode_version(CertVersion::V3),
+            Err(DpeErrorCode::BufferTooSmall(version_size as u32))
+        );
+        // Nothing is written on failure
+        assert_eq!(w.offset, 0);
+
+        let mut buf = [0u8; 16];
+        let mut w = CertWriter::new(&mut buf, true);
+        assert_eq!(w.encode_version(CertVersion::V3), Ok(version_size));
+
+        // The needed size accounts for what has already been written
+        let issuer_der = encode_test_issuer();
+        let sig = EcdsaSig {
+            r: CryptoBuf::new(&[0xCC; ECC_INT_SIZE]).unwrap(),
+            s: CryptoBuf::new(&[0xDD; ECC_INT_SIZE]).unwrap(),
+        };
+        let signer_id = SignerId::IssuerAndSerialNumber {
+            issuer_name: &issuer_der,
+            serial_number: TEST_SERIAL,
+        };
+        let signer_info_size = CertWriter::get_signer_info_size(
+            &signer_id,
+            &sig,
+            CertWriter::HASH_OID,
+            /*tagged=*/ true,
+        )
+        .unwrap();
+        assert_eq!(
+            w.encode_signer_info(&signer_id, &sig, None),
+            Err(DpeErrorCode::BufferTooSmall(
+                (version_size + signer_info_size) as u32
+            ))
+        );
+        assert_eq!(
+            DpeErrorCode::BufferTooSmall(10).get_error_detail(),
+            Some(10)
+        );
+    }
+
+    #[test]
+    fn test_fwid_bytes() {
+        let tci = TciMeasurement([0x42; DPE_PROFILE.get_hash_size()]);
+
+        let mut expected = [0u8; 128];
+        let mut w = CertWriter::new(&mut expected, true);
+        let expected_len = w
+            .encode_fwid(&tci, CertWriter::HASH_OID, tci.0.len())
+            .unwrap();
+
+        let mut actual = [0u8; 128];
+        let mut w = CertWriter::new(&mut actual, true);
+        let actual_len = w
+            .encode_fwid_bytes(CertWriter::HASH_OID, &tci.0[..])
+            .unwrap();
+        assert_eq!(&actual[..actual_len], &expected[..expected_len]);
+
+        // Digests of other algorithms
+        for (hash_oid, size) in [
+            (CertWriter::SHA256_OID, 32),
+            (CertWriter::SHA384_OID, 48),
+            (CertWriter::SHA512_OID, 64),
+            (CertWriter::SHA512_256_OID, 32),
+        ] {
+            let digest = vec![0x24; size];
+            let mut w = CertWriter::new(&mut actual, true);
+            let len = w.encode_fwid_bytes(hash_oid, &digest).unwrap();
+            let fwid = asn1::parse_single::<Fwid>(&actual[..len]).unwrap();
+            assert_eq!(fwid.digest, digest);
+
+            // Wrong digest size
+            let mut w = CertWriter::new(&mut actual, true);
+            assert_eq!(
+                w.encode_fwid_bytes(hash_oid, &digest[1..]),
+                Err(DpeErrorCode::InvalidArgument)
+            );
+        }
+
+        // Unknown hash algorithm
+        let mut w = CertWriter::new(&mut actual, true);
+        assert_eq!(
+            w.encode_fwid_bytes(CertWriter::EC_PUB_OID, &tci.0),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_truncated_fwid() {
+        let tci = TciMeasurement([0x42; DPE_PROFILE.get_hash_size()]);
+        let mut buf = [0u8; 128];
+        let mut w = CertWriter::new(&mut buf, true);
+        let bytes_written = w.encode_fwid(&tci, CertWriter::SHA256_OID, 16).unwrap();
+        assert_eq!(
+            bytes_written,
+            CertWriter::get_fwid_size(CertWriter::SHA256_OID, 16, /*tagged=*/ true).unwrap()
+        );
+
+        let fwid = asn1::parse_single::<Fwid>(&buf[..bytes_written]).unwrap();
+        assert_eq!(
+            fwid._hash_alg,
+            asn1::ObjectIdentifier::from_string("2.16.840.1.101.3.4.2.1").unwrap()
+        );
+        assert_eq!(fwid.digest, &tci.0[..16]);
+
+        // Longer than a SHA-256 digest, or empty
+        for digest_len in [0, 33] {
+            let mut w = CertWriter::new(&mut buf, true);
+            assert_eq!(
+                w.encode_fwid(&tci, CertWriter::SHA256_OID, digest_len),
+                Err(DpeErrorCode::InvalidArgument)
+            );
+        }
+    }
+
+    #[test]
+    fn test_fwid_sha512_256() {
+        let digest = [0x5C; 32];
+        let mut buf = [0u8; 128];
+        let mut w = CertWriter::new(&mut buf, true);
+        let bytes_written = w
+            .encode_fwid_bytes(CertWriter::get_hash_oid(Some(HashAlg::Sha512_256)), &digest)
+            .unwrap();
+
+        let fwid = asn1::parse_single::<Fwid>(&buf[..bytes_written]).unwrap();
+        assert_eq!(
+            fwid._hash_alg,
+            asn1::ObjectIdentifier::from_string("2.16.840.1.101.3.4.2.6").unwrap()
+        );
+        assert_eq!(fwid.digest, digest);
+
+        // Selected for all FWIDs of a MultiTcbInfo. Without `fwid_digests`,
+        // the TCIs must be the size of its digests.
+        let node = TciNodeData::new();
+        let measurements = MeasurementData {
+            tci_nodes: &[node],
+            supports_extend_tci: true,
+            fwid_hash_alg: Some(HashAlg::Sha512_256),
+            ..Default::default()
+        };
+        let mut buf = [0u8; 512];
+        let mut w = CertWriter::new(&mut buf, true);
+        match DPE_PROFILE {
+            DpeProfile::P256Sha256 => {
+                let bytes_written = w.encode_multi_tcb_info(&measurements).unwrap();
+                assert_eq!(
+                    bytes_written,
+                    CertWriter::get_multi_tcb_info_size(&measurements, true).unwrap()
+                );
+            }
+            DpeProfile::P384Sha384 => assert_eq!(
+                w.encode_multi_tcb_info(&measurements),
+                Err(DpeErrorCode::InvalidArgument)
+            ),
+        }
+    }
+
+    #[test]
+    fn test_fwid_hash_alg_keeps_signature_alg() {
+        // FWIDs take the OID and digest size of `fwid_hash_alg`, but the
+        // signature keeps the profile's hash: SHA-384 FWIDs in a P-256
+        // certificate and SHA-256 FWIDs in a P-384 one.
+        const FWID_SIZE: usize = match DPE_PROFILE {
+            DpeProfile::P256Sha256 => 48,
+            DpeProfile::P384Sha384 => 32,
+        };
+        let (fwid_hash_alg, fwid_oid, sig_oid) = match DPE_PROFILE {
+            DpeProfile::P256Sha256 => (
+                HashAlg::Sha384,
+                "2.16.840.1.101.3.4.2.2",
+                oid!(1.2.840 .10045 .4 .3 .2),
+            ),
+            DpeProfile::P384Sha384 => (
+                HashAlg::Sha256,
+                "2.16.840.1.101.3.4.2.1",
+                oid!(1.2.840 .10045 .4 .3 .3),
+            ),
+        };
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let test_sig = EcdsaSig {
+            r: CryptoBuf::new(&[0xCC; ECC_INT_SIZE]).unwrap(),
+            s: CryptoBuf::new(&[0xDD; ECC_INT_SIZE]).unwrap(),
+        };
+        let node = TciNodeData::new();
+        let current = [0x11; FWID_SIZE];
+        let cumulative = [0x22; FWID_SIZE];
+        let measurements = MeasurementData {
+            label: &[0xCC; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[node],
+            supports_extend_tci: true,
+            fwid_hash_alg: Some(fwid_hash_alg),
+            fwid_digests: &[FwidDigests {
+                current: &current,
+                cumulative: &cumulative,
+            }],
+            ..Default::default()
+        };
+
+        let tbs_size = CertWriter::get_tbs_size(
+            TEST_SERIAL,
+            &issuer_der,
+            &TEST_SUBJECT_NAME,
+            &test_pub,
+            &measurements,
+            /*tagged=*/ true,
+        )
+        .unwrap();
+        let mut tbs = [0u8; 2048];
+        let mut w = CertWriter::new(&mut tbs, true);
+        let tbs_len = w
+            .encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+            )
+            .unwrap();
+        assert_eq!(tbs_len, tbs_size);
+        let mut cert_buf = [0u8; 2048];
+        let mut w = CertWriter::new(&mut cert_buf, true);
+        let cert_len = w
+            .encode_ecdsa_certificate(&tbs[..tbs_len], &test_sig)
+            .unwrap();
+
+        let (_, cert) = X509Certificate::from_der(&cert_buf[..cert_len]).unwrap();
+        assert_eq!(cert.signature_algorithm.algorithm, sig_oid);
+        assert_eq!(cert.tbs_certificate.signature.algorithm, sig_oid);
+        let ext = cert
+            .extensions()
+            .iter()
+            .find(|ext| ext.oid == oid!(2.23.133 .5 .4 .5))
+            .unwrap();
+        let mut tcb_infos = asn1::parse_single::<asn1::SequenceOf<TcbInfo>>(ext.value).unwrap();
+        let fwids: Vec<Fwid> = tcb_infos.next().unwrap().fwids.unwrap().collect();
+        assert!(tcb_infos.next().is_none());
+        assert_eq!(fwids.len(), 2);
+        for fwid in &fwids {
+            assert_eq!(
+                fwid._hash_alg,
+                asn1::ObjectIdentifier::from_string(fwid_oid).unwrap()
+            );
+        }
+        assert_eq!(fwids[0].digest, current);
+        assert_eq!(fwids[1].digest, cumulative);
+
+        // Digests that do not match the FWID hash are rejected, including the
+        // TCIs when `fwid_digests` does not replace them
+        let short = [0x33; FWID_SIZE - 1];
+        for fwid_digests in [
+            &[][..],
+            &[FwidDigests {
+                current: &current,
+                cumulative: &short,
+            }],
+        ] {
+            let measurements = MeasurementData {
+                fwid_digests,
+                ..measurements
+            };
+            assert_eq!(
+                measurements.validate(DPE_PROFILE),
+                Err(DpeErrorCode::InvalidArgument)
+            );
+            let mut w = CertWriter::new(&mut tbs, true);
+            assert_eq!(
+                w.encode_ecdsa_tbs(
+                    TEST_SERIAL,
+                    &issuer_der,
+                    &TEST_SUBJECT_NAME,
+                    &test_pub,
+                    &measurements,
+                ),
+                Err(DpeErrorCode::InvalidArgument)
+            );
+        }
+    }
+
+    #[test]
+    fn test_max_tci_nodes() {
+        let nodes = [TciNodeData::new(); MAX_TCI_NODES + 1];
+        let mut buf = vec![0u8; 8192];
+
+        let measurements = MeasurementData {
+            tci_nodes: &nodes[..MAX_TCI_NODES],
+            supports_extend_tci: true,
+            ..Default::default()
+        };
+        let mut w = CertWriter::new(&mut buf, true);
+        let bytes_written = w.encode_multi_tcb_info(&measurements).unwrap();
+        assert_eq!(
+            CertWriter::get_multi_tcb_info_size(&measurements, true).unwrap(),
+            bytes_written
+        );
+
+        let measurements = MeasurementData {
+            tci_nodes: &nodes,
+            supports_extend_tci: true,
+            ..Default::default()
+        };
+        let mut w = CertWriter::new(&mut buf, true);
+        assert_eq!(
+            w.encode_multi_tcb_info(&measurements),
+            Err(DpeErrorCode::MaxTcis)
+        );
+        assert_eq!(
+            CertWriter::get_multi_tcb_info_size(&measurements, true),
+            Err(DpeErrorCode::MaxTcis)
+        );
+    }
+
+    #[test]
+    fn test_tcb_info_extra_fwids() {
+        let mut node = TciNodeData::new();
+        node.tci_current = TciMeasurement([0x11; DPE_PROFILE.get_hash_size()]);
+        node.tci_cumulative = TciMeasurement([0x22; DPE_PROFILE.get_hash_size()]);
+        let extra_fwids = [TciMeasurement([0x33; DPE_PROFILE.get_hash_size()])];
+
+        // current, cumulative and one additional FWID
+        let mut cert = [0u8; 512];
+        let mut w = CertWriter::new(&mut cert, true);
+        let bytes_written = w
+            .encode_tcb_info(
+                &node,
+                /*has_fwids=*/ true,
+                /*supports_extend_tci=*/ true,
+                &extra_fwids,
+                /*fwid_digests=*/ None,
+                CertWriter::HASH_OID,
+            )
+            .unwrap();
+        assert_eq!(
+            bytes_written,
+            CertWriter::get_tcb_info_size(
+                &node,
+                /*has_fwids=*/ true,
+                /*supports_extend_tci=*/ true,
+                &extra_fwids,
+                /*fwid_digests=*/ None,
+                CertWriter::HASH_OID,
+                /*tagged=*/ true,
+            )
+            .unwrap()
+        );
+
+        let parsed_tcb_info = asn1::parse_single::<TcbInfo>(&cert[..bytes_written]).unwrap();
+        let digests: Vec<&[u8]> = parsed_tcb_info.fwids.unwrap().map(|f| f.digest).collect();
+        assert_eq!(
+            digests,
+            [
+                &node.tci_current.0[..],
+                &node.tci_cumulative.0[..],
+                &extra_fwids[0].0[..]
+            ]
+        );
+
+        // Nodes in a MultiTcbInfo may carry different numbers of FWIDs
+        let nodes = [node, node];
+        let measurements = MeasurementData {
+            tci_nodes: &nodes,
+            supports_extend_tci: true,
+            extra_fwids: &[&[], &extra_fwids],
+            ..Default::default()
+        };
+        let mut w = CertWriter::new(&mut cert, true);
+        let bytes_written = w.encode_multi_tcb_info(&measurements).unwrap();
+        assert_eq!(
+            bytes_written,
+            CertWriter::get_multi_tcb_info_size(&measurements, true).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_tcb_info_without_fwids() {
+        let mut node = TciNodeData::new();
+        node.tci_type = 0x11223344;
+        node.locality = 0x55667788;
+
+        let mut cert = [0u8; 512];
+        let mut w = CertWriter::new(&mut cert, true);
+        let bytes_written = w
+            .encode_tcb_info(
+                &node,
+                /*has_fwids=*/ false,
+                /*supports_extend_tci=*/ true,
+                &[],
+                /*fwid_digests=*/ None,
+                CertWriter::HASH_OID,
+            )
+            .unwrap();
+        assert_eq!(
+            bytes_written,
+            CertWriter::get_tcb_info_size(
+                &node,
+                /*has_fwids=*/ false,
+                /*supports_extend_tci=*/ true,
+                &[],
+                /*fwid_digests=*/ None,
+                CertWriter::HASH_OID,
+                /*tagged=*/ true,
+            )
+            .unwrap()
+        );
+
+        // vendorInfo and type are still present
+        let parsed_tcb_info = asn1::parse_single::<TcbInfo>(&cert[..bytes_written]).unwrap();
+        assert!(parsed_tcb_info.fwids.is_none());
+        assert_eq!(
+            parsed_tcb_info.vendor_info.unwrap(),
+            node.locality.to_be_bytes()
+        );
+        assert_eq!(
+            parsed_tcb_info.tci_type.unwrap(),
+            node.tci_type.to_be_bytes()
+        );
+
+        // Only the second node of a MultiTcbInfo omits its fwids
+        let nodes = [TciNodeData::new(), node];
+        let measurements = MeasurementData {
+            tci_nodes: &nodes,
+            supports_extend_tci: true,
+            nodes_without_fwids: 0b10,
+            ..Default::default()
+        };
+        let mut w = CertWriter::new(&mut cert, true);
+        let bytes_written = w.encode_multi_tcb_info(&measurements).unwrap();
+        assert_eq!(
+            bytes_written,
+            CertWriter::get_multi_tcb_info_size(&measurements, true).unwrap()
+        );
+        let mut parser = X509ExtensionParser::new().with_deep_parse_extensions(false);
+        let ext = parser.parse(&cert[..bytes_written]).unwrap().1;
+        let tcb_infos: Vec<TcbInfo> = asn1::parse_single::<asn1::SequenceOf<TcbInfo>>(ext.value)
+            .unwrap()
+            .collect();
+        assert_eq!(tcb_infos.len(), 2);
+        assert!(tcb_infos[0].fwids.is_some());
+        assert!(tcb_infos[1].fwids.is_none());
+
+        // A node without fwids cannot have additional FWIDs
+        let extra_fwids = [TciMeasurement([0x33; DPE_PROFILE.get_hash_size()])];
+        let measurements = MeasurementData {
+            extra_fwids: &[&[], &extra_fwids],
+            ..measurements
+        };
+        assert_eq!(
+            CertWriter::get_multi_tcb_info_size(&measurements, true),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_mixed_extend_tci() {
+        let mut nodes = [TciNodeData::new(); 3];
+        for (i, node) in nodes.iter_mut().enumerate() {
+            node.tci_current = TciMeasurement([0x10 + i as u8; DPE_PROFILE.get_tci_size()]);
+            node.tci_cumulative = TciMeasurement([0x20 + i as u8; DPE_PROFILE.get_tci_size()]);
+        }
+        // The first node follows the certificate-wide setting
+        let overrides = [None, Some(true), Some(false)];
+
+        let mut cert = [0u8; 1024];
+        for (supports_extend_tci, expected) in [(false, [1, 2, 1]), (true, [2, 2, 1])] {
+            let measurements = MeasurementData {
+                tci_nodes: &nodes,
+                supports_extend_tci,
+                extend_tci_overrides: &overrides,
+                ..Default::default()
+            };
+            let mut w = CertWriter::new(&mut cert, true);
+            let bytes_written = w.encode_multi_tcb_info(&measurements).unwrap();
+            assert_eq!(
+                bytes_written,
+                CertWriter::get_multi_tcb_info_size(&measurements, true).unwrap()
+            );
+
+            // Streaming the TcbInfos applies the overrides the same way
+            let mut streamed = [0u8; 1024];
+            let mut w = CertWriter::new(&mut streamed, true);
+            w.begin_multi_tcb_info().unwrap();
+            for (node, extend_tci_override) in nodes.iter().zip(overrides) {
+                w.append_tcb_info(node, supports_extend_tci, extend_tci_override, &[], None)
+                    .unwrap();
+            }
+            let streamed_len = w.finish_multi_tcb_info().unwrap();
+            assert_eq!(streamed[..streamed_len], cert[..bytes_written]);
+
+            let mut parser = X509ExtensionParser::new().with_deep_parse_extensions(false);
+            let ext = parser.parse(&cert[..bytes_written]).unwrap().1;
+            let tcb_infos = asn1::parse_single::<asn1::SequenceOf<TcbInfo>>(ext.value).unwrap();
+            for ((tcb_info, node), expected) in tcb_infos.zip(&nodes).zip(expected) {
+                let fwids: Vec<Fwid> = tcb_info.fwids.unwrap().collect();
+                assert_eq!(fwids.len(), expected);
+                assert_eq!(fwids[0].digest, node.tci_current.0);
+                if expected == 2 {
+                    assert_eq!(fwids[1].digest, node.tci_cumulative.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_build_dpe_cert() {
+        let mut env = DpeEnv::<TestTypes> {
+            crypto: crypto::OpensslCrypto::new(),
+            platform: DefaultPlatform,
+        };
+        let subject_name = Name {
+            cn: DirectoryString::PrintableString(b"DPE Leaf"),
+            serial: DirectoryString::PrintableString(b"0123456789ABCDEF0123456789ABCDEF"),
+            order: NameOrder::CnFirst,
+        };
+        let label = [0xCC; DPE_PROFILE.get_hash_size()];
+        let algs = DPE_PROFILE.alg_len();
+        let cdi = env
+            .crypto
+            .derive_cdi(algs, &CryptoBuf::default(algs), b"DPE")
+            .unwrap();
+        let (_, test_pub) = env
+            .crypto
+            .derive_key_pair(algs, &cdi, &label, b"ECC")
+            .unwrap();
+        let measurements = MeasurementData {
+            label: &label,
+            tci_nodes: &[TciNodeData::new()],
+            ..Default::default()
+        };
+
+        let mut cert = [0u8; 4096];
+        let cert_len = build_dpe_cert(
+            &mut env,
+            &[0x5A; 8],
+            &subject_name,
+            &test_pub,
+            &measurements,
+            |crypto, digest| crypto.ecdsa_sign_with_alias(DPE_PROFILE.alg_len(), digest),
+            &mut CertWriter::new(&mut cert, /*crit_dice=*/ false),
+        )
+        .unwrap();
+
+        let (rem, parsed) = X509CertificateParser::new()
+            .parse(&cert[..cert_len])
+            .unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(parsed.raw_serial(), &[0x5A; 8]);
+        let tcb_info = parsed
+            .get_extension_unique(&oid!(2.23.133 .5 .4 .5))
+            .unwrap()
+            .unwrap();
+        assert!(!tcb_info.critical);
+
+        // The validity was read from the platform
+        let leaf = openssl::x509::X509::from_der(&cert[..cert_len]).unwrap();
+        let mut not_before = [0u8; GENERALIZED_TIME_SIZE];
+        let mut not_after = [0u8; GENERALIZED_TIME_SIZE];
+        env.platform
+            .get_cert_validity(&mut not_before, &mut not_after)
+            .unwrap();
+        let not_before =
+            openssl::asn1::Asn1Time::from_str(str::from_utf8(&not_before).unwrap()).unwrap();
+        assert!(leaf.not_before() == not_before);
+
+        // The certificate chains to the platform certificate
+        let issuer_der = match DPE_PROFILE {
+            DpeProfile::P256Sha256 => {
+                &include_bytes!("../../platform/src/test_data/cert_256.der")[..]
+            }
+            DpeProfile::P384Sha384 => {
+                &include_bytes!("../../platform/src/test_data/cert_384.der")[..]
+            }
+        };
+        let issuer = openssl::x509::X509::from_der(issuer_der).unwrap();
+        assert_eq!(issuer.issued(&leaf), openssl::x509::X509VerifyResult::OK);
+        assert!(leaf.verify(&issuer.public_key().unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_single_tcb_info() {
+        let mut tbs_buf = [0u8; 4096];
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let mut node = TciNodeData::new();
+        node.tci_current = TciMeasurement([0x5A; DPE_PROFILE.get_tci_size()]);
+
+        let measurements = MeasurementData {
+            label: &[0xCC; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[node],
+            single_tcb_info: true,
+            ..Default::default()
+        };
+
+        let mut w = CertWriter::new(&mut tbs_buf, true);
+        let bytes_written = w
+            .encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+            )
+            .unwrap();
+        assert_eq!(
+            bytes_written,
+            CertWriter::get_tbs_size(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+                /*tagged=*/ true,
+            )
+            .unwrap()
+        );
+
+        let mut parser = TbsCertificateParser::new().with_deep_parse_extensions(false);
+        let (_, tbs) = parser.parse(&tbs_buf[..bytes_written]).unwrap();
+        assert!(tbs
+            .get_extension_unique(&oid!(2.23.133 .5 .4 .5))
+            .unwrap()
+            .is_none());
+        let ext = tbs
+            .get_extension_unique(&oid!(2.23.133 .5 .4 .1))
+            .unwrap()
+            .unwrap();
+        assert!(ext.critical);
+        let tcb_info = asn1::parse_single::<TcbInfo>(ext.value).unwrap();
+        let fwids: Vec<Fwid> = tcb_info.fwids.unwrap().collect();
+        assert_eq!(fwids[0].digest, node.tci_current.0);
+
+        // A single TcbInfo can only describe one TCI node
+        let nodes = [node; 2];
+        let measurements = MeasurementData {
+            tci_nodes: &nodes,
+            ..measurements
+        };
+        let mut w = CertWriter::new(&mut tbs_buf, true);
+        assert_eq!(
+            w.encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+            ),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+    }
+
+    fn get_key_usage(is_ca: bool) -> KeyUsage {
+        let measurements = MeasurementData {
+            is_ca,
+            ..Default::default()
+        };
+        let (key_usage, _) = encode_test_key_usage(&measurements);
+        key_usage
+    }
+
+    /// Encode a keyUsage extension, returning it parsed and its raw value
+    fn encode_test_key_usage(measurements: &MeasurementData) -> (KeyUsage, Vec<u8>) {
+        let mut cert = [0u8; 32];
+        let mut w = CertWriter::new(&mut cert, true);
+        let bytes_written = w.encode_key_usage(measurements).unwrap();
+        assert_eq!(
+            bytes_written,
+            CertWriter::get_key_usage_size(measurements, /*tagged=*/ true).unwrap()
+        );
+
+        let mut parser = X509ExtensionParser::new().with_deep_parse_extensions(false);
+        let ext = parser.parse(&cert[..bytes_written]).unwrap().1;
+        (KeyUsage::from_der(ext.value).unwrap().1, ext.value.to_vec())
+    }
+
+    #[test]
+    fn test_key_usage() {
+        // Make sure leaf keyUsage is only digitalSignature
+        let leaf_key_usage = get_key_usage(/*is_ca=*/ false);
+        let expected = 1u16;
+        assert!(leaf_key_usage.flags | expected == expected);
+
+        // Make sure leaf keyUsage is digitalSignature | keyCertSign
+        let ca_key_usage = get_key_usage(/*is_ca=*/ true);
+        let expected = (1u16 << 5) | 1u16;
+        assert!(ca_key_usage.flags | expected == expected);
+    }
+
+    #[test]
+    fn test_key_usage_decipher_only() {
+        // decipherOnly needs a second value byte
+        let measurements = MeasurementData {
+            key_usage: Some(KeyUsageFlags::KEY_AGREEMENT | KeyUsageFlags::DECIPHER_ONLY),
+            ..Default::default()
+        };
+        let (key_usage, value) = encode_test_key_usage(&measurements);
+        assert_eq!(value, [0x03, 0x03, 0x07, 0x08, 0x80]);
+        assert!(key_usage.key_agreement());
+        assert!(key_usage.decipher_only());
+        assert_eq!(key_usage.flags, (1 << 8) | (1 << 4));
+
+        // Without it, a single value byte with the trailing zero bits unused
+        let measurements = MeasurementData {
+            key_usage: Some(KeyUsageFlags::KEY_AGREEMENT | KeyUsageFlags::ENCIPHER_ONLY),
+            ..Default::default()
+        };
+        let (key_usage, value) = encode_test_key_usage(&measurements);
+        assert_eq!(value, [0x03, 0x02, 0x00, 0x09]);
+        assert!(key_usage.encipher_only());
+        assert!(!key_usage.decipher_only());
+
+        // At least one bit must be set
+        let measurements = MeasurementData {
+            key_usage: Some(KeyUsageFlags::empty()),
+            ..Default::default()
+        };
+        assert_eq!(
+            CertWriter::get_key_usage_size(&measurements, /*tagged=*/ true),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_tbs() {
+        let mut cert = [0u8; 4096];
+        let mut w = CertWriter::new(&mut cert, true);
+
+        let test_serial = [0x1F; 20];
+        let issuer_der = encode_test_issuer();
+
+        let test_subject_name = Name {
+            cn: DirectoryString::PrintableString(b"DPE Leaf"),
+            serial: DirectoryString::PrintableString(&[0x00; DPE_PROFILE.get_hash_size() * 2]),
+            order: NameOrder::CnFirst,
+        };
+
+        const ECC_INT_SIZE: usize = DPE_PROFILE.get_ecc_int_size();
+        let test_pub = EcdsaPub::from_coords(
+            &[0xAA; ECC_INT_SIZE],
+            &[0xBB; ECC_INT_SIZE],
+            DPE_PROFILE.alg_len(),
+        )
+        .unwrap();
+
+        let node = TciNodeData::new();
+
+        let measurements = MeasurementData {
+            label: &[0xCC; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[node],
+            is_ca: false,
+            supports_extend_tci: true,
+            ..Default::default()
+        };
+
+        let bytes_written = w
+            .encode_ecdsa_tbs(
+                &test_serial,
+                &issuer_der,
+                &test_subject_name,
+                &test_pub,
+                &measurements,
+            )
+            .unwrap();
+
+        let mut parser = TbsCertificateParser::new().with_deep_parse_extensions(false);
+        let cert = match parser.parse(&cert) {
+            Ok((rem, parsed_cert)) => {
+                assert_eq!(parsed_cert.version(), X509Version::V3);
+                assert_eq!(rem.len(), cert.len() - bytes_written);
+                parsed_cert
+            }
+            Err(e) => panic!("x509 parsing failed: {:?}", e),
+        };
+
+        let ueid = cert
+            .get_extension_unique(&oid!(2.23.133 .5 .4 .4))
+            .unwrap()
+            .unwrap();
+        assert!(ueid.critical);
+        let parsed_ueid = asn1::parse_single::<Ueid>(ueid.value).unwrap();
+        assert_eq!(parsed_ueid.ueid, measurements.label);
+    }
+
+    #[test]
+    fn test_to_hex() {
+        // SHA-256("abc")
+        let digest = [
+            0xBA, 0x78, 0x16, 0xBF, 0x8F, 0x01, 0xCF, 0xEA, 0x41, 0x41, 0x40, 0xDE, 0x5D, 0xAE,
+            0x22, 0x23, 0xB0, 0x03, 0x61, 0xA3, 0x96, 0x17, 0x7A, 0x9C, 0xB4, 0x10, 0xFF, 0x61,
+            0xF2, 0x00, 0x15, 0xAD,
+        ];
+        let expected = "BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD";
+
+        let mut out = [0u8; 80];
+        let len = to_hex(&digest, &mut out, /*uppercase=*/ true).unwrap();
+        assert_eq!(str::from_utf8(&out[..len]).unwrap(), expected);
+        let len = to_hex(&digest, &mut out, /*uppercase=*/ false).unwrap();
+        assert_eq!(
+            str::from_utf8(&out[..len]).unwrap(),
+            expected.to_lowercase()
+        );
+
+        let mut out = [0u8; 63];
+        assert_eq!(
+            to_hex(&digest, &mut out, true),
+            Err(DpeErrorCode::BufferTooSmall(64))
+        );
+    }
+
+    #[test]
+    fn test_name_with_pubkey_serial() {
+        let mut crypto = crypto::OpensslCrypto::new();
+        let pubkey = EcdsaPub::from_coords(
+            &[0xAA; ECC_INT_SIZE],
+            &[0xBB; ECC_INT_SIZE],
+            DPE_PROFILE.alg_len(),
+        )
+        .unwrap();
+
+        let mut serial_buf = [0u8; DPE_PROFILE.get_hash_size() * 2];
+        let name = Name::with_pubkey_serial(
+            DirectoryString::PrintableString(b"DPE Leaf"),
+            &mut crypto,
+            &pubkey,
+            &mut serial_buf,
+        )
+        .unwrap();
+
+        // Matches the serial the Crypto implementation derives
+        let mut expected = [0u8; DPE_PROFILE.get_hash_size() * 2];
+        crypto
+            .get_pubkey_serial(DPE_PROFILE.alg_len(), &pubkey, &mut expected)
+            .unwrap();
+        assert_eq!(name.serial.bytes(), expected);
+
+        let mut short = [0u8; DPE_PROFILE.get_hash_size()];
+        assert!(matches!(
+            Name::with_pubkey_serial(
+                DirectoryString::PrintableString(b"DPE Leaf"),
+                &mut crypto,
+                &pubkey,
+                &mut short,
+            ),
+            Err(DpeErrorCode::BufferTooSmall(_))
+        ));
+    }
+
+    #[test]
+    fn test_format_generalized_time() {
+        for (unix_secs, expected) in [
+            (0, "19700101000000Z"),
+            // Leap days, including in a year divisible by 400
+            (951_782_400, "20000229000000Z"),
+            (1_709_251_199, "20240229235959Z"),
+            // 2100 is not a leap year
+            (4_107_456_000, "21000228000000Z"),
+            (4_107_542_400, "21000301000000Z"),
+            (253_402_300_799, "99991231235959Z"),
+        ] {
+            let mut out = [0u8; GENERALIZED_TIME_SIZE];
+            format_generalized_time(unix_secs, &mut out).unwrap();
+            assert_eq!(str::from_utf8(&out).unwrap(), expected);
+        }
+
+        let mut out = [0u8; GENERALIZED_TIME_SIZE];
+        assert_eq!(
+            format_generalized_time(253_402_300_800, &mut out),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_validity_time_encoding() {
+        const NOT_BEFORE: &str = "20240101000000Z";
+        const NOT_AFTER: &str = "20991231235959Z";
+
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::from_coords(
+            &[0xAA; ECC_INT_SIZE],
+            &[0xBB; ECC_INT_SIZE],
+            DPE_PROFILE.alg_len(),
+        )
+        .unwrap();
+        let node = TciNodeData::new();
+
+        let encode_validity = |time_encoding| {
+            let measurements = MeasurementData {
+                label: &[0xCC; DPE_PROFILE.get_hash_size()],
+                tci_nodes: &[node],
+                validity: Some(Validity {
+                    not_before: NOT_BEFORE,
+                    not_after: NOT_AFTER,
+                }),
+                time_encoding,
+                ..Default::default()
+            };
+
+            let mut tbs_buf = [0u8; 2048];
+            let mut w = CertWriter::new(&mut tbs_buf, true);
+            let tbs_written = w
+                .encode_ecdsa_tbs(
+                    TEST_SERIAL,
+                    &issuer_der,
+                    &TEST_SUBJECT_NAME,
+                    &test_pub,
+                    &measurements,
+                )
+                .unwrap();
+            assert_eq!(
+                tbs_written,
+                CertWriter::get_tbs_size(
+                    TEST_SERIAL,
+                    &issuer_der,
+                    &TEST_SUBJECT_NAME,
+                    &test_pub,
+                    &measurements,
+                    /*tagged=*/ true,
+                )
+                .unwrap()
+            );
+
+            let tbs = cms::cert::x509::TbsCertificate::from_der(&tbs_buf[..tbs_written]).unwrap();
+            der::Encode::to_der(&tbs.validity).unwrap()
+        };
+
+        let expected_time =
+            |tag: u8, time: &str| [&[tag, time.len() as u8], time.as_bytes()].concat();
+
+        // DICE: GeneralizedTime even for a 2024 date
+        let validity = encode_validity(TimeEncoding::default());
+        assert_eq!(
+            validity[2..],
+            [
+                expected_time(0x18, NOT_BEFORE),
+                expected_time(0x18, NOT_AFTER)
+            ]
+            .concat()
+        );
+
+        // RFC 5280: UTCTime before 2050, GeneralizedTime afterwards
+        let validity = encode_validity(TimeEncoding::Rfc5280);
+        assert_eq!(
+            validity[2..],
+            [
+                expected_time(0x17, &NOT_BEFORE[2..]),
+                expected_time(0x18, NOT_AFTER)
+            ]
+            .concat()
+        );
+
+        // Malformed dates are rejected
+        for time in ["2024010100000Z", "20240101000000", "2024010100000AZ"] {
+            let measurements = MeasurementData {
+                validity: Some(Validity {
+                    not_before: time,
+                    not_after: NOT_AFTER,
+                }),
+                ..Default::default()
+            };
+            assert_eq!(
+                CertWriter::get_validity_size(&measurements, /*tagged=*/ true),
+                Err(DpeErrorCode::InvalidArgument)
+            );
+        }
+    }
+
+    #[test]
+    fn test_measurement_data_validate() {
+        let nodes = [TciNodeData::new(); MAX_TCI_NODES + 1];
+        let label = [0; DPE_PROFILE.ueid_size() + 1];
+        let extra_fwids: &[TciMeasurement] = &[TciMeasurement::default()];
+        let base = MeasurementData {
+            label: &label[..DPE_PROFILE.ueid_size()],
+            tci_nodes: &nodes[..1],
+            ..Default::default()
+        };
+        assert_eq!(base.validate(DPE_PROFILE), Ok(()));
+
+        let cases = [
+            (
+                MeasurementData {
+                    tci_nodes: &[],
+                    ..base
+                },
+                DpeErrorCode::InvalidArgument,
+            ),
+            (
+                MeasurementData {
+                    tci_nodes: &nodes,
+                    ..base
+                },
+                DpeErrorCode::MaxTcis,
+            ),
+            (
+                MeasurementData {
+                    extra_fwids: &[&[], &[]],
+                    ..base
+                },
+                DpeErrorCode::InvalidArgument,
+            ),
+            (
+                MeasurementData {
+                    extra_fwids: &[extra_fwids],
+                    nodes_without_fwids: 1,
+                    ..base
+                },
+                DpeErrorCode::InvalidArgument,
+            ),
+            (
+                MeasurementData {
+                    fwid_hash_alg: Some(HashAlg::Sha512),
+                    ..base
+                },
+                DpeErrorCode::InvalidArgument,
+            ),
+            (
+                MeasurementData { label: &[], ..base },
+                DpeErrorCode::InvalidArgument,
+            ),
+            (
+                MeasurementData {
+                    label: &label,
+                    ..base
+                },
+                DpeErrorCode::InvalidArgument,
+            ),
+            (
+                MeasurementData {
+                    key_usage: Some(KeyUsageFlags::empty()),
+                    ..base
+                },
+                DpeErrorCode::InvalidArgument,
+            ),
+            (
+                MeasurementData {
+                    version: CertVersion::V1,
+                    subject_unique_id: Some(&[0x01]),
+                    ..base
+                },
+                DpeErrorCode::InvalidArgument,
+            ),
+        ];
+
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let issuer_der = encode_test_issuer();
+        for (measurements, err) in cases {
+            assert_eq!(measurements.validate(DPE_PROFILE), Err(err));
+
+            // Both encoders validate before writing anything
+            let mut buf = [0u8; 2048];
+            let mut w = CertWriter::new(&mut buf, true);
+            assert_eq!(
+                w.encode_ecdsa_tbs(
+                    TEST_SERIAL,
+                    &issuer_der,
+                    &TEST_SUBJECT_NAME,
+                    &test_pub,
+                    &measurements,
+                ),
+                Err(err)
+            );
+            assert_eq!(
+                w.encode_certification_request_info(
+                    &test_pub,
+                    Some(&TEST_SUBJECT_NAME),
+                    &measurements,
+                ),
+                Err(err)
+            );
+            assert!(w.written().is_empty());
+        }
+
+        // A label is only checked if it is included
+        let measurements = MeasurementData {
+            label: &[],
+            include_ueid: false,
+            ..base
+        };
+        assert_eq!(measurements.validate(DPE_PROFILE), Ok(()));
+
+        // The UEID size follows the profile passed in
+        let measurements = MeasurementData {
+            label: &[0; 48],
+            ..base
+        };
+        assert_eq!(measurements.validate(DpeProfile::P384Sha384), Ok(()));
+        assert_eq!(
+            measurements.validate(DpeProfile::P256Sha256),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_ueid_value() {
+        let label = [0xA5; DPE_PROFILE.get_hash_size()];
+        let mut buf = [0u8; 128];
+        let mut w = CertWriter::new(&mut buf, true);
+        let bytes_written = w.encode_ueid_value(&label).unwrap();
+        assert_eq!(
+            bytes_written,
+            CertWriter::get_ueid_value_size(&label).unwrap()
+        );
+
+        let parsed_ueid = asn1::parse_single::<Ueid>(&buf[..bytes_written]).unwrap();
+        assert_eq!(parsed_ueid.ueid, label);
+    }
+
+    #[test]
+    fn test_ueid() {
+        let labels = [0xA5; DPE_PROFILE.ueid_size() + 1];
+        for len in [1, 16, DPE_PROFILE.ueid_size()] {
+            let label = &labels[..len];
+            let measurements = MeasurementData {
+                label,
+                ..Default::default()
+            };
+            let mut buf = [0u8; 128];
+            let mut w = CertWriter::new(&mut buf, true);
+            let bytes_written = w.encode_ueid(&measurements).unwrap();
+            assert_eq!(
+                bytes_written,
+                CertWriter::get_ueid_size(&measurements, /*tagged=*/ true).unwrap()
+            );
+
+            let (rem, ext) = X509Extension::from_der(&buf[..bytes_written]).unwrap();
+            assert!(rem.is_empty());
+            assert_eq!(ext.oid, oid!(2.23.133 .5 .4 .4));
+            assert!(ext.critical);
+            let parsed_ueid = asn1::parse_single::<Ueid>(ext.value).unwrap();
+            assert_eq!(parsed_ueid.ueid, label);
+        }
+
+        // Empty and oversized labels are rejected
+        for len in [0, DPE_PROFILE.ueid_size() + 1] {
+            let measurements = MeasurementData {
+                label: &labels[..len],
+                ..Default::default()
+            };
+            let mut buf = [0u8; 128];
+            let mut w = CertWriter::new(&mut buf, true);
+            assert_eq!(
+                w.encode_ueid(&measurements),
+                Err(DpeErrorCode::InvalidArgument)
+            );
+        }
+    }
+
+    #[test]
+    fn test_ueid_encoding() {
+        let label = [0xA5; DPE_PROFILE.ueid_size()];
+        for ueid_encoding in [UeidEncoding::Sequence, UeidEncoding::OctetString] {
+            let measurements = MeasurementData {
+                label: &label,
+                ueid_encoding,
+                ..Default::default()
+            };
+            let mut buf = [0u8; 128];
+            let mut w = CertWriter::new(&mut buf, true);
+            let bytes_written = w.encode_ueid(&measurements).unwrap();
+            assert_eq!(
+                bytes_written,
+                CertWriter::get_ueid_size(&measurements, /*tagged=*/ true).unwrap()
+            );
+
+            let (rem, ext) = X509Extension::from_der(&buf[..bytes_written]).unwrap();
+            assert!(rem.is_empty());
+            assert_eq!(ext.oid, oid!(2.23.133 .5 .4 .4));
+            let ueid = match ueid_encoding {
+                UeidEncoding::Sequence => asn1::parse_single::<Ueid>(ext.value).unwrap().ueid,
+                UeidEncoding::OctetString => asn1::parse_single::<&[u8]>(ext.value).unwrap(),
+            };
+            assert_eq!(ueid, label);
+        }
+    }
+
+    #[test]
+    fn test_leaf_basic_constraints_criticality() {
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::from_coords(
+            &[0xAA; ECC_INT_SIZE],
+            &[0xBB; ECC_INT_SIZE],
+            DPE_PROFILE.alg_len(),
+        )
+        .unwrap();
+        let node = TciNodeData::new();
+
+        for (is_ca, leaf_basic_constraints_critical, expected_critical) in [
+            (false, true, true),
+            (false, false, false),
+            // Always critical for CAs
+            (true, false, true),
+        ] {
+            let measurements = MeasurementData {
+                label: &[0xCC; DPE_PROFILE.get_hash_size()],
+                tci_nodes: &[node],
+                is_ca,
+                leaf_basic_constraints_critical,
+                ..Default::default()
+            };
+
+            let mut tbs_buf = [0u8; 2048];
+            let mut w = CertWriter::new(&mut tbs_buf, true);
+            let bytes_written = w
+                .encode_ecdsa_tbs(
+                    TEST_SERIAL,
+                    &issuer_der,
+                    &TEST_SUBJECT_NAME,
+                    &test_pub,
+                    &measurements,
+                )
+                .unwrap();
+
+            let mut parser = TbsCertificateParser::new().with_deep_parse_extensions(true);
+            let (_, tbs) = parser.parse(&tbs_buf[..bytes_written]).unwrap();
+            let basic_constraints = tbs.basic_constraints().unwrap().unwrap();
+            assert_eq!(basic_constraints.critical, expected_critical);
+            assert_eq!(basic_constraints.value.ca, is_ca);
+        }
+    }
+
+    #[test]
+    fn test_no_ueid() {
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::from_coords(
+            &[0xAA; ECC_INT_SIZE],
+            &[0xBB; ECC_INT_SIZE],
+            DPE_PROFILE.alg_len(),
+        )
+        .unwrap();
+        let node = TciNodeData::new();
+        let measurements = MeasurementData {
+            label: &[0xCC; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[node],
+            include_ueid: false,
+            ..Default::default()
+        };
+
+        let mut tbs_buf = [0u8; 2048];
+        let mut w = CertWriter::new(&mut tbs_buf, true);
+        let bytes_written = w
+            .encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+            )
+            .unwrap();
+        assert_eq!(
+            bytes_written,
+            CertWriter::get_tbs_size(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+                /*tagged=*/ true,
+            )
+            .unwrap()
+        );
+
+        let mut parser = TbsCertificateParser::new().with_deep_parse_extensions(false);
+        let (_, tbs) = parser.parse(&tbs_buf[..bytes_written]).unwrap();
+        assert!(tbs
+            .get_extension_unique(&oid!(2.23.133 .5 .4 .4))
+            .unwrap()
+            .is_none());
+        assert!(tbs
+            .get_extension_unique(&oid!(2.23.133 .5 .4 .5))
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn test_tbs_unique_ids() {
+        let mut cert = [0u8; 4096];
+        let mut w = CertWriter::new(&mut cert, true);
+
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let subject_unique_id = [0x5A; 16];
+
+        let measurements = MeasurementData {
+            label: &[0xCC; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[TciNodeData::new()],
+            is_ca: false,
+            supports_extend_tci: true,
+            subject_unique_id: Some(&subject_unique_id),
+            ..Default::default()
+        };
+
+        let bytes_written = w
+            .encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+            )
+            .unwrap();
+
+        assert_eq!(
+            CertWriter::get_tbs_size(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+                /*tagged=*/ true,
+            )
+            .unwrap(),
+            bytes_written
+        );
+
+        // x509-parser decodes the unique IDs as EXPLICIT rather than
+        // IMPLICIT, so use the RustCrypto parser instead.
+        let tbs = cms::cert::x509::TbsCertificate::from_der(&cert[..bytes_written]).unwrap();
+        assert!(tbs.issuer_unique_id.is_none());
+        let subject_uid = tbs.subject_unique_id.unwrap();
+        assert_eq!(subject_uid.unused_bits(), 0);
+        assert_eq!(subject_uid.raw_bytes(), subject_unique_id);
+    }
+
+    #[test]
+    fn test_full_featured_cert() {
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::from_coords(
+            &[0xAA; ECC_INT_SIZE],
+            &[0xBB; ECC_INT_SIZE],
+            DPE_PROFILE.alg_len(),
+        )
+        .unwrap();
+
+        let mut node = TciNodeData::new();
+        node.tci_current = TciMeasurement([0x11; DPE_PROFILE.get_hash_size()]);
+        node.tci_cumulative = TciMeasurement([0x22; DPE_PROFILE.get_hash_size()]);
+        let nodes = [node, node];
+        let extra_fwids = [TciMeasurement([0x33; DPE_PROFILE.get_hash_size()])];
+
+        // subjectKeyIdentifier extension
+        let mut ski = vec![
+            0x30, 0x1D, 0x06, 0x03, 0x55, 0x1D, 0x0E, 0x04, 0x16, 0x04, 0x14,
+        ];
+        ski.extend_from_slice(&[0x5A; 20]);
+        let raw_extensions: [&[u8]; 1] = [&ski];
+
+        let issuer_unique_id = [0x1A; 8];
+        let subject_unique_id = [0x2B; 16];
+        let label = [0xCC; DPE_PROFILE.get_hash_size()];
+        let measurements = MeasurementData {
+            label: &label,
+            tci_nodes: &nodes,
+            is_ca: true,
+            supports_extend_tci: true,
+            extend_tci_overrides: &[Some(false)],
+            issuer_unique_id: Some(&issuer_unique_id),
+            subject_unique_id: Some(&subject_unique_id),
+            extra_fwids: &[&[], &extra_fwids],
+            nodes_without_fwids: 0,
+            raw_extensions: &raw_extensions,
+            policy_constraints: Some(PolicyConstraints {
+                require_explicit_policy: Some(1),
+                inhibit_policy_mapping: Some(2),
+            }),
+            inhibit_any_policy: Some(3),
+            // Only used for CSRs
+            challenge_password: None,
+            cert_profile: CertProfile::Dice,
+            fwid_hash_alg: Some(match DPE_PROFILE {
+                DpeProfile::P256Sha256 => HashAlg::Sha512_256,
+                DpeProfile::P384Sha384 => HashAlg::Sha384,
+            }),
+            fwid_digests: &[FwidDigests {
+                current: &[0x44; DPE_PROFILE.get_hash_size()],
+                cumulative: &[],
+            }],
+            signature_alg_oid: None,
+            subject_public_key_info: None,
+            validity: Some(Validity {
+                not_before: "20240101000000Z",
+                not_after: "20341231235959Z",
+            }),
+            time_encoding: TimeEncoding::AlwaysGeneralized,
+            include_ueid: true,
+            leaf_basic_constraints_critical: true,
+            key_usage: None,
+            path_len_constraint: Some(4),
+            version: CertVersion::V3,
+            point_format: PointFormat::Uncompressed,
+            key_purposes: KeyPurposes::Dice,
+            single_tcb_info: false,
+            ueid_encoding: UeidEncoding::Sequence,
+            self_signed: false,
+        };
+
+        let mut tbs_buf = [0u8; 2048];
+        let mut w = CertWriter::new(&mut tbs_buf, true);
+        let tbs_written = w
+            .encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+            )
+            .unwrap();
+        assert_eq!(
+            tbs_written,
+            CertWriter::get_tbs_size(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+                /*tagged=*/ true,
+            )
+            .unwrap()
+        );
+
+        let test_sig = EcdsaSig {
+            r: CryptoBuf::new(&[0xCC; ECC_INT_SIZE]).unwrap(),
+            s: CryptoBuf::new(&[0xDD; ECC_INT_SIZE]).unwrap(),
+        };
+        let mut cert_buf = [0u8; 2048];
+        let mut w = CertWriter::new(&mut cert_buf, true);
+        let cert_written = w
+            .encode_ecdsa_certificate(&tbs_buf[..tbs_written], &test_sig)
+            .unwrap();
+
+        // x509-parser decodes the unique IDs as EXPLICIT rather than
+        // IMPLICIT, so parse the certificate with the RustCrypto parser and
+        // hand each extension to x509-parser individually.
+        let cert = cms::cert::x509::Certificate::from_der(&cert_buf[..cert_written]).unwrap();
+        let tbs = cert.tbs_certificate;
+        assert_eq!(tbs.issuer_unique_id.unwrap().raw_bytes(), issuer_unique_id);
+        assert_eq!(
+            tbs.subject_unique_id.unwrap().raw_bytes(),
+            subject_unique_id
+        );
+
+        let extensions = tbs.extensions.unwrap();
+        assert_eq!(extensions.len(), 8);
+        for ext in extensions {
+            let ext_der = der::Encode::to_der(&ext).unwrap();
+            let (_, ext) = X509Extension::from_der(&ext_der).unwrap();
+            match ext.oid.to_id_string().as_str() {
+                "2.23.133.5.4.5" => {
+                    assert!(ext.critical);
+                    let tcb_infos =
+                        asn1::parse_single::<asn1::SequenceOf<TcbInfo>>(ext.value).unwrap();
+                    let fwid_counts: Vec<usize> = tcb_infos
+                        .map(|tcb_info| tcb_info.fwids.unwrap().count())
+                        .collect();
+                    // The first node overrides supports_extend_tci
+                    assert_eq!(fwid_counts, [1, 3]);
+                }
+                "2.23.133.5.4.4" => {
+                    assert!(ext.critical);
+                    let ueid = asn1::parse_single::<Ueid>(ext.value).unwrap();
+                    assert_eq!(ueid.ueid, label);
+                }
+                _ => match ext.parsed_extension() {
+                    ParsedExtension::BasicConstraints(basic_constraints) => {
+                        assert!(ext.critical);
+                        assert!(basic_constraints.ca);
+                        assert_eq!(basic_constraints.path_len_constraint, Some(4));
+                    }
+                    ParsedExtension::KeyUsage(key_usage) => {
+                        assert!(ext.critical);
+                        assert!(key_usage.digital_signature());
+                        assert!(key_usage.key_cert_sign());
+                    }
+                    ParsedExtension::ExtendedKeyUsage(eku) => {
+                        assert!(ext.critical);
+                        assert_eq!(eku.other, [oid!(2.23.133 .5 .4 .100 .12)]);
+                    }
+                    ParsedExtension::PolicyConstraints(constraints) => {
+                        assert!(ext.critical);
+                        assert_eq!(constraints.require_explicit_policy, Some(1));
+                        assert_eq!(constraints.inhibit_policy_mapping, Some(2));
+                    }
+                    ParsedExtension::InhibitAnyPolicy(inhibit_any_policy) => {
+                        assert!(ext.critical);
+                        assert_eq!(inhibit_any_policy.skip_certs, 3);
+                    }
+                    ParsedExtension::SubjectKeyIdentifier(id) => {
+                        assert!(!ext.critical);
+                        assert_eq!(id.0, [0x5A; 20]);
+                    }
+                    _ => panic!("unexpected extension {}", ext.oid),
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn test_measurement_data_round_trip() {
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::from_coords(
+            &[0xAA; ECC_INT_SIZE],
+            &[0xBB; ECC_INT_SIZE],
+            DPE_PROFILE.alg_len(),
+        )
+        .unwrap();
+        let test_sig = EcdsaSig {
+            r: CryptoBuf::new(&[0xCC; ECC_INT_SIZE]).unwrap(),
+            s: CryptoBuf::new(&[0xDD; ECC_INT_SIZE]).unwrap(),
+        };
+        let label = [0x4C; DPE_PROFILE.get_hash_size()];
+
+        for (is_ca, supports_extend_tci) in [(false, true), (true, true), (true, false)] {
+            let tci_nodes: Vec<TciNodeData> = (0..3u8)
+                .map(|i| TciNodeData {
+                    tci_type: 0x1000 + i as u32,
+                    tci_cumulative: match supports_extend_tci {
+                        true => TciMeasurement([0x20 + i; DPE_PROFILE.get_tci_size()]),
+                        false => TciMeasurement([0; DPE_PROFILE.get_tci_size()]),
+                    },
+                    tci_current: TciMeasurement([0x10 + i; DPE_PROFILE.get_tci_size()]),
+                    locality: 0x2000 + i as u32,
+                })
+                .collect();
+            let measurements = MeasurementData {
+                label: &label,
+                tci_nodes: &tci_nodes,
+                is_ca,
+                supports_extend_tci,
+                ..Default::default()
+            };
+
+            let mut tbs_buf = [0u8; 2048];
+            let mut w = CertWriter::new(&mut tbs_buf, true);
+            let tbs_written = w
+                .encode_ecdsa_tbs(
+                    TEST_SERIAL,
+                    &issuer_der,
+                    &TEST_SUBJECT_NAME,
+                    &test_pub,
+                    &measurements,
+                )
+                .unwrap();
+            let mut cert_buf = [0u8; 2048];
+            let mut w = CertWriter::new(&mut cert_buf, true);
+            let cert_written = w
+                .encode_ecdsa_certificate(&tbs_buf[..tbs_written], &test_sig)
+                .unwrap();
+
+            let parsed = measurement_data_from_cert(&cert_buf[..cert_written]).unwrap();
+            assert!(
+                parsed
+                    == OwnedMeasurementData {
+                        label: label.to_vec(),
+                        tci_nodes,
+                        is_ca,
+                        supports_extend_tci,
+                    }
+            );
+        }
+
+        assert_eq!(
+            measurement_data_from_cert(&[0x30, 0x00]).err(),
+            Some(DpeErrorCode::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_external_signature_certificate() {
+        // Dummy composite signature OID 1.3.6.1.4.1.55555.1
+        const COMPOSITE_OID: &[u8] = &[0x2B, 0x06, 0x01, 0x04, 0x01, 0x83, 0xB2, 0x03, 0x01];
+        let raw_sig = [0x5A; 300];
+
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::from_coords(
+            &[0xAA; ECC_INT_SIZE],
+            &[0xBB; ECC_INT_SIZE],
+            DPE_PROFILE.alg_len(),
+        )
+        .unwrap();
+        let node = TciNodeData::new();
+        let encode_tbs = |signature_alg_oid, tbs: &mut [u8]| -> usize {
+            let measurements = MeasurementData {
+                label: &[0; DPE_PROFILE.get_hash_size()],
+                tci_nodes: &[node],
+                signature_alg_oid,
+                ..Default::default()
+            };
+            let mut w = CertWriter::new(tbs, true);
+            let tbs_len = w
+                .encode_ecdsa_tbs(
+                    TEST_SERIAL,
+                    &issuer_der,
+                    &TEST_SUBJECT_NAME,
+                    &test_pub,
+                    &measurements,
+                )
+                .unwrap();
+            assert_eq!(
+                CertWriter::get_tbs_size(
+                    TEST_SERIAL,
+                    &issuer_der,
+                    &TEST_SUBJECT_NAME,
+                    &test_pub,
+                    &measurements,
+                    /*tagged=*/ true
+                )
+                .unwrap(),
+                tbs_len
+            );
+            tbs_len
+        };
+
+        let mut tbs = [0u8; 1024];
+        let tbs_len = encode_tbs(Some(COMPOSITE_OID), &mut tbs);
+        let mut cert_buf = [0u8; 2048];
+        let mut w = CertWriter::new(&mut cert_buf, true);
+        let cert_len = w
+            .encode_certificate(&tbs[..tbs_len], COMPOSITE_OID, &raw_sig)
+            .unwrap();
+
+        let (_, cert) = X509Certificate::from_der(&cert_buf[..cert_len]).unwrap();
+        assert_eq!(
+            cert.signature_algorithm.algorithm.to_id_string(),
+            "1.3.6.1.4.1.55555.1"
+        );
+        assert!(cert.signature_algorithm.parameters.is_none());
+        assert_eq!(cert.tbs_certificate.signature, cert.signature_algorithm);
+        assert_eq!(cert.signature_value.data, &raw_sig[..]);
+    }
+
+    #[test]
+    fn test_written() {
+        let mut tbs = [0u8; 1024];
+        let (tbs_len, _) = build_test_tbs(/*is_ca=*/ false, &mut tbs);
+        let test_sig = EcdsaSig {
+            r: CryptoBuf::new(&[0xCC; ECC_INT_SIZE]).unwrap(),
+            s: CryptoBuf::new(&[0xDD; ECC_INT_SIZE]).unwrap(),
+        };
+
+        let mut cert_buf = [0u8; 2048];
+        let mut w = CertWriter::new(&mut cert_buf, true);
+        assert!(w.written().is_empty());
+        let cert_len = w
+            .encode_ecdsa_certificate(&tbs[..tbs_len], &test_sig)
+            .unwrap();
+        let written = w.written().to_vec();
+        assert_eq!(written, cert_buf[..cert_len]);
+    }
+
+    #[test]
+    fn test_extensions_never_empty() {
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let node = TciNodeData::new();
+
+        // Everything optional is left out of a leaf certificate
+        for cert_profile in [CertProfile::Dice, CertProfile::AndroidAttestation] {
+            let measurements = MeasurementData {
+                label: &[],
+                tci_nodes: &[node],
+                include_ueid: false,
+                cert_profile,
+                ..Default::default()
+            };
+            let mut cert = [0u8; 1024];
+            let mut w = CertWriter::new(&mut cert, true);
+            let bytes_written = w
+                .encode_ecdsa_tbs(
+                    TEST_SERIAL,
+                    &issuer_der,
+                    &TEST_SUBJECT_NAME,
+                    &test_pub,
+                    &measurements,
+                )
+                .unwrap();
+
+            // RFC 5280 requires at least one entry in a present Extensions
+            let tbs = cms::cert::x509::TbsCertificate::from_der(&cert[..bytes_written]).unwrap();
+            let extensions = tbs.extensions.unwrap();
+            let oids: Vec<String> = extensions
+                .iter()
+                .map(|ext| ext.extn_id.to_string())
+                .collect();
+            for oid in ["2.23.133.5.4.5", "2.5.29.19", "2.5.29.15"] {
+                assert!(oids.iter().any(|o| o == oid), "{oid} missing");
+            }
+            assert!(!oids.iter().any(|o| o == "2.23.133.5.4.4"));
+        }
+    }
+
+    #[test]
+    fn test_cert_version() {
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let unique_id = [0x5A; 16];
+        let raw_extensions: [&[u8]; 1] = [&[0x30, 0x00]];
+        let node = TciNodeData::new();
+        let base = MeasurementData {
+            label: &[0xCC; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[node],
+            supports_extend_tci: true,
+            ..Default::default()
+        };
+        // v1 and v2 certificates take no extension inputs
+        let no_extensions = MeasurementData {
+            tci_nodes: &[],
+            ..base
+        };
+
+        let encode_tbs = |measurements: &MeasurementData, cert: &mut [u8]| {
+            let mut w = CertWriter::new(cert, true);
+            w.encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                measurements,
+            )
+        };
+
+        for (version, expected_version, unique_ids) in [
+            (CertVersion::V1, cms::cert::x509::Version::V1, false),
+            (CertVersion::V2, cms::cert::x509::Version::V2, true),
+            (CertVersion::V3, cms::cert::x509::Version::V3, true),
+        ] {
+            let subject_unique_id = unique_ids.then_some(&unique_id[..]);
+            let measurements = if version == CertVersion::V3 {
+                MeasurementData {
+                    version,
+                    subject_unique_id,
+                    ..base
+                }
+            } else {
+                MeasurementData {
+                    version,
+                    subject_unique_id,
+                    ..no_extensions
+                }
+            };
+            let mut cert = [0u8; 1024];
+            let bytes_written = encode_tbs(&measurements, &mut cert).unwrap();
+            assert_eq!(
+                CertWriter::get_tbs_size(
+                    TEST_SERIAL,
+                    &issuer_der,
+                    &TEST_SUBJECT_NAME,
+                    &test_pub,
+                    &measurements,
+                    /*tagged=*/ true,
+                )
+                .unwrap(),
+                bytes_written
+            );
+
+            // x509-parser decodes the unique IDs as EXPLICIT rather than
+            // IMPLICIT, so use the RustCrypto parser instead.
+            let tbs = cms::cert::x509::TbsCertificate::from_der(&cert[..bytes_written]).unwrap();
+            assert_eq!(tbs.version, expected_version);
+            assert_eq!(tbs.subject_unique_id.is_some(), unique_ids);
+            // Only v3 certificates have extensions
+            assert_eq!(tbs.extensions.is_some(), version == CertVersion::V3);
+        }
+
+        // v1 omits the DEFAULT version field
+        assert_eq!(
+            CertWriter::get_version_size(CertVersion::V1, /*tagged=*/ true),
+            Ok(0)
+        );
+        let mut cert = [0u8; 1024];
+        let v1_len = encode_tbs(
+            &MeasurementData {
+                version: CertVersion::V1,
+                ..no_extensions
+            },
+            &mut cert,
+        )
+        .unwrap();
+        let (_, contents, _) = CertWriter::split_element(&cert[..v1_len]).unwrap();
+        assert_eq!(contents[0], CertWriter::INTEGER_TAG);
+
+        // Minimal v1 and v2 inputs encode despite the default include_ueid,
+        // which is ignored without Extensions
+        for (version, expected_version) in [
+            (CertVersion::V1, cms::cert::x509::Version::V1),
+            (CertVersion::V2, cms::cert::x509::Version::V2),
+        ] {
+            let measurements = MeasurementData {
+                version,
+                ..Default::default()
+            };
+            assert!(measurements.include_ueid);
+            assert_eq!(measurements.validate(DPE_PROFILE), Ok(()));
+            let mut cert = [0u8; 1024];
+            let bytes_written = encode_tbs(&measurements, &mut cert).unwrap();
+            let tbs = cms::cert::x509::TbsCertificate::from_der(&cert[..bytes_written]).unwrap();
+            assert_eq!(tbs.version, expected_version);
+            assert!(tbs.extensions.is_none());
+        }
+
+        // Fields the version cannot hold are rejected rather than dropped
+        let mut cert = [0u8; 1024];
+        let v1 = MeasurementData {
+            version: CertVersion::V1,
+            ..no_extensions
+        };
+        let v2 = MeasurementData {
+            version: CertVersion::V2,
+            ..no_extensions
+        };
+        for measurements in [
+            MeasurementData {
+                issuer_unique_id: Some(&unique_id),
+                ..v1
+            },
+            MeasurementData {
+                key_usage: Some(KeyUsageFlags::DIGITAL_SIGNATURE),
+                ..v1
+            },
+            MeasurementData {
+                raw_extensions: &raw_extensions,
+                ..v2
+            },
+            MeasurementData {
+                tci_nodes: base.tci_nodes,
+                ..v2
+            },
+            MeasurementData { is_ca: true, ..v2 },
+            MeasurementData {
+                path_len_constraint: Some(1),
+                ..v2
+            },
+            MeasurementData {
+                key_purposes: KeyPurposes::Custom(DiceKeyPurposes::ECA),
+                ..v2
+            },
+            MeasurementData {
+                single_tcb_info: true,
+                ..v2
+            },
+        ] {
+            assert_eq!(
+                measurements.validate(DPE_PROFILE),
+                Err(DpeErrorCode::InvalidArgument)
+            );
+            assert_eq!(
+                encode_tbs(&measurements, &mut cert),
+                Err(DpeErrorCode::InvalidArgument)
+            );
+        }
+
+        // v3 allows both unique IDs and extensions
+        let measurements = MeasurementData {
+            version: CertVersion::V3,
+            issuer_unique_id: Some(&unique_id),
+            raw_extensions: &raw_extensions,
+            ..base
+        };
+        assert!(encode_tbs(&measurements, &mut cert).is_ok());
+    }
+
+    #[test]
+    fn test_oversized_issuer() {
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let measurements = MeasurementData {
+            label: &[0xCC; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[TciNodeData::new()],
+            supports_extend_tci: true,
+            ..Default::default()
+        };
+
+        // A well-formed SEQUENCE one byte longer than allowed
+        let content_len = MAX_ISSUER_NAME_SIZE + 1 - 4;
+        let mut issuer_der = vec![0x30, 0x82];
+        issuer_der.extend_from_slice(&(content_len as u16).to_be_bytes());
+        issuer_der.resize(MAX_ISSUER_NAME_SIZE + 1, 0x5A);
+
+        // Rejected before anything is written, even with a large buffer
+        let mut cert = [0u8; 4096];
+        let mut w = CertWriter::new(&mut cert, true);
+        assert_eq!(
+            w.encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+            ),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+        assert_eq!(w.offset, 0);
+    }
+
+    #[test]
+    fn test_tbs_sig_alg_mismatch() {
+        const OTHER_OID: &[u8] = &[0x2B, 0x06, 0x01, 0x04, 0x01, 0x83, 0xB2, 0x03, 0x02];
+        let test_sig = EcdsaSig {
+            r: CryptoBuf::new(&[0xCC; ECC_INT_SIZE]).unwrap(),
+            s: CryptoBuf::new(&[0xDD; ECC_INT_SIZE]).unwrap(),
+        };
+
+        let mut tbs = [0u8; 1024];
+        let (tbs_len, _) = build_test_tbs(/*is_ca=*/ false, &mut tbs);
+        let tbs = &tbs[..tbs_len];
+        assert_eq!(
+            CertWriter::validate_tbs_sig_alg(tbs, CertWriter::ECDSA_OID),
+            Ok(())
+        );
+
+        // The outer algorithm differs from the TBS one
+        let mut cert_buf = [0u8; 2048];
+        let mut w = CertWriter::new(&mut cert_buf, true);
+        assert_eq!(
+            w.encode_certificate(tbs, OTHER_OID, &[0x5A; 64]),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+
+        // The TBS algorithm differs from the outer ECDSA one
+        let mut bad_tbs = tbs.to_vec();
+        let oid_start = bad_tbs
+            .windows(CertWriter::ECDSA_OID.len())
+            .position(|w| w == CertWriter::ECDSA_OID)
+            .unwrap();
+        bad_tbs[oid_start + 1] ^= 0xFF;
+        let mut w = CertWriter::new(&mut cert_buf, true);
+        assert_eq!(
+            w.encode_ecdsa_certificate(&bad_tbs, &test_sig),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+
+        // Malformed TBS
+        assert_eq!(
+            CertWriter::validate_tbs_sig_alg(&tbs[..tbs.len() - 1], CertWriter::ECDSA_OID),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_spki_raw() {
+        let key = openssl::pkey::PKey::generate_ed25519().unwrap();
+        let spki_der = key.public_key_to_der().unwrap();
+
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let node = TciNodeData::new();
+        let measurements = MeasurementData {
+            label: &[0; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[node],
+            subject_public_key_info: Some(&spki_der),
+            ..Default::default()
+        };
+
+        let mut tbs = [0u8; 1024];
+        let mut w = CertWriter::new(&mut tbs, true);
+        let tbs_len = w
+            .encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+            )
+            .unwrap();
+        assert_eq!(
+            CertWriter::get_tbs_size(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+                /*tagged=*/ true
+            )
+            .unwrap(),
+            tbs_len
+        );
+
+        let test_sig = EcdsaSig {
+            r: CryptoBuf::new(&[0xCC; ECC_INT_SIZE]).unwrap(),
+            s: CryptoBuf::new(&[0xDD; ECC_INT_SIZE]).unwrap(),
+        };
+        let mut cert_buf = [0u8; 2048];
+        let mut w = CertWriter::new(&mut cert_buf, true);
+        let cert_len = w
+            .encode_ecdsa_certificate(&tbs[..tbs_len], &test_sig)
+            .unwrap();
+
+        let (_, cert) = X509Certificate::from_der(&cert_buf[..cert_len]).unwrap();
+        assert_eq!(cert.public_key().raw, spki_der);
+        assert_eq!(cert.public_key().algorithm.algorithm, oid!(1.3.101 .112));
+
+        // Anything but a SubjectPublicKeyInfo is rejected
+        let mut w = CertWriter::new(&mut cert_buf, true);
+        assert_eq!(
+            w.encode_spki_raw(&spki_der[..spki_der.len() - 1]),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+        assert_eq!(
+            w.encode_spki_raw(&issuer_der),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_cert_self_test() {
+        // The platform under test serves a chain ending in the certificate of
+        // the alias key for `DPE_PROFILE`
+        let (alias_cert, other_cert) = match DPE_PROFILE {
+            DpeProfile::P256Sha256 => (
+                &include_bytes!("../../platform/src/test_data/cert_256.der")[..],
+                &include_bytes!("../../platform/src/test_data/cert_384.der")[..],
+            ),
+            DpeProfile::P384Sha384 => (
+                &include_bytes!("../../platform/src/test_data/cert_384.der")[..],
+                &include_bytes!("../../platform/src/test_data/cert_256.der")[..],
+            ),
+        };
+        let mut crypto = crypto::OpensslCrypto::new();
+        let mut platform = TestPlatform {
+            cert_chain: Some(alias_cert),
+            ..Default::default()
+        };
+        run_cert_self_test(&mut crypto, &mut platform).unwrap();
+
+        // The alias certificate is found at the end of a longer chain
+        let chain = [other_cert, alias_cert].concat().leak();
+        let mut platform = TestPlatform {
+            cert_chain: Some(chain),
+            ..Default::default()
+        };
+        run_cert_self_test(&mut crypto, &mut platform).unwrap();
+
+        // A chain that does not end in the alias certificate fails
+        let chain = [alias_cert, other_cert].concat().leak();
+        let mut platform = TestPlatform {
+            cert_chain: Some(chain),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_cert_self_test(&mut crypto, &mut platform),
+            Err(DpeErrorCode::InternalError)
+        );
+
+        // A corrupted signature fails
+        let mut platform = TestPlatform {
+            cert_chain: Some(alias_cert),
+            ..Default::default()
+        };
+        let mut cert_buf = [0u8; 2048];
+        let cert_len = issue_self_test_cert(&mut crypto, &mut platform, &mut cert_buf).unwrap();
+        verify_self_test_cert(&mut crypto, &mut platform, &cert_buf[..cert_len]).unwrap();
+        cert_buf[cert_len - 1] ^= 0x01;
+        assert_eq!(
+            verify_self_test_cert(&mut crypto, &mut platform, &cert_buf[..cert_len]),
+            Err(DpeErrorCode::InternalError)
+        );
+
+        // Unless the crypto implementation keeps the default ecdsa_verify, in
+        // which case the signature check is skipped
+        struct NoVerifyCrypto(crypto::OpensslCrypto);
+        impl Crypto for NoVerifyCrypto {
+            type Cdi = <crypto::OpensslCrypto as Crypto>::Cdi;
+            type Hasher<'c> = <crypto::OpensslCrypto as Crypto>::Hasher<'c>;
+            type PrivKey = <crypto::OpensslCrypto as Crypto>::PrivKey;
+
+            fn rand_bytes(&mut self, dst: &mut [u8]) -> Result<(), CryptoError> {
+                self.0.rand_bytes(dst)
+            }
+
+            fn hash_initialize(&mut self, algs: AlgLen) -> Result<Self::Hasher<'_>, CryptoError> {
+                self.0.hash_initialize(algs)
+            }
+
+            fn derive_cdi(
+                &mut self,
+                algs: AlgLen,
+                measurement: &Digest,
+                info: &[u8],
+            ) -> Result<Self::Cdi, CryptoError> {
+                self.0.derive_cdi(algs, measurement, info)
+            }
+
+            fn derive_key_pair(
+                &mut self,
+                algs: AlgLen,
+                cdi: &Self::Cdi,
+                label: &[u8],
+                info: &[u8],
+            ) -> Result<(Self::PrivKey, EcdsaPub), CryptoError> {
+                self.0.derive_key_pair(algs, cdi, label, info)
+            }
+
+            fn ecdsa_sign_with_alias(
+                &mut self,
+                algs: AlgLen,
+                digest: &Digest,
+            ) -> Result<EcdsaSig, CryptoError> {
+                self.0.ecdsa_sign_with_alias(algs, digest)
+            }
+
+            fn ecdsa_sign_with_derived(
+                &mut self,
+                algs: AlgLen,
+                digest: &Digest,
+                priv_key: &Self::PrivKey,
+                pub_key: &EcdsaPub,
+            ) -> Result<EcdsaSig, CryptoError> {
+                self.0
+                    .ecdsa_sign_with_derived(algs, digest, priv_key, pub_key)
+            }
+
+            fn hmac_sign_with_derived(
+                &mut self,
+                algs: AlgLen,
+                cdi: &Self::Cdi,
+                label: &[u8],
+                info: &[u8],
+                digest: &Digest,
+            ) -> Result<HmacSig, CryptoError> {
+                self.0
+                    .hmac_sign_with_derived(algs, cdi, label, info, digest)
+            }
+        }
+        let mut no_verify = NoVerifyCrypto(crypto::OpensslCrypto::new());
+        verify_self_test_cert(&mut no_verify, &mut platform, &cert_buf[..cert_len]).unwrap();
+        run_cert_self_test(&mut no_verify, &mut platform).unwrap();
+
+        // A certificate without the DICE extensions fails
+        let mut cert_buf = [0u8; 2048];
+        let (cert_len, _) = build_test_cert(/*is_ca=*/ false, &mut cert_buf);
+        CertWriter::validate_self_test_cert(&cert_buf[..cert_len]).unwrap();
+        let ueid_oid_start = cert_buf
+            .windows(CertWriter::UEID_OID.len())
+            .position(|w| w == CertWriter::UEID_OID)
+            .unwrap();
+        cert_buf[ueid_oid_start + CertWriter::UEID_OID.len() - 1] ^= 0xFF;
+        assert_eq!(
+            CertWriter::validate_self_test_cert(&cert_buf[..cert_len]),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_self_test_cert_device_ueid() {
+        let mut cert_buf = [0u8; 2048];
+        let cert_len = issue_self_test_cert(
+            &mut crypto::OpensslCrypto::new(),
+            &mut DefaultPlatform,
+            &mut cert_buf,
+        )
+        .unwrap();
+
+        let (_, cert) = X509Certificate::from_der(&cert_buf[..cert_len]).unwrap();
+        let ueid = cert
+            .get_extension_unique(&oid!(2.23.133 .5 .4 .4))
+            .unwrap()
+            .unwrap();
+        let parsed_ueid = asn1::parse_single::<Ueid>(ueid.value).unwrap();
+        assert_eq!(parsed_ueid.ueid, platform::default::DEVICE_UEID);
+    }
+
+    #[test]
+    fn test_sort_set_of() {
+        let a: &[u8] = &[0x02, 0x01, 0x01];
+        let b: &[u8] = &[0x02, 0x01, 0x02];
+        let c: &[u8] = &[0x04, 0x00];
+        let mut d = vec![0x30, 0x81, 0x80];
+        d.extend_from_slice(&[0xEE; 0x80]);
+
+        for order in [
+            [&a, &b, &c, &&d[..]],
+            [&&d[..], &c, &b, &a],
+            [&b, &&d[..], &a, &c],
+        ] {
+            let mut buf = [0u8; 256];
+            let mut w = CertWriter::new(&mut buf, true);
+            for element in order {
+                w.encode_bytes(element).unwrap();
+            }
+            w.sort_set_of(0).unwrap();
+            let len = w.offset;
+            assert_eq!(&buf[..len], [a, b, c, &d].concat());
+        }
+    }
+
+    #[test]
+    fn test_read_csr_chunks() {
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let test_sig = EcdsaSig {
+            r: CryptoBuf::new(&[0xCC; ECC_INT_SIZE]).unwrap(),
+            s: CryptoBuf::new(&[0xDD; ECC_INT_SIZE]).unwrap(),
+        };
+        let nodes = [TciNodeData::new(); MAX_TCI_NODES];
+        let measurements = MeasurementData {
+            label: &[0; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &nodes,
+            supports_extend_tci: true,
+            ..Default::default()
+        };
+
+        let mut cri = vec![0u8; 8192];
+        let mut w = CertWriter::new(&mut cri, true);
+        let cri_len = w
+            .encode_certification_request_info(&test_pub, Some(&TEST_SUBJECT_NAME), &measurements)
+            .unwrap();
+        let mut csr = vec![0u8; 8192];
+        let mut w = CertWriter::new(&mut csr, true);
+        let csr_len = w.encode_csr(&cri[..cri_len], &test_sig).unwrap();
+        let csr = &csr[..csr_len];
+        const CHUNK_SIZE: usize = 512;
+        assert!(csr.len() > 4 * CHUNK_SIZE);
+
+        let mut reassembled = vec![];
+        let mut chunk = [0u8; CHUNK_SIZE];
+        let mut offset = 0;
+        while offset < csr.len() as u32 {
+            let len = platform::read_chunk(csr, offset, CHUNK_SIZE as u32, &mut chunk).unwrap();
+            reassembled.extend_from_slice(&chunk[..len as usize]);
+            offset += len;
+        }
+        assert_eq!(reassembled, csr);
+        X509CertificationRequest::from_der(&reassembled).unwrap();
+
+        // Reading past the end or into a short buffer fails
+        assert_eq!(
+            platform::read_chunk(csr, offset, 1, &mut chunk),
+            Err(platform::PlatformError::InvalidChunk)
+        );
+        assert_eq!(
+            platform::read_chunk(csr, 0, CHUNK_SIZE as u32 + 1, &mut chunk),
+            Err(platform::PlatformError::InvalidChunk)
+        );
+    }
+
+    #[test]
+    fn test_csr_attributes_sorted() {
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let node = TciNodeData::new();
+        let measurements = MeasurementData {
+            label: &[0; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[node],
+            challenge_password: Some(b"hunter2"),
+            ..Default::default()
+        };
+
+        let mut cri = [0u8; 1024];
+        let mut w = CertWriter::new(&mut cri, true);
+        let cri_len = w
+            .encode_certification_request_info(&test_pub, Some(&TEST_SUBJECT_NAME), &measurements)
+            .unwrap();
+        assert_eq!(
+            cri_len,
+            CertWriter::get_certification_request_info_size(
+                Some(&TEST_SUBJECT_NAME),
+                &test_pub,
+                &measurements,
+                /*tagged=*/ true
+            )
+            .unwrap()
+        );
+
+        let cri = cms::cert::x509::request::CertReqInfo::from_der(&cri[..cri_len]).unwrap();
+        let attributes: Vec<Vec<u8>> = cri
+            .attributes
+            .iter()
+            .map(|attr| der::Encode::to_der(attr).unwrap())
+            .collect();
+        assert_eq!(attributes.len(), 2);
+        assert!(attributes[0] < attributes[1]);
+
+        // extensionRequest was written first but sorts last
+        let oids: Vec<String> = cri
+            .attributes
+            .iter()
+            .map(|attr| attr.oid.to_string())
+            .collect();
+        assert_eq!(oids, ["1.2.840.113549.1.9.7", "1.2.840.113549.1.9.14"]);
+        let password = cri.attributes.get(0).unwrap().values.get(0).unwrap();
+        assert_eq!(password.value(), b"hunter2");
+    }
+
+    #[test]
+    fn test_ring_cert_writer() {
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let node = TciNodeData::new();
+        let measurements = MeasurementData {
+            label: &[0; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[node],
+            ..Default::default()
+        };
+        let sig = EcdsaSig {
+            r: CryptoBuf::new(&[0xCC; ECC_INT_SIZE]).unwrap(),
+            s: CryptoBuf::new(&[0xDD; ECC_INT_SIZE]).unwrap(),
+        };
+        let encode = |serial: u8, w: &mut CertWriter| {
+            let mut tbs = [0u8; 1024];
+            let tbs_len = CertWriter::new(&mut tbs, true).encode_ecdsa_tbs(
+                &[serial; 20],
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &measurements,
+            )?;
+            w.encode_ecdsa_certificate(&tbs[..tbs_len], &sig)
+        };
+        let mut cert = [0u8; 1024];
+        let cert_len = encode(1, &mut CertWriter::new(&mut cert, true)).unwrap();
+
+        // Room for three and a half certificates
+        let mut buf = vec![0u8; cert_len * 7 / 2];
+        let buf_len = buf.len();
+        let mut ring = RingCertWriter::new(&mut buf, true);
+        assert!(ring.is_empty());
+        for serial in 1..=5 {
+            assert_eq!(ring.write_cert(|w| encode(serial, w)), Ok(cert_len));
+        }
+
+        // Only the newest complete certificates remain, oldest first
+        let serials = |ring: &RingCertWriter| -> Vec<u8> {
+            ring.certs()
+                .map(|cert| {
+                    let (rem, cert) = X509Certificate::from_der(cert).unwrap();
+                    assert!(rem.is_empty());
+                    cert.raw_serial()[0]
+                })
+                .collect()
+        };
+        assert_eq!(ring.len(), 3);
+        assert_eq!(serials(&ring), [3, 4, 5]);
+
+        // A failed write leaves the ring untouched
+        assert_eq!(
+            ring.write_cert(|_| Err(DpeErrorCode::InternalError)),
+            Err(DpeErrorCode::InternalError)
+        );
+        assert_eq!(serials(&ring), [3, 4, 5]);
+
+        // So does a certificate that can never fit
+        assert_eq!(
+            ring.write_cert(|w| w.encode_bytes(&vec![0; buf_len + 1])),
+            Err(DpeErrorCode::BufferTooSmall(buf_len as u32 + 1))
+        );
+        assert_eq!(serials(&ring), [3, 4, 5]);
+
+        // Unless it only turns out too large once partially written over the
+        // oldest certificate, which then stays dropped
+        assert_eq!(
+            ring.write_cert(|w| Ok(w.encode_bytes(&[0; 8])? + w.encode_bytes(&vec![0; buf_len])?)),
+            Err(DpeErrorCode::BufferTooSmall(buf_len as u32 + 8))
+        );
+        assert_eq!(serials(&ring), [4, 5]);
+
+        let mut small = vec![0u8; cert_len - 1];
+        let mut ring = RingCertWriter::new(&mut small, true);
+        assert!(matches!(
+            ring.write_cert(|w| encode(1, w)),
+            Err(DpeErrorCode::BufferTooSmall(_))
+        ));
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn test_extensions_digest() {
+        let mut crypto = crypto::OpensslCrypto::new();
+        let mut node = TciNodeData::new();
+        node.tci_current = TciMeasurement([0xAA; DPE_PROFILE.get_tci_size()]);
+        let label = [0xCC; DPE_PROFILE.ueid_size()];
+        let mut digest = |node: TciNodeData| {
+            let measurements = MeasurementData {
+                label: &label,
+                tci_nodes: &[node],
+                ..Default::default()
+            };
+            let mut scratch = [0u8; 1024];
+            let mut out = [0u8; DPE_PROFILE.get_hash_size()];
+            let len = CertWriter::extensions_digest(
+                &mut crypto,
+                DPE_PROFILE,
+                &measurements,
+                &mut scratch,
+                &mut out,
+            )
+            .unwrap();
+            assert_eq!(len, out.len());
+            out
+        };
+
+        let first = digest(node);
+        assert_eq!(first, digest(node));
+
+        node.tci_current = TciMeasurement([0xBB; DPE_PROFILE.get_tci_size()]);
+        assert_ne!(first, digest(node));
+
+        let measurements = MeasurementData {
+            label: &label,
+            tci_nodes: &[node],
+            ..Default::default()
+        };
+        let mut scratch = [0u8; 1024];
+        let mut out = [0u8; DPE_PROFILE.get_hash_size() - 1];
+        assert_eq!(
+            CertWriter::extensions_digest(
+                &mut crypto,
+                DPE_PROFILE,
+                &measurements,
+                &mut scratch,
+                &mut out
+            ),
+            Err(DpeErrorCode::BufferTooSmall(
+                DPE_PROFILE.get_hash_size() as u32
+            ))
+        );
+
+        // The extensions must fit in the scratch buffer
+        let mut scratch = [0u8; 16];
+        let mut out = [0u8; DPE_PROFILE.get_hash_size()];
+        assert!(matches!(
+            CertWriter::extensions_digest(
+                &mut crypto,
+                DPE_PROFILE,
+                &measurements,
+                &mut scratch,
+                &mut out
+            ),
+            Err(DpeErrorCode::BufferTooSmall(_))
+        ));
+    }
+
+    /// Load the platform test key for the current profile, returning it with
+    /// its public key and the profile's digest
+    fn csr_test_key() -> (
+        openssl::ec::EcKey<openssl::pkey::Private>,
+        EcdsaPub,
+        openssl::hash::MessageDigest,
+    ) {
+        let (key_der, md) = match DPE_PROFILE {
+            DpeProfile::P256Sha256 => (
+                &include_bytes!("../../platform/src/test_data/key_256.der")[..],
+                openssl::hash::MessageDigest::sha256(),
+            ),
+            DpeProfile::P384Sha384 => (
+                &include_bytes!("../../platform/src/test_data/key_384.der")[..],
+                openssl::hash::MessageDigest::sha384(),
+            ),
+        };
+        let key = openssl::ec::EcKey::private_key_from_der(key_der).unwrap();
+        let mut ctx = openssl::bn::BigNumContext::new().unwrap();
+        let mut x = openssl::bn::BigNum::new().unwrap();
+        let mut y = openssl::bn::BigNum::new().unwrap();
+        key.public_key()
+            .affine_coordinates(key.group(), &mut x, &mut y, &mut ctx)
+            .unwrap();
+        let pub_key = EcdsaPub::from_coords(
+            &x.to_vec_padded(ECC_INT_SIZE as i32).unwrap(),
+            &y.to_vec_padded(ECC_INT_SIZE as i32).unwrap(),
+            DPE_PROFILE.alg_len(),
+        )
+        .unwrap();
+        (key, pub_key, md)
+    }
+
+    /// Sign `cri` with `key` as a CSR signer would
+    fn csr_test_sign(
+        key: &openssl::ec::EcKey<openssl::pkey::Private>,
+        md: openssl::hash::MessageDigest,
+        cri: &[u8],
+    ) -> EcdsaSig {
+        let digest = openssl::hash::hash(md, cri).unwrap();
+        let sig = openssl::ecdsa::EcdsaSig::sign(&digest, key).unwrap();
+        EcdsaSig {
+            r: CryptoBuf::new(&sig.r().to_vec()).unwrap(),
+            s: CryptoBuf::new(&sig.s().to_vec()).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_max_csr_size() {
+        let (key, pub_key, md) = csr_test_key();
+        let nodes = [TciNodeData::new(); MAX_TCI_NODES];
+        let measurements = MeasurementData {
+            label: &[0xCC; DPE_PROFILE.ueid_size()],
+            tci_nodes: &nodes,
+            supports_extend_tci: true,
+            ..Default::default()
+        };
+
+        let mut cri = vec![0u8; 8192];
+        let mut w = CertWriter::new(&mut cri, true);
+        let cri_len = w
+            .encode_certification_request_info(&pub_key, Some(&TEST_SUBJECT_NAME), &measurements)
+            .unwrap();
+        let cri = &cri[..cri_len];
+        let sig = csr_test_sign(&key, md, cri);
+
+        let mut csr = vec![0u8; 8192];
+        let mut w = CertWriter::new(&mut csr, true);
+        let csr_len = w.encode_csr(cri, &sig).unwrap();
+
+        // Only the leading zeros of the signature integers are unaccounted for
+        const BOUND: usize = DpeProfile::max_csr_size(MAX_TCI_NODES);
+        assert!(csr_len <= BOUND);
+        assert!(BOUND - csr_len <= 2);
+
+        // Smaller chains fit in smaller buffers, and larger ones are clamped
+        assert!(DpeProfile::max_csr_size(1) < BOUND);
+        assert_eq!(DpeProfile::max_csr_size(0), DpeProfile::max_csr_size(1));
+        assert_eq!(DpeProfile::max_csr_size(MAX_TCI_NODES + 1), BOUND);
+
+        // The bound agrees with the CSR size helpers for every chain length
+        let ecc_int = [0xFF; ECC_INT_SIZE];
+        let subject_name = Name {
+            cn: DirectoryString::PrintableString(CERTIFY_KEY_SUBJECT_CN),
+            serial: DirectoryString::PrintableString(&[b'0'; DPE_PROFILE.get_hash_size() * 2]),
+            order: NameOrder::CnFirst,
+        };
+        let pubkey = EcdsaPub::from_coords(&ecc_int, &ecc_int, DPE_PROFILE.alg_len()).unwrap();
+        let sig = EcdsaSig {
+            r: CryptoBuf::new(&ecc_int).unwrap(),
+            s: CryptoBuf::new(&ecc_int).unwrap(),
+        };
+        for tci_count in 1..=MAX_TCI_NODES {
+            for is_ca in [false, true] {
+                let measurements = MeasurementData {
+                    label: &[0; DPE_PROFILE.ueid_size()],
+                    tci_nodes: &nodes[..tci_count],
+                    is_ca,
+                    supports_extend_tci: true,
+                    ..Default::default()
+                };
+                let cert_req_info_size = CertWriter::get_certification_request_info_size(
+                    Some(&subject_name),
+                    &pubkey,
+                    &measurements,
+                    /*tagged=*/ true,
+                )
+                .unwrap();
+                assert_eq!(
+                    CertWriter::get_csr_size(cert_req_info_size, &sig, /*tagged=*/ true),
+                    Ok(DpeProfile::max_csr_size(tci_count)),
+                    "tci_count={tci_count} is_ca={is_ca}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_csr() {
+        let (key, pub_key, md) = csr_test_key();
+        let mut node = TciNodeData::new();
+        node.tci_current = TciMeasurement([0x5A; DPE_PROFILE.get_tci_size()]);
+        let measurements = MeasurementData {
+            label: &[0xCC; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[node],
+            supports_extend_tci: true,
+            ..Default::default()
+        };
+
+        let mut cri = [0u8; 1024];
+        let mut w = CertWriter::new(&mut cri, true);
+        let cri_len = w
+            .encode_certification_request_info(&pub_key, Some(&TEST_SUBJECT_NAME), &measurements)
+            .unwrap();
+        let cri = &cri[..cri_len];
+        let sig = csr_test_sign(&key, md, cri);
+
+        let mut csr = [0u8; 1024];
+        let mut w = CertWriter::new(&mut csr, true);
+        let csr_len = w.encode_csr(cri, &sig).unwrap();
+        assert_eq!(
+            csr_len,
+            CertWriter::get_csr_size(cri_len, &sig, /*tagged=*/ true).unwrap()
+        );
+        let csr = &csr[..csr_len];
+
+        // The signature covers the CertificationRequestInfo as written
+        let req = openssl::x509::X509Req::from_der(csr).unwrap();
+        assert_eq!(req.to_der().unwrap(), csr);
+        let pkey = openssl::pkey::PKey::from_ec_key(key).unwrap();
+        assert!(req.verify(&pkey).unwrap());
+        let (rem, parsed) = X509CertificationRequest::from_der(csr).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(parsed.certification_request_info.raw, cri);
+
+        let mut tampered = csr.to_vec();
+        let label_offset = tampered
+            .windows(DPE_PROFILE.get_hash_size())
+            .position(|w| w == measurements.label)
+            .unwrap();
+        tampered[label_offset] ^= 1;
+        let req = openssl::x509::X509Req::from_der(&tampered).unwrap();
+        assert!(!req.verify(&pkey).unwrap());
+
+        // The extensionRequest attribute carries the DICE extensions
+        let attr = parsed
+            .certification_request_info
+            .find_attribute(&oid!(1.2.840 .113549 .1 .9 .14))
+            .unwrap();
+        let ParsedCriAttribute::ExtensionRequest(ext_req) = attr.parsed_attribute() else {
+            panic!("extensionRequest was not parsed");
+        };
+        let dice_oids = [oid!(2.23.133 .5 .4 .5), oid!(2.23.133 .5 .4 .4)];
+        for dice_oid in &dice_oids {
+            let ext = ext_req
+                .extensions
+                .iter()
+                .find(|ext| &ext.oid == dice_oid)
+                .unwrap();
+            assert!(ext.critical);
+        }
+        let ext = ext_req
+            .extensions
+            .iter()
+            .find(|ext| ext.oid == dice_oids[0])
+            .unwrap();
+        let tcb_infos = asn1::parse_single::<asn1::SequenceOf<TcbInfo>>(ext.value).unwrap();
+        let fwids: Vec<Fwid> = tcb_infos.last().unwrap().fwids.unwrap().collect();
+        assert_eq!(fwids[0].digest, node.tci_current.0);
+    }
+
+    #[test]
+    fn test_validate_der() {
+        // SEQUENCE { INTEGER 5, SEQUENCE { OCTET STRING { 0x30 } } }
+        let der = [0x30, 0x08, 0x02, 0x01, 0x05, 0x30, 0x03, 0x04, 0x01, 0x30];
+        assert_eq!(CertWriter::validate_der(&der), Ok(()));
+
+        // Primitive contents are opaque, even if they look like a bad header
+        assert_eq!(CertWriter::validate_der(&der[7..]), Ok(()));
+
+        // The inner SEQUENCE runs past its parent
+        let mut bad = der;
+        bad[6] = 0x04;
+        assert_eq!(CertWriter::validate_der(&bad), Err(5));
+
+        // Truncated top-level element
+        assert_eq!(CertWriter::validate_der(&der[..9]), Err(0));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "malformed DER at offset")]
+    fn test_debug_check_der() {
+        let (key, pub_key, md) = csr_test_key();
+        let measurements = MeasurementData {
+            label: &[0xCC; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[TciNodeData::new()],
+            ..Default::default()
+        };
+
+        let mut cri = [0u8; 1024];
+        let mut w = CertWriter::new(&mut cri, true);
+        let cri_len = w
+            .encode_certification_request_info(&pub_key, Some(&TEST_SUBJECT_NAME), &measurements)
+            .unwrap();
+        let sig = csr_test_sign(&key, md, &cri[..cri_len]);
+
+        let mut csr = [0u8; 1024];
+        let mut w = CertWriter::new(&mut csr, true);
+        w.corrupt_der = true;
+        let _ = w.encode_csr(&cri[..cri_len], &sig);
+    }
+
+    #[test]
+    fn test_csr_empty_subject() {
+        let (key, pub_key, md) = csr_test_key();
+        let node = TciNodeData::new();
+        let measurements = MeasurementData {
+            label: &[0xCC; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[node],
+            ..Default::default()
+        };
 
+        let mut cri = [0u8; 1024];
+        let mut w = CertWriter::new(&mut cri, true);
+        let cri_len = w
+            .encode_certification_request_info(&pub_key, None, &measurements)
+            .unwrap();
         assert_eq!(
-            CertWriter::get_ecdsa_subject_pubkey_info_size(&test_key, true).unwrap(),
-            bytes_written
+            cri_len,
+            CertWriter::get_certification_request_info_size(
+                None,
+                &pub_key,
+                &measurements,
+                /*tagged=*/ true
+            )
+            .unwrap()
         );
+
+        let sig = csr_test_sign(&key, md, &cri[..cri_len]);
+        let mut csr = [0u8; 1024];
+        let mut w = CertWriter::new(&mut csr, true);
+        let csr_len = w.encode_csr(&cri[..cri_len], &sig).unwrap();
+
+        // The subject is an empty RDNSequence for the CA to fill in
+        let req = openssl::x509::X509Req::from_der(&csr[..csr_len]).unwrap();
+        assert_eq!(req.subject_name().entries().count(), 0);
+        let pkey = openssl::pkey::PKey::from_ec_key(key).unwrap();
+        assert!(req.verify(&pkey).unwrap());
     }
 
     #[test]
-    fn test_tcb_info() {
-        let mut node = TciNodeData::new();
+    fn test_android_attestation_profile() {
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let node = TciNodeData::new();
 
-        node.tci_type = 0x11223344;
-        node.tci_cumulative = TciMeasurement([0xaau8; DPE_PROFILE.get_hash_size()]);
-        node.tci_current = TciMeasurement([0xbbu8; DPE_PROFILE.get_hash_size()]);
-        node.locality = 0xFFFFFFFF;
+        let get_extensions = |cert_profile: CertProfile| -> Vec<(String, bool)> {
+            let measurements = MeasurementData {
+                label: &[0; DPE_PROFILE.get_hash_size()],
+                tci_nodes: &[node],
+                is_ca: true,
+                cert_profile,
+                ..Default::default()
+            };
+
+            let mut cert = [0u8; 1024];
+            let mut w = CertWriter::new(&mut cert, /*crit_dice=*/ true);
+            let bytes_written = w
+                .encode_ecdsa_tbs(
+                    TEST_SERIAL,
+                    &issuer_der,
+                    &TEST_SUBJECT_NAME,
+                    &test_pub,
+                    &measurements,
+                )
+                .unwrap();
+            assert_eq!(
+                bytes_written,
+                CertWriter::get_tbs_size(
+                    TEST_SERIAL,
+                    &issuer_der,
+                    &TEST_SUBJECT_NAME,
+                    &test_pub,
+                    &measurements,
+                    /*tagged=*/ true,
+                )
+                .unwrap()
+            );
 
-        let mut cert = [0u8; 256];
-        let mut w = CertWriter::new(&mut cert, true);
-        let mut supports_extend_tci = true;
-        let mut bytes_written = w.encode_tcb_info(&node, supports_extend_tci).unwrap();
+            let mut parser = TbsCertificateParser::new().with_deep_parse_extensions(true);
+            let tbs = parser.parse(&cert[..bytes_written]).unwrap().1;
+            tbs.extensions()
+                .iter()
+                .map(|ext| (ext.oid.to_id_string(), ext.critical))
+                .collect()
+        };
 
-        let mut parsed_tcb_info = asn1::parse_single::<TcbInfo>(&cert[..bytes_written]).unwrap();
+        let expected: Vec<(String, bool)> = [
+            ("2.5.29.19", true),
+            ("2.5.29.15", true),
+            ("2.5.29.37", false),
+            ("2.23.133.5.4.5", false),
+            ("2.23.133.5.4.4", false),
+        ]
+        .iter()
+        .map(|(oid, crit)| (oid.to_string(), *crit))
+        .collect();
+        assert_eq!(get_extensions(CertProfile::AndroidAttestation), expected);
+
+        let expected: Vec<(String, bool)> = [
+            ("2.23.133.5.4.5", true),
+            ("2.23.133.5.4.4", true),
+            ("2.5.29.19", true),
+            ("2.5.29.15", true),
+            ("2.5.29.37", true),
+        ]
+        .iter()
+        .map(|(oid, crit)| (oid.to_string(), *crit))
+        .collect();
+        assert_eq!(get_extensions(CertProfile::Dice), expected);
+    }
 
+    #[test]
+    fn test_checked_sum() {
+        assert_eq!(CertWriter::checked_sum(&[]), Ok(0));
+        assert_eq!(CertWriter::checked_sum(&[1, 2, 3]), Ok(6));
         assert_eq!(
-            bytes_written,
-            CertWriter::get_tcb_info_size(&node, supports_extend_tci, true).unwrap()
+            CertWriter::checked_sum(&[usize::MAX - 1, 1]),
+            Ok(usize::MAX)
         );
-
-        // FWIDs
-        let mut fwid_itr = parsed_tcb_info.fwids.unwrap();
-        let expected_current = fwid_itr.next().unwrap().digest;
-        let expected_cumulative = fwid_itr.next().unwrap().digest;
-        assert_eq!(expected_current, node.tci_current.0);
-        assert_eq!(expected_cumulative, node.tci_cumulative.0);
-
         assert_eq!(
-            parsed_tcb_info.tci_type.unwrap(),
-            node.tci_type.to_be_bytes()
+            CertWriter::checked_sum(&[usize::MAX - 1, 1, 1]),
+            Err(DpeErrorCode::InternalError)
         );
         assert_eq!(
-            parsed_tcb_info.vendor_info.unwrap(),
-            node.locality.to_be_bytes()
+            CertWriter::checked_sum(&[usize::MAX, usize::MAX]),
+            Err(DpeErrorCode::InternalError)
         );
 
-        // test tbs_info with supports_extend_tci = false
-        supports_extend_tci = false;
-        w = CertWriter::new(&mut cert, true);
-        bytes_written = w.encode_tcb_info(&node, supports_extend_tci).unwrap();
-
-        parsed_tcb_info = asn1::parse_single::<TcbInfo>(&cert[..bytes_written]).unwrap();
-
+        // Structures too large for a size field are rejected before any sum
+        // could wrap
         assert_eq!(
-            bytes_written,
-            CertWriter::get_tcb_info_size(&node, supports_extend_tci, true).unwrap()
+            CertWriter::get_structure_size(usize::MAX, /*tagged=*/ true),
+            Err(DpeErrorCode::InternalError)
         );
 
-        // Check that only FWID[0] is present
-        let mut fwid_itr = parsed_tcb_info.fwids.unwrap();
-        let expected_current = fwid_itr.next().unwrap().digest;
-        assert!(fwid_itr.next().is_none());
-        assert_eq!(expected_current, node.tci_current.0);
-    }
-
-    fn get_key_usage(is_ca: bool) -> KeyUsage {
-        let mut cert = [0u8; 32];
-        let mut w = CertWriter::new(&mut cert, true);
-        let bytes_written = w.encode_key_usage(is_ca).unwrap();
+        // Size helpers handed a length just short of usize::MAX fail where a
+        // wrapping sum would report a small size
+        const ECC_INT_SIZE: usize = DPE_PROFILE.get_ecc_int_size();
+        let sig = EcdsaSig {
+            r: CryptoBuf::new(&[0xCC; ECC_INT_SIZE]).unwrap(),
+            s: CryptoBuf::new(&[0xDD; ECC_INT_SIZE]).unwrap(),
+        };
+        let huge = usize::MAX - 8;
         assert_eq!(
-            bytes_written,
-            CertWriter::get_key_usage_size(/*tagged=*/ true).unwrap()
+            CertWriter::get_ecdsa_certificate_size(huge, &sig, /*tagged=*/ false),
+            Err(DpeErrorCode::InternalError)
         );
-
-        let mut parser = X509ExtensionParser::new().with_deep_parse_extensions(false);
-        let ext = parser.parse(&cert[..bytes_written]).unwrap().1;
-        KeyUsage::from_der(ext.value).unwrap().1
+        assert_eq!(
+            CertWriter::get_csr_size(huge, &sig, /*tagged=*/ false),
+            Err(DpeErrorCode::InternalError)
+        );
+        assert!(CertWriter::get_ecdsa_certificate_size(8, &sig, /*tagged=*/ false).is_ok());
+        assert!(CertWriter::get_csr_size(8, &sig, /*tagged=*/ false).is_ok());
     }
 
     #[test]
-    fn test_key_usage() {
-        // Make sure leaf keyUsage is only digitalSignature
-        let leaf_key_usage = get_key_usage(/*is_ca=*/ false);
-        let expected = 1u16;
-        assert!(leaf_key_usage.flags | expected == expected);
-
-        // Make sure leaf keyUsage is digitalSignature | keyCertSign
-        let ca_key_usage = get_key_usage(/*is_ca=*/ true);
-        let expected = (1u16 << 5) | 1u16;
-        assert!(ca_key_usage.flags | expected == expected);
+    fn test_single_pass_structure() {
+        // Cover each width of the back-patched size field
+        for contents_size in [0, 1, 127, 128, 255, 256, 1000] {
+            let contents = vec![0xA5; contents_size];
+
+            let mut expected = [0u8; 1024 + 4];
+            let mut w = CertWriter::new(&mut expected, true);
+            let expected_len = w
+                .encode_structure(
+                    CertWriter::OCTET_STRING_TAG,
+                    || Ok(contents.len()),
+                    |w| w.encode_bytes(&contents),
+                )
+                .unwrap();
+
+            let mut actual = [0u8; 1024 + 4];
+            let mut w = CertWriter::new_single_pass(&mut actual, true);
+            let actual_len = w
+                .encode_structure(
+                    CertWriter::OCTET_STRING_TAG,
+                    || panic!("size must not be computed in single-pass mode"),
+                    |w| w.encode_bytes(&contents),
+                )
+                .unwrap();
+
+            assert_eq!(actual_len, expected_len);
+            assert_eq!(w.offset, actual_len);
+            assert_eq!(&actual[..actual_len], &expected[..expected_len]);
+        }
     }
 
     #[test]
-    fn test_tbs() {
-        let mut cert = [0u8; 4096];
-        let mut w = CertWriter::new(&mut cert, true);
-
-        let test_serial = [0x1F; 20];
+    fn test_single_pass_matches_two_pass() {
         let issuer_der = encode_test_issuer();
-
-        let test_subject_name = Name {
-            cn: DirectoryString::PrintableString(b"DPE Leaf"),
-            serial: DirectoryString::PrintableString(&[0x00; DPE_PROFILE.get_hash_size() * 2]),
-        };
-
-        const ECC_INT_SIZE: usize = DPE_PROFILE.get_ecc_int_size();
-        let test_pub = EcdsaPub {
-            x: CryptoBuf::new(&[0xAA; ECC_INT_SIZE]).unwrap(),
-            y: CryptoBuf::new(&[0xBB; ECC_INT_SIZE]).unwrap(),
-        };
-
-        let node = TciNodeData::new();
-
-        let measurements = MeasurementData {
-            label: &[0xCC; DPE_PROFILE.get_hash_size()],
-            tci_nodes: &[node],
-            is_ca: false,
-            supports_extend_tci: true,
-        };
-
-        let bytes_written = w
-            .encode_ecdsa_tbs(
-                &test_serial,
-                &issuer_der,
-                &test_subject_name,
-                &test_pub,
-                &measurements,
-            )
-            .unwrap();
-
-        let mut parser = TbsCertificateParser::new().with_deep_parse_extensions(false);
-        let cert = match parser.parse(&cert) {
-            Ok((rem, parsed_cert)) => {
-                assert_eq!(parsed_cert.version(), X509Version::V3);
-                assert_eq!(rem.len(), cert.len() - bytes_written);
-                parsed_cert
-            }
-            Err(e) => panic!("x509 parsing failed: {:?}", e),
+        let test_pub = EcdsaPub::from_coords(
+            &[0xAA; ECC_INT_SIZE],
+            &[0xBB; ECC_INT_SIZE],
+            DPE_PROFILE.alg_len(),
+        )
+        .unwrap();
+        let test_sig = EcdsaSig {
+            r: CryptoBuf::new(&[0xCC; ECC_INT_SIZE]).unwrap(),
+            s: CryptoBuf::new(&[0xDD; ECC_INT_SIZE]).unwrap(),
         };
-
-        let ueid = cert
-            .get_extension_unique(&oid!(2.23.133 .5 .4 .4))
-            .unwrap()
-            .unwrap();
-        assert!(ueid.critical);
-        let parsed_ueid = asn1::parse_single::<Ueid>(ueid.value).unwrap();
-        assert_eq!(parsed_ueid.ueid, measurements.label);
+        let nodes = [TciNodeData::new(), TciNodeData::new()];
+
+        for is_ca in [false, true] {
+            let measurements = MeasurementData {
+                label: &[0xCC; DPE_PROFILE.get_hash_size()],
+                tci_nodes: &nodes,
+                is_ca,
+                supports_extend_tci: true,
+                ..Default::default()
+            };
+
+            let encode_cert = |single_pass: bool, cert: &mut [u8]| -> usize {
+                let mut tbs = [0u8; 2048];
+                let mut w = if single_pass {
+                    CertWriter::new_single_pass(&mut tbs, true)
+                } else {
+                    CertWriter::new(&mut tbs, true)
+                };
+                let tbs_len = w
+                    .encode_ecdsa_tbs(
+                        TEST_SERIAL,
+                        &issuer_der,
+                        &TEST_SUBJECT_NAME,
+                        &test_pub,
+                        &measurements,
+                    )
+                    .unwrap();
+
+                let mut w = if single_pass {
+                    CertWriter::new_single_pass(cert, true)
+                } else {
+                    CertWriter::new(cert, true)
+                };
+                w.encode_ecdsa_certificate(&tbs[..tbs_len], &test_sig)
+                    .unwrap()
+            };
+
+            let encode_csr = |single_pass: bool, csr: &mut [u8]| -> usize {
+                let mut cri = [0u8; 2048];
+                let mut w = if single_pass {
+                    CertWriter::new_single_pass(&mut cri, true)
+                } else {
+                    CertWriter::new(&mut cri, true)
+                };
+                let cri_len = w
+                    .encode_certification_request_info(
+                        &test_pub,
+                        Some(&TEST_SUBJECT_NAME),
+                        &measurements,
+                    )
+                    .unwrap();
+
+                let mut w = if single_pass {
+                    CertWriter::new_single_pass(csr, true)
+                } else {
+                    CertWriter::new(csr, true)
+                };
+                w.encode_csr(&cri[..cri_len], &test_sig).unwrap()
+            };
+
+            let mut two_pass = [0u8; 2048];
+            let mut single_pass = [0u8; 2048];
+
+            let two_pass_len = encode_cert(false, &mut two_pass);
+            let single_pass_len = encode_cert(true, &mut single_pass);
+            assert_eq!(&single_pass[..single_pass_len], &two_pass[..two_pass_len]);
+            X509Certificate::from_der(&single_pass[..single_pass_len]).unwrap();
+
+            let two_pass_len = encode_csr(false, &mut two_pass);
+            let single_pass_len = encode_csr(true, &mut single_pass);
+            assert_eq!(&single_pass[..single_pass_len], &two_pass[..two_pass_len]);
+            X509CertificationRequest::from_der(&single_pass[..single_pass_len]).unwrap();
+        }
     }
 
     const TEST_SERIAL: &[u8] = &[0x1F; 20];
     const TEST_ISSUER_NAME: Name = Name {
         cn: DirectoryString::PrintableString(b"Caliptra Alias"),
         serial: DirectoryString::PrintableString(&[0x00; DPE_PROFILE.get_hash_size() * 2]),
+        order: NameOrder::CnFirst,
     };
     const TEST_SUBJECT_NAME: Name = Name {
         cn: DirectoryString::PrintableString(b"DPE Leaf"),
         serial: DirectoryString::PrintableString(&[0x00; DPE_PROFILE.get_hash_size() * 2]),
+        order: NameOrder::CnFirst,
     };
 
     const ECC_INT_SIZE: usize = DPE_PROFILE.get_ecc_int_size();
@@ -2049,10 +10829,12 @@ mod tests {
         let mut issuer_writer = CertWriter::new(&mut issuer_der, true);
         let issuer_len = issuer_writer.encode_rdn(&TEST_ISSUER_NAME).unwrap();
 
-        let test_pub = EcdsaPub {
-            x: CryptoBuf::new(&[0xAA; ECC_INT_SIZE]).unwrap(),
-            y: CryptoBuf::new(&[0xBB; ECC_INT_SIZE]).unwrap(),
-        };
+        let test_pub = EcdsaPub::from_coords(
+            &[0xAA; ECC_INT_SIZE],
+            &[0xBB; ECC_INT_SIZE],
+            DPE_PROFILE.alg_len(),
+        )
+        .unwrap();
 
         let node = TciNodeData::new();
 
@@ -2061,6 +10843,7 @@ mod tests {
             tci_nodes: &[node],
             is_ca,
             supports_extend_tci: true,
+            ..Default::default()
         };
 
         let mut tbs_writer = CertWriter::new(cert_buf, true);
@@ -2177,4 +10960,782 @@ mod tests {
             Err(_) => panic!("multiple extended key usage extensions found"),
         };
     }
+
+    #[test]
+    fn test_attestation_key_purposes() {
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let node = TciNodeData::new();
+
+        let get_eku = |is_ca: bool| {
+            let measurements = MeasurementData {
+                label: &[0; DPE_PROFILE.get_hash_size()],
+                tci_nodes: &[node],
+                is_ca,
+                key_purposes: KeyPurposes::AttestationKey,
+                ..Default::default()
+            };
+            let mut tbs = [0u8; 1024];
+            let mut w = CertWriter::new(&mut tbs, true);
+            let bytes_written = w
+                .encode_ecdsa_tbs(
+                    TEST_SERIAL,
+                    &issuer_der,
+                    &TEST_SUBJECT_NAME,
+                    &test_pub,
+                    &measurements,
+                )
+                .unwrap();
+
+            let mut parser = TbsCertificateParser::new().with_deep_parse_extensions(true);
+            let tbs = parser.parse(&tbs[..bytes_written]).unwrap().1;
+            let eku = tbs.extended_key_usage().unwrap().unwrap();
+            assert!(eku.critical);
+            eku.value
+                .other
+                .iter()
+                .map(|oid| oid.to_id_string())
+                .collect::<Vec<_>>()
+        };
+
+        // tcg-kp-AIKCertificate and tcg-dice-kp-attestLoc
+        assert_eq!(
+            get_eku(/*is_ca=*/ false),
+            ["2.23.133.8.3", "2.23.133.5.4.100.9"]
+        );
+
+        // CA certificates keep tcg-dice-kp-eca
+        assert_eq!(get_eku(/*is_ca=*/ true), ["2.23.133.5.4.100.12"]);
+    }
+
+    #[test]
+    fn test_custom_key_purposes() {
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let node = TciNodeData::new();
+        let base = MeasurementData {
+            label: &[0; DPE_PROFILE.get_hash_size()],
+            tci_nodes: &[node],
+            key_purposes: KeyPurposes::Custom(
+                DiceKeyPurposes::ATTEST_LOC | DiceKeyPurposes::IDENTITY_LOC,
+            ),
+            ..Default::default()
+        };
+
+        let mut tbs = [0u8; 1024];
+        let mut w = CertWriter::new(&mut tbs, true);
+        let bytes_written = w
+            .encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &base,
+            )
+            .unwrap();
+
+        let mut parser = TbsCertificateParser::new().with_deep_parse_extensions(true);
+        let parsed = parser.parse(&tbs[..bytes_written]).unwrap().1;
+        let eku = parsed.extended_key_usage().unwrap().unwrap();
+        // tcg-dice-kp-identityLoc and tcg-dice-kp-attestLoc, in OID order
+        assert_eq!(
+            eku.value.other,
+            [oid!(2.23.133 .5 .4 .100 .7), oid!(2.23.133 .5 .4 .100 .9)]
+        );
+
+        // Every flag maps to its own OID
+        let all = MeasurementData {
+            key_purposes: KeyPurposes::Custom(DiceKeyPurposes::all()),
+            ..base
+        };
+        let oids: Vec<_> = CertWriter::get_key_purpose_oids(&all).collect();
+        let expected: Vec<_> = (6..=12)
+            .map(|arc| [0x67, 0x81, 0x05, 0x05, 0x04, 0x64, arc])
+            .collect();
+        assert_eq!(oids, expected);
+
+        // A leaf certificate needs at least one key purpose
+        let none = MeasurementData {
+            key_purposes: KeyPurposes::Custom(DiceKeyPurposes::empty()),
+            ..base
+        };
+        assert_eq!(
+            none.validate(DPE_PROFILE),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+        let mut w = CertWriter::new(&mut tbs, true);
+        assert_eq!(
+            w.encode_ecdsa_tbs(
+                TEST_SERIAL,
+                &issuer_der,
+                &TEST_SUBJECT_NAME,
+                &test_pub,
+                &none,
+            ),
+            Err(DpeErrorCode::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_cert_roles() {
+        let issuer_der = encode_test_issuer();
+        let test_pub = EcdsaPub::zeroed(DPE_PROFILE.alg_len());
+        let node = TciNodeData::new();
+        let eca = oid!(2.23.133 .5 .4 .100 .12);
+        let attest_loc = oid!(2.23.133 .5 .4 .100 .9);
+
+        // (role, keyUsage, cA, pathLenConstraint, EKU key purposes)
+        let roles = [
+            (
+                CertRole::AttestationLeaf,
+                KeyUsageFlags::DIGITAL_SIGNATURE,
+                false,
+                None,
+                Some(vec![attest_loc]),
+            ),
+            (
+                CertRole::EmbeddedCa,
+                KeyUsageFlags::DIGITAL_SIGNATURE | KeyUsageFlags::KEY_CERT_SIGN,
+                true,
+                None,
+                Some(vec![eca.clone()]),
+            ),
+            (
+                CertRole::CrossSigningCa,
+                KeyUsageFlags::KEY_CERT_SIGN | KeyUsageFlags::CRL_SIGN,
+                true,
+                Some(1),
+                Some(vec![eca]),
+            ),
+            (
+                CertRole::KeyAgreement,
+                KeyUsageFlags::KEY_AGREEMENT,
+                false,
+                None,
+                None,
+            ),
+        ];
+
+        for (role, key_usage, is_ca, path_len, key_purposes) in roles {
+            let mut measurements = MeasurementData {
+                label: &[0; DPE_PROFILE.get_hash_size()],
+                tci_nodes: &[node],
+                ..Default::default()
+            };
+            measurements.apply_role(role);
+            assert_eq!(measurements.validate(DPE_PROFILE), Ok(()), "{role:?}");
+
+            let mut tbs = [0u8; 1024];
+            let mut w = CertWriter::new(&mut tbs, true);
+            let bytes_written = w
+                .encode_ecdsa_tbs(
+                    TEST_SERIAL,
+                    &issuer_der,
+                    &TEST_SUBJECT_NAME,
+                    &test_pub,
+                    &measurements,
+                )
+                .unwrap();
+
+            let mut parser = TbsCertificateParser::new().with_deep_parse_extensions(true);
+            let parsed = parser.parse(&tbs[..bytes_written]).unwrap().1;
+
+            let parsed_key_usage = parsed.key_usage().unwrap().unwrap().value;
+            assert_eq!(
+                parsed_key_usage.flags,
+                key_usage.bits().reverse_bits(),
+                "{role:?}"
+            );
+
+            let basic_constraints = parsed.basic_constraints().unwrap().unwrap().value;
+            assert_eq!(basic_constraints.ca, is_ca, "{role:?}");
+            assert_eq!(basic_constraints.path_len_constraint, path_len, "{role:?}");
+
+            let eku = parsed.extended_key_usage().unwrap();
+            assert_eq!(
+                eku.map(|eku| eku.value.other.clone()),
+                key_purposes,
+                "{role:?}"
+            );
+        }
+
+        // is_ca is shorthand for the attestation leaf and embedded CA roles
+        for (is_ca, role) in [
+            (false, CertRole::AttestationLeaf),
+            (true, CertRole::EmbeddedCa),
+        ] {
+            let shorthand = MeasurementData {
+                tci_nodes: &[node],
+                is_ca,
+                ..Default::default()
+            };
+            let extensions = role.extensions();
+            assert!(shorthand.get_key_usage() == extensions.key_usage);
+            assert_eq!(
+                shorthand.path_len_constraint,
+                extensions.path_len_constraint
+            );
+            let mut with_role = MeasurementData {
+                tci_nodes: &[node],
+                ..Default::default()
+            };
+            with_role.apply_role(role);
+            assert!(CertWriter::get_key_purpose_oids(&shorthand)
+                .eq(CertWriter::get_key_purpose_oids(&with_role)));
+        }
+    }
+
+    /// Inputs shared by the `Encoder` size checks for one combination of
+    /// measurement parameters
+    struct SizeCase<'a> {
+        measurements: MeasurementData<'a>,
+        issuer_der: Vec<u8>,
+        pubkey: &'a EcdsaPub,
+        sig: &'a EcdsaSig,
+        issuer_serial: Vec<u8>,
+        spki: Vec<u8>,
+        tbs: Vec<u8>,
+        cert: Vec<u8>,
+        cert_sig: Vec<u8>,
+        cert_req_info: Vec<u8>,
+        csr: Vec<u8>,
+    }
+
+    impl SizeCase<'_> {
+        fn signer_id(&self) -> SignerId {
+            SignerId::IssuerAndSerialNumber {
+                issuer_name: &self.issuer_der,
+                serial_number: TEST_SERIAL,
+            }
+        }
+    }
+
+    /// Returns the size an encoder's size function predicts, including the
+    /// tag and size fields, and the bytes it wrote
+    type SizeCheck = fn(&SizeCase, &mut CertWriter) -> Result<(usize, usize), DpeErrorCode>;
+
+    /// Every encoder, including every public one. Adding a variant without
+    /// a size check in `Encoder::size_check` does not compile, and
+    /// `check_sizes` runs all of them.
+    #[derive(Clone, Copy, Debug, strum_macros::EnumIter)]
+    enum Encoder {
+        IntegerFixedWidth,
+        NameFromDer,
+        Rdn,
+        Validity,
+        EcdsaSubjectPubkeyInfo,
+        Spki,
+        SpkiRaw,
+        Version,
+        Fwid,
+        FwidBytes,
+        TcbInfo,
+        DiceTcbInfo,
+        MultiTcbInfo,
+        Ueid,
+        UeidValue,
+        HolderReference,
+        EatClaimsExtension,
+        AltSignatureAlgorithm,
+        AltSignatureValue,
+        BasicConstraints,
+        KeyUsage,
+        ExtendedKeyUsage,
+        PolicyConstraints,
+        InhibitAnyPolicy,
+        Extensions,
+        ExtensionRequest,
+        Attributes,
+        SignerInfo,
+        IssuerAndSerialNumber,
+        EcdsaTbs,
+        SelfSignedEcdsaTbs,
+        EcdsaTbsRecord,
+        EcdsaCertificate,
+        Certificate,
+        CertificationRequestInfo,
+        Csr,
+        Cms,
+        CertsOnlyCms,
+    }
+
+    impl Encoder {
+        /// The encoder's size function, checked against what it writes
+        fn size_check(self) -> SizeCheck {
+            match self {
+                Encoder::IntegerFixedWidth => |c, w| {
+                    let integer = c.measurements.label.len() as u64;
+                    Ok((
+                        CertWriter::get_integer_fixed_width_size(9, /*tagged=*/ true)?,
+                        w.encode_integer_fixed_width(integer, 9)?,
+                    ))
+                },
+                Encoder::NameFromDer => {
+                    |c, w| Ok((c.issuer_der.len(), w.encode_name_from_der(&c.issuer_der)?))
+                }
+                Encoder::Rdn => |_, w| {
+                    Ok((
+                        CertWriter::get_rdn_size(&TEST_SUBJECT_NAME, /*tagged=*/ true)?,
+                        w.encode_rdn(&TEST_SUBJECT_NAME)?,
+                    ))
+                },
+                Encoder::Validity => |c, w| {
+                    Ok((
+                        CertWriter::get_validity_size(&c.measurements, /*tagged=*/ true)?,
+                        w.encode_validity(&c.measurements)?,
+                    ))
+                },
+                Encoder::EcdsaSubjectPubkeyInfo => |c, w| {
+                    let point_format = c.measurements.point_format;
+                    Ok((
+                        CertWriter::get_ecdsa_subject_pubkey_info_size(
+                            c.pubkey,
+                            point_format,
+                            /*tagged=*/ true,
+                        )?,
+                        w.encode_ecdsa_subject_pubkey_info(c.pubkey, point_format)?,
+                    ))
+                },
+                Encoder::Spki => |c, w| {
+                    Ok((
+                        CertWriter::get_spki_size(c.pubkey, &c.measurements)?,
+                        w.encode_spki(c.pubkey, &c.measurements)?,
+                    ))
+                },
+                Encoder::SpkiRaw => |c, w| Ok((c.spki.len(), w.encode_spki_raw(&c.spki)?)),
+                Encoder::Version => |c, w| {
+                    let version = c.measurements.version;
+                    Ok((
+                        CertWriter::get_version_size(version, /*tagged=*/ true)?,
+                        w.encode_version(version)?,
+                    ))
+                },
+                Encoder::Fwid => |c, w| {
+                    let hash_oid = CertWriter::get_hash_oid(None);
+                    let tci = &c.measurements.tci_nodes[0].tci_current;
+                    Ok((
+                        CertWriter::get_fwid_size(hash_oid, tci.0.len(), /*tagged=*/ true)?,
+                        w.encode_fwid(tci, hash_oid, tci.0.len())?,
+                    ))
+                },
+                Encoder::FwidBytes => |c, w| {
+                    let hash_oid = CertWriter::get_hash_oid(None);
+                    let digest = &c.measurements.tci_nodes[0].tci_cumulative.0;
+                    Ok((
+                        CertWriter::get_fwid_size(hash_oid, digest.len(), /*tagged=*/ true)?,
+                        w.encode_fwid_bytes(hash_oid, digest)?,
+                    ))
+                },
+                Encoder::TcbInfo => |c, w| {
+                    let node = &c.measurements.tci_nodes[0];
+                    let extend_tci = c.measurements.supports_extend_tci;
+                    let hash_oid = CertWriter::get_hash_oid(None);
+                    Ok((
+                        CertWriter::get_tcb_info_size(
+                            node,
+                            /*has_fwids=*/ true,
+                            extend_tci,
+                            &[],
+                            /*fwid_digests=*/ None,
+                            hash_oid,
+                            /*tagged=*/ true,
+                        )?,
+                        w.encode_tcb_info(
+                            node,
+                            /*has_fwids=*/ true,
+                            extend_tci,
+                            &[],
+                            /*fwid_digests=*/ None,
+                            hash_oid,
+                        )?,
+                    ))
+                },
+                Encoder::DiceTcbInfo => |c, w| {
+                    Ok((
+                        CertWriter::get_dice_tcb_info_size(&c.measurements, /*tagged=*/ true)?,
+                        w.encode_dice_tcb_info(&c.measurements)?,
+                    ))
+                },
+                Encoder::MultiTcbInfo => |c, w| {
+                    Ok((
+                        CertWriter::get_multi_tcb_info_size(
+                            &c.measurements,
+                            /*tagged=*/ true,
+                        )?,
+                        w.encode_multi_tcb_info(&c.measurements)?,
+                    ))
+                },
+                Encoder::Ueid => |c, w| {
+                    Ok((
+                        CertWriter::get_ueid_size(&c.measurements, /*tagged=*/ true)?,
+                        w.encode_ueid(&c.measurements)?,
+                    ))
+                },
+                Encoder::UeidValue => |c, w| {
+                    let label = c.measurements.label;
+                    Ok((
+                        CertWriter::get_ueid_value_size(label)?,
+                        w.encode_ueid_value(label)?,
+                    ))
+                },
+                Encoder::HolderReference => |c, w| {
+                    Ok((
+                        CertWriter::get_holder_reference_size(
+                            &c.issuer_serial,
+                            /*tagged=*/ true,
+                        )?,
+                        w.encode_holder_reference(&c.issuer_serial)?,
+                    ))
+                },
+                Encoder::EatClaimsExtension => |c, w| {
+                    let cbor = c.measurements.label;
+                    Ok((
+                        CertWriter::get_eat_claims_extension_size(cbor, /*tagged=*/ true)?,
+                        w.encode_eat_claims_extension(cbor)?,
+                    ))
+                },
+                Encoder::AltSignatureAlgorithm => |_, w| {
+                    Ok((
+                        CertWriter::get_alt_signature_algorithm_size(/*tagged=*/ true)?,
+                        w.encode_alt_signature_algorithm()?,
+                    ))
+                },
+                Encoder::AltSignatureValue => |c, w| {
+                    Ok((
+                        CertWriter::get_alt_signature_value_size(c.sig, /*tagged=*/ true)?,
+                        w.encode_alt_signature_value(c.sig)?,
+                    ))
+                },
+                Encoder::BasicConstraints => |c, w| {
+                    Ok((
+                        CertWriter::get_basic_constraints_size(
+                            &c.measurements,
+                            /*tagged=*/ true,
+                        )?,
+                        w.encode_basic_constraints(&c.measurements)?,
+                    ))
+                },
+                Encoder::KeyUsage => |c, w| {
+                    Ok((
+                        CertWriter::get_key_usage_size(&c.measurements, /*tagged=*/ true)?,
+                        w.encode_key_usage(&c.measurements)?,
+                    ))
+                },
+                Encoder::ExtendedKeyUsage => |c, w| {
+                    Ok((
+                        CertWriter::get_extended_key_usage_size(
+                            &c.measurements,
+                            /*tagged=*/ true,
+                        )?,
+                        w.encode_extended_key_usage(&c.measurements)?,
+                    ))
+                },
+                Encoder::PolicyConstraints => |c, w| {
+                    Ok((
+                        CertWriter::get_policy_constraints_size(
+                            &c.measurements,
+                            /*tagged=*/ true,
+                        )?,
+                        w.encode_policy_constraints(&c.measurements)?,
+                    ))
+                },
+                Encoder::InhibitAnyPolicy => |c, w| {
+                    Ok((
+                        CertWriter::get_inhibit_any_policy_size(
+                            &c.measurements,
+                            /*tagged=*/ true,
+                        )?,
+                        w.encode_inhibit_any_policy(&c.measurements)?,
+                    ))
+                },
+                Encoder::Extensions => |c, w| {
+                    Ok((
+                        CertWriter::get_extensions_size(
+                            &c.measurements,
+                            /*tagged=*/ true,
+                            /*explicit=*/ true,
+                        )?,
+                        w.encode_extensions(&c.measurements, /*explicit=*/ true)?,
+                    ))
+                },
+                Encoder::ExtensionRequest => |c, w| {
+                    Ok((
+                        CertWriter::get_extension_request_size(
+                            &c.measurements,
+                            /*tagged=*/ true,
+                        )?,
+                        w.encode_extension_request(&c.measurements)?,
+                    ))
+                },
+                Encoder::Attributes => |c, w| {
+                    Ok((
+                        CertWriter::get_attributes_size(&c.measurements, /*tagged=*/ true)?,
+                        w.encode_attributes(&c.measurements)?,
+                    ))
+                },
+                Encoder::SignerInfo => |c, w| {
+                    Ok((
+                        CertWriter::get_signer_info_size(
+                            &c.signer_id(),
+                            c.sig,
+                            CertWriter::get_hash_oid(None),
+                            /*tagged=*/ true,
+                        )?,
+                        w.encode_signer_info(&c.signer_id(), c.sig, None)?,
+                    ))
+                },
+                Encoder::IssuerAndSerialNumber => |c, w| {
+                    Ok((
+                        CertWriter::get_issuer_and_serial_number_size(
+                            TEST_SERIAL,
+                            &c.issuer_der,
+                            /*tagged=*/ true,
+                        )?,
+                        w.encode_issuer_and_serial_number(TEST_SERIAL, &c.issuer_der)?,
+                    ))
+                },
+                Encoder::EcdsaTbs => |c, w| {
+                    Ok((
+                        CertWriter::get_tbs_size(
+                            TEST_SERIAL,
+                            &c.issuer_der,
+                            &TEST_SUBJECT_NAME,
+                            c.pubkey,
+                            &c.measurements,
+                            /*tagged=*/ true,
+                        )?,
+                        w.encode_ecdsa_tbs(
+                            TEST_SERIAL,
+                            &c.issuer_der,
+                            &TEST_SUBJECT_NAME,
+                            c.pubkey,
+                            &c.measurements,
+                        )?,
+                    ))
+                },
+                Encoder::SelfSignedEcdsaTbs => |c, w| {
+                    let mut subject_der = [0u8; MAX_ISSUER_NAME_SIZE];
+                    let subject_len = TEST_SUBJECT_NAME.to_der(&mut subject_der)?;
+                    Ok((
+                        CertWriter::get_tbs_size(
+                            TEST_SERIAL,
+                            &subject_der[..subject_len],
+                            &TEST_SUBJECT_NAME,
+                            c.pubkey,
+                            &c.measurements,
+                            /*tagged=*/ true,
+                        )?,
+                        w.encode_self_signed_ecdsa_tbs(
+                            TEST_SERIAL,
+                            &TEST_SUBJECT_NAME,
+                            c.pubkey,
+                            &c.measurements,
+                        )?,
+                    ))
+                },
+                Encoder::EcdsaTbsRecord => |c, w| {
+                    let mut crypto = crypto::OpensslCrypto::new();
+                    let record = w.encode_ecdsa_tbs_record(
+                        &mut crypto,
+                        TEST_SERIAL,
+                        &c.issuer_der,
+                        &TEST_SUBJECT_NAME,
+                        c.pubkey,
+                        &c.measurements,
+                    )?;
+                    Ok((c.tbs.len(), record.tbs_len))
+                },
+                Encoder::EcdsaCertificate => |c, w| {
+                    Ok((
+                        CertWriter::get_ecdsa_certificate_size(
+                            c.tbs.len(),
+                            c.sig,
+                            /*tagged=*/ true,
+                        )?,
+                        w.encode_ecdsa_certificate(&c.tbs, c.sig)?,
+                    ))
+                },
+                Encoder::Certificate => |c, w| {
+                    // The same certificate as encode_ecdsa_certificate, from the raw
+                    // DER signature
+                    Ok((
+                        c.cert.len(),
+                        w.encode_certificate(&c.tbs, CertWriter::ECDSA_OID, &c.cert_sig)?,
+                    ))
+                },
+                Encoder::CertificationRequestInfo => |c, w| {
+                    Ok((
+                        CertWriter::get_certification_request_info_size(
+                            Some(&TEST_SUBJECT_NAME),
+                            c.pubkey,
+                            &c.measurements,
+                            /*tagged=*/ true,
+                        )?,
+                        w.encode_certification_request_info(
+                            c.pubkey,
+                            Some(&TEST_SUBJECT_NAME),
+                            &c.measurements,
+                        )?,
+                    ))
+                },
+                Encoder::Csr => |c, w| {
+                    Ok((
+                        CertWriter::get_csr_size(
+                            c.cert_req_info.len(),
+                            c.sig,
+                            /*tagged=*/ true,
+                        )?,
+                        w.encode_csr(&c.cert_req_info, c.sig)?,
+                    ))
+                },
+                Encoder::Cms => |c, w| {
+                    Ok((
+                        CertWriter::get_cms_size(
+                            c.csr.len(),
+                            &c.signer_id(),
+                            c.sig,
+                            CertWriter::get_hash_oid(None),
+                            /*tagged=*/ true,
+                        )?,
+                        w.encode_cms(&c.csr, &c.signer_id(), c.sig, None)?,
+                    ))
+                },
+                Encoder::CertsOnlyCms => |c, w| {
+                    Ok((
+                        CertWriter::get_certs_only_cms_size(&c.cert, /*tagged=*/ true)?,
+                        w.encode_certs_only_cms(&c.cert)?,
+                    ))
+                },
+            }
+        }
+    }
+
+    fn encode_to_vec(
+        encode: impl FnOnce(&mut CertWriter) -> Result<usize, DpeErrorCode>,
+    ) -> Vec<u8> {
+        let mut buf = vec![0u8; 1 << 15];
+        let mut w = CertWriter::new(&mut buf, true);
+        let len = encode(&mut w).unwrap();
+        buf.truncate(len);
+        buf
+    }
+
+    #[test]
+    fn test_size_matches_encode() {
+        let issuer_der = encode_test_issuer();
+        let pubkey = EcdsaPub {
+            x: CryptoBuf::new(&[0xAA; ECC_INT_SIZE]).unwrap(),
+            y: CryptoBuf::new(&[0xBB; ECC_INT_SIZE]).unwrap(),
+        };
+        let sig = EcdsaSig {
+            r: CryptoBuf::new(&[0xCC; ECC_INT_SIZE]).unwrap(),
+            s: CryptoBuf::new(&[0xDD; ECC_INT_SIZE]).unwrap(),
+        };
+        let nodes: Vec<_> = (0..MAX_TCI_NODES)
+            .map(|i| TciNodeData {
+                tci_type: i as u32,
+                tci_current: TciMeasurement([i as u8; DPE_PROFILE.get_tci_size()]),
+                tci_cumulative: TciMeasurement([!i as u8; DPE_PROFILE.get_tci_size()]),
+                ..TciNodeData::new()
+            })
+            .collect();
+        let label = [0x4C; DPE_PROFILE.ueid_size()];
+
+        for node_count in [1, 2, MAX_TCI_NODES] {
+            for is_ca in [false, true] {
+                for supports_extend_tci in [false, true] {
+                    for label_len in [1, DPE_PROFILE.get_hash_size() / 2, label.len()] {
+                        let measurements = MeasurementData {
+                            label: &label[..label_len],
+                            tci_nodes: &nodes[..node_count],
+                            is_ca,
+                            supports_extend_tci,
+                            policy_constraints: Some(PolicyConstraints {
+                                require_explicit_policy: Some(0),
+                                inhibit_policy_mapping: None,
+                            }),
+                            inhibit_any_policy: Some(1),
+                            challenge_password: Some(b"password"),
+                            ..Default::default()
+                        };
+                        check_sizes(&issuer_der, &pubkey, &sig, measurements);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check the size of every `Encoder` with both two-pass and single-pass
+    /// writers
+    fn check_sizes(
+        issuer_der: &[u8],
+        pubkey: &EcdsaPub,
+        sig: &EcdsaSig,
+        measurements: MeasurementData,
+    ) {
+        let case_name = format!(
+            "nodes={} is_ca={} extend_tci={} label_len={}",
+            measurements.tci_nodes.len(),
+            measurements.is_ca,
+            measurements.supports_extend_tci,
+            measurements.label.len()
+        );
+        let issuer_serial =
+            encode_to_vec(|w| w.encode_issuer_and_serial_number(TEST_SERIAL, issuer_der));
+        let spki = encode_to_vec(|w| {
+            w.encode_ecdsa_subject_pubkey_info(pubkey, measurements.point_format)
+        });
+        let tbs = encode_to_vec(|w| {
+            w.encode_ecdsa_tbs(
+                TEST_SERIAL,
+                issuer_der,
+                &TEST_SUBJECT_NAME,
+                pubkey,
+                &measurements,
+            )
+        });
+        let cert = encode_to_vec(|w| w.encode_ecdsa_certificate(&tbs, sig));
+        // signatureValue is the last element of the certificate
+        let (_, cert_contents, _) = CertWriter::split_element(&cert).unwrap();
+        let (_, _, rest) = CertWriter::split_element(cert_contents).unwrap();
+        let (_, _, rest) = CertWriter::split_element(rest).unwrap();
+        let (_, sig_bits, _) = CertWriter::split_element(rest).unwrap();
+        let cert_sig = sig_bits[1..].to_vec();
+        let cert_req_info = encode_to_vec(|w| {
+            w.encode_certification_request_info(pubkey, Some(&TEST_SUBJECT_NAME), &measurements)
+        });
+        let csr = encode_to_vec(|w| w.encode_csr(&cert_req_info, sig));
+        let case = SizeCase {
+            measurements,
+            issuer_der: issuer_der.to_vec(),
+            pubkey,
+            sig,
+            issuer_serial,
+            spki,
+            tbs,
+            cert,
+            cert_sig,
+            cert_req_info,
+            csr,
+        };
+
+        for encoder in Encoder::iter() {
+            let check = encoder.size_check();
+            let name = format!("{encoder:?}");
+            let mut two_pass = vec![0u8; 1 << 15];
+            let mut w = CertWriter::new(&mut two_pass, true);
+            let (size, bytes_written) = check(&case, &mut w)
+                .unwrap_or_else(|e| panic!("{name} failed with {e:?} for {case_name}"));
+            assert_eq!(size, bytes_written, "{name} for {case_name}");
+
+            let mut single_pass = vec![0u8; 1 << 15];
+            let mut w = CertWriter::new_single_pass(&mut single_pass, true);
+            let (_, single_pass_written) = check(&case, &mut w).unwrap();
+            assert_eq!(
+                single_pass[..single_pass_written],
+                two_pass[..bytes_written],
+                "{name} single-pass output for {case_name}"
+            );
+        }
+    }
 }
@@ -1,7 +1,8 @@
 // Licensed under the Apache-2.0 license
 
-use crate::{Platform, PlatformError, MAX_CHUNK_SIZE};
-use core::cmp::min;
+use crate::{
+    read_chunk, Platform, PlatformError, StringType, GENERALIZED_TIME_SIZE, MAX_CHUNK_SIZE,
+};
 use openssl::x509::X509;
 
 pub struct DefaultPlatform;
@@ -9,6 +10,18 @@ pub struct DefaultPlatform;
 pub const AUTO_INIT_LOCALITY: u32 = 0;
 pub const VENDOR_ID: u32 = 0;
 pub const VENDOR_SKU: u32 = 0;
+/// RFC 9711 RAND UEID: type byte 0x01 followed by 16 fixed test bytes
+pub const DEVICE_UEID: [u8; 17] = [
+    0x01, 0x44, 0x50, 0x45, 0x20, 0x54, 0x65, 0x73, 0x74, 0x20, 0x44, 0x65, 0x76, 0x69, 0x63, 0x65,
+    0x21,
+];
+/// 1.3.6.1.4.1.32473, the private enterprise number RFC 5612 reserves for
+/// documentation
+pub const VENDOR_OID_ARC: &[u8] = &[0x2B, 0x06, 0x01, 0x04, 0x01, 0x81, 0xFD, 0x59];
+/// Certificates are valid from February 27th, 2023 00:00:00 until
+/// December 31st, 9999 23:59:59
+pub const NOT_BEFORE: &[u8; GENERALIZED_TIME_SIZE] = b"20230227000000Z";
+pub const NOT_AFTER: &[u8; GENERALIZED_TIME_SIZE] = b"99991231235959Z";
 
 // Run ./generate.sh to generate all test certs and test private keys
 #[cfg(feature = "dpe_profile_p256_sha256")]
@@ -17,6 +30,13 @@ pub const TEST_CERT_CHAIN: &[u8] = include_bytes!("test_data/cert_256.der");
 #[cfg(feature = "dpe_profile_p384_sha384")]
 pub const TEST_CERT_CHAIN: &[u8] = include_bytes!("test_data/cert_384.der");
 
+/// DPE profile number of the test signing key
+#[cfg(feature = "dpe_profile_p256_sha256")]
+pub const SUPPORTED_PROFILES: &[u32] = &[1];
+
+#[cfg(feature = "dpe_profile_p384_sha384")]
+pub const SUPPORTED_PROFILES: &[u32] = &[2];
+
 impl Platform for DefaultPlatform {
     fn get_certificate_chain(
         &mut self,
@@ -24,20 +44,12 @@ impl Platform for DefaultPlatform {
         size: u32,
         out: &mut [u8; MAX_CHUNK_SIZE],
     ) -> Result<u32, PlatformError> {
-        let len = TEST_CERT_CHAIN.len() as u32;
-        if offset >= len {
-            return Err(PlatformError::CertificateChainError);
-        }
-
-        let cert_chunk_range_end = min(offset + size, len);
-        let bytes_written = cert_chunk_range_end - offset;
-        if bytes_written as usize > MAX_CHUNK_SIZE {
-            return Err(PlatformError::CertificateChainError);
-        }
+        read_chunk(TEST_CERT_CHAIN, offset, size, out)
+            .map_err(|_| PlatformError::CertificateChainError)
+    }
 
-        out[..bytes_written as usize]
-            .copy_from_slice(&TEST_CERT_CHAIN[offset as usize..cert_chunk_range_end as usize]);
-        Ok(bytes_written)
+    fn get_certificate_chain_len(&mut self) -> Result<u32, PlatformError> {
+        Ok(TEST_CERT_CHAIN.len() as u32)
     }
 
     fn get_issuer_name(&mut self, out: &mut [u8; MAX_CHUNK_SIZE]) -> Result<usize, PlatformError> {
@@ -53,6 +65,34 @@ impl Platform for DefaultPlatform {
         Ok(issuer_name.len())
     }
 
+    fn get_device_ueid(&mut self, out: &mut [u8]) -> Result<usize, PlatformError> {
+        out.get_mut(..DEVICE_UEID.len())
+            .ok_or(PlatformError::DeviceUeidError(0))?
+            .copy_from_slice(&DEVICE_UEID);
+        Ok(DEVICE_UEID.len())
+    }
+
+    fn get_cert_validity(
+        &mut self,
+        not_before: &mut [u8; GENERALIZED_TIME_SIZE],
+        not_after: &mut [u8; GENERALIZED_TIME_SIZE],
+    ) -> Result<(), PlatformError> {
+        *not_before = *NOT_BEFORE;
+        *not_after = *NOT_AFTER;
+        Ok(())
+    }
+
+    fn get_vendor_oid_arc(&mut self, out: &mut [u8]) -> Result<usize, PlatformError> {
+        out.get_mut(..VENDOR_OID_ARC.len())
+            .ok_or(PlatformError::VendorOidArcError(0))?
+            .copy_from_slice(VENDOR_OID_ARC);
+        Ok(VENDOR_OID_ARC.len())
+    }
+
+    fn get_name_string_type(&mut self) -> Result<StringType, PlatformError> {
+        Ok(StringType::PrintableString)
+    }
+
     fn get_vendor_id(&mut self) -> Result<u32, PlatformError> {
         Ok(VENDOR_ID)
     }
@@ -69,4 +109,103 @@ impl Platform for DefaultPlatform {
         print!("{str}");
         Ok(())
     }
+
+    fn supported_profiles(&mut self) -> Result<&[u32], PlatformError> {
+        Ok(SUPPORTED_PROFILES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MAX_DEVICE_UEID_SIZE, MAX_VENDOR_OID_ARC_SIZE};
+    use openssl::nid::Nid;
+
+    #[test]
+    fn test_supported_profiles() {
+        // The reported profile must match the curve of the test signing key
+        let cert = X509::from_der(TEST_CERT_CHAIN).unwrap();
+        let profile = match cert
+            .public_key()
+            .unwrap()
+            .ec_key()
+            .unwrap()
+            .group()
+            .curve_name()
+        {
+            Some(Nid::X9_62_PRIME256V1) => 1,
+            Some(Nid::SECP384R1) => 2,
+            _ => panic!("unexpected test key curve"),
+        };
+
+        assert_eq!(DefaultPlatform.supported_profiles().unwrap(), &[profile]);
+    }
+
+    #[test]
+    fn test_get_certificate_chain_len() {
+        assert_eq!(
+            DefaultPlatform.get_certificate_chain_len().unwrap(),
+            TEST_CERT_CHAIN.len() as u32
+        );
+    }
+
+    #[test]
+    fn test_get_device_ueid() {
+        let mut out = [0u8; MAX_DEVICE_UEID_SIZE];
+        let len = DefaultPlatform.get_device_ueid(&mut out).unwrap();
+        assert_eq!(out[..len], DEVICE_UEID);
+
+        let mut out = [0u8; DEVICE_UEID.len() - 1];
+        assert_eq!(
+            DefaultPlatform.get_device_ueid(&mut out),
+            Err(PlatformError::DeviceUeidError(0))
+        );
+    }
+
+    #[test]
+    fn test_get_vendor_oid_arc() {
+        let mut out = [0u8; MAX_VENDOR_OID_ARC_SIZE];
+        let len = DefaultPlatform.get_vendor_oid_arc(&mut out).unwrap();
+        assert_eq!(&out[..len], VENDOR_OID_ARC);
+        let oid = openssl::asn1::Asn1Object::from_str("1.3.6.1.4.1.32473").unwrap();
+        assert_eq!(oid.as_slice(), VENDOR_OID_ARC);
+
+        let mut out = [0u8; VENDOR_OID_ARC.len() - 1];
+        assert_eq!(
+            DefaultPlatform.get_vendor_oid_arc(&mut out),
+            Err(PlatformError::VendorOidArcError(0))
+        );
+    }
+
+    #[test]
+    fn test_get_cert_validity() {
+        let mut not_before = [0u8; GENERALIZED_TIME_SIZE];
+        let mut not_after = [0u8; GENERALIZED_TIME_SIZE];
+        DefaultPlatform
+            .get_cert_validity(&mut not_before, &mut not_after)
+            .unwrap();
+        assert_eq!(&not_before, NOT_BEFORE);
+        assert_eq!(&not_after, NOT_AFTER);
+    }
+
+    #[test]
+    fn test_get_certificate_chain_overflow() {
+        let mut out = [0u8; MAX_CHUNK_SIZE];
+        let len = TEST_CERT_CHAIN.len() as u32;
+
+        // A huge size is clamped to the end of the chain
+        assert_eq!(
+            DefaultPlatform
+                .get_certificate_chain(len - 1, u32::MAX, &mut out)
+                .unwrap(),
+            1
+        );
+        assert_eq!(out[0], TEST_CERT_CHAIN[len as usize - 1]);
+
+        // An offset past the end is rejected rather than wrapping
+        assert_eq!(
+            DefaultPlatform.get_certificate_chain(u32::MAX - 1, u32::MAX, &mut out),
+            Err(PlatformError::CertificateChainError)
+        );
+    }
 }
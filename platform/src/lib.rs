@@ -15,6 +15,15 @@ pub mod printer;
 
 pub const MAX_CHUNK_SIZE: usize = 2048;
 
+/// Largest UEID allowed by RFC 9711 (EAT)
+pub const MAX_DEVICE_UEID_SIZE: usize = 33;
+
+/// Largest vendor OID arc returned by `Platform::get_vendor_oid_arc`
+pub const MAX_VENDOR_OID_ARC_SIZE: usize = 32;
+
+/// Length of a GeneralizedTime string of the form `YYYYMMDDHHMMSSZ`
+pub const GENERALIZED_TIME_SIZE: usize = 15;
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[repr(u16)]
 pub enum PlatformError {
@@ -22,6 +31,17 @@ pub enum PlatformError {
     NotImplemented = 0x2,
     IssuerNameError(u32) = 0x3,
     PrintError(u32) = 0x4,
+    InvalidChunk = 0x5,
+    DeviceUeidError(u32) = 0x6,
+    VendorOidArcError(u32) = 0x7,
+}
+
+/// ASN.1 string type for the attributes of names DPE encodes
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum StringType {
+    #[default]
+    PrintableString,
+    Utf8String,
 }
 
 impl PlatformError {
@@ -38,13 +58,56 @@ impl PlatformError {
             PlatformError::NotImplemented => None,
             PlatformError::IssuerNameError(code) => Some(*code),
             PlatformError::PrintError(code) => Some(*code),
+            PlatformError::InvalidChunk => None,
+            PlatformError::DeviceUeidError(code) => Some(*code),
+            PlatformError::VendorOidArcError(code) => Some(*code),
         }
     }
 }
 
+/// Copies the chunk of `data` starting at `offset` into `out`, for payloads
+/// such as certificate chains, CSRs and CMS messages that are read in pieces.
+///
+/// Returns the number of bytes written, which is less than `size` if the
+/// chunk runs past the end of `data`.
+///
+/// # Arguments
+///
+/// * `data` - The whole payload.
+/// * `offset` - Index where to start reading bytes from in `data`. Must be
+///   within `data`.
+/// * `size` - The requested size of the chunk. The actual chunk must fit in
+///   `out`.
+/// * `out` - Output buffer for the chunk to be written to.
+pub fn read_chunk(
+    data: &[u8],
+    offset: u32,
+    size: u32,
+    out: &mut [u8],
+) -> Result<u32, PlatformError> {
+    let len = data.len() as u32;
+    if offset >= len {
+        return Err(PlatformError::InvalidChunk);
+    }
+
+    // `offset < len`, so the subtraction below cannot underflow
+    let end = core::cmp::min(offset.saturating_add(size), len);
+    let bytes_written = end - offset;
+    if bytes_written as usize > out.len() {
+        return Err(PlatformError::InvalidChunk);
+    }
+
+    out[..bytes_written as usize].copy_from_slice(&data[offset as usize..end as usize]);
+    Ok(bytes_written)
+}
+
 pub trait Platform {
     /// Retrieves a chunk of the parent certificates in the certificate chain.
     ///
+    /// A platform that has no certificate chain, such as one that is not yet
+    /// provisioned, returns `Ok(0)` when reading at offset 0. Reading at or
+    /// past the end of a non-empty chain is an error.
+    ///
     /// # Arguments
     ///
     /// * `offset` - Index where to start reading bytes from in the cert chain.
@@ -57,6 +120,13 @@ pub trait Platform {
         out: &mut [u8; MAX_CHUNK_SIZE],
     ) -> Result<u32, PlatformError>;
 
+    /// Retrieves the total length in bytes of the parent certificate chain.
+    ///
+    /// Callers can use this to size buffers and bound the number of
+    /// `get_certificate_chain` calls needed to read the whole chain. Returns
+    /// 0 if the platform has no certificate chain.
+    fn get_certificate_chain_len(&mut self) -> Result<u32, PlatformError>;
+
     /// Retrieves the parent certificate's DER encoded issuer name.
     ///
     /// # Arguments
@@ -64,6 +134,56 @@ pub trait Platform {
     /// * `out` - Output buffer for issuer name to be written to.
     fn get_issuer_name(&mut self, out: &mut [u8; MAX_CHUNK_SIZE]) -> Result<usize, PlatformError>;
 
+    /// Retrieves the device's UEID, a device-unique identifier in the format
+    /// of RFC 9711 (EAT), for certificates the platform issues itself.
+    ///
+    /// CertifyKey keeps populating tcg-dice-Ueid from its LABEL input, as the
+    /// DPE specification requires.
+    ///
+    /// Returns the number of bytes written, at most `MAX_DEVICE_UEID_SIZE`.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - Output buffer for the UEID to be written to.
+    fn get_device_ueid(&mut self, out: &mut [u8]) -> Result<usize, PlatformError>;
+
+    /// Retrieves the validity period of the certificates DPE issues, e.g. a
+    /// manufacturing date read from fuses and a policy horizon.
+    ///
+    /// Both dates are GeneralizedTime strings of the form `YYYYMMDDHHMMSSZ`.
+    ///
+    /// # Arguments
+    ///
+    /// * `not_before` - Output buffer for the start of the validity period.
+    /// * `not_after` - Output buffer for the end of the validity period.
+    fn get_cert_validity(
+        &mut self,
+        not_before: &mut [u8; GENERALIZED_TIME_SIZE],
+        not_after: &mut [u8; GENERALIZED_TIME_SIZE],
+    ) -> Result<(), PlatformError>;
+
+    /// Retrieves the OID arc vendor-specific certificate extensions are
+    /// namespaced under, typically the vendor's IANA private enterprise
+    /// number arc 1.3.6.1.4.1.<PEN>.
+    ///
+    /// The arc is written as the DER contents of an OBJECT IDENTIFIER,
+    /// without the tag and length. Extension OIDs are formed by appending
+    /// subidentifiers to it.
+    ///
+    /// Returns the number of bytes written, at most `MAX_VENDOR_OID_ARC_SIZE`.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - Output buffer for the OID arc to be written to.
+    fn get_vendor_oid_arc(&mut self, out: &mut [u8]) -> Result<usize, PlatformError>;
+
+    /// Retrieves the string type subject name attributes are encoded with.
+    ///
+    /// Chain builders may match names by comparing their DER encoding, so
+    /// this must be the type used by the issuer name returned from
+    /// `get_issuer_name`.
+    fn get_name_string_type(&mut self) -> Result<StringType, PlatformError>;
+
     fn get_vendor_id(&mut self) -> Result<u32, PlatformError>;
 
     fn get_vendor_sku(&mut self) -> Result<u32, PlatformError>;
@@ -71,4 +191,10 @@ pub trait Platform {
     fn get_auto_init_locality(&mut self) -> Result<u32, PlatformError>;
 
     fn write_str(&mut self, str: &str) -> Result<(), PlatformError>;
+
+    /// Retrieves the DPE profiles the platform's signing key supports.
+    ///
+    /// Profiles are identified by the profile numbers used in the DPE
+    /// GetProfile response, e.g. 1 for P256Sha256 and 2 for P384Sha384.
+    fn supported_profiles(&mut self) -> Result<&[u32], PlatformError>;
 }